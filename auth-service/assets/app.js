@@ -30,6 +30,26 @@ signupLoginLink.addEventListener("click", (e) => {
     signupSection.style.display = "none";
 });
 
+// `/authorize` and the `/device` page send users here to log in, passing themselves along
+// as `return_to`. Only those are returned to, so the parameter can't be used as an open
+// redirect.
+const returnTo = new URLSearchParams(window.location.search).get("return_to");
+
+function onLoggedIn() {
+    if (returnTo && (returnTo.startsWith("/authorize?") || returnTo.startsWith("/device?"))) {
+        window.location.assign(returnTo);
+    } else {
+        alert("You have successfully logged in.");
+    }
+}
+
+// The upstream provider sends users back through `/federated/callback`, which returns them
+// to `return_to` itself.
+if (returnTo) {
+    const federatedLoginLink = document.getElementById("federated-login-link");
+    federatedLoginLink.href = "/federated/login?" + new URLSearchParams({ return_to: returnTo });
+}
+
 // -----------------------------------------------------
 
 const loginForm = document.getElementById("login-form");
@@ -66,7 +86,7 @@ loginButton.addEventListener("click", (e) => {
             loginForm.email.value = "";
             loginForm.password.value = "";
             loginErrAlter.style.display = "none";
-            alert("You have successfully logged in.");
+            onLoggedIn();
         } else {
             response.json().then(data => {
                 let error_msg = data.error;
@@ -104,7 +124,15 @@ signupButton.addEventListener("click", (e) => {
             signupForm.password.value = "";
             signupForm.twoFA.checked = false;
             signupErrAlter.style.display = "none";
-            alert("You have successfully created a user.");
+            response.json().then(data => {
+                if (data.recoveryCodes) {
+                    alert("You have successfully created a user.\n\n"
+                        + "Save these recovery codes somewhere safe. Each one can be used once to log in if you lose access to your second factor, and they won't be shown again:\n\n"
+                        + data.recoveryCodes.join("\n"));
+                } else {
+                    alert("You have successfully created a user.");
+                }
+            });
             loginSection.style.display = "block";
             twoFASection.style.display = "none";
             signupSection.style.display = "none";
@@ -145,7 +173,7 @@ TwoFAButton.addEventListener("click", (e) => {
             TwoFAForm.email_code.value = "";
             TwoFAForm.login_attempt_id.value = "";
             TwoFAErrAlter.style.display = "none";
-            alert("You have successfully logged in.");
+            onLoggedIn();
             loginSection.style.display = "block";
             twoFASection.style.display = "none";
             signupSection.style.display = "none";
@@ -161,4 +189,212 @@ TwoFAButton.addEventListener("click", (e) => {
             });
         }
     });
-});
\ No newline at end of file
+});
+
+// -----------------------------------------------------
+
+const resetRequestSection = document.getElementById("reset-request-section");
+const resetConfirmSection = document.getElementById("reset-confirm-section");
+
+const resetRequestLink = document.getElementById("reset-request-link");
+const resetRequestLoginLink = document.getElementById("reset-request-login-link");
+
+resetRequestLink.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    loginSection.style.display = "none";
+    resetRequestSection.style.display = "block";
+});
+
+resetRequestLoginLink.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    loginSection.style.display = "block";
+    resetRequestSection.style.display = "none";
+});
+
+const resetRequestForm = document.getElementById("reset-request-form");
+const resetRequestButton = document.getElementById("reset-request-form-submit");
+const resetRequestErrAlter = document.getElementById("reset-request-err-alert");
+
+resetRequestButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const email = resetRequestForm.email.value;
+
+    fetch('/password-reset/request', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ email }),
+    }).then(response => {
+        if (response.ok) {
+            resetRequestForm.email.value = "";
+            resetRequestErrAlter.style.display = "none";
+            alert("If an account exists for that address, we have sent it a reset link.");
+            loginSection.style.display = "block";
+            resetRequestSection.style.display = "none";
+        } else {
+            response.json().then(data => {
+                let error_msg = data.error;
+                if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
+                    resetRequestErrAlter.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
+                    resetRequestErrAlter.style.display = "block";
+                } else {
+                    resetRequestErrAlter.style.display = "none";
+                }
+            });
+        }
+    });
+});
+
+const resetConfirmForm = document.getElementById("reset-confirm-form");
+const resetConfirmButton = document.getElementById("reset-confirm-form-submit");
+const resetConfirmErrAlter = document.getElementById("reset-confirm-err-alert");
+
+// Reset links in emails point back here with the token in the query string.
+const resetToken = new URLSearchParams(window.location.search).get("reset_token");
+if (resetToken) {
+    resetConfirmForm.token.value = resetToken;
+    loginSection.style.display = "none";
+    resetConfirmSection.style.display = "block";
+}
+
+resetConfirmButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const token = resetConfirmForm.token.value;
+    const password = resetConfirmForm.password.value;
+
+    fetch('/password-reset/confirm', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ token, password }),
+    }).then(response => {
+        if (response.ok) {
+            resetConfirmForm.token.value = "";
+            resetConfirmForm.password.value = "";
+            resetConfirmErrAlter.style.display = "none";
+            alert("Your password has been updated. Please log in again.");
+            window.history.replaceState({}, "", "/");
+            loginSection.style.display = "block";
+            resetConfirmSection.style.display = "none";
+        } else {
+            response.json().then(data => {
+                let error_msg = data.error;
+                if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
+                    resetConfirmErrAlter.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
+                    resetConfirmErrAlter.style.display = "block";
+                } else {
+                    resetConfirmErrAlter.style.display = "none";
+                }
+            });
+        }
+    });
+});
+
+// Verification links in emails point back here with the token in the query string.
+const verifyToken = new URLSearchParams(window.location.search).get("verify_token");
+if (verifyToken) {
+    window.history.replaceState({}, "", "/");
+
+    fetch('/verify-email', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ token: verifyToken }),
+    }).then(response => {
+        if (response.ok) {
+            alert("Your email has been verified. You can now log in.");
+        } else {
+            alert("This verification link is invalid or has expired.");
+        }
+    });
+}
+
+// Unlock links in lockout emails point back here with the token in the query string.
+const unlockToken = new URLSearchParams(window.location.search).get("unlock_token");
+if (unlockToken) {
+    window.history.replaceState({}, "", "/");
+
+    fetch('/login/unlock', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ token: unlockToken }),
+    }).then(response => {
+        if (response.ok) {
+            alert("Your account has been unlocked. You can now log in.");
+        } else {
+            alert("This unlock link is invalid or has expired.");
+        }
+    });
+}
+
+// Logs in without a password using the email typed into the login form.
+const magicLinkLink = document.getElementById("magic-link-link");
+magicLinkLink.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const email = loginForm.email.value;
+
+    fetch('/login/magic-link', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ email }),
+    }).then(response => {
+        if (response.ok) {
+            loginErrAlter.style.display = "none";
+            alert("If the account exists, a login link has been sent to your email.");
+        } else {
+            response.json().then(data => {
+                let error_msg = data.error;
+                if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
+                    loginErrAlter.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
+                    loginErrAlter.style.display = "block";
+                } else {
+                    loginErrAlter.style.display = "none";
+                }
+            });
+        }
+    });
+});
+
+// Login links in emails point back here. Redeeming them with a POST keeps mail scanners
+// that prefetch links from using them up.
+const magicToken = new URLSearchParams(window.location.search).get("magic_token");
+if (magicToken) {
+    window.history.replaceState({}, "", "/");
+
+    fetch('/login/magic-link/callback', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ token: magicToken }),
+    }).then(response => {
+        if (response.status === 206) {
+            // The link's token names the account in its (unencrypted) payload.
+            const payload = JSON.parse(atob(magicToken.split(".")[1].replace(/-/g, "+").replace(/_/g, "/")));
+            TwoFAForm.email.value = payload.sub;
+            response.json().then(data => {
+                TwoFAForm.login_attempt_id.value = data.loginAttemptId;
+            });
+
+            loginSection.style.display = "none";
+            twoFASection.style.display = "block";
+            signupSection.style.display = "none";
+        } else if (response.status === 200) {
+            onLoggedIn();
+        } else {
+            alert("This login link is invalid, has expired, or has already been used.");
+        }
+    });
+}
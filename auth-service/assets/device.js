@@ -0,0 +1,99 @@
+const userCodeSection = document.getElementById("user-code-section");
+const confirmSection = document.getElementById("confirm-section");
+const doneSection = document.getElementById("done-section");
+
+const userCodeForm = document.getElementById("user-code-form");
+const userCodeButton = document.getElementById("user-code-form-submit");
+const userCodeErrAlert = document.getElementById("user-code-err-alert");
+
+const confirmErrAlert = document.getElementById("confirm-err-alert");
+const approveButton = document.getElementById("approve-button");
+const denyButton = document.getElementById("deny-button");
+
+// Devices show a link with the code filled in; it is also kept when coming back from the
+// login page.
+const presetUserCode = new URLSearchParams(window.location.search).get("user_code");
+if (presetUserCode) {
+    userCodeForm.user_code.value = presetUserCode;
+}
+
+function showError(alertElement, data) {
+    let error_msg = data.error;
+    if (error_msg !== undefined && error_msg !== null && error_msg !== "") {
+        alertElement.innerHTML = `<span><strong>Error: </strong>${error_msg}</span>`;
+        alertElement.style.display = "block";
+    } else {
+        alertElement.style.display = "none";
+    }
+}
+
+// Users who aren't logged in are sent to the login page, which comes back here afterwards.
+function handleError(response, alertElement) {
+    response.json().then(data => {
+        if (data.error === "Missing auth token" || data.error === "Invalid auth token") {
+            const returnTo = "/device?user_code=" + encodeURIComponent(userCodeForm.user_code.value);
+            window.location.assign("/?return_to=" + encodeURIComponent(returnTo));
+        } else {
+            showError(alertElement, data);
+        }
+    });
+}
+
+userCodeButton.addEventListener("click", (e) => {
+    e.preventDefault();
+
+    const userCode = userCodeForm.user_code.value;
+
+    fetch('/device/lookup', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ userCode }),
+    }).then(response => {
+        if (response.ok) {
+            response.json().then(data => {
+                document.getElementById("confirm-client").textContent = data.clientId;
+                document.getElementById("confirm-scope").textContent = data.scope;
+
+                userCodeErrAlert.style.display = "none";
+                userCodeSection.style.display = "none";
+                confirmSection.style.display = "block";
+            });
+        } else {
+            handleError(response, userCodeErrAlert);
+        }
+    });
+});
+
+function confirmRequest(approve) {
+    const userCode = userCodeForm.user_code.value;
+
+    fetch('/device/confirm', {
+        method: 'POST',
+        headers: {
+            'Content-Type': 'application/json',
+        },
+        body: JSON.stringify({ userCode, approve }),
+    }).then(response => {
+        if (response.ok) {
+            document.getElementById("done-message").textContent =
+                approve ? "Your device is connected" : "The request was denied";
+
+            confirmSection.style.display = "none";
+            doneSection.style.display = "block";
+        } else {
+            handleError(response, confirmErrAlert);
+        }
+    });
+}
+
+approveButton.addEventListener("click", (e) => {
+    e.preventDefault();
+    confirmRequest(true);
+});
+
+denyButton.addEventListener("click", (e) => {
+    e.preventDefault();
+    confirmRequest(false);
+});
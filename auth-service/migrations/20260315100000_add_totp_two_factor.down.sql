@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS totp_secrets;
+
+ALTER TABLE users DROP COLUMN IF EXISTS two_fa_method;
@@ -0,0 +1,9 @@
+ALTER TABLE users ADD COLUMN IF NOT EXISTS two_fa_method TEXT NOT NULL DEFAULT 'email';
+
+CREATE TABLE IF NOT EXISTS totp_secrets(
+   email TEXT NOT NULL PRIMARY KEY REFERENCES users(email) ON DELETE CASCADE,
+   -- Secrets are stored encrypted with TOTP_ENCRYPTION_KEY.
+   secret TEXT,
+   pending_secret TEXT,
+   last_used_step BIGINT
+);
@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS passkeys(
+   credential_id TEXT NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   public_key JSONB NOT NULL,
+   sign_count BIGINT NOT NULL,
+   transports JSONB,
+   -- The full webauthn-rs credential record, which also carries the backup flags and
+   -- attestation needed to rebuild a Passkey. sign_count above is authoritative.
+   credential JSONB NOT NULL,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS passkeys_email_idx ON passkeys(email);
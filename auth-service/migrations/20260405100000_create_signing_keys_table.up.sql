@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS signing_keys(
+   kid TEXT NOT NULL PRIMARY KEY,
+   private_key TEXT NOT NULL,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   retired_at TIMESTAMPTZ
+);
+
+-- At most one key can be active at a time.
+CREATE UNIQUE INDEX IF NOT EXISTS signing_keys_active_idx ON signing_keys ((retired_at IS NULL))
+    WHERE retired_at IS NULL;
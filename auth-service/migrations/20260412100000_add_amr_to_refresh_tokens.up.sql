@@ -0,0 +1,2 @@
+-- The methods the user logged in with, so refreshed access tokens keep reporting them.
+ALTER TABLE refresh_tokens ADD COLUMN IF NOT EXISTS amr TEXT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,6 @@
+CREATE TABLE IF NOT EXISTS oauth_clients(
+   client_id TEXT NOT NULL PRIMARY KEY,
+   -- Client secrets are generated with enough entropy that a SHA-256 digest is safe to store.
+   client_secret_hash TEXT NOT NULL,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,2 @@
+-- Where `/authorize` may send users back to after they sign in.
+ALTER TABLE oauth_clients ADD COLUMN IF NOT EXISTS redirect_uris TEXT[] NOT NULL DEFAULT '{}';
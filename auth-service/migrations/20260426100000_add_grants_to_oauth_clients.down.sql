@@ -0,0 +1,3 @@
+ALTER TABLE oauth_clients
+   DROP COLUMN IF EXISTS allowed_scopes,
+   DROP COLUMN IF EXISTS allowed_grant_types;
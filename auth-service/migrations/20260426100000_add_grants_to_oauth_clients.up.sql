@@ -0,0 +1,7 @@
+ALTER TABLE oauth_clients
+   ADD COLUMN IF NOT EXISTS allowed_scopes TEXT[] NOT NULL DEFAULT '{}',
+   ADD COLUMN IF NOT EXISTS allowed_grant_types TEXT[] NOT NULL DEFAULT '{}';
+
+-- Clients registered so far could only sign users in through `/authorize`.
+UPDATE oauth_clients
+   SET allowed_scopes = '{openid,email}', allowed_grant_types = '{authorization_code}';
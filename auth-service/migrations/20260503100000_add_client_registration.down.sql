@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS initial_access_tokens;
+ALTER TABLE oauth_clients
+   DROP COLUMN IF EXISTS client_name,
+   DROP COLUMN IF EXISTS registration_access_token_hash;
@@ -0,0 +1,10 @@
+ALTER TABLE oauth_clients
+   ADD COLUMN IF NOT EXISTS client_name TEXT,
+   -- Only clients that registered themselves (RFC 7591) can manage their own configuration.
+   ADD COLUMN IF NOT EXISTS registration_access_token_hash TEXT;
+
+CREATE TABLE IF NOT EXISTS initial_access_tokens(
+   token_hash TEXT NOT NULL PRIMARY KEY,
+   expires_at TIMESTAMPTZ NOT NULL,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
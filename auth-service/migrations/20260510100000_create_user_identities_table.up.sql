@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS user_identities(
+   -- The upstream provider's issuer and the `sub` it identifies the user by.
+   provider TEXT NOT NULL,
+   subject TEXT NOT NULL,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   PRIMARY KEY (provider, subject)
+);
+
+CREATE INDEX IF NOT EXISTS user_identities_email_idx ON user_identities(email);
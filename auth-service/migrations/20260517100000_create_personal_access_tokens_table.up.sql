@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS personal_access_tokens(
+   id UUID NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   name TEXT NOT NULL,
+   token_hash TEXT NOT NULL UNIQUE,
+   scopes TEXT[] NOT NULL DEFAULT '{}',
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   -- NULL for tokens that never expire.
+   expires_at TIMESTAMPTZ,
+   last_used_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS personal_access_tokens_email_idx ON personal_access_tokens(email);
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS sessions(
+   -- Shared with the session's refresh token family and the `sid` claim of its JWTs.
+   id UUID NOT NULL PRIMARY KEY,
+   email TEXT NOT NULL REFERENCES users(email) ON DELETE CASCADE,
+   created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   last_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+   ip TEXT,
+   user_agent TEXT,
+   amr TEXT[] NOT NULL DEFAULT '{}'
+);
+
+CREATE INDEX IF NOT EXISTS sessions_email_idx ON sessions(email);
@@ -0,0 +1,2 @@
+DELETE FROM signing_keys WHERE private_key IS NULL;
+ALTER TABLE signing_keys ALTER COLUMN private_key SET NOT NULL;
@@ -0,0 +1,3 @@
+-- Removed keys keep their row, without the private key, so a key that was rotated away is
+-- never taken up again, e.g. when it is still in the key file at the next start.
+ALTER TABLE signing_keys ALTER COLUMN private_key DROP NOT NULL;
@@ -0,0 +1,5 @@
+-- A rotated in key is published before it signs anything. The key it replaces is retired
+-- at the same moment the new key activates, so exactly one key signs at any time.
+ALTER TABLE signing_keys ADD COLUMN IF NOT EXISTS activates_at TIMESTAMPTZ;
+UPDATE signing_keys SET activates_at = created_at WHERE activates_at IS NULL;
+ALTER TABLE signing_keys ALTER COLUMN activates_at SET NOT NULL;
@@ -0,0 +1,3 @@
+DELETE FROM refresh_tokens WHERE client_id IS NOT NULL;
+ALTER TABLE refresh_tokens DROP COLUMN IF EXISTS scope;
+ALTER TABLE refresh_tokens DROP COLUMN IF EXISTS client_id;
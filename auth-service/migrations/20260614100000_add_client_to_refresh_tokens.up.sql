@@ -0,0 +1,5 @@
+-- Refresh tokens issued to a client at /token, with the scope it was granted. Tokens of
+-- first-party sessions have neither.
+ALTER TABLE refresh_tokens
+    ADD COLUMN IF NOT EXISTS client_id TEXT REFERENCES oauth_clients(client_id) ON DELETE CASCADE;
+ALTER TABLE refresh_tokens ADD COLUMN IF NOT EXISTS scope TEXT;
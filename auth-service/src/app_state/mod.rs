@@ -1,33 +1,116 @@
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
-use crate::domain::{BannedTokenStore, EmailClient, TwoFACodeStore, UserStore};
+use crate::{
+    domain::{
+        AuthorizationCodeStore, BannedTokenStore, DeviceCodeStore, EmailClient,
+        FederatedLoginStore, IdentityProvider, LoginLockoutStore, MagicLinkStore, OAuthClientStore,
+        PasskeyCeremonyStore, PasskeyStore, PersonalAccessTokenStore, RateLimitStore,
+        RecoveryCodeStore, RefreshTokenStore, SessionStore, SigningKeyStore, TotpSecretStore,
+        TwoFACodeStore, UserIdentityStore, UserStore,
+    },
+    utils::signing_key::Keyring,
+};
 
 pub type UserStoreType = Arc<RwLock<dyn UserStore + Send + Sync>>;
 pub type BannedTokenStoreType = Arc<RwLock<dyn BannedTokenStore + Send + Sync>>;
 pub type TwoFACodeStoreType = Arc<RwLock<dyn TwoFACodeStore + Send + Sync>>;
+pub type RefreshTokenStoreType = Arc<RwLock<dyn RefreshTokenStore + Send + Sync>>;
+pub type RateLimitStoreType = Arc<RwLock<dyn RateLimitStore + Send + Sync>>;
+pub type TotpSecretStoreType = Arc<RwLock<dyn TotpSecretStore + Send + Sync>>;
+pub type PasskeyStoreType = Arc<RwLock<dyn PasskeyStore + Send + Sync>>;
+pub type PasskeyCeremonyStoreType = Arc<RwLock<dyn PasskeyCeremonyStore + Send + Sync>>;
+pub type RecoveryCodeStoreType = Arc<RwLock<dyn RecoveryCodeStore + Send + Sync>>;
+pub type MagicLinkStoreType = Arc<RwLock<dyn MagicLinkStore + Send + Sync>>;
+pub type SigningKeyStoreType = Arc<RwLock<dyn SigningKeyStore + Send + Sync>>;
+pub type KeyringType = Arc<Keyring>;
+pub type OAuthClientStoreType = Arc<RwLock<dyn OAuthClientStore + Send + Sync>>;
+pub type AuthorizationCodeStoreType = Arc<RwLock<dyn AuthorizationCodeStore + Send + Sync>>;
+pub type DeviceCodeStoreType = Arc<RwLock<dyn DeviceCodeStore + Send + Sync>>;
+pub type FederatedLoginStoreType = Arc<RwLock<dyn FederatedLoginStore + Send + Sync>>;
+pub type UserIdentityStoreType = Arc<RwLock<dyn UserIdentityStore + Send + Sync>>;
+pub type PersonalAccessTokenStoreType = Arc<RwLock<dyn PersonalAccessTokenStore + Send + Sync>>;
+pub type SessionStoreType = Arc<RwLock<dyn SessionStore + Send + Sync>>;
+pub type LoginLockoutStoreType = Arc<RwLock<dyn LoginLockoutStore + Send + Sync>>;
 pub type EmailClientType = Arc<dyn EmailClient + Send + Sync>;
+pub type IdentityProviderType = Arc<dyn IdentityProvider + Send + Sync>;
 
 #[derive(Clone)]
 pub struct AppState {
     pub user_store: UserStoreType,
     pub banned_token_store: BannedTokenStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
+    pub refresh_token_store: RefreshTokenStoreType,
+    pub rate_limit_store: RateLimitStoreType,
+    pub totp_secret_store: TotpSecretStoreType,
+    pub passkey_store: PasskeyStoreType,
+    pub passkey_ceremony_store: PasskeyCeremonyStoreType,
+    pub recovery_code_store: RecoveryCodeStoreType,
+    pub magic_link_store: MagicLinkStoreType,
+    pub signing_key_store: SigningKeyStoreType,
+    pub keyring: KeyringType,
+    pub oauth_client_store: OAuthClientStoreType,
+    pub authorization_code_store: AuthorizationCodeStoreType,
+    pub device_code_store: DeviceCodeStoreType,
+    pub federated_login_store: FederatedLoginStoreType,
+    pub user_identity_store: UserIdentityStoreType,
+    pub personal_access_token_store: PersonalAccessTokenStoreType,
+    pub session_store: SessionStoreType,
+    pub login_lockout_store: LoginLockoutStoreType,
     pub email_client: EmailClientType,
+    /// `None` when no upstream provider is configured, which disables federated login.
+    pub identity_provider: Option<IdentityProviderType>,
 }
 
 impl AppState {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         user_store: UserStoreType,
         banned_token_store: BannedTokenStoreType,
         two_fa_code_store: TwoFACodeStoreType,
+        refresh_token_store: RefreshTokenStoreType,
+        rate_limit_store: RateLimitStoreType,
+        totp_secret_store: TotpSecretStoreType,
+        passkey_store: PasskeyStoreType,
+        passkey_ceremony_store: PasskeyCeremonyStoreType,
+        recovery_code_store: RecoveryCodeStoreType,
+        magic_link_store: MagicLinkStoreType,
+        signing_key_store: SigningKeyStoreType,
+        keyring: KeyringType,
+        oauth_client_store: OAuthClientStoreType,
+        authorization_code_store: AuthorizationCodeStoreType,
+        device_code_store: DeviceCodeStoreType,
+        federated_login_store: FederatedLoginStoreType,
+        user_identity_store: UserIdentityStoreType,
+        personal_access_token_store: PersonalAccessTokenStoreType,
+        session_store: SessionStoreType,
+        login_lockout_store: LoginLockoutStoreType,
         email_client: EmailClientType,
+        identity_provider: Option<IdentityProviderType>,
     ) -> Self {
         Self {
             user_store,
             banned_token_store,
             two_fa_code_store,
+            refresh_token_store,
+            rate_limit_store,
+            totp_secret_store,
+            passkey_store,
+            passkey_ceremony_store,
+            recovery_code_store,
+            magic_link_store,
+            signing_key_store,
+            keyring,
+            oauth_client_store,
+            authorization_code_store,
+            device_code_store,
+            federated_login_store,
+            user_identity_store,
+            personal_access_token_store,
+            session_store,
+            login_lockout_store,
             email_client,
+            identity_provider,
         }
     }
 }
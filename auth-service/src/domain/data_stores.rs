@@ -1,8 +1,14 @@
-use super::{Email, Password, User};
+use super::{AuthMethod, Email, Password, RateLimitDecision, RateLimitQuota, TwoFAMethod, User};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
 use color_eyre::eyre::{eyre, Report, Result};
-use rand::Rng;
-use secrecy::{ExposeSecret, SecretString};
+use rand::{Rng, RngCore};
+use secrecy::{ExposeSecret, SecretSlice, SecretString};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
 use thiserror::Error;
+use uuid::Uuid;
+use webauthn_rs::prelude::{Passkey, PasskeyAuthentication, PasskeyRegistration};
 
 #[async_trait::async_trait]
 pub trait UserStore {
@@ -12,6 +18,21 @@ pub trait UserStore {
 
     async fn validate_user(&self, email: &Email, password: &Password)
         -> Result<(), UserStoreError>;
+
+    async fn update_password(
+        &mut self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError>;
+
+    async fn mark_verified(&mut self, email: &Email) -> Result<(), UserStoreError>;
+
+    /// Switches the user's second factor to `method`, turning 2FA on if it was off.
+    async fn set_two_fa_method(
+        &mut self,
+        email: &Email,
+        method: TwoFAMethod,
+    ) -> Result<(), UserStoreError>;
 }
 
 #[async_trait::async_trait]
@@ -19,6 +40,23 @@ pub trait BannedTokenStore {
     async fn store_token(&mut self, token: SecretString) -> Result<(), BannedTokenStoreError>;
 
     async fn contains_token(&self, token: &SecretString) -> Result<bool, BannedTokenStoreError>;
+
+    /// Bans `token` unless it already is, returning whether this call banned it. Makes a
+    /// token single-use: only the request that bans it may act on it.
+    async fn consume_token(&mut self, token: SecretString) -> Result<bool, BannedTokenStoreError>;
+
+    /// Bans every token issued to `subject` before `not_before`, replacing any earlier
+    /// cut-off.
+    async fn ban_tokens_before(
+        &mut self,
+        subject: &str,
+        not_before: DateTime<Utc>,
+    ) -> Result<(), BannedTokenStoreError>;
+
+    async fn get_tokens_not_before(
+        &self,
+        subject: &str,
+    ) -> Result<Option<DateTime<Utc>>, BannedTokenStoreError>;
 }
 
 #[derive(Debug, Error)]
@@ -51,13 +89,82 @@ pub enum BannedTokenStoreError {
     UnexpectedError(#[source] Report),
 }
 
+#[async_trait::async_trait]
+pub trait RateLimitStore {
+    /// Records a hit against `key` and returns how many hits the key has seen in the
+    /// current fixed window of `window_seconds`, including this one.
+    async fn hit(&mut self, key: &str, window_seconds: u64) -> Result<u64, RateLimitStoreError>;
+
+    /// Checks a request against `key`'s `quota`, and uses up part of the allowance if it
+    /// is let through. Checking and updating must be atomic across replicas.
+    async fn acquire(
+        &mut self,
+        key: &str,
+        quota: &RateLimitQuota,
+    ) -> Result<RateLimitDecision, RateLimitStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum RateLimitStoreError {
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+/// Failed logins and lockouts, by account or source address.
+#[async_trait::async_trait]
+pub trait LoginLockoutStore {
+    /// Records a failed login against `key` and returns how many failures the key has had
+    /// in the last `window_seconds`, including this one.
+    async fn record_failure(
+        &mut self,
+        key: &str,
+        window_seconds: u64,
+    ) -> Result<u64, LoginLockoutStoreError>;
+
+    /// Returns how many failures `key` has had in the last `window_seconds`.
+    async fn count_failures(
+        &self,
+        key: &str,
+        window_seconds: u64,
+    ) -> Result<u64, LoginLockoutStoreError>;
+
+    async fn clear_failures(&mut self, key: &str) -> Result<(), LoginLockoutStoreError>;
+
+    /// Locks `key` until `until` and starts its failure count over. Each lock is
+    /// remembered for a day, so repeated lockouts can be made longer.
+    async fn lock(&mut self, key: &str, until: DateTime<Utc>)
+        -> Result<(), LoginLockoutStoreError>;
+
+    async fn get_lockout(&self, key: &str) -> Result<Lockout, LoginLockoutStoreError>;
+
+    /// Lifts the lock, and forgets the failures and earlier lockouts of `key`.
+    async fn unlock(&mut self, key: &str) -> Result<(), LoginLockoutStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum LoginLockoutStoreError {
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Lockout {
+    /// Only set while the lock is in force.
+    pub locked_until: Option<DateTime<Utc>>,
+    /// How many times the key has been locked in the last day.
+    pub lockouts: u32,
+}
+
 #[async_trait::async_trait]
 pub trait TwoFACodeStore {
+    /// Starts a 2FA login for `email`. `first_factor` is how the user got this far, so the
+    /// session can list it alongside the second factor.
     async fn add_code(
         &mut self,
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
+        first_factor: AuthMethod,
     ) -> Result<(), TwoFACodeStoreError>;
 
     async fn remove_code(&mut self, email: &Email) -> Result<(), TwoFACodeStoreError>;
@@ -66,6 +173,27 @@ pub trait TwoFACodeStore {
         &self,
         email: &Email,
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError>;
+
+    async fn get_first_factor(&self, email: &Email) -> Result<AuthMethod, TwoFACodeStoreError>;
+
+    /// Counts a wrong code entered for `login_attempt_id` and returns the failures so far.
+    async fn record_failed_attempt(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+        failed_at: DateTime<Utc>,
+    ) -> Result<FailedAttempts, TwoFACodeStoreError>;
+
+    async fn get_failed_attempts(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<FailedAttempts, TwoFACodeStoreError>;
+}
+
+/// The wrong codes entered for one login attempt.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FailedAttempts {
+    pub count: u32,
+    pub last_failed_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Error)]
@@ -86,12 +214,51 @@ impl PartialEq for TwoFACodeStoreError {
     }
 }
 
+#[async_trait::async_trait]
+pub trait MagicLinkStore {
+    async fn add_login_attempt(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+        email: &Email,
+    ) -> Result<(), MagicLinkStoreError>;
+
+    /// Removes the login attempt and returns whose it was, so each link can only be
+    /// redeemed once.
+    async fn take_login_attempt(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<Email, MagicLinkStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum MagicLinkStoreError {
+    #[error("Login attempt not found")]
+    LoginAttemptNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for MagicLinkStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::LoginAttemptNotFound, Self::LoginAttemptNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct LoginAttemptId(SecretString);
 
 impl PartialEq for LoginAttemptId {
     fn eq(&self, other: &Self) -> bool {
-        self.0.expose_secret() == other.0.expose_secret()
+        bool::from(
+            self.0
+                .expose_secret()
+                .as_bytes()
+                .ct_eq(other.0.expose_secret().as_bytes()),
+        )
     }
 }
 
@@ -122,17 +289,24 @@ pub struct TwoFACode(SecretString);
 
 impl PartialEq for TwoFACode {
     fn eq(&self, other: &Self) -> bool {
-        self.0.expose_secret() == other.0.expose_secret()
+        bool::from(
+            self.0
+                .expose_secret()
+                .as_bytes()
+                .ct_eq(other.0.expose_secret().as_bytes()),
+        )
     }
 }
 
 impl TwoFACode {
+    /// Accepts any six digit code. Emailed codes never start with a zero, but codes from
+    /// authenticator apps can.
     pub fn parse(code: SecretString) -> Result<Self> {
-        let code_as_u32 = code.expose_secret().parse::<u32>()?;
-        if (100_000..=999_999).contains(&code_as_u32) {
+        let digits = code.expose_secret();
+        if digits.len() == 6 && digits.bytes().all(|b| b.is_ascii_digit()) {
             Ok(Self(code))
         } else {
-            Err(eyre!("Invalid email code"))
+            Err(eyre!("Invalid 2FA code"))
         }
     }
 }
@@ -153,3 +327,1180 @@ impl AsRef<SecretString> for TwoFACode {
         &self.0
     }
 }
+
+#[async_trait::async_trait]
+pub trait RefreshTokenStore {
+    async fn add_token(
+        &mut self,
+        token: &RefreshToken,
+        record: RefreshTokenRecord,
+    ) -> Result<(), RefreshTokenStoreError>;
+
+    /// Marks `token` as used and returns its record. A token may only be consumed once;
+    /// presenting it again yields `RefreshTokenStoreError::TokenReused`. Only the client the
+    /// token was issued to can consume it, and first-party tokens only with `None`; for
+    /// anyone else it is `TokenNotFound`.
+    async fn consume_token(
+        &mut self,
+        token: &RefreshToken,
+        client_id: Option<&str>,
+    ) -> Result<RefreshTokenRecord, RefreshTokenStoreError>;
+
+    /// Revokes every token in the family `token` belongs to.
+    async fn revoke_family(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError>;
+
+    /// Revokes every token in the family `token` belongs to, if it was issued to
+    /// `client_id`. Other tokens are left alone.
+    async fn revoke_client_family(
+        &mut self,
+        token: &RefreshToken,
+        client_id: &str,
+    ) -> Result<(), RefreshTokenStoreError>;
+
+    /// Revokes every refresh token issued to `email`, across all families.
+    async fn revoke_all_tokens(&mut self, email: &Email) -> Result<(), RefreshTokenStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum RefreshTokenStoreError {
+    #[error("Refresh token not found")]
+    TokenNotFound,
+    #[error("Refresh token reused")]
+    TokenReused,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for RefreshTokenStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::TokenNotFound, Self::TokenNotFound)
+                | (Self::TokenReused, Self::TokenReused)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+/// Everything the store keeps about a refresh token besides the token itself.
+/// Tokens issued by rotating another token share its `family_id`, `amr` and `client`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RefreshTokenRecord {
+    pub email: Email,
+    pub family_id: Uuid,
+    pub expires_at: DateTime<Utc>,
+    pub amr: Vec<AuthMethod>,
+    /// `None` for first-party sessions, whose tokens only ever live in the refresh cookie.
+    pub client: Option<RefreshTokenClient>,
+}
+
+/// The client a refresh token was issued to at `/token`, and the scope it was granted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RefreshTokenClient {
+    pub client_id: String,
+    pub scope: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RefreshToken(SecretString);
+
+impl PartialEq for RefreshToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+const REFRESH_TOKEN_BYTES: usize = 32;
+
+impl RefreshToken {
+    pub fn parse(token: SecretString) -> Result<Self> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(token.expose_secret())
+            .map_err(|_| eyre!("Invalid refresh token"))?;
+        if bytes.len() == REFRESH_TOKEN_BYTES {
+            Ok(Self(token))
+        } else {
+            Err(eyre!("Invalid refresh token"))
+        }
+    }
+}
+
+impl Default for RefreshToken {
+    fn default() -> Self {
+        let mut bytes = [0u8; REFRESH_TOKEN_BYTES];
+        rand::rng().fill_bytes(&mut bytes);
+        Self(SecretString::new(
+            URL_SAFE_NO_PAD.encode(bytes).into_boxed_str(),
+        ))
+    }
+}
+
+impl AsRef<SecretString> for RefreshToken {
+    fn as_ref(&self) -> &SecretString {
+        &self.0
+    }
+}
+
+/// Keeps each user's authenticator app secret. A new secret starts out pending and only
+/// replaces the active one once the user has proven they can generate codes from it.
+#[async_trait::async_trait]
+pub trait TotpSecretStore {
+    async fn add_pending_secret(
+        &mut self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), TotpSecretStoreError>;
+
+    async fn get_pending_secret(&self, email: &Email) -> Result<TotpSecret, TotpSecretStoreError>;
+
+    /// Makes the pending secret the active one. `step` is the time step of the code used to
+    /// confirm it, which is recorded as used.
+    async fn activate_pending_secret(
+        &mut self,
+        email: &Email,
+        step: u64,
+    ) -> Result<(), TotpSecretStoreError>;
+
+    async fn get_secret(&self, email: &Email) -> Result<TotpSecret, TotpSecretStoreError>;
+
+    /// Records that a code from time step `step` has been accepted. Fails with
+    /// `TotpSecretStoreError::StepAlreadyUsed` for that step or any earlier one, so a code
+    /// can't be replayed while it's still inside the drift window.
+    async fn use_step(&mut self, email: &Email, step: u64) -> Result<(), TotpSecretStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum TotpSecretStoreError {
+    #[error("TOTP secret not found")]
+    SecretNotFound,
+    #[error("TOTP step already used")]
+    StepAlreadyUsed,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for TotpSecretStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::SecretNotFound, Self::SecretNotFound)
+                | (Self::StepAlreadyUsed, Self::StepAlreadyUsed)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct TotpSecret(SecretSlice<u8>);
+
+// RFC 4226 recommends 160 bit shared secrets.
+const TOTP_SECRET_BYTES: usize = 20;
+
+impl TotpSecret {
+    pub fn parse(secret: Vec<u8>) -> Result<Self> {
+        if secret.len() == TOTP_SECRET_BYTES {
+            Ok(Self(secret.into()))
+        } else {
+            Err(eyre!("Invalid TOTP secret"))
+        }
+    }
+}
+
+impl Default for TotpSecret {
+    fn default() -> Self {
+        let mut bytes = vec![0u8; TOTP_SECRET_BYTES];
+        rand::rng().fill_bytes(&mut bytes);
+        Self(bytes.into())
+    }
+}
+
+impl AsRef<SecretSlice<u8>> for TotpSecret {
+    fn as_ref(&self) -> &SecretSlice<u8> {
+        &self.0
+    }
+}
+
+#[async_trait::async_trait]
+pub trait PasskeyStore {
+    async fn add_passkey(
+        &mut self,
+        email: &Email,
+        passkey: &Passkey,
+    ) -> Result<(), PasskeyStoreError>;
+
+    async fn get_passkeys(&self, email: &Email) -> Result<Vec<Passkey>, PasskeyStoreError>;
+
+    /// Saves the sign counter and backup flags of a passkey after it was used to log in.
+    async fn update_passkey(&mut self, passkey: &Passkey) -> Result<(), PasskeyStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum PasskeyStoreError {
+    #[error("Passkey already registered")]
+    PasskeyAlreadyExists,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for PasskeyStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::PasskeyAlreadyExists, Self::PasskeyAlreadyExists)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+/// Holds the server side state of WebAuthn ceremonies between their start and finish
+/// requests. Each state can be taken once.
+#[async_trait::async_trait]
+pub trait PasskeyCeremonyStore {
+    async fn add_registration(
+        &mut self,
+        email: &Email,
+        state: PasskeyRegistration,
+    ) -> Result<(), PasskeyCeremonyStoreError>;
+
+    async fn take_registration(
+        &mut self,
+        email: &Email,
+    ) -> Result<PasskeyRegistration, PasskeyCeremonyStoreError>;
+
+    async fn add_authentication(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+        email: &Email,
+        state: PasskeyAuthentication,
+    ) -> Result<(), PasskeyCeremonyStoreError>;
+
+    async fn take_authentication(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(Email, PasskeyAuthentication), PasskeyCeremonyStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum PasskeyCeremonyStoreError {
+    #[error("Ceremony not found")]
+    CeremonyNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for PasskeyCeremonyStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::CeremonyNotFound, Self::CeremonyNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[async_trait::async_trait]
+pub trait RecoveryCodeStore {
+    /// Replaces all of the user's recovery codes, used or not, with `codes`.
+    async fn set_codes(
+        &mut self,
+        email: &Email,
+        codes: &[RecoveryCode],
+    ) -> Result<(), RecoveryCodeStoreError>;
+
+    async fn count_unused_codes(&self, email: &Email) -> Result<usize, RecoveryCodeStoreError>;
+
+    /// Marks `code` as used. A code may only be consumed once; presenting it again yields
+    /// `RecoveryCodeStoreError::CodeNotFound`.
+    async fn consume_code(
+        &mut self,
+        email: &Email,
+        code: &RecoveryCode,
+    ) -> Result<(), RecoveryCodeStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum RecoveryCodeStoreError {
+    #[error("Recovery code not found")]
+    CodeNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for RecoveryCodeStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::CodeNotFound, Self::CodeNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+/// A one-time code that stands in for a second factor, formatted as `xxxxx-xxxxx`.
+#[derive(Clone, Debug)]
+pub struct RecoveryCode(SecretString);
+
+// Lowercase letters and digits, minus the ones that are easy to misread (0/o, 1/l/i).
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+const RECOVERY_CODE_GROUP_LENGTH: usize = 5;
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+impl PartialEq for RecoveryCode {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+impl RecoveryCode {
+    /// Accepts codes with or without the dash, in any case, and normalizes them.
+    pub fn parse(code: SecretString) -> Result<Self> {
+        let chars = code
+            .expose_secret()
+            .chars()
+            .filter(|c| *c != '-' && !c.is_whitespace())
+            .map(|c| c.to_ascii_lowercase())
+            .collect::<String>();
+
+        let valid = chars.len() == 2 * RECOVERY_CODE_GROUP_LENGTH
+            && chars.bytes().all(|b| RECOVERY_CODE_ALPHABET.contains(&b));
+        if !valid {
+            return Err(eyre!("Invalid recovery code"));
+        }
+
+        let (first, second) = chars.split_at(RECOVERY_CODE_GROUP_LENGTH);
+        Ok(Self(SecretString::new(
+            format!("{}-{}", first, second).into_boxed_str(),
+        )))
+    }
+}
+
+impl Default for RecoveryCode {
+    fn default() -> Self {
+        let mut rng = rand::rng();
+        let mut group = || {
+            (0..RECOVERY_CODE_GROUP_LENGTH)
+                .map(|_| {
+                    RECOVERY_CODE_ALPHABET[rng.random_range(0..RECOVERY_CODE_ALPHABET.len())]
+                        as char
+                })
+                .collect::<String>()
+        };
+        let code = format!("{}-{}", group(), group());
+        Self(SecretString::new(code.into_boxed_str()))
+    }
+}
+
+impl AsRef<SecretString> for RecoveryCode {
+    fn as_ref(&self) -> &SecretString {
+        &self.0
+    }
+}
+
+/// Keeps the JWT signing keys. Exactly one key is active and signs new tokens; retired
+/// keys only verify the tokens they signed before they were replaced.
+#[async_trait::async_trait]
+pub trait SigningKeyStore {
+    /// Adds a key that becomes the active one at `activates_at`, retiring whichever key
+    /// would be active before then at that same moment. Returns whether the key was added;
+    /// it isn't if the kid is already known, e.g. because another instance just added it.
+    async fn add_key(
+        &mut self,
+        kid: &str,
+        private_key_pem: &SecretString,
+        activates_at: DateTime<Utc>,
+    ) -> Result<bool, SigningKeyStoreError>;
+
+    /// Whether the key was ever added, including keys since removed.
+    async fn contains_key(&self, kid: &str) -> Result<bool, SigningKeyStoreError>;
+
+    /// Returns every key not retired at or before `retired_after`, newest first. That
+    /// includes keys that have yet to activate.
+    async fn get_keys(
+        &self,
+        retired_after: DateTime<Utc>,
+    ) -> Result<Vec<SigningKeyRecord>, SigningKeyStoreError>;
+
+    /// Deletes the private keys of keys retired at or before `retired_before`. Their kids
+    /// are kept, so `contains_key` still knows them and a removed key is never added again.
+    async fn remove_retired_keys(
+        &mut self,
+        retired_before: DateTime<Utc>,
+    ) -> Result<(), SigningKeyStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum SigningKeyStoreError {
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for SigningKeyStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SigningKeyRecord {
+    pub kid: String,
+    pub private_key_pem: SecretString,
+    /// Until then the key is only published, so verifiers can pick it up before it's used.
+    pub activates_at: DateTime<Utc>,
+    /// `None` for the newest key. A key being rotated away from is retired in the future,
+    /// when the key replacing it activates.
+    pub retired_at: Option<DateTime<Utc>>,
+}
+
+/// Keeps the OAuth clients that may call the `/oauth/*` endpoints, sign users in through
+/// `/authorize` or get tokens of their own. Only a digest of each client secret is stored.
+#[async_trait::async_trait]
+pub trait OAuthClientStore {
+    /// Clients that registered themselves (RFC 7591) also get a `registration_access_token`
+    /// to manage their configuration with.
+    async fn add_client(
+        &mut self,
+        client: &OAuthClient,
+        client_secret: &ClientSecret,
+        registration_access_token: Option<&RegistrationToken>,
+    ) -> Result<(), OAuthClientStoreError>;
+
+    async fn get_client(&self, client_id: &str) -> Result<OAuthClient, OAuthClientStoreError>;
+
+    async fn list_clients(&self) -> Result<Vec<OAuthClient>, OAuthClientStoreError>;
+
+    /// Replaces everything about the client except its secret.
+    async fn update_client(&mut self, client: &OAuthClient) -> Result<(), OAuthClientStoreError>;
+
+    async fn update_secret(
+        &mut self,
+        client_id: &str,
+        client_secret: &ClientSecret,
+    ) -> Result<(), OAuthClientStoreError>;
+
+    async fn delete_client(&mut self, client_id: &str) -> Result<(), OAuthClientStoreError>;
+
+    /// Checks `client_secret` against the secret registered for `client_id` and returns the
+    /// client.
+    async fn validate_client(
+        &self,
+        client_id: &str,
+        client_secret: &SecretString,
+    ) -> Result<OAuthClient, OAuthClientStoreError>;
+
+    /// Checks a registration access token (RFC 7592) against the one the client was given
+    /// when it registered itself, and returns the client.
+    async fn validate_registration_access_token(
+        &self,
+        client_id: &str,
+        registration_access_token: &SecretString,
+    ) -> Result<OAuthClient, OAuthClientStoreError>;
+
+    async fn add_initial_access_token(
+        &mut self,
+        initial_access_token: &RegistrationToken,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), OAuthClientStoreError>;
+
+    /// Uses up an unexpired initial access token (RFC 7591 section 3), so each one can only
+    /// register a single client.
+    async fn use_initial_access_token(
+        &mut self,
+        initial_access_token: &SecretString,
+    ) -> Result<(), OAuthClientStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum OAuthClientStoreError {
+    #[error("Client already exists")]
+    ClientAlreadyExists,
+    #[error("Client not found")]
+    ClientNotFound,
+    #[error("Invalid credentials")]
+    InvalidCredentials,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for OAuthClientStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::ClientAlreadyExists, Self::ClientAlreadyExists)
+                | (Self::ClientNotFound, Self::ClientNotFound)
+                | (Self::InvalidCredentials, Self::InvalidCredentials)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OAuthClient {
+    pub client_id: String,
+    /// A human readable name, as in RFC 7591's `client_name`.
+    pub client_name: Option<String>,
+    /// `/authorize` only sends users back to one of these, compared as exact strings.
+    pub redirect_uris: Vec<String>,
+    /// The most a client may ask for; tokens are only ever issued for a subset of these.
+    pub allowed_scopes: Vec<String>,
+    pub allowed_grant_types: Vec<GrantType>,
+}
+
+impl OAuthClient {
+    /// Whether the client may get tokens for itself. Those name the client id as their
+    /// subject, where tokens for users name an email address, so an id that parses as one
+    /// could pass the client off as that user.
+    pub fn can_act_for_itself(&self) -> bool {
+        self.allowed_grant_types
+            .contains(&GrantType::ClientCredentials)
+            && Email::parse(SecretString::new(self.client_id.clone().into_boxed_str())).is_err()
+    }
+}
+
+/// The `grant_type`s a client may redeem at `/token` (RFC 6749 section 4).
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GrantType {
+    /// Users signing in to the client through `/authorize`.
+    AuthorizationCode,
+    /// The client acting on its own behalf, e.g. a backend job calling another service.
+    ClientCredentials,
+    /// Users signing in to a CLI or other device without a browser (RFC 8628).
+    #[serde(rename = "urn:ietf:params:oauth:grant-type:device_code")]
+    DeviceCode,
+    /// Keeping a user signed in after an authorization code or device code grant. Clients
+    /// allowed this grant get a refresh token alongside the access token.
+    RefreshToken,
+}
+
+impl GrantType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::AuthorizationCode => "authorization_code",
+            Self::ClientCredentials => "client_credentials",
+            Self::DeviceCode => "urn:ietf:params:oauth:grant-type:device_code",
+            Self::RefreshToken => "refresh_token",
+        }
+    }
+
+    pub fn parse(grant_type: &str) -> Result<Self> {
+        match grant_type {
+            "authorization_code" => Ok(Self::AuthorizationCode),
+            "client_credentials" => Ok(Self::ClientCredentials),
+            "urn:ietf:params:oauth:grant-type:device_code" => Ok(Self::DeviceCode),
+            "refresh_token" => Ok(Self::RefreshToken),
+            _ => Err(eyre!("Unknown grant type: {}", grant_type)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientSecret(SecretString);
+
+const CLIENT_SECRET_BYTES: usize = 32;
+
+impl Default for ClientSecret {
+    fn default() -> Self {
+        let mut bytes = [0u8; CLIENT_SECRET_BYTES];
+        rand::rng().fill_bytes(&mut bytes);
+        Self(SecretString::new(
+            URL_SAFE_NO_PAD.encode(bytes).into_boxed_str(),
+        ))
+    }
+}
+
+impl AsRef<SecretString> for ClientSecret {
+    fn as_ref(&self) -> &SecretString {
+        &self.0
+    }
+}
+
+/// A bearer token for client registration: either an initial access token, which lets a
+/// client register itself, or the registration access token it then manages itself with.
+#[derive(Debug, Clone)]
+pub struct RegistrationToken(SecretString);
+
+const REGISTRATION_TOKEN_BYTES: usize = 32;
+
+impl Default for RegistrationToken {
+    fn default() -> Self {
+        let mut bytes = [0u8; REGISTRATION_TOKEN_BYTES];
+        rand::rng().fill_bytes(&mut bytes);
+        Self(SecretString::new(
+            URL_SAFE_NO_PAD.encode(bytes).into_boxed_str(),
+        ))
+    }
+}
+
+impl AsRef<SecretString> for RegistrationToken {
+    fn as_ref(&self) -> &SecretString {
+        &self.0
+    }
+}
+
+/// Holds authorization codes issued by `/authorize` until the client redeems them at
+/// `/token`.
+#[async_trait::async_trait]
+pub trait AuthorizationCodeStore {
+    async fn add_code(
+        &mut self,
+        code: &AuthorizationCode,
+        grant: AuthorizationGrant,
+    ) -> Result<(), AuthorizationCodeStoreError>;
+
+    /// Removes the code and returns what it grants, so each code can only be redeemed once.
+    async fn take_code(
+        &mut self,
+        code: &AuthorizationCode,
+    ) -> Result<AuthorizationGrant, AuthorizationCodeStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum AuthorizationCodeStoreError {
+    #[error("Code not found")]
+    CodeNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for AuthorizationCodeStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::CodeNotFound, Self::CodeNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+/// What the user agreed to at `/authorize`, checked again when the code is redeemed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthorizationGrant {
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub email: Email,
+    pub scope: String,
+    pub nonce: Option<String>,
+    /// The PKCE `S256` challenge the client's `code_verifier` must hash to.
+    pub code_challenge: String,
+    pub amr: Vec<AuthMethod>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthorizationCode(SecretString);
+
+impl PartialEq for AuthorizationCode {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+const AUTHORIZATION_CODE_BYTES: usize = 32;
+
+impl AuthorizationCode {
+    pub fn parse(code: SecretString) -> Result<Self> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(code.expose_secret())
+            .map_err(|_| eyre!("Invalid authorization code"))?;
+        if bytes.len() == AUTHORIZATION_CODE_BYTES {
+            Ok(Self(code))
+        } else {
+            Err(eyre!("Invalid authorization code"))
+        }
+    }
+}
+
+impl Default for AuthorizationCode {
+    fn default() -> Self {
+        let mut bytes = [0u8; AUTHORIZATION_CODE_BYTES];
+        rand::rng().fill_bytes(&mut bytes);
+        Self(SecretString::new(
+            URL_SAFE_NO_PAD.encode(bytes).into_boxed_str(),
+        ))
+    }
+}
+
+impl AsRef<SecretString> for AuthorizationCode {
+    fn as_ref(&self) -> &SecretString {
+        &self.0
+    }
+}
+
+/// Holds device authorization requests (RFC 8628) from `/device/code` while the user
+/// decides on them and until the device collects the result at `/token`.
+#[async_trait::async_trait]
+pub trait DeviceCodeStore {
+    /// Fails with `UserCodeAlreadyExists` if another pending request has the same user code.
+    async fn add_grant(
+        &mut self,
+        device_code: &DeviceCode,
+        user_code: &UserCode,
+        grant: DeviceGrant,
+    ) -> Result<(), DeviceCodeStoreError>;
+
+    /// Looks up a request the user has not decided on yet by the code they typed in.
+    async fn get_grant(&self, user_code: &UserCode) -> Result<DeviceGrant, DeviceCodeStoreError>;
+
+    /// Records the user's decision. Each user code can only be decided on once.
+    async fn decide(
+        &mut self,
+        user_code: &UserCode,
+        status: DeviceGrantStatus,
+    ) -> Result<(), DeviceCodeStoreError>;
+
+    /// Returns the request for the device. Once decided it is removed, so the result can
+    /// only be collected once.
+    async fn poll(&mut self, device_code: &DeviceCode)
+        -> Result<DeviceGrant, DeviceCodeStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum DeviceCodeStoreError {
+    #[error("User code already exists")]
+    UserCodeAlreadyExists,
+    #[error("Grant not found")]
+    GrantNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for DeviceCodeStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::UserCodeAlreadyExists, Self::UserCodeAlreadyExists)
+                | (Self::GrantNotFound, Self::GrantNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceGrant {
+    pub client_id: String,
+    pub scope: String,
+    pub status: DeviceGrantStatus,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeviceGrantStatus {
+    Pending,
+    Approved { email: Email, amr: Vec<AuthMethod> },
+    Denied,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeviceCode(SecretString);
+
+impl PartialEq for DeviceCode {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+const DEVICE_CODE_BYTES: usize = 32;
+
+impl DeviceCode {
+    pub fn parse(code: SecretString) -> Result<Self> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(code.expose_secret())
+            .map_err(|_| eyre!("Invalid device code"))?;
+        if bytes.len() == DEVICE_CODE_BYTES {
+            Ok(Self(code))
+        } else {
+            Err(eyre!("Invalid device code"))
+        }
+    }
+}
+
+impl Default for DeviceCode {
+    fn default() -> Self {
+        let mut bytes = [0u8; DEVICE_CODE_BYTES];
+        rand::rng().fill_bytes(&mut bytes);
+        Self(SecretString::new(
+            URL_SAFE_NO_PAD.encode(bytes).into_boxed_str(),
+        ))
+    }
+}
+
+impl AsRef<SecretString> for DeviceCode {
+    fn as_ref(&self) -> &SecretString {
+        &self.0
+    }
+}
+
+/// The short code a user types in to approve a device. Consonants only, so codes are
+/// easy to read out and never spell words (RFC 8628 section 6.1), shown as `BCDF-GHJK`.
+#[derive(Debug, Clone)]
+pub struct UserCode(SecretString);
+
+impl PartialEq for UserCode {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+const USER_CODE_ALPHABET: &[u8] = b"BCDFGHJKLMNPQRSTVWXZ";
+const USER_CODE_LENGTH: usize = 8;
+
+impl UserCode {
+    /// Accepts the code in any case, with or without the dash and spaces.
+    pub fn parse(code: SecretString) -> Result<Self> {
+        let code: String = code
+            .expose_secret()
+            .chars()
+            .filter(|c| *c != '-' && !c.is_whitespace())
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        if code.len() == USER_CODE_LENGTH && code.bytes().all(|b| USER_CODE_ALPHABET.contains(&b)) {
+            Ok(Self(SecretString::new(code.into_boxed_str())))
+        } else {
+            Err(eyre!("Invalid user code"))
+        }
+    }
+
+    /// The code as shown to the user.
+    pub fn display(&self) -> String {
+        let (first, second) = self.0.expose_secret().split_at(USER_CODE_LENGTH / 2);
+        format!("{}-{}", first, second)
+    }
+}
+
+impl Default for UserCode {
+    fn default() -> Self {
+        let mut rng = rand::rng();
+        let code: String = (0..USER_CODE_LENGTH)
+            .map(|_| USER_CODE_ALPHABET[rng.random_range(0..USER_CODE_ALPHABET.len())] as char)
+            .collect();
+        Self(SecretString::new(code.into_boxed_str()))
+    }
+}
+
+impl AsRef<SecretString> for UserCode {
+    fn as_ref(&self) -> &SecretString {
+        &self.0
+    }
+}
+
+/// Remembers a login sent to the upstream identity provider until the user comes back to
+/// `/federated/callback` with its `state`.
+#[async_trait::async_trait]
+pub trait FederatedLoginStore {
+    async fn add_login(
+        &mut self,
+        state: &FederatedLoginState,
+        login: FederatedLogin,
+    ) -> Result<(), FederatedLoginStoreError>;
+
+    /// Removes the login and returns it, so each `state` can only be used once.
+    async fn take_login(
+        &mut self,
+        state: &FederatedLoginState,
+    ) -> Result<FederatedLogin, FederatedLoginStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum FederatedLoginStoreError {
+    #[error("Login not found")]
+    LoginNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for FederatedLoginStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::LoginNotFound, Self::LoginNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+/// What is needed to finish a login once the upstream provider redirects back.
+#[derive(Debug, Clone)]
+pub struct FederatedLogin {
+    /// Must come back in the upstream ID token.
+    pub nonce: String,
+    /// The PKCE verifier for the code the upstream provider hands out.
+    pub code_verifier: SecretString,
+    /// Where to send the user afterwards. Only `/authorize` and `/device` are returned to.
+    pub return_to: Option<String>,
+    /// Set when a logged in user is linking the upstream identity to their account,
+    /// rather than logging in with it.
+    pub link_to: Option<Email>,
+}
+
+/// The OAuth `state` of a login at the upstream provider.
+#[derive(Debug, Clone)]
+pub struct FederatedLoginState(SecretString);
+
+impl PartialEq for FederatedLoginState {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+const FEDERATED_LOGIN_STATE_BYTES: usize = 32;
+
+impl FederatedLoginState {
+    pub fn parse(state: SecretString) -> Result<Self> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(state.expose_secret())
+            .map_err(|_| eyre!("Invalid federated login state"))?;
+        if bytes.len() == FEDERATED_LOGIN_STATE_BYTES {
+            Ok(Self(state))
+        } else {
+            Err(eyre!("Invalid federated login state"))
+        }
+    }
+}
+
+impl Default for FederatedLoginState {
+    fn default() -> Self {
+        let mut bytes = [0u8; FEDERATED_LOGIN_STATE_BYTES];
+        rand::rng().fill_bytes(&mut bytes);
+        Self(SecretString::new(
+            URL_SAFE_NO_PAD.encode(bytes).into_boxed_str(),
+        ))
+    }
+}
+
+impl AsRef<SecretString> for FederatedLoginState {
+    fn as_ref(&self) -> &SecretString {
+        &self.0
+    }
+}
+
+/// Ties identities at upstream providers to local users, so a user keeps logging in to the
+/// same account even if the provider later asserts a different email address.
+#[async_trait::async_trait]
+pub trait UserIdentityStore {
+    /// Fails with `IdentityAlreadyLinked` if the identity belongs to any user already.
+    async fn add_identity(
+        &mut self,
+        email: &Email,
+        provider: &str,
+        subject: &str,
+    ) -> Result<(), UserIdentityStoreError>;
+
+    async fn get_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<UserIdentity, UserIdentityStoreError>;
+
+    async fn get_identities(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<UserIdentity>, UserIdentityStoreError>;
+
+    /// Only removes the identity if it is linked to `email`.
+    async fn remove_identity(
+        &mut self,
+        email: &Email,
+        provider: &str,
+        subject: &str,
+    ) -> Result<(), UserIdentityStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum UserIdentityStoreError {
+    #[error("Identity already linked")]
+    IdentityAlreadyLinked,
+    #[error("Identity not found")]
+    IdentityNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for UserIdentityStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::IdentityAlreadyLinked, Self::IdentityAlreadyLinked)
+                | (Self::IdentityNotFound, Self::IdentityNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+/// A user's account at an upstream provider, identified by the provider's issuer and the
+/// `sub` claim of its ID tokens.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserIdentity {
+    pub provider: String,
+    pub subject: String,
+    pub email: Email,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Keeps the personal access tokens users create for scripts and other non-interactive
+/// clients. Only a hash of each token is stored.
+#[async_trait::async_trait]
+pub trait PersonalAccessTokenStore {
+    async fn add_token(
+        &mut self,
+        token: &PersonalAccessToken,
+        record: PersonalAccessTokenRecord,
+    ) -> Result<(), PersonalAccessTokenStoreError>;
+
+    async fn get_tokens(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<PersonalAccessTokenRecord>, PersonalAccessTokenStoreError>;
+
+    /// Looks up an unexpired token and records that it was just used. Fails with
+    /// `TokenNotFound` for unknown, revoked and expired tokens alike.
+    async fn use_token(
+        &mut self,
+        token: &PersonalAccessToken,
+    ) -> Result<PersonalAccessTokenRecord, PersonalAccessTokenStoreError>;
+
+    /// Only revokes the token if it belongs to `email`.
+    async fn revoke_token(
+        &mut self,
+        email: &Email,
+        id: Uuid,
+    ) -> Result<(), PersonalAccessTokenStoreError>;
+
+    async fn revoke_all_tokens(
+        &mut self,
+        email: &Email,
+    ) -> Result<(), PersonalAccessTokenStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum PersonalAccessTokenStoreError {
+    #[error("Personal access token not found")]
+    TokenNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for PersonalAccessTokenStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::TokenNotFound, Self::TokenNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PersonalAccessTokenRecord {
+    pub id: Uuid,
+    pub email: Email,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    /// `None` for tokens that never expire.
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// A long-lived bearer token a user creates for themselves. The prefix tells it apart from
+/// the JWTs `validate_token` otherwise expects.
+#[derive(Debug, Clone)]
+pub struct PersonalAccessToken(SecretString);
+
+impl PartialEq for PersonalAccessToken {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.expose_secret() == other.0.expose_secret()
+    }
+}
+
+const PERSONAL_ACCESS_TOKEN_PREFIX: &str = "pat_";
+const PERSONAL_ACCESS_TOKEN_BYTES: usize = 32;
+
+impl PersonalAccessToken {
+    pub fn parse(token: SecretString) -> Result<Self> {
+        let bytes = token
+            .expose_secret()
+            .strip_prefix(PERSONAL_ACCESS_TOKEN_PREFIX)
+            .and_then(|encoded| URL_SAFE_NO_PAD.decode(encoded).ok())
+            .ok_or_else(|| eyre!("Invalid personal access token"))?;
+        if bytes.len() == PERSONAL_ACCESS_TOKEN_BYTES {
+            Ok(Self(token))
+        } else {
+            Err(eyre!("Invalid personal access token"))
+        }
+    }
+}
+
+impl Default for PersonalAccessToken {
+    fn default() -> Self {
+        let mut bytes = [0u8; PERSONAL_ACCESS_TOKEN_BYTES];
+        rand::rng().fill_bytes(&mut bytes);
+        Self(SecretString::new(
+            format!(
+                "{}{}",
+                PERSONAL_ACCESS_TOKEN_PREFIX,
+                URL_SAFE_NO_PAD.encode(bytes)
+            )
+            .into_boxed_str(),
+        ))
+    }
+}
+
+impl AsRef<SecretString> for PersonalAccessToken {
+    fn as_ref(&self) -> &SecretString {
+        &self.0
+    }
+}
+
+/// Keeps a record of each login session so users can see where they are logged in and end
+/// sessions on other devices. A session lives until it is revoked or its refresh token
+/// expires.
+#[async_trait::async_trait]
+pub trait SessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError>;
+
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError>;
+
+    /// `false` once the session has been revoked.
+    async fn contains_session(&self, id: Uuid) -> Result<bool, SessionStoreError>;
+
+    /// Records that the session was just used. Fails with `SessionNotFound` if it has
+    /// been revoked.
+    async fn touch_session(&mut self, id: Uuid) -> Result<(), SessionStoreError>;
+
+    /// Only revokes the session if it belongs to `email`.
+    async fn revoke_session(&mut self, email: &Email, id: Uuid) -> Result<(), SessionStoreError>;
+
+    async fn revoke_all_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError>;
+}
+
+#[derive(Debug, Error)]
+pub enum SessionStoreError {
+    #[error("Session not found")]
+    SessionNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for SessionStoreError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::SessionNotFound, Self::SessionNotFound)
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub id: Uuid,
+    pub email: Email,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+    /// The address the session was started from.
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    /// How the user logged in, as in the `amr` claim.
+    pub amr: Vec<AuthMethod>,
+}
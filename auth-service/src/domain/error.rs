@@ -3,8 +3,6 @@ use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum AuthAPIError {
-    #[error("User already exists")]
-    UserAlreadyExists,
     #[error("Invalid credentials")]
     InvalidCredentials,
     #[error("Incorrect credentials")]
@@ -13,6 +11,77 @@ pub enum AuthAPIError {
     MissingToken,
     #[error("Invalid token")]
     InvalidToken,
+    #[error("Email not verified")]
+    EmailNotVerified,
+    #[error("Too many requests")]
+    TooManyRequests,
+    #[error("Too many attempts")]
+    TooManyAttempts,
+    #[error("Login locked")]
+    LoginLocked(LockoutScope),
+    #[error("TOTP not enrolled")]
+    TotpNotEnrolled,
+    #[error("OAuth client not found")]
+    ClientNotFound,
+    #[error("Federated login disabled")]
+    FederatedLoginDisabled,
+    #[error("Identity already linked")]
+    IdentityAlreadyLinked,
+    #[error("Identity not found")]
+    IdentityNotFound,
+    #[error("Identity not linked")]
+    IdentityNotLinked,
+    #[error("Personal access token not found")]
+    PersonalAccessTokenNotFound,
+    #[error("Session not found")]
+    SessionNotFound,
+    #[error("User not found")]
+    UserNotFound,
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+/// What too many failed logins have locked: the account, or the address they came from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LockoutScope {
+    Account,
+    Address,
+}
+
+/// Errors from the `/oauth/*` and OpenID Connect endpoints. These answer with the error
+/// codes of RFC 6749 section 5.2 (and RFC 6750 for `/userinfo`, RFC 8628 for device
+/// codes, RFC 7591 for client registration) instead of `AuthAPIError`'s messages, since
+/// OAuth clients match on them.
+#[derive(Debug, Error)]
+pub enum OAuthError {
+    #[error("Invalid request")]
+    InvalidRequest,
+    #[error("Invalid client")]
+    InvalidClient,
+    #[error("Invalid grant")]
+    InvalidGrant,
+    #[error("Unsupported grant type")]
+    UnsupportedGrantType,
+    #[error("Unauthorized client")]
+    UnauthorizedClient,
+    #[error("Invalid scope")]
+    InvalidScope,
+    #[error("Invalid target")]
+    InvalidTarget,
+    #[error("Invalid token")]
+    InvalidToken,
+    #[error("Authorization pending")]
+    AuthorizationPending,
+    #[error("Slow down")]
+    SlowDown,
+    #[error("Access denied")]
+    AccessDenied,
+    #[error("Expired token")]
+    ExpiredToken,
+    #[error("Invalid redirect URI")]
+    InvalidRedirectUri,
+    #[error("Invalid client metadata")]
+    InvalidClientMetadata,
     #[error("Unexpected error")]
     UnexpectedError(#[source] Report),
 }
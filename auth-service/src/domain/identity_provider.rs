@@ -0,0 +1,64 @@
+use color_eyre::eyre::Report;
+use secrecy::SecretString;
+use thiserror::Error;
+
+use super::Email;
+
+/// An upstream OpenID Connect provider users can log in through instead of with a
+/// password.
+#[async_trait::async_trait]
+pub trait IdentityProvider {
+    /// The provider's issuer identifier, which scopes the subjects it asserts.
+    fn issuer(&self) -> &str;
+
+    /// Where to send the user to log in. `code_challenge` is a PKCE `S256` challenge.
+    async fn authorization_url(
+        &self,
+        state: &SecretString,
+        nonce: &str,
+        code_challenge: &str,
+        redirect_uri: &str,
+    ) -> Result<String, IdentityProviderError>;
+
+    /// Redeems the code the provider redirected back with and validates the ID token that
+    /// comes with it, which must carry `nonce`.
+    async fn authenticate(
+        &self,
+        code: &SecretString,
+        code_verifier: &SecretString,
+        redirect_uri: &str,
+        nonce: &str,
+    ) -> Result<UpstreamIdentity, IdentityProviderError>;
+}
+
+#[derive(Debug, Error)]
+pub enum IdentityProviderError {
+    #[error("Invalid grant")]
+    InvalidGrant,
+    #[error("Invalid ID token")]
+    InvalidIdToken(#[source] Report),
+    #[error("Unexpected error")]
+    UnexpectedError(#[source] Report),
+}
+
+impl PartialEq for IdentityProviderError {
+    fn eq(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::InvalidGrant, Self::InvalidGrant)
+                | (Self::InvalidIdToken(_), Self::InvalidIdToken(_))
+                | (Self::UnexpectedError(_), Self::UnexpectedError(_))
+        )
+    }
+}
+
+/// Who the upstream provider says the user is, taken from a validated ID token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpstreamIdentity {
+    pub subject: String,
+    pub email: Option<Email>,
+    /// Whether the provider asserts it has checked the user controls `email`.
+    pub email_verified: bool,
+    /// The provider's own `amr` values.
+    pub amr: Vec<String>,
+}
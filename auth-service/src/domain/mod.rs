@@ -2,12 +2,16 @@ pub mod data_stores;
 pub mod email;
 pub mod email_client;
 mod error;
+pub mod identity_provider;
 mod password;
+mod rate_limit;
 mod user;
 
 pub use data_stores::*;
 pub use email::*;
 pub use email_client::*;
 pub use error::*;
+pub use identity_provider::*;
 pub use password::*;
+pub use rate_limit::*;
 pub use user::*;
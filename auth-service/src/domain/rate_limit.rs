@@ -0,0 +1,142 @@
+/// Allows `limit` requests per `period_seconds`. The whole allowance can be used in one
+/// burst, after which requests are let through evenly spaced, one every
+/// `period_seconds / limit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitQuota {
+    pub limit: u32,
+    pub period_seconds: u64,
+}
+
+/// Whether a request fits its quota, and what to tell the client about it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub limit: u32,
+    /// How many more requests would be let through right now.
+    pub remaining: u32,
+    /// Milliseconds until the whole allowance is available again.
+    pub reset_after_ms: u64,
+    /// Milliseconds until a request would be let through; zero if this one was.
+    pub retry_after_ms: u64,
+}
+
+impl RateLimitQuota {
+    pub fn new(limit: u32, period_seconds: u64) -> Self {
+        Self {
+            limit,
+            period_seconds,
+        }
+    }
+
+    /// Checks a request made at `now` with the generic cell rate algorithm (GCRA). The only
+    /// state is the theoretical arrival time (TAT): when the key's allowance would be
+    /// back to full. Both times are in milliseconds since the epoch.
+    ///
+    /// Returns the decision, and the TAT to store if the request was let through.
+    pub fn check(&self, tat: Option<i64>, now: i64) -> (RateLimitDecision, Option<i64>) {
+        let limit = self.limit.max(1);
+        let interval = (self.period_seconds as i64 * 1000 / limit as i64).max(1);
+        let period = interval * limit as i64;
+
+        let tat = tat.unwrap_or(now).max(now);
+        let new_tat = tat + interval;
+        let allow_at = new_tat - period;
+
+        if now < allow_at {
+            let decision = RateLimitDecision {
+                allowed: false,
+                limit,
+                remaining: 0,
+                reset_after_ms: (tat - now) as u64,
+                retry_after_ms: (allow_at - now) as u64,
+            };
+
+            return (decision, None);
+        }
+
+        let decision = RateLimitDecision {
+            allowed: true,
+            limit,
+            remaining: ((now - allow_at) / interval) as u32,
+            reset_after_ms: (new_tat - now) as u64,
+            retry_after_ms: 0,
+        };
+
+        (decision, Some(new_tat))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000_000;
+
+    // Runs `requests` requests at `now` and returns the last decision and the TAT.
+    fn burst(
+        quota: &RateLimitQuota,
+        mut tat: Option<i64>,
+        now: i64,
+        requests: u32,
+    ) -> (RateLimitDecision, Option<i64>) {
+        let mut last = None;
+        for _ in 0..requests {
+            let (decision, new_tat) = quota.check(tat, now);
+            tat = new_tat.or(tat);
+            last = Some(decision);
+        }
+        (last.unwrap(), tat)
+    }
+
+    #[test]
+    fn should_allow_whole_limit_in_one_burst() {
+        let quota = RateLimitQuota::new(5, 60);
+
+        let (decision, _) = quota.check(None, NOW);
+        assert!(decision.allowed);
+        assert_eq!(decision.limit, 5);
+        assert_eq!(decision.remaining, 4);
+        assert_eq!(decision.reset_after_ms, 12_000);
+
+        let (decision, _) = burst(&quota, None, NOW, 5);
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 0);
+        assert_eq!(decision.reset_after_ms, 60_000);
+    }
+
+    #[test]
+    fn should_reject_request_over_limit_until_interval_passes() {
+        let quota = RateLimitQuota::new(5, 60);
+        let (_, tat) = burst(&quota, None, NOW, 5);
+
+        let (decision, new_tat) = quota.check(tat, NOW);
+        assert!(!decision.allowed);
+        assert_eq!(decision.remaining, 0);
+        assert_eq!(decision.retry_after_ms, 12_000);
+        assert_eq!(new_tat, None);
+
+        let (decision, _) = quota.check(tat, NOW + 11_999);
+        assert!(!decision.allowed);
+        assert_eq!(decision.retry_after_ms, 1);
+
+        let (decision, _) = quota.check(tat, NOW + 12_000);
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 0);
+    }
+
+    #[test]
+    fn should_refill_allowance_over_period() {
+        let quota = RateLimitQuota::new(5, 60);
+        let (_, tat) = burst(&quota, None, NOW, 5);
+
+        let (decision, _) = quota.check(tat, NOW + 60_000);
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 4);
+
+        // An old TAT counts the same as none.
+        let (decision, new_tat) = quota.check(tat, NOW + 3_600_000);
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 4);
+        assert_eq!(new_tat, Some(NOW + 3_600_000 + 12_000));
+    }
+}
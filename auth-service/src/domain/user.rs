@@ -1,3 +1,6 @@
+use color_eyre::eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+
 use super::{Email, Password};
 
 #[derive(Clone, Debug, PartialEq)]
@@ -5,6 +8,8 @@ pub struct User {
     pub email: Email,
     pub password: Password,
     pub requires_2fa: bool,
+    pub verified: bool,
+    pub two_fa_method: TwoFAMethod,
 }
 
 impl User {
@@ -13,6 +18,82 @@ impl User {
             email,
             password,
             requires_2fa,
+            verified: false,
+            two_fa_method: TwoFAMethod::Email,
+        }
+    }
+}
+
+/// How a user with 2FA enabled proves the second factor.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TwoFAMethod {
+    /// A one-time code emailed on every login.
+    Email,
+    /// A code from an authenticator app (RFC 6238).
+    Totp,
+}
+
+impl TwoFAMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Email => "email",
+            Self::Totp => "totp",
+        }
+    }
+
+    pub fn parse(method: &str) -> Result<Self> {
+        match method {
+            "email" => Ok(Self::Email),
+            "totp" => Ok(Self::Totp),
+            _ => Err(eyre!("Unknown 2FA method: {}", method)),
+        }
+    }
+}
+
+/// A way a user proved who they are when starting a session, as reported in the `amr`
+/// claim (RFC 8176).
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum AuthMethod {
+    #[serde(rename = "pwd")]
+    Password,
+    /// An emailed 2FA code, an authenticator app code or a recovery code.
+    #[serde(rename = "otp")]
+    OneTimeCode,
+    #[serde(rename = "hwk")]
+    Passkey,
+    /// Not in the RFC 8176 registry, which has nothing for login links.
+    #[serde(rename = "email")]
+    MagicLink,
+    /// A login at the upstream identity provider. Not in the RFC 8176 registry either.
+    #[serde(rename = "fed")]
+    Federated,
+    /// Added alongside the second factor once 2FA has been completed.
+    #[serde(rename = "mfa")]
+    MultiFactor,
+}
+
+impl AuthMethod {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Password => "pwd",
+            Self::OneTimeCode => "otp",
+            Self::Passkey => "hwk",
+            Self::MagicLink => "email",
+            Self::Federated => "fed",
+            Self::MultiFactor => "mfa",
+        }
+    }
+
+    pub fn parse(method: &str) -> Result<Self> {
+        match method {
+            "pwd" => Ok(Self::Password),
+            "otp" => Ok(Self::OneTimeCode),
+            "hwk" => Ok(Self::Passkey),
+            "email" => Ok(Self::MagicLink),
+            "fed" => Ok(Self::Federated),
+            "mfa" => Ok(Self::MultiFactor),
+            _ => Err(eyre!("Unknown authentication method: {}", method)),
         }
     }
 }
@@ -1,16 +1,18 @@
 use axum::{
-    http::{Method, StatusCode},
+    extract::{connect_info::IntoMakeServiceWithConnectInfo, ConnectInfo},
+    http::{header, Method, StatusCode},
+    middleware::AddExtension,
     response::{IntoResponse, Response},
-    routing::post,
+    routing::{delete, get, post, put},
     serve::Serve,
     Json, Router,
 };
-use domain::AuthAPIError;
+use domain::{AuthAPIError, LockoutScope, OAuthError};
 use redis::{Client, RedisResult};
 use secrecy::{ExposeSecret, SecretString};
 use serde::{Deserialize, Serialize};
 use sqlx::{postgres::PgPoolOptions, PgPool};
-use std::error::Error;
+use std::{error::Error, net::SocketAddr};
 use tokio::net::TcpListener;
 use tower_http::{
     cors::CorsLayer,
@@ -18,7 +20,10 @@ use tower_http::{
     trace::TraceLayer,
 };
 
-use utils::tracing::{make_span_with_request_id, on_request, on_response};
+use utils::{
+    rate_limit::{rate_limited, RateLimits},
+    tracing::{make_span_with_request_id, on_request, on_response},
+};
 
 pub mod app_state;
 use app_state::AppState;
@@ -30,14 +35,22 @@ pub mod utils;
 
 // This struct encapsulates our application-related logic.
 pub struct Application {
-    server: Serve<TcpListener, Router, Router>,
+    server: Serve<
+        TcpListener,
+        IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+        AddExtension<Router, ConnectInfo<SocketAddr>>,
+    >,
     // address is exposed as a public field
     // so we have access to it in tests.
     pub address: String,
 }
 
 impl Application {
-    pub async fn build(app_state: AppState, address: &str) -> Result<Self, Box<dyn Error>> {
+    pub async fn build(
+        app_state: AppState,
+        rate_limits: RateLimits,
+        address: &str,
+    ) -> Result<Self, Box<dyn Error>> {
         let assets_dir =
             ServeDir::new("assets").not_found_service(ServeFile::new("assets/index.html"));
 
@@ -54,13 +67,166 @@ impl Application {
             .allow_credentials(true)
             .allow_origin(allowed_origins);
 
+        // Limits are counted in Redis, so they hold across all replicas.
+        let rate_limit_store = app_state.rate_limit_store.clone();
+
         let router = Router::new()
             .fallback_service(assets_dir)
-            .route("/signup", post(signup))
-            .route("/login", post(login))
-            .route("/verify-2fa", post(verify_2fa))
+            .route("/.well-known/jwks.json", get(jwks))
+            .route(
+                "/.well-known/openid-configuration",
+                get(openid_configuration),
+            )
+            .route(
+                "/signup",
+                rate_limited(post(signup), &rate_limit_store, rate_limits.signup),
+            )
+            .route(
+                "/login",
+                rate_limited(post(login), &rate_limit_store, rate_limits.login),
+            )
+            .route(
+                "/login/magic-link",
+                rate_limited(
+                    post(request_magic_link),
+                    &rate_limit_store,
+                    rate_limits.magic_link,
+                ),
+            )
+            .route("/login/magic-link/callback", post(redeem_magic_link))
+            .route(
+                "/login/unlock",
+                rate_limited(post(unlock_account), &rate_limit_store, rate_limits.unlock),
+            )
+            .route(
+                "/verify-2fa",
+                rate_limited(post(verify_2fa), &rate_limit_store, rate_limits.verify_2fa),
+            )
             .route("/logout", post(logout))
+            .route("/logout-all", post(logout_all))
             .route("/verify-token", post(verify_token))
+            .route("/token/refresh", post(refresh_token))
+            .route(
+                "/password-reset/request",
+                rate_limited(
+                    post(request_password_reset),
+                    &rate_limit_store,
+                    rate_limits.password_reset,
+                ),
+            )
+            .route("/password-reset/confirm", post(confirm_password_reset))
+            .route("/verify-email", post(verify_email))
+            .route(
+                "/verify-email/resend",
+                rate_limited(
+                    post(resend_verification_email),
+                    &rate_limit_store,
+                    rate_limits.verify_email_resend,
+                ),
+            )
+            .route("/2fa/totp/enroll", post(enroll_totp))
+            .route("/2fa/totp/confirm", post(confirm_totp))
+            .route("/2fa/method", put(set_two_fa_method))
+            .route("/2fa/recovery-codes", post(regenerate_recovery_codes))
+            .route(
+                "/verify-2fa/recovery",
+                rate_limited(
+                    post(verify_2fa_recovery_code),
+                    &rate_limit_store,
+                    rate_limits.verify_2fa,
+                ),
+            )
+            .route("/passkeys/register/start", post(start_passkey_registration))
+            .route(
+                "/passkeys/register/finish",
+                post(finish_passkey_registration),
+            )
+            .route(
+                "/login/passkey/start",
+                rate_limited(
+                    post(start_passkey_login),
+                    &rate_limit_store,
+                    rate_limits.login,
+                ),
+            )
+            .route(
+                "/login/passkey/finish",
+                rate_limited(
+                    post(finish_passkey_login),
+                    &rate_limit_store,
+                    rate_limits.passkey_login,
+                ),
+            )
+            .route(
+                "/verify-2fa/passkey/start",
+                rate_limited(
+                    post(start_passkey_2fa),
+                    &rate_limit_store,
+                    rate_limits.verify_2fa,
+                ),
+            )
+            .route(
+                "/verify-2fa/passkey/finish",
+                rate_limited(
+                    post(finish_passkey_2fa),
+                    &rate_limit_store,
+                    rate_limits.verify_2fa,
+                ),
+            )
+            .route("/federated/login", get(start_federated_login))
+            .route("/federated/callback", get(finish_federated_login))
+            .route("/federated/identities", get(list_identities))
+            .route("/federated/link", post(start_identity_link))
+            .route("/federated/unlink", post(unlink_identity))
+            .route(
+                "/personal-access-tokens",
+                get(list_personal_access_tokens).post(create_personal_access_token),
+            )
+            .route(
+                "/personal-access-tokens/{id}",
+                delete(revoke_personal_access_token),
+            )
+            .route("/sessions", get(list_sessions))
+            .route("/sessions/{id}", delete(revoke_session))
+            .route("/authorize", get(authorize))
+            .route(
+                "/token",
+                rate_limited(post(token), &rate_limit_store, rate_limits.token),
+            )
+            .route("/userinfo", get(userinfo).post(userinfo))
+            .route("/device/code", post(device_authorization))
+            .route_service("/device", ServeFile::new("assets/device.html"))
+            .route("/device/lookup", post(lookup_device_request))
+            .route("/device/confirm", post(confirm_device_request))
+            .route("/register", post(register_client))
+            .route(
+                "/register/{client_id}",
+                get(get_registered_client)
+                    .put(update_registered_client)
+                    .delete(delete_registered_client),
+            )
+            .route("/oauth/introspect", post(introspect))
+            .route("/oauth/revoke", post(revoke))
+            .route("/admin/signing-keys/rotate", post(rotate_signing_key))
+            .route("/admin/logout-all", post(admin_logout_all))
+            .route(
+                "/admin/oauth-clients",
+                get(list_oauth_clients).post(create_oauth_client),
+            )
+            .route(
+                "/admin/oauth-clients/{client_id}",
+                get(get_oauth_client)
+                    .put(update_oauth_client)
+                    .delete(delete_oauth_client),
+            )
+            .route(
+                "/admin/oauth-clients/{client_id}/secret",
+                post(rotate_oauth_client_secret),
+            )
+            .route(
+                "/admin/initial-access-tokens",
+                post(create_initial_access_token),
+            )
             .with_state(app_state)
             .layer(cors)
             .layer(
@@ -75,7 +241,11 @@ impl Application {
 
         let listener = TcpListener::bind(address).await?;
         let address = listener.local_addr()?.to_string();
-        let server = axum::serve(listener, router);
+        // Sessions record the address they were started from.
+        let server = axum::serve(
+            listener,
+            router.into_make_service_with_connect_info::<SocketAddr>(),
+        );
 
         Ok(Application { server, address })
     }
@@ -96,13 +266,40 @@ impl IntoResponse for AuthAPIError {
         log_error_chain(&self);
 
         let (status, error_message) = match self {
-            AuthAPIError::UserAlreadyExists => (StatusCode::CONFLICT, "User already exists"),
             AuthAPIError::InvalidCredentials => (StatusCode::BAD_REQUEST, "Invalid credentials"),
             AuthAPIError::IncorrectCredentials => {
                 (StatusCode::UNAUTHORIZED, "Incorrect credentials")
             }
             AuthAPIError::MissingToken => (StatusCode::BAD_REQUEST, "Missing auth token"),
             AuthAPIError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid auth token"),
+            AuthAPIError::EmailNotVerified => (StatusCode::FORBIDDEN, "Email not verified"),
+            AuthAPIError::TooManyRequests => (StatusCode::TOO_MANY_REQUESTS, "Too many requests"),
+            AuthAPIError::TooManyAttempts => (StatusCode::TOO_MANY_REQUESTS, "Too many attempts"),
+            AuthAPIError::LoginLocked(LockoutScope::Account) => {
+                (StatusCode::LOCKED, "Account temporarily locked")
+            }
+            AuthAPIError::LoginLocked(LockoutScope::Address) => {
+                (StatusCode::TOO_MANY_REQUESTS, "Too many failed logins")
+            }
+            AuthAPIError::TotpNotEnrolled => (StatusCode::BAD_REQUEST, "TOTP not enrolled"),
+            AuthAPIError::ClientNotFound => (StatusCode::NOT_FOUND, "OAuth client not found"),
+            AuthAPIError::FederatedLoginDisabled => {
+                (StatusCode::NOT_FOUND, "Federated login is not configured")
+            }
+            AuthAPIError::IdentityAlreadyLinked => (
+                StatusCode::CONFLICT,
+                "Identity is already linked to another account",
+            ),
+            AuthAPIError::IdentityNotFound => (StatusCode::NOT_FOUND, "Linked identity not found"),
+            AuthAPIError::IdentityNotLinked => (
+                StatusCode::FORBIDDEN,
+                "Log in and link this identity to your account first",
+            ),
+            AuthAPIError::PersonalAccessTokenNotFound => {
+                (StatusCode::NOT_FOUND, "Personal access token not found")
+            }
+            AuthAPIError::SessionNotFound => (StatusCode::NOT_FOUND, "Session not found"),
+            AuthAPIError::UserNotFound => (StatusCode::NOT_FOUND, "User not found"),
             AuthAPIError::UnexpectedError(_) => {
                 (StatusCode::INTERNAL_SERVER_ERROR, "Unexpected error")
             }
@@ -114,6 +311,60 @@ impl IntoResponse for AuthAPIError {
     }
 }
 
+impl IntoResponse for OAuthError {
+    fn into_response(self) -> Response {
+        log_error_chain(&self);
+
+        // RFC 6749 section 5.2 and RFC 6750 section 3: a 401 must say which credentials
+        // were expected.
+        let (status, error_code, challenge) = match self {
+            OAuthError::InvalidRequest => (StatusCode::BAD_REQUEST, "invalid_request", None),
+            OAuthError::InvalidClient => {
+                (StatusCode::UNAUTHORIZED, "invalid_client", Some("Basic"))
+            }
+            OAuthError::InvalidGrant => (StatusCode::BAD_REQUEST, "invalid_grant", None),
+            OAuthError::UnsupportedGrantType => {
+                (StatusCode::BAD_REQUEST, "unsupported_grant_type", None)
+            }
+            OAuthError::UnauthorizedClient => {
+                (StatusCode::BAD_REQUEST, "unauthorized_client", None)
+            }
+            OAuthError::InvalidScope => (StatusCode::BAD_REQUEST, "invalid_scope", None),
+            OAuthError::InvalidTarget => (StatusCode::BAD_REQUEST, "invalid_target", None),
+            OAuthError::InvalidToken => (
+                StatusCode::UNAUTHORIZED,
+                "invalid_token",
+                Some(r#"Bearer error="invalid_token""#),
+            ),
+            OAuthError::AuthorizationPending => {
+                (StatusCode::BAD_REQUEST, "authorization_pending", None)
+            }
+            OAuthError::SlowDown => (StatusCode::BAD_REQUEST, "slow_down", None),
+            OAuthError::AccessDenied => (StatusCode::BAD_REQUEST, "access_denied", None),
+            OAuthError::ExpiredToken => (StatusCode::BAD_REQUEST, "expired_token", None),
+            OAuthError::InvalidRedirectUri => {
+                (StatusCode::BAD_REQUEST, "invalid_redirect_uri", None)
+            }
+            OAuthError::InvalidClientMetadata => {
+                (StatusCode::BAD_REQUEST, "invalid_client_metadata", None)
+            }
+            OAuthError::UnexpectedError(_) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "server_error", None)
+            }
+        };
+        let body = Json(ErrorResponse {
+            error: error_code.to_owned(),
+        });
+
+        match challenge {
+            Some(challenge) => {
+                (status, [(header::WWW_AUTHENTICATE, challenge)], body).into_response()
+            }
+            None => (status, body).into_response(),
+        }
+    }
+}
+
 fn log_error_chain(e: &(dyn Error + 'static)) {
     let separator =
         "\n-----------------------------------------------------------------------------------\n";
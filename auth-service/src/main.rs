@@ -5,15 +5,29 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 
 use auth_service::{
-    app_state::AppState,
+    app_state::{AppState, IdentityProviderType, KeyringType, SigningKeyStoreType},
     domain::Email,
     get_postgres_pool, get_redis_client,
     services::{
-        data_stores::{PostgresUserStore, RedisBannedTokenStore, RedisTwoFACodeStore},
+        data_stores::{
+            PostgresOAuthClientStore, PostgresPasskeyStore, PostgresPersonalAccessTokenStore,
+            PostgresRecoveryCodeStore, PostgresRefreshTokenStore, PostgresSessionStore,
+            PostgresSigningKeyStore, PostgresTotpSecretStore, PostgresUserIdentityStore,
+            PostgresUserStore, RedisAuthorizationCodeStore, RedisBannedTokenStore,
+            RedisDeviceCodeStore, RedisFederatedLoginStore, RedisLoginLockoutStore,
+            RedisMagicLinkStore, RedisPasskeyCeremonyStore, RedisRateLimitStore,
+            RedisTwoFACodeStore,
+        },
+        oidc_identity_provider::OidcIdentityProvider,
         postmark_email_client::PostmarkEmailClient,
     },
     utils::{
-        constants::{prod, DATABASE_URL, POSTMARK_AUTH_TOKEN, REDIS_HOST_NAME},
+        constants::{
+            prod, AUTH_SERVICE_URL, DATABASE_URL, POSTMARK_AUTH_TOKEN, REDIS_HOST_NAME,
+            UPSTREAM_OIDC_CLIENT_ID, UPSTREAM_OIDC_CLIENT_SECRET, UPSTREAM_OIDC_ISSUER,
+        },
+        rate_limit::RateLimits,
+        signing_key::Keyring,
         tracing::init_tracing,
     },
     Application,
@@ -23,21 +37,76 @@ use auth_service::{
 async fn main() {
     color_eyre::install().expect("Failed to install color_eyre");
     init_tracing().expect("Failed to initialize tracing");
+    // Fail before serving anything rather than on the first request that builds a link.
+    lazy_static::initialize(&AUTH_SERVICE_URL);
 
     let pg_pool = configure_postgresql().await;
-    let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
+    let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool.clone())));
+    let refresh_token_store =
+        Arc::new(RwLock::new(PostgresRefreshTokenStore::new(pg_pool.clone())));
+    let totp_secret_store = Arc::new(RwLock::new(PostgresTotpSecretStore::new(pg_pool.clone())));
+    let passkey_store = Arc::new(RwLock::new(PostgresPasskeyStore::new(pg_pool.clone())));
+    let recovery_code_store =
+        Arc::new(RwLock::new(PostgresRecoveryCodeStore::new(pg_pool.clone())));
+    let signing_key_store: SigningKeyStoreType =
+        Arc::new(RwLock::new(PostgresSigningKeyStore::new(pg_pool.clone())));
+    let oauth_client_store = Arc::new(RwLock::new(PostgresOAuthClientStore::new(pg_pool.clone())));
+    let user_identity_store =
+        Arc::new(RwLock::new(PostgresUserIdentityStore::new(pg_pool.clone())));
+    let personal_access_token_store = Arc::new(RwLock::new(PostgresPersonalAccessTokenStore::new(
+        pg_pool.clone(),
+    )));
+    let session_store = Arc::new(RwLock::new(PostgresSessionStore::new(pg_pool)));
+    let keyring = Arc::new(
+        Keyring::load(&signing_key_store)
+            .await
+            .expect("Failed to load JWT signing keys"),
+    );
+    tokio::spawn(refresh_keyring(keyring.clone(), signing_key_store.clone()));
     let redis_conn = Arc::new(RwLock::new(configure_redis()));
     let banned_token_store = Arc::new(RwLock::new(RedisBannedTokenStore::new(redis_conn.clone())));
-    let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_conn)));
+    let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_conn.clone())));
+    let rate_limit_store = Arc::new(RwLock::new(RedisRateLimitStore::new(redis_conn.clone())));
+    let passkey_ceremony_store = Arc::new(RwLock::new(RedisPasskeyCeremonyStore::new(
+        redis_conn.clone(),
+    )));
+    let magic_link_store = Arc::new(RwLock::new(RedisMagicLinkStore::new(redis_conn.clone())));
+    let authorization_code_store = Arc::new(RwLock::new(RedisAuthorizationCodeStore::new(
+        redis_conn.clone(),
+    )));
+    let device_code_store = Arc::new(RwLock::new(RedisDeviceCodeStore::new(redis_conn.clone())));
+    let federated_login_store = Arc::new(RwLock::new(RedisFederatedLoginStore::new(
+        redis_conn.clone(),
+    )));
+    let login_lockout_store = Arc::new(RwLock::new(RedisLoginLockoutStore::new(redis_conn)));
     let email_client = Arc::new(configure_postmark_email_client());
+    let identity_provider = configure_identity_provider();
     let app_state = AppState::new(
         user_store,
         banned_token_store,
         two_fa_code_store,
+        refresh_token_store,
+        rate_limit_store,
+        totp_secret_store,
+        passkey_store,
+        passkey_ceremony_store,
+        recovery_code_store,
+        magic_link_store,
+        signing_key_store,
+        keyring,
+        oauth_client_store,
+        authorization_code_store,
+        device_code_store,
+        federated_login_store,
+        user_identity_store,
+        personal_access_token_store,
+        session_store,
+        login_lockout_store,
         email_client,
+        identity_provider,
     );
 
-    let app = Application::build(app_state, prod::APP_ADDRESS)
+    let app = Application::build(app_state, RateLimits::default(), prod::APP_ADDRESS)
         .await
         .expect("Failed to build app");
 
@@ -74,6 +143,43 @@ fn configure_postmark_email_client() -> PostmarkEmailClient {
     )
 }
 
+// Federated login is only enabled when an upstream issuer is configured, and then needs
+// the client credentials registered there too.
+fn configure_identity_provider() -> Option<IdentityProviderType> {
+    let issuer = UPSTREAM_OIDC_ISSUER.clone()?;
+
+    let client_id = UPSTREAM_OIDC_CLIENT_ID
+        .clone()
+        .expect("UPSTREAM_OIDC_CLIENT_ID must be set when UPSTREAM_OIDC_ISSUER is.");
+    let client_secret = UPSTREAM_OIDC_CLIENT_SECRET
+        .clone()
+        .expect("UPSTREAM_OIDC_CLIENT_SECRET must be set when UPSTREAM_OIDC_ISSUER is.");
+
+    let http_client = Client::builder()
+        .timeout(prod::identity_provider::TIMEOUT)
+        .build()
+        .expect("Failed to build HTTP client");
+
+    Some(Arc::new(OidcIdentityProvider::new(
+        issuer,
+        client_id,
+        client_secret,
+        http_client,
+    )))
+}
+
+// Another instance may have rotated the signing key, so the keyring is reloaded regularly
+// to start trusting the new key.
+async fn refresh_keyring(keyring: KeyringType, signing_key_store: SigningKeyStoreType) {
+    let mut interval = tokio::time::interval(prod::KEYRING_REFRESH_INTERVAL);
+    loop {
+        interval.tick().await;
+        if let Err(e) = keyring.refresh(&signing_key_store).await {
+            tracing::error!("failed to refresh JWT signing keys: {:?}", e);
+        }
+    }
+}
+
 fn configure_redis() -> redis::Connection {
     get_redis_client(REDIS_HOST_NAME.to_owned())
         .expect("Failed to get Redis client")
@@ -0,0 +1,222 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::{Duration, Utc};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, LockoutScope, UserStoreError},
+    utils::{
+        auth::{generate_purpose_token, validate_purpose_token, ClientInfo, TokenPurpose},
+        constants::{
+            AUTH_SERVICE_URL, LOGIN_FAILURE_WINDOW_SECONDS, LOGIN_IP_FAILURE_LIMIT,
+            LOGIN_LOCKOUT_SECONDS, LOGIN_LOCKOUT_THRESHOLD, MAX_LOGIN_LOCKOUT_SECONDS,
+        },
+        email::send_in_background,
+    },
+};
+
+/// Lifts a lockout with the link from the email sent when the account was locked.
+#[tracing::instrument(name = "Unlock account", skip_all)]
+pub async fn unlock_account(
+    State(state): State<AppState>,
+    Json(request): Json<UnlockAccountRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let claims = validate_purpose_token(
+        &request.token,
+        TokenPurpose::AccountUnlock,
+        state.banned_token_store.clone(),
+        &state.keyring,
+    )
+    .await
+    .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let email = Email::parse(SecretString::new(claims.sub.into_boxed_str()))
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    state
+        .login_lockout_store
+        .write()
+        .await
+        .unlock(&account_key(&email))
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let response = Json(UnlockAccountResponse {
+        message: "Account unlocked".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+/// Turns a login away before its password is checked if the account is locked, or its
+/// address has failed too often.
+pub(crate) async fn check_login_lockout(
+    state: &AppState,
+    email: &Email,
+    client: &ClientInfo,
+) -> Result<(), AuthAPIError> {
+    let login_lockout_store = state.login_lockout_store.read().await;
+
+    if let Some(ip) = &client.ip {
+        let failures = login_lockout_store
+            .count_failures(&address_key(ip), *LOGIN_FAILURE_WINDOW_SECONDS)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+        if failures >= *LOGIN_IP_FAILURE_LIMIT {
+            return Err(AuthAPIError::LoginLocked(LockoutScope::Address));
+        }
+    }
+
+    let lockout = login_lockout_store
+        .get_lockout(&account_key(email))
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    if lockout
+        .locked_until
+        .is_some_and(|locked_until| Utc::now() < locked_until)
+    {
+        return Err(AuthAPIError::LoginLocked(LockoutScope::Account));
+    }
+
+    Ok(())
+}
+
+/// Counts a wrong password against the account and the address it came from. Returns the
+/// error to answer with: the account gets locked, and its owner emailed an unlock link,
+/// once it has failed too often.
+///
+/// Unknown accounts are counted and locked just the same, so lockouts don't give away
+/// which addresses are registered.
+pub(crate) async fn record_failed_login(
+    state: &AppState,
+    email: &Email,
+    client: &ClientInfo,
+) -> AuthAPIError {
+    match lock_after_failed_login(state, email, client).await {
+        Ok(true) => AuthAPIError::LoginLocked(LockoutScope::Account),
+        Ok(false) => AuthAPIError::IncorrectCredentials,
+        Err(e) => e,
+    }
+}
+
+/// Forgets the account's failures once its password has been entered correctly.
+pub(crate) async fn clear_failed_logins(
+    state: &AppState,
+    email: &Email,
+) -> Result<(), AuthAPIError> {
+    state
+        .login_lockout_store
+        .write()
+        .await
+        .clear_failures(&account_key(email))
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
+
+async fn lock_after_failed_login(
+    state: &AppState,
+    email: &Email,
+    client: &ClientInfo,
+) -> Result<bool, AuthAPIError> {
+    let mut login_lockout_store = state.login_lockout_store.write().await;
+
+    if let Some(ip) = &client.ip {
+        login_lockout_store
+            .record_failure(&address_key(ip), *LOGIN_FAILURE_WINDOW_SECONDS)
+            .await
+            .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    }
+
+    let key = account_key(email);
+
+    let failures = login_lockout_store
+        .record_failure(&key, *LOGIN_FAILURE_WINDOW_SECONDS)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    if failures < *LOGIN_LOCKOUT_THRESHOLD {
+        return Ok(false);
+    }
+
+    let lockout = login_lockout_store
+        .get_lockout(&key)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let lockout_seconds = LOGIN_LOCKOUT_SECONDS
+        .saturating_mul(1 << lockout.lockouts.min(16))
+        .min(MAX_LOGIN_LOCKOUT_SECONDS);
+
+    login_lockout_store
+        .lock(&key, Utc::now() + Duration::seconds(lockout_seconds as i64))
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    drop(login_lockout_store);
+
+    send_unlock_email(state, email, lockout_seconds).await?;
+
+    Ok(true)
+}
+
+async fn send_unlock_email(
+    state: &AppState,
+    email: &Email,
+    lockout_seconds: u64,
+) -> Result<(), AuthAPIError> {
+    match state.user_store.read().await.get_user(email).await {
+        Ok(_) => {}
+        Err(UserStoreError::UserNotFound) => return Ok(()),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    let token = generate_purpose_token(email, TokenPurpose::AccountUnlock, &state.keyring)
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    let link = format!(
+        "{}/?unlock_token={}",
+        AUTH_SERVICE_URL.as_str(),
+        token.expose_secret()
+    );
+
+    // Unknown accounts are locked without an email, so the lockout response mustn't wait
+    // for one either.
+    let email_client = state.email_client.clone();
+    let email = email.clone();
+    send_in_background(async move {
+        email_client
+            .send_email(
+                &email,
+                "Your account has been locked",
+                &format!(
+                    "After several failed login attempts, logging in to your account is blocked for {} minutes. If this was you, use the link below to unlock it now. If it wasn't, someone may be guessing your password, and you should consider changing it.\n\n{}",
+                    lockout_seconds.div_ceil(60),
+                    link
+                ),
+            )
+            .await
+    });
+
+    Ok(())
+}
+
+fn account_key(email: &Email) -> String {
+    format!("account:{}", email.as_ref().expose_secret())
+}
+
+fn address_key(ip: &str) -> String {
+    format!("ip:{}", ip)
+}
+
+#[derive(Deserialize)]
+pub struct UnlockAccountRequest {
+    pub token: SecretString,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct UnlockAccountResponse {
+    pub message: String,
+}
@@ -0,0 +1,210 @@
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Form, Json,
+};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, DeviceCode, DeviceCodeStoreError, DeviceGrant, DeviceGrantStatus, Email,
+        GrantType, OAuthError, UserCode,
+    },
+    utils::{
+        auth::{AuthenticatedClient, AuthenticatedUser},
+        constants::{AUTH_SERVICE_URL, DEVICE_CODE_POLL_INTERVAL_SECONDS, DEVICE_CODE_TTL_SECONDS},
+    },
+};
+
+use super::token::requested_scope;
+
+// User codes are short, so each user may only try this many per window.
+const USER_CODE_LIMIT: u64 = 10;
+const USER_CODE_WINDOW_SECONDS: u64 = 600; // 10 minutes
+
+// A fresh user code is drawn if one is already taken by another pending request.
+const USER_CODE_ATTEMPTS: usize = 3;
+
+/// Device authorization endpoint (RFC 8628 section 3.1). The device shows the user code
+/// and verification URI, then polls `/token` with the device code until the user has
+/// approved or denied the request on `/device`.
+#[tracing::instrument(name = "Device authorization", skip_all)]
+pub async fn device_authorization(
+    State(state): State<AppState>,
+    AuthenticatedClient { client }: AuthenticatedClient,
+    Form(request): Form<DeviceAuthorizationRequest>,
+) -> Result<impl IntoResponse, OAuthError> {
+    if !client.allowed_grant_types.contains(&GrantType::DeviceCode) {
+        return Err(OAuthError::UnauthorizedClient);
+    }
+
+    let scope = requested_scope(&client, request.scope)?;
+
+    let device_code = DeviceCode::default();
+    let mut attempts = 0;
+    let user_code = loop {
+        let user_code = UserCode::default();
+        let grant = DeviceGrant {
+            client_id: client.client_id.clone(),
+            scope: scope.clone(),
+            status: DeviceGrantStatus::Pending,
+        };
+
+        attempts += 1;
+        match state
+            .device_code_store
+            .write()
+            .await
+            .add_grant(&device_code, &user_code, grant)
+            .await
+        {
+            Ok(()) => break user_code,
+            Err(DeviceCodeStoreError::UserCodeAlreadyExists) if attempts < USER_CODE_ATTEMPTS => {}
+            Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+        }
+    };
+
+    let verification_uri = format!("{}/device", AUTH_SERVICE_URL.as_str());
+    let response = DeviceAuthorizationResponse {
+        device_code: device_code.as_ref().expose_secret().to_owned(),
+        user_code: user_code.display(),
+        verification_uri_complete: format!(
+            "{}?user_code={}",
+            verification_uri,
+            user_code.display()
+        ),
+        verification_uri,
+        expires_in: DEVICE_CODE_TTL_SECONDS,
+        interval: DEVICE_CODE_POLL_INTERVAL_SECONDS,
+    };
+
+    Ok((
+        [
+            (header::CACHE_CONTROL, "no-store"),
+            (header::PRAGMA, "no-cache"),
+        ],
+        Json(response),
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct DeviceAuthorizationRequest {
+    pub scope: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DeviceAuthorizationResponse {
+    pub device_code: String,
+    pub user_code: String,
+    pub verification_uri: String,
+    pub verification_uri_complete: String,
+    pub expires_in: u64,
+    pub interval: u64,
+}
+
+/// Shows the logged in user which client is asking for which scopes before they decide.
+#[tracing::instrument(name = "Look up device request", skip_all)]
+pub async fn lookup_device_request(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(request): Json<DeviceLookupRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (_, grant) = find_device_request(&state, &user.email, request.user_code).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(DeviceRequestResponse {
+            client_id: grant.client_id,
+            scope: grant.scope,
+        }),
+    ))
+}
+
+/// Approves or denies a device's request on behalf of the logged in user.
+#[tracing::instrument(name = "Confirm device request", skip_all)]
+pub async fn confirm_device_request(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(request): Json<DeviceConfirmRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let (user_code, _) = find_device_request(&state, &user.email, request.user_code).await?;
+
+    let status = if request.approve {
+        DeviceGrantStatus::Approved {
+            email: user.email,
+            amr: user.amr,
+        }
+    } else {
+        DeviceGrantStatus::Denied
+    };
+
+    match state
+        .device_code_store
+        .write()
+        .await
+        .decide(&user_code, status)
+        .await
+    {
+        Ok(()) => Ok(StatusCode::OK),
+        Err(DeviceCodeStoreError::GrantNotFound) => Err(AuthAPIError::IncorrectCredentials),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+async fn find_device_request(
+    state: &AppState,
+    email: &Email,
+    user_code: SecretString,
+) -> Result<(UserCode, DeviceGrant), AuthAPIError> {
+    let user_code = UserCode::parse(user_code).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let hits = state
+        .rate_limit_store
+        .write()
+        .await
+        .hit(
+            &format!("device_user_code:{}", email.as_ref().expose_secret()),
+            USER_CODE_WINDOW_SECONDS,
+        )
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    if hits > USER_CODE_LIMIT {
+        return Err(AuthAPIError::TooManyRequests);
+    }
+
+    match state
+        .device_code_store
+        .read()
+        .await
+        .get_grant(&user_code)
+        .await
+    {
+        Ok(grant) => Ok((user_code, grant)),
+        Err(DeviceCodeStoreError::GrantNotFound) => Err(AuthAPIError::IncorrectCredentials),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DeviceLookupRequest {
+    #[serde(rename = "userCode")]
+    pub user_code: SecretString,
+}
+
+#[derive(Deserialize)]
+pub struct DeviceConfirmRequest {
+    #[serde(rename = "userCode")]
+    pub user_code: SecretString,
+    pub approve: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DeviceRequestResponse {
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    pub scope: String,
+}
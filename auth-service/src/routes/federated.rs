@@ -0,0 +1,468 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Redirect},
+    Json,
+};
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    CookieJar,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::RngCore;
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, AuthMethod, Email, FederatedLogin, FederatedLoginState,
+        FederatedLoginStoreError, IdentityProvider, IdentityProviderError, Password, TwoFAMethod,
+        UpstreamIdentity, User, UserIdentity, UserIdentityStoreError, UserStoreError,
+    },
+    utils::{
+        auth::{start_session, AuthenticatedUser, ClientInfo},
+        constants::{AUTH_SERVICE_URL, FEDERATED_LOGIN_COOKIE_NAME},
+    },
+};
+
+/// Sends the user to log in at the upstream identity provider, which redirects back to
+/// `/federated/callback`.
+#[tracing::instrument(name = "Start federated login", skip_all)]
+pub async fn start_federated_login(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    Query(request): Query<StartFederatedLoginRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let return_to = request
+        .return_to
+        .filter(|return_to| is_return_target(return_to));
+
+    let (jar, url) = begin_upstream_login(&state, jar, return_to, None).await?;
+
+    Ok((jar, Redirect::to(&url)))
+}
+
+/// Starts linking an upstream identity to the logged in user, who confirms their password
+/// first. Returns the upstream login page to send the user to, after which
+/// `/federated/callback` links the identity instead of logging in with it.
+#[tracing::instrument(name = "Start identity link", skip_all)]
+pub async fn start_identity_link(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    user: AuthenticatedUser,
+    Json(request): Json<LinkIdentityRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    reauthenticate(&state, &user.email, request.password).await?;
+
+    let (jar, url) = begin_upstream_login(&state, jar, None, Some(user.email)).await?;
+
+    let response = Json(LinkIdentityResponse {
+        authorization_url: url,
+    });
+
+    Ok((jar, response))
+}
+
+/// Where the upstream identity provider sends the user back to. Logs in the local user the
+/// upstream identity is linked to, linking it by email address on the first visit, and
+/// redirects to `return_to` or the login page.
+#[tracing::instrument(name = "Finish federated login", skip_all)]
+pub async fn finish_federated_login(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Query(request): Query<FederatedCallbackRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let Some(identity_provider) = state.identity_provider.clone() else {
+        return (jar, Err(AuthAPIError::FederatedLoginDisabled));
+    };
+
+    let expected_state = jar
+        .get(FEDERATED_LOGIN_COOKIE_NAME)
+        .map(|cookie| cookie.value().to_owned());
+    let jar = jar.remove(FEDERATED_LOGIN_COOKIE_NAME);
+
+    let login = match take_login(&state, expected_state, request.state).await {
+        Ok(login) => login,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    // The provider answers with `error` instead of a code when the user cancels or may not
+    // log in (RFC 6749 section 4.1.2.1).
+    let Some(code) = request.code.filter(|_| request.error.is_none()) else {
+        return (jar, Err(AuthAPIError::IncorrectCredentials));
+    };
+
+    let identity = match identity_provider
+        .authenticate(&code, &login.code_verifier, &redirect_uri(), &login.nonce)
+        .await
+    {
+        Ok(identity) => identity,
+        Err(IdentityProviderError::InvalidGrant | IdentityProviderError::InvalidIdToken(_)) => {
+            return (jar, Err(AuthAPIError::IncorrectCredentials))
+        }
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    if let Some(email) = login.link_to {
+        let result = link_identity(&state, &email, identity_provider.issuer(), &identity)
+            .await
+            .map(|()| Redirect::to("/"));
+        return (jar, result);
+    }
+
+    let email = match find_or_create_user(&state, identity_provider.as_ref(), &identity).await {
+        Ok(email) => email,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    // The upstream provider enforces its own login policy, so local 2FA isn't asked for on
+    // top, the same as for passkeys. Users with 2FA only get here with identities they
+    // linked themselves.
+    let mut amr = vec![AuthMethod::Federated];
+    if identity.amr.iter().any(|method| method == "mfa") {
+        amr.push(AuthMethod::MultiFactor);
+    }
+
+    let (auth_cookie, refresh_cookie) = match start_session(&state, &email, &amr, &client).await {
+        Ok(cookies) => cookies,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
+    };
+
+    let return_to = login.return_to.unwrap_or_else(|| "/".to_owned());
+
+    (
+        jar.add(auth_cookie).add(refresh_cookie),
+        Ok(Redirect::to(&return_to)),
+    )
+}
+
+/// Lists the upstream identities the logged in user can log in with.
+#[tracing::instrument(name = "List identities", skip_all)]
+pub async fn list_identities(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let identities = state
+        .user_identity_store
+        .read()
+        .await
+        .get_identities(&user.email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let response: Vec<IdentityResponse> =
+        identities.into_iter().map(IdentityResponse::from).collect();
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Stops an upstream identity from logging in to the user's account. The user confirms
+/// their password first, so they are never left without a way to log in.
+#[tracing::instrument(name = "Unlink identity", skip_all)]
+pub async fn unlink_identity(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(request): Json<UnlinkIdentityRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    reauthenticate(&state, &user.email, request.password).await?;
+
+    match state
+        .user_identity_store
+        .write()
+        .await
+        .remove_identity(&user.email, &request.provider, &request.subject)
+        .await
+    {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(UserIdentityStoreError::IdentityNotFound) => Err(AuthAPIError::IdentityNotFound),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct StartFederatedLoginRequest {
+    pub return_to: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct FederatedCallbackRequest {
+    pub code: Option<SecretString>,
+    pub state: Option<SecretString>,
+    pub error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct LinkIdentityRequest {
+    pub password: SecretString,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkIdentityResponse {
+    pub authorization_url: String,
+}
+
+#[derive(Deserialize)]
+pub struct UnlinkIdentityRequest {
+    pub provider: String,
+    pub subject: String,
+    pub password: SecretString,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdentityResponse {
+    pub provider: String,
+    pub subject: String,
+    pub created_at: String,
+}
+
+impl From<UserIdentity> for IdentityResponse {
+    fn from(identity: UserIdentity) -> Self {
+        Self {
+            provider: identity.provider,
+            subject: identity.subject,
+            created_at: identity.created_at.to_rfc3339(),
+        }
+    }
+}
+
+/// Remembers what the callback needs and ties the login to this browser with a cookie, so
+/// nobody can log a victim in to the attacker's own account by getting them to open a
+/// callback URL (RFC 6749 section 10.12). Returns the upstream login page.
+async fn begin_upstream_login(
+    state: &AppState,
+    jar: CookieJar,
+    return_to: Option<String>,
+    link_to: Option<Email>,
+) -> Result<(CookieJar, String), AuthAPIError> {
+    let identity_provider = state
+        .identity_provider
+        .as_ref()
+        .ok_or(AuthAPIError::FederatedLoginDisabled)?;
+
+    let login_state = FederatedLoginState::default();
+    let nonce = random_token();
+    let code_verifier = random_token();
+    let code_challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+
+    let url = identity_provider
+        .authorization_url(
+            login_state.as_ref(),
+            &nonce,
+            &code_challenge,
+            &redirect_uri(),
+        )
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    state
+        .federated_login_store
+        .write()
+        .await
+        .add_login(
+            &login_state,
+            FederatedLogin {
+                nonce,
+                code_verifier: SecretString::new(code_verifier.into_boxed_str()),
+                return_to,
+                link_to,
+            },
+        )
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let cookie = Cookie::build((
+        FEDERATED_LOGIN_COOKIE_NAME,
+        login_state.as_ref().expose_secret().to_owned(),
+    ))
+    .path("/")
+    .http_only(true)
+    .same_site(SameSite::Lax)
+    .build();
+
+    Ok((jar.add(cookie), url))
+}
+
+/// Takes the login the upstream provider sent the user back for, if it was started in this
+/// browser.
+async fn take_login(
+    state: &AppState,
+    expected_state: Option<String>,
+    actual_state: Option<SecretString>,
+) -> Result<FederatedLogin, AuthAPIError> {
+    let login_state = match (expected_state, actual_state) {
+        (Some(expected), Some(actual))
+            if bool::from(expected.as_bytes().ct_eq(actual.expose_secret().as_bytes())) =>
+        {
+            FederatedLoginState::parse(actual).map_err(|_| AuthAPIError::InvalidToken)?
+        }
+        _ => return Err(AuthAPIError::InvalidToken),
+    };
+
+    match state
+        .federated_login_store
+        .write()
+        .await
+        .take_login(&login_state)
+        .await
+    {
+        Ok(login) => Ok(login),
+        Err(FederatedLoginStoreError::LoginNotFound) => Err(AuthAPIError::InvalidToken),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+/// Links the identity to `email`, whatever address the provider asserts for it.
+async fn link_identity(
+    state: &AppState,
+    email: &Email,
+    provider: &str,
+    identity: &UpstreamIdentity,
+) -> Result<(), AuthAPIError> {
+    let mut user_identity_store = state.user_identity_store.write().await;
+
+    match user_identity_store
+        .get_identity(provider, &identity.subject)
+        .await
+    {
+        Ok(linked) if linked.email == *email => return Ok(()),
+        Ok(_) => return Err(AuthAPIError::IdentityAlreadyLinked),
+        Err(UserIdentityStoreError::IdentityNotFound) => {}
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    match user_identity_store
+        .add_identity(email, provider, &identity.subject)
+        .await
+    {
+        Ok(()) => Ok(()),
+        Err(UserIdentityStoreError::IdentityAlreadyLinked) => {
+            Err(AuthAPIError::IdentityAlreadyLinked)
+        }
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+/// Returns the user the identity is linked to. Identities that aren't linked yet are linked
+/// to the user with the email address the provider asserts, who is created if needed.
+/// Users with 2FA have to link identities themselves, since logging in upstream would
+/// otherwise skip their second factor.
+async fn find_or_create_user(
+    state: &AppState,
+    identity_provider: &(dyn IdentityProvider + Send + Sync),
+    identity: &UpstreamIdentity,
+) -> Result<Email, AuthAPIError> {
+    let provider = identity_provider.issuer();
+
+    match state
+        .user_identity_store
+        .read()
+        .await
+        .get_identity(provider, &identity.subject)
+        .await
+    {
+        Ok(linked) => return Ok(linked.email),
+        Err(UserIdentityStoreError::IdentityNotFound) => {}
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    // An address the provider hasn't checked could belong to anyone, including the owner of
+    // an existing local account.
+    let email = match &identity.email {
+        Some(email) if identity.email_verified => email.clone(),
+        _ => return Err(AuthAPIError::EmailNotVerified),
+    };
+
+    let mut user_store = state.user_store.write().await;
+
+    match user_store.get_user(&email).await {
+        Ok(user) if user.verified && user.requires_2fa => {
+            return Err(AuthAPIError::IdentityNotLinked)
+        }
+        Ok(user) if user.verified => {}
+        // Whoever signed up with this address never proved they own it, so the password they
+        // chose stops working now that the real owner has.
+        Ok(_) => {
+            user_store
+                .update_password(&email, random_password())
+                .await
+                .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+            user_store
+                .mark_verified(&email)
+                .await
+                .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+        }
+        // Users who only ever log in upstream get a password nobody knows. They can still
+        // set one through a password reset.
+        Err(UserStoreError::UserNotFound) => {
+            let user = User {
+                email: email.clone(),
+                password: random_password(),
+                requires_2fa: false,
+                verified: true,
+                two_fa_method: TwoFAMethod::Email,
+            };
+            user_store
+                .add_user(user)
+                .await
+                .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    drop(user_store);
+
+    link_identity(state, &email, provider, identity).await?;
+
+    Ok(email)
+}
+
+/// Asks for the password again before changing how the account can be logged in to, so a
+/// session left open on a shared computer isn't enough.
+async fn reauthenticate(
+    state: &AppState,
+    email: &Email,
+    password: SecretString,
+) -> Result<(), AuthAPIError> {
+    let password = Password::parse(password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    match state
+        .user_store
+        .read()
+        .await
+        .validate_user(email, &password)
+        .await
+    {
+        Ok(()) => Ok(()),
+        Err(UserStoreError::InvalidCredentials | UserStoreError::UserNotFound) => {
+            Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+fn redirect_uri() -> String {
+    format!("{}/federated/callback", AUTH_SERVICE_URL.as_str())
+}
+
+// The same pages the login page returns to, so `return_to` can't be used as an open
+// redirect.
+fn is_return_target(return_to: &str) -> bool {
+    return_to.starts_with("/authorize?") || return_to.starts_with("/device?")
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn random_password() -> Password {
+    Password::parse(SecretString::new(random_token().into_boxed_str()))
+        .expect("random tokens are long enough to be passwords")
+}
@@ -0,0 +1,130 @@
+use axum::{extract::State, response::IntoResponse, Form, Json};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthMethod, OAuthError, PersonalAccessToken, PersonalAccessTokenRecord,
+        PersonalAccessTokenStoreError,
+    },
+    utils::{
+        auth::{validate_jwt, AuthenticatedClient, Claims},
+        constants::JWT_ISSUER,
+    },
+};
+
+/// Token introspection (RFC 7662). Any token that `validate_token` would refuse, including
+/// banned, revoked and expired ones, is reported as inactive without saying why.
+#[tracing::instrument(name = "Introspect token", skip_all)]
+pub async fn introspect(
+    State(state): State<AppState>,
+    _client: AuthenticatedClient,
+    Form(request): Form<IntrospectRequest>,
+) -> Result<impl IntoResponse, OAuthError> {
+    let token = request.token.ok_or(OAuthError::InvalidRequest)?;
+
+    if let Ok(personal_access_token) = PersonalAccessToken::parse(token.clone()) {
+        let response = match state
+            .personal_access_token_store
+            .write()
+            .await
+            .use_token(&personal_access_token)
+            .await
+        {
+            Ok(record) => IntrospectResponse::from(record),
+            Err(PersonalAccessTokenStoreError::TokenNotFound) => IntrospectResponse::default(),
+            Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+        };
+
+        return Ok(Json(response));
+    }
+
+    // Refresh tokens can only be looked up by consuming them, so they always come back
+    // inactive. A `token_type_hint` is only a hint and is ignored.
+    let response = match validate_jwt(
+        &token,
+        None,
+        state.banned_token_store.clone(),
+        state.session_store.clone(),
+        &state.keyring,
+    )
+    .await
+    {
+        Ok(claims) => IntrospectResponse::from(claims),
+        Err(_) => IntrospectResponse::default(),
+    };
+
+    Ok(Json(response))
+}
+
+#[derive(Deserialize)]
+pub struct IntrospectRequest {
+    pub token: Option<SecretString>,
+}
+
+/// Inactive tokens only carry `active: false`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct IntrospectResponse {
+    pub active: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exp: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aud: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amr: Option<Vec<AuthMethod>>,
+}
+
+impl From<Claims> for IntrospectResponse {
+    fn from(claims: Claims) -> Self {
+        Self {
+            active: true,
+            scope: claims.scope,
+            client_id: claims.client_id,
+            token_type: Some("Bearer".to_owned()),
+            exp: Some(claims.exp),
+            iat: Some(claims.iat),
+            nbf: Some(claims.nbf),
+            sub: Some(claims.sub),
+            aud: Some(claims.aud),
+            iss: Some(claims.iss),
+            jti: Some(claims.jti),
+            amr: Some(claims.amr),
+        }
+    }
+}
+
+// Personal access tokens are good for every audience, so they carry no `aud`.
+impl From<PersonalAccessTokenRecord> for IntrospectResponse {
+    fn from(record: PersonalAccessTokenRecord) -> Self {
+        let timestamp = |time: chrono::DateTime<chrono::Utc>| time.timestamp() as usize;
+
+        Self {
+            active: true,
+            scope: (!record.scopes.is_empty()).then(|| record.scopes.join(" ")),
+            token_type: Some("Bearer".to_owned()),
+            exp: record.expires_at.map(timestamp),
+            iat: Some(timestamp(record.created_at)),
+            sub: Some(record.email.as_ref().expose_secret().to_owned()),
+            iss: Some(JWT_ISSUER.clone()),
+            jti: Some(record.id.to_string()),
+            ..Self::default()
+        }
+    }
+}
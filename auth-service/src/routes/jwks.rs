@@ -0,0 +1,24 @@
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+
+use crate::{app_state::AppState, utils::constants::JWKS_MAX_AGE_SECONDS};
+
+/// Publishes the public signing keys so other services can verify our JWTs themselves.
+/// Verifiers should pick the key matching the token's `kid` header. Retired keys stay
+/// listed until the tokens they signed have expired, and a rotated in key is listed before
+/// it signs anything.
+#[tracing::instrument(name = "JWKS", skip_all)]
+pub async fn jwks(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        [(
+            header::CACHE_CONTROL,
+            format!("public, max-age={}", JWKS_MAX_AGE_SECONDS),
+        )],
+        Json(state.keyring.jwk_set()),
+    )
+}
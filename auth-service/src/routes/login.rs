@@ -5,14 +5,19 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, LoginAttemptId, Password, TwoFACode},
-    utils::auth::generate_auth_cookie,
+    domain::{
+        AuthAPIError, AuthMethod, Email, LoginAttemptId, Password, TwoFACode, TwoFAMethod, User,
+    },
+    utils::auth::{start_session, ClientInfo},
 };
 
+use super::account_lockout::{check_login_lockout, clear_failed_logins, record_failed_login};
+
 #[tracing::instrument(name = "Login", skip_all)]
 pub async fn login(
     State(state): State<AppState>,
     jar: CookieJar,
+    client: ClientInfo,
     Json(request): Json<LoginRequest>,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
     let password =
@@ -26,13 +31,30 @@ pub async fn login(
         Err(e) => return (jar, Err(e)),
     };
 
-    let user_store = &state.user_store.read().await;
+    if let Err(e) = check_login_lockout(&state, &email, &client).await {
+        return (jar, Err(e));
+    }
+
+    let password_is_valid = state
+        .user_store
+        .read()
+        .await
+        .validate_user(&email, &password)
+        .await
+        .is_ok();
+
+    if !password_is_valid {
+        return (jar, Err(record_failed_login(&state, &email, &client).await));
+    }
 
-    if user_store.validate_user(&email, &password).await.is_err() {
-        return (jar, Err(AuthAPIError::IncorrectCredentials));
+    if let Err(e) = clear_failed_logins(&state, &email).await {
+        return (jar, Err(e));
     }
 
-    let user = match user_store
+    let user = match state
+        .user_store
+        .read()
+        .await
         .get_user(&email)
         .await
         .map_err(|_| AuthAPIError::IncorrectCredentials)
@@ -41,64 +63,84 @@ pub async fn login(
         Err(e) => return (jar, Err(e)),
     };
 
+    if !user.verified {
+        return (jar, Err(AuthAPIError::EmailNotVerified));
+    }
+
     match user.requires_2fa {
-        true => handle_2fa(jar, &user.email, &state).await,
-        false => handle_no_2fa(&user.email, jar).await,
+        true => handle_2fa(jar, &user, AuthMethod::Password, &state).await,
+        false => handle_no_2fa(&user.email, &[AuthMethod::Password], jar, &state, &client).await,
     }
 }
 
+/// Asks for the second factor once `user` has passed `first_factor`.
 #[tracing::instrument(name = "Handle 2FA flow", skip_all)]
-async fn handle_2fa(
+pub(crate) async fn handle_2fa(
     jar: CookieJar,
-    email: &Email,
+    user: &User,
+    first_factor: AuthMethod,
     state: &AppState,
 ) -> (
     CookieJar,
     Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
 ) {
+    let email = &user.email;
     let login_attempt_id = LoginAttemptId::default();
+    // TOTP users get a code too, so every login attempt is tracked the same way, but it's
+    // never sent and verify_2fa checks their authenticator app instead.
     let code = TwoFACode::default();
 
     if let Err(e) = state
         .two_fa_code_store
         .write()
         .await
-        .add_code(email.clone(), login_attempt_id.clone(), code.clone())
+        .add_code(
+            email.clone(),
+            login_attempt_id.clone(),
+            code.clone(),
+            first_factor,
+        )
         .await
     {
         return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
     }
 
-    if let Err(e) = state
-        .email_client
-        .send_email(email, "2FA Code", code.as_ref().expose_secret())
-        .await
-    {
-        return (jar, Err(AuthAPIError::UnexpectedError(e)));
+    if user.two_fa_method == TwoFAMethod::Email {
+        if let Err(e) = state
+            .email_client
+            .send_email(email, "2FA Code", code.as_ref().expose_secret())
+            .await
+        {
+            return (jar, Err(AuthAPIError::UnexpectedError(e)));
+        }
     }
 
     let response = Json(LoginResponse::TwoFactorAuth(TwoFactorAuthResponse {
         message: "2FA required".to_owned(),
         login_attempt_id: login_attempt_id.as_ref().expose_secret().to_string(),
+        two_fa_method: user.two_fa_method,
     }));
 
     (jar, Ok((StatusCode::PARTIAL_CONTENT, response)))
 }
 
 #[tracing::instrument(name = "Handle non-2FA flow", skip_all)]
-async fn handle_no_2fa(
+pub(crate) async fn handle_no_2fa(
     email: &Email,
+    amr: &[AuthMethod],
     jar: CookieJar,
+    state: &AppState,
+    client: &ClientInfo,
 ) -> (
     CookieJar,
     Result<(StatusCode, Json<LoginResponse>), AuthAPIError>,
 ) {
-    let auth_cookie = match generate_auth_cookie(email) {
-        Ok(cookie) => cookie,
+    let (auth_cookie, refresh_cookie) = match start_session(state, email, amr, client).await {
+        Ok(cookies) => cookies,
         Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
     };
 
-    let updated_jar = jar.add(auth_cookie);
+    let updated_jar = jar.add(auth_cookie).add(refresh_cookie);
 
     (
         updated_jar,
@@ -124,4 +166,6 @@ pub struct TwoFactorAuthResponse {
     pub message: String,
     #[serde(rename = "loginAttemptId")]
     pub login_attempt_id: String,
+    #[serde(rename = "twoFAMethod")]
+    pub two_fa_method: TwoFAMethod,
 }
@@ -1,11 +1,17 @@
-use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use axum_extra::extract::CookieJar;
-use secrecy::SecretString;
+use chrono::Utc;
+use color_eyre::eyre::Report;
+use secrecy::{ExposeSecret, SecretString};
+use serde::Deserialize;
 
 use crate::{
     app_state::AppState,
-    domain::AuthAPIError,
-    utils::{auth::validate_token, constants::JWT_COOKIE_NAME},
+    domain::{AuthAPIError, Email, RefreshToken, SessionStoreError, UserStoreError},
+    utils::{
+        auth::{validate_session_jwt, AdminCaller, AuthenticatedUser},
+        constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME},
+    },
 };
 
 #[tracing::instrument(name = "Logout", skip_all)]
@@ -20,15 +26,26 @@ pub async fn logout(
 
     let token = SecretString::new(cookie.value().to_owned().into_boxed_str());
 
-    let _ = match validate_token(&token, state.banned_token_store.clone())
-        .await
-        .map_err(|_| AuthAPIError::InvalidToken)
+    let (claims, session_id) = match validate_session_jwt(
+        &token,
+        state.banned_token_store.clone(),
+        state.session_store.clone(),
+        &state.keyring,
+    )
+    .await
+    .map_err(|_| AuthAPIError::InvalidToken)
     {
         Ok(claims) => claims,
         Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
     };
 
-    let jar = jar.remove(JWT_COOKIE_NAME);
+    let refresh_token = jar
+        .get(REFRESH_COOKIE_NAME)
+        .and_then(|cookie| {
+            RefreshToken::parse(SecretString::new(cookie.value().to_owned().into())).ok()
+        });
+
+    let jar = jar.remove(JWT_COOKIE_NAME).remove(REFRESH_COOKIE_NAME);
 
     if let Err(e) = state
         .banned_token_store
@@ -40,5 +57,111 @@ pub async fn logout(
         return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
     }
 
+    let email = match Email::parse(SecretString::new(claims.sub.into_boxed_str())) {
+        Ok(email) => email,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
+    };
+
+    match state
+        .session_store
+        .write()
+        .await
+        .revoke_session(&email, session_id)
+        .await
+    {
+        Ok(()) | Err(SessionStoreError::SessionNotFound) => {}
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    }
+
+    if let Some(refresh_token) = refresh_token {
+        if let Err(e) = state
+            .refresh_token_store
+            .write()
+            .await
+            .revoke_family(&refresh_token)
+            .await
+        {
+            return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+        }
+    }
+
     (jar, Ok(StatusCode::OK))
 }
+
+/// Logs the caller out on every device, not just this one.
+#[tracing::instrument(name = "Logout everywhere", skip_all)]
+pub async fn logout_all(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    jar: CookieJar,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let jar = jar.remove(JWT_COOKIE_NAME).remove(REFRESH_COOKIE_NAME);
+
+    if let Err(e) = end_all_sessions(&state, &user.email).await {
+        return (jar, Err(AuthAPIError::UnexpectedError(e)));
+    }
+
+    (jar, Ok(StatusCode::OK))
+}
+
+/// Lets support staff log a user out on every device, e.g. when their account is
+/// compromised.
+#[tracing::instrument(name = "Admin logout everywhere", skip_all)]
+pub async fn admin_logout_all(
+    State(state): State<AppState>,
+    _admin: AdminCaller,
+    Json(request): Json<AdminLogoutAllRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    match state.user_store.read().await.get_user(&email).await {
+        Ok(_) => {}
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::UserNotFound),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    end_all_sessions(&state, &email)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Bans every access token issued to `email` so far and revokes its sessions, refresh
+/// tokens and personal access tokens, so nothing it holds can be used or renewed.
+pub(crate) async fn end_all_sessions(state: &AppState, email: &Email) -> Result<(), Report> {
+    state
+        .banned_token_store
+        .write()
+        .await
+        .ban_tokens_before(email.as_ref().expose_secret(), Utc::now())
+        .await?;
+
+    state
+        .session_store
+        .write()
+        .await
+        .revoke_all_sessions(email)
+        .await?;
+
+    state
+        .refresh_token_store
+        .write()
+        .await
+        .revoke_all_tokens(email)
+        .await?;
+
+    state
+        .personal_access_token_store
+        .write()
+        .await
+        .revoke_all_tokens(email)
+        .await?;
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct AdminLogoutAllRequest {
+    pub email: SecretString,
+}
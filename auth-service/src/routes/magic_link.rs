@@ -0,0 +1,158 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, AuthMethod, Email, LoginAttemptId, MagicLinkStoreError, UserStoreError,
+    },
+    utils::{
+        auth::{generate_magic_link_token, validate_purpose_token, ClientInfo, TokenPurpose},
+        constants::AUTH_SERVICE_URL,
+        email::send_in_background,
+    },
+};
+
+use super::login::{handle_2fa, handle_no_2fa};
+
+#[tracing::instrument(name = "Request magic link", skip_all)]
+pub async fn request_magic_link(
+    State(state): State<AppState>,
+    Json(request): Json<MagicLinkRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Answer the same way whether or not the account exists, so this route can't be used
+    // to find out which addresses are registered.
+    let response = Json(MagicLinkResponse {
+        message: "If the account exists, a login link has been sent".to_owned(),
+    });
+
+    match state.user_store.read().await.get_user(&email).await {
+        Ok(_) => {}
+        Err(UserStoreError::UserNotFound) => return Ok((StatusCode::OK, response)),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    let login_attempt_id = LoginAttemptId::default();
+
+    state
+        .magic_link_store
+        .write()
+        .await
+        .add_login_attempt(&login_attempt_id, &email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let token = generate_magic_link_token(&email, &login_attempt_id, &state.keyring)
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    // The link opens the UI, which redeems it with a POST. Redeeming on GET would let mail
+    // scanners that prefetch links burn them before the user clicks.
+    let link = format!(
+        "{}/?magic_token={}",
+        AUTH_SERVICE_URL.as_str(),
+        token.expose_secret()
+    );
+
+    let email_client = state.email_client.clone();
+    send_in_background(async move {
+        email_client
+            .send_email(
+                &email,
+                "Your login link",
+                &format!(
+                    "Use the link below to log in. It expires in 10 minutes and can only be used once.\n\n{}",
+                    link
+                ),
+            )
+            .await
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+/// Redeems a magic link. Accounts with 2FA get the same 206 response as a password login
+/// and finish through `/verify-2fa`.
+#[tracing::instrument(name = "Redeem magic link", skip_all)]
+pub async fn redeem_magic_link(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(request): Json<RedeemMagicLinkRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let claims = match validate_purpose_token(
+        &request.token,
+        TokenPurpose::MagicLink,
+        state.banned_token_store.clone(),
+        &state.keyring,
+    )
+    .await
+    {
+        Ok(claims) => claims,
+        Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+    };
+
+    let login_attempt_id =
+        match LoginAttemptId::parse(SecretString::new(claims.jti.into_boxed_str())) {
+            Ok(login_attempt_id) => login_attempt_id,
+            Err(_) => return (jar, Err(AuthAPIError::InvalidToken)),
+        };
+
+    let email = match state
+        .magic_link_store
+        .write()
+        .await
+        .take_login_attempt(&login_attempt_id)
+        .await
+    {
+        Ok(email) => email,
+        Err(MagicLinkStoreError::LoginAttemptNotFound) => {
+            return (jar, Err(AuthAPIError::InvalidToken))
+        }
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    if email.as_ref().expose_secret() != claims.sub {
+        return (jar, Err(AuthAPIError::InvalidToken));
+    }
+
+    let mut user_store = state.user_store.write().await;
+
+    let user = match user_store.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return (jar, Err(AuthAPIError::InvalidToken)),
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    // Following the link proves the user controls the address.
+    if !user.verified {
+        if let Err(e) = user_store.mark_verified(&email).await {
+            return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+        }
+    }
+
+    drop(user_store);
+
+    match user.requires_2fa {
+        true => handle_2fa(jar, &user, AuthMethod::MagicLink, &state).await,
+        false => handle_no_2fa(&user.email, &[AuthMethod::MagicLink], jar, &state, &client).await,
+    }
+}
+
+#[derive(Deserialize)]
+pub struct MagicLinkRequest {
+    pub email: SecretString,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MagicLinkResponse {
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+pub struct RedeemMagicLinkRequest {
+    pub token: SecretString,
+}
@@ -1,11 +1,51 @@
+mod account_lockout;
+mod device;
+mod federated;
+mod introspect;
+mod jwks;
 mod login;
 mod logout;
+mod magic_link;
+mod oauth_clients;
+mod oidc;
+mod passkey;
+mod password_reset;
+mod personal_access_tokens;
+mod recovery_codes;
+mod refresh_token;
+mod registration;
+mod revoke;
+mod sessions;
+mod signing_keys;
 mod signup;
+mod token;
+mod totp;
 mod verify_2fa;
+mod verify_email;
 mod verify_token;
 
+pub use account_lockout::*;
+pub use device::*;
+pub use federated::*;
+pub use introspect::*;
+pub use jwks::*;
 pub use login::*;
 pub use logout::*;
+pub use magic_link::*;
+pub use oauth_clients::*;
+pub use oidc::*;
+pub use passkey::*;
+pub use password_reset::*;
+pub use personal_access_tokens::*;
+pub use recovery_codes::*;
+pub use refresh_token::*;
+pub use registration::*;
+pub use revoke::*;
+pub use sessions::*;
+pub use signing_keys::*;
 pub use signup::*;
+pub use token::*;
+pub use totp::*;
 pub use verify_2fa::*;
+pub use verify_email::*;
 pub use verify_token::*;
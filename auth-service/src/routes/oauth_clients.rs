@@ -0,0 +1,305 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{Duration, Utc};
+use color_eyre::eyre::eyre;
+use reqwest::Url;
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, ClientSecret, GrantType, OAuthClient, OAuthClientStoreError,
+        RegistrationToken,
+    },
+    utils::auth::AdminCaller,
+};
+
+/// Registers a new OAuth client. The secret is only ever returned here and by
+/// `rotate_oauth_client_secret`.
+#[tracing::instrument(name = "Create OAuth client", skip_all)]
+pub async fn create_oauth_client(
+    State(state): State<AppState>,
+    _admin: AdminCaller,
+    Json(request): Json<OAuthClientRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let client = request.into_client(Uuid::new_v4().to_string())?;
+    let client_secret = ClientSecret::default();
+
+    state
+        .oauth_client_store
+        .write()
+        .await
+        .add_client(&client, &client_secret, None)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(OAuthClientSecretResponse {
+            client_id: client.client_id,
+            client_secret: client_secret.as_ref().expose_secret().to_owned(),
+        }),
+    ))
+}
+
+#[tracing::instrument(name = "List OAuth clients", skip_all)]
+pub async fn list_oauth_clients(
+    State(state): State<AppState>,
+    _admin: AdminCaller,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let clients = state
+        .oauth_client_store
+        .read()
+        .await
+        .list_clients()
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let clients: Vec<OAuthClientResponse> = clients.into_iter().map(Into::into).collect();
+
+    Ok((StatusCode::OK, Json(clients)))
+}
+
+#[tracing::instrument(name = "Get OAuth client", skip_all)]
+pub async fn get_oauth_client(
+    State(state): State<AppState>,
+    _admin: AdminCaller,
+    Path(client_id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let client = state
+        .oauth_client_store
+        .read()
+        .await
+        .get_client(&client_id)
+        .await
+        .map_err(map_store_error)?;
+
+    Ok((StatusCode::OK, Json(OAuthClientResponse::from(client))))
+}
+
+/// Replaces the client's redirect URIs, scopes and grant types. Tokens already issued keep
+/// their scopes until they expire.
+#[tracing::instrument(name = "Update OAuth client", skip_all)]
+pub async fn update_oauth_client(
+    State(state): State<AppState>,
+    _admin: AdminCaller,
+    Path(client_id): Path<String>,
+    Json(request): Json<OAuthClientRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let client = request.into_client(client_id)?;
+
+    state
+        .oauth_client_store
+        .write()
+        .await
+        .update_client(&client)
+        .await
+        .map_err(map_store_error)?;
+
+    Ok((StatusCode::OK, Json(OAuthClientResponse::from(client))))
+}
+
+/// Deletes the client. Tokens already issued to it stay valid until they expire.
+#[tracing::instrument(name = "Delete OAuth client", skip_all)]
+pub async fn delete_oauth_client(
+    State(state): State<AppState>,
+    _admin: AdminCaller,
+    Path(client_id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    state
+        .oauth_client_store
+        .write()
+        .await
+        .delete_client(&client_id)
+        .await
+        .map_err(map_store_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Replaces the client's secret. The old secret stops working straight away.
+#[tracing::instrument(name = "Rotate OAuth client secret", skip_all)]
+pub async fn rotate_oauth_client_secret(
+    State(state): State<AppState>,
+    _admin: AdminCaller,
+    Path(client_id): Path<String>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let client_secret = ClientSecret::default();
+
+    state
+        .oauth_client_store
+        .write()
+        .await
+        .update_secret(&client_id, &client_secret)
+        .await
+        .map_err(map_store_error)?;
+
+    Ok((
+        StatusCode::OK,
+        Json(OAuthClientSecretResponse {
+            client_id,
+            client_secret: client_secret.as_ref().expose_secret().to_owned(),
+        }),
+    ))
+}
+
+// Long enough to hand the token to whoever runs the new client.
+const INITIAL_ACCESS_TOKEN_TTL_SECONDS: i64 = 604_800; // 7 days
+
+/// Issues an initial access token that lets one client register itself at `/register`.
+#[tracing::instrument(name = "Create initial access token", skip_all)]
+pub async fn create_initial_access_token(
+    State(state): State<AppState>,
+    _admin: AdminCaller,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let token = RegistrationToken::default();
+    let expires_at = Duration::try_seconds(INITIAL_ACCESS_TOKEN_TTL_SECONDS)
+        .and_then(|ttl| Utc::now().checked_add_signed(ttl))
+        .ok_or(AuthAPIError::UnexpectedError(eyre!(
+            "failed to compute initial access token expiry"
+        )))?;
+
+    state
+        .oauth_client_store
+        .write()
+        .await
+        .add_initial_access_token(&token, expires_at)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(InitialAccessTokenResponse {
+            initial_access_token: token.as_ref().expose_secret().to_owned(),
+            expires_in: INITIAL_ACCESS_TOKEN_TTL_SECONDS,
+        }),
+    ))
+}
+
+fn map_store_error(e: OAuthClientStoreError) -> AuthAPIError {
+    match e {
+        OAuthClientStoreError::ClientNotFound => AuthAPIError::ClientNotFound,
+        e => AuthAPIError::UnexpectedError(e.into()),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct OAuthClientRequest {
+    #[serde(rename = "clientName")]
+    pub client_name: Option<String>,
+    #[serde(rename = "redirectUris", default)]
+    pub redirect_uris: Vec<String>,
+    #[serde(rename = "allowedScopes", default)]
+    pub allowed_scopes: Vec<String>,
+    #[serde(rename = "allowedGrantTypes")]
+    pub allowed_grant_types: Vec<GrantType>,
+}
+
+impl OAuthClientRequest {
+    /// A client signing users in needs at least one redirect URI, and one acting for itself
+    /// an id that can't be taken for a user's.
+    fn into_client(self, client_id: String) -> Result<OAuthClient, AuthAPIError> {
+        let redirect_uris_valid = self
+            .redirect_uris
+            .iter()
+            .all(|uri| parse_redirect_uri(uri).is_some());
+        let scopes_valid = self
+            .allowed_scopes
+            .iter()
+            .all(|scope| is_scope_token(scope));
+        let signs_users_in = self
+            .allowed_grant_types
+            .contains(&GrantType::AuthorizationCode);
+
+        if !redirect_uris_valid
+            || !scopes_valid
+            || self.allowed_grant_types.is_empty()
+            || (signs_users_in && self.redirect_uris.is_empty())
+        {
+            return Err(AuthAPIError::InvalidCredentials);
+        }
+
+        let client = OAuthClient {
+            client_id,
+            client_name: self.client_name,
+            redirect_uris: self.redirect_uris,
+            allowed_scopes: self.allowed_scopes,
+            allowed_grant_types: self.allowed_grant_types,
+        };
+
+        // New clients get a UUID, but clients from before the registry may have any id.
+        if client
+            .allowed_grant_types
+            .contains(&GrantType::ClientCredentials)
+            && !client.can_act_for_itself()
+        {
+            return Err(AuthAPIError::InvalidCredentials);
+        }
+
+        Ok(client)
+    }
+}
+
+/// Redirect URIs must be absolute and fragment free (RFC 6749 section 3.1.2).
+pub(super) fn parse_redirect_uri(uri: &str) -> Option<Url> {
+    Url::parse(uri)
+        .ok()
+        .filter(|url| url.fragment().is_none() && url.has_host())
+}
+
+/// RFC 6749 section 3.3: a scope token is printable ASCII other than space, `"` and `\`.
+pub(super) fn is_scope_token(scope: &str) -> bool {
+    !scope.is_empty()
+        && scope
+            .chars()
+            .all(|c| c.is_ascii_graphic() && c != '"' && c != '\\')
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct OAuthClientResponse {
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    #[serde(rename = "clientName", default, skip_serializing_if = "Option::is_none")]
+    pub client_name: Option<String>,
+    #[serde(rename = "redirectUris")]
+    pub redirect_uris: Vec<String>,
+    #[serde(rename = "allowedScopes")]
+    pub allowed_scopes: Vec<String>,
+    #[serde(rename = "allowedGrantTypes")]
+    pub allowed_grant_types: Vec<GrantType>,
+}
+
+impl From<OAuthClient> for OAuthClientResponse {
+    fn from(client: OAuthClient) -> Self {
+        Self {
+            client_id: client.client_id,
+            client_name: client.client_name,
+            redirect_uris: client.redirect_uris,
+            allowed_scopes: client.allowed_scopes,
+            allowed_grant_types: client.allowed_grant_types,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct OAuthClientSecretResponse {
+    #[serde(rename = "clientId")]
+    pub client_id: String,
+    #[serde(rename = "clientSecret")]
+    pub client_secret: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct InitialAccessTokenResponse {
+    #[serde(rename = "initialAccessToken")]
+    pub initial_access_token: String,
+    /// Seconds until the token expires if it hasn't been used.
+    #[serde(rename = "expiresIn")]
+    pub expires_in: i64,
+}
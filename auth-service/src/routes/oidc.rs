@@ -0,0 +1,323 @@
+use axum::{
+    extract::{OriginalUri, Query, State},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Redirect},
+    Json,
+};
+use axum_extra::extract::CookieJar;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::Algorithm;
+use reqwest::Url;
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthorizationCode, AuthorizationGrant, Email, GrantType, OAuthClient,
+        OAuthClientStoreError, OAuthError,
+    },
+    utils::{
+        auth::{bearer_token, validate_jwt, validate_session_jwt, Claims},
+        constants::{AUTH_SERVICE_URL, JWT_COOKIE_NAME, JWT_ISSUER},
+    },
+};
+
+/// The OpenID Connect scopes. Clients may be allowed other scopes of their own.
+pub(super) const SUPPORTED_SCOPES: [&str; 2] = ["openid", "email"];
+
+/// OpenID Connect discovery document, so relying parties can configure themselves from
+/// the issuer URL alone.
+#[tracing::instrument(name = "OpenID configuration", skip_all)]
+pub async fn openid_configuration(State(state): State<AppState>) -> impl IntoResponse {
+    let endpoint = |path: &str| format!("{}{}", AUTH_SERVICE_URL.as_str(), path);
+    let list = |values: &[&str]| values.iter().map(|value| value.to_string()).collect();
+
+    let configuration = OpenIdConfiguration {
+        issuer: JWT_ISSUER.clone(),
+        authorization_endpoint: endpoint("/authorize"),
+        token_endpoint: endpoint("/token"),
+        userinfo_endpoint: endpoint("/userinfo"),
+        jwks_uri: endpoint("/.well-known/jwks.json"),
+        introspection_endpoint: endpoint("/oauth/introspect"),
+        revocation_endpoint: endpoint("/oauth/revoke"),
+        device_authorization_endpoint: endpoint("/device/code"),
+        registration_endpoint: endpoint("/register"),
+        scopes_supported: list(&SUPPORTED_SCOPES),
+        response_types_supported: list(&["code"]),
+        grant_types_supported: list(&[
+            "authorization_code",
+            "client_credentials",
+            "refresh_token",
+            "urn:ietf:params:oauth:grant-type:device_code",
+        ]),
+        subject_types_supported: list(&["public"]),
+        id_token_signing_alg_values_supported: vec![state.keyring.signing_key().algorithm()],
+        token_endpoint_auth_methods_supported: list(&["client_secret_basic"]),
+        code_challenge_methods_supported: list(&["S256"]),
+        claims_supported: list(&[
+            "iss",
+            "sub",
+            "aud",
+            "exp",
+            "iat",
+            "nonce",
+            "amr",
+            "email",
+            "email_verified",
+        ]),
+    };
+
+    (
+        [(header::CACHE_CONTROL, "public, max-age=300")],
+        Json(configuration),
+    )
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct OpenIdConfiguration {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    pub jwks_uri: String,
+    pub introspection_endpoint: String,
+    pub revocation_endpoint: String,
+    pub device_authorization_endpoint: String,
+    pub registration_endpoint: String,
+    pub scopes_supported: Vec<String>,
+    pub response_types_supported: Vec<String>,
+    pub grant_types_supported: Vec<String>,
+    pub subject_types_supported: Vec<String>,
+    pub id_token_signing_alg_values_supported: Vec<Algorithm>,
+    pub token_endpoint_auth_methods_supported: Vec<String>,
+    pub code_challenge_methods_supported: Vec<String>,
+    pub claims_supported: Vec<String>,
+}
+
+/// Authorization endpoint for the authorization code flow with PKCE. Users who aren't
+/// logged in are sent to the login page, which comes back here once the login (and any
+/// second factor) has set the auth cookie. Only `S256` PKCE challenges are accepted.
+#[tracing::instrument(name = "Authorize", skip_all)]
+pub async fn authorize(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    OriginalUri(uri): OriginalUri,
+    Query(request): Query<AuthorizeRequest>,
+) -> Result<Redirect, OAuthError> {
+    // Until the redirect URI is known to belong to the client, errors are shown to the
+    // user instead of being sent anywhere (RFC 6749 section 4.1.2.1).
+    let (Some(client_id), Some(redirect_uri)) = (&request.client_id, &request.redirect_uri) else {
+        return Err(OAuthError::InvalidRequest);
+    };
+
+    let client = match state
+        .oauth_client_store
+        .read()
+        .await
+        .get_client(client_id)
+        .await
+    {
+        Ok(client) => client,
+        Err(OAuthClientStoreError::ClientNotFound) => return Err(OAuthError::InvalidRequest),
+        Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+    };
+
+    if !client.redirect_uris.contains(redirect_uri) {
+        return Err(OAuthError::InvalidRequest);
+    }
+
+    let mut callback = Url::parse(redirect_uri).map_err(|_| OAuthError::InvalidRequest)?;
+
+    let (key, value) = match grant_code(&state, &jar, &client, &request).await? {
+        AuthorizeOutcome::Code(code) => ("code", code.as_ref().expose_secret().to_owned()),
+        AuthorizeOutcome::Error(error) => ("error", error.to_owned()),
+        AuthorizeOutcome::Login => return Ok(Redirect::to(login_url(&uri.to_string())?.as_str())),
+    };
+
+    callback.query_pairs_mut().append_pair(key, &value);
+    if let Some(state) = &request.state {
+        callback.query_pairs_mut().append_pair("state", state);
+    }
+
+    Ok(Redirect::to(callback.as_str()))
+}
+
+#[derive(Deserialize)]
+pub struct AuthorizeRequest {
+    pub response_type: Option<String>,
+    pub client_id: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub scope: Option<String>,
+    pub state: Option<String>,
+    pub nonce: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<String>,
+    pub prompt: Option<String>,
+}
+
+enum AuthorizeOutcome {
+    Code(AuthorizationCode),
+    /// The user has to log in first.
+    Login,
+    /// An error code for the client, from RFC 6749 section 4.1.2.1 or OpenID Connect
+    /// Core section 3.1.2.6.
+    Error(&'static str),
+}
+
+/// Checks the rest of an authorization request from a known client and, if the user is
+/// logged in, issues a code for them.
+async fn grant_code(
+    state: &AppState,
+    jar: &CookieJar,
+    client: &OAuthClient,
+    request: &AuthorizeRequest,
+) -> Result<AuthorizeOutcome, OAuthError> {
+    if request.response_type.as_deref() != Some("code") {
+        return Ok(AuthorizeOutcome::Error("unsupported_response_type"));
+    }
+
+    if !client
+        .allowed_grant_types
+        .contains(&GrantType::AuthorizationCode)
+    {
+        return Ok(AuthorizeOutcome::Error("unauthorized_client"));
+    }
+
+    // Scopes the client may not have are dropped rather than refused, as RFC 6749
+    // section 3.3 allows.
+    let scopes: Vec<&str> = request
+        .scope
+        .as_deref()
+        .unwrap_or_default()
+        .split(' ')
+        .filter(|scope| client.allowed_scopes.iter().any(|allowed| allowed == scope))
+        .collect();
+
+    if !scopes.contains(&"openid") {
+        return Ok(AuthorizeOutcome::Error("invalid_scope"));
+    }
+
+    let Some(code_challenge) = &request.code_challenge else {
+        return Ok(AuthorizeOutcome::Error("invalid_request"));
+    };
+
+    // An S256 challenge is always an unpadded base64url SHA-256 digest.
+    let is_sha256 = URL_SAFE_NO_PAD
+        .decode(code_challenge)
+        .is_ok_and(|digest| digest.len() == 32);
+
+    if request.code_challenge_method.as_deref() != Some("S256") || !is_sha256 {
+        return Ok(AuthorizeOutcome::Error("invalid_request"));
+    }
+
+    let Some(claims) = logged_in_user(state, jar).await else {
+        return Ok(match request.prompt.as_deref() {
+            Some("none") => AuthorizeOutcome::Error("login_required"),
+            _ => AuthorizeOutcome::Login,
+        });
+    };
+
+    let email = Email::parse(SecretString::new(claims.sub.into_boxed_str()))
+        .map_err(OAuthError::UnexpectedError)?;
+
+    let code = AuthorizationCode::default();
+    let grant = AuthorizationGrant {
+        client_id: client.client_id.clone(),
+        redirect_uri: request.redirect_uri.clone().unwrap_or_default(),
+        email,
+        scope: scopes.join(" "),
+        nonce: request.nonce.clone(),
+        code_challenge: code_challenge.clone(),
+        amr: claims.amr,
+    };
+
+    state
+        .authorization_code_store
+        .write()
+        .await
+        .add_code(&code, grant)
+        .await
+        .map_err(|e| OAuthError::UnexpectedError(e.into()))?;
+
+    Ok(AuthorizeOutcome::Code(code))
+}
+
+async fn logged_in_user(state: &AppState, jar: &CookieJar) -> Option<Claims> {
+    let cookie = jar.get(JWT_COOKIE_NAME)?;
+    let token = SecretString::new(cookie.value().to_owned().into_boxed_str());
+
+    validate_session_jwt(
+        &token,
+        state.banned_token_store.clone(),
+        state.session_store.clone(),
+        &state.keyring,
+    )
+    .await
+    .ok()
+    .map(|(claims, _)| claims)
+}
+
+/// The login page takes a `return_to` path to come back to once the user is logged in.
+fn login_url(return_to: &str) -> Result<Url, OAuthError> {
+    let mut url = Url::parse(AUTH_SERVICE_URL.as_str())
+        .and_then(|url| url.join("/"))
+        .map_err(|e| OAuthError::UnexpectedError(e.into()))?;
+
+    url.query_pairs_mut().append_pair("return_to", return_to);
+
+    Ok(url)
+}
+
+/// UserInfo endpoint. Takes an access token from `/token` as a bearer token; our own
+/// cookies carry no `openid` scope and are refused.
+#[tracing::instrument(name = "Userinfo", skip_all)]
+pub async fn userinfo(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<UserinfoResponse>, OAuthError> {
+    let token = bearer_token(&headers).ok_or(OAuthError::InvalidToken)?;
+
+    let claims = validate_jwt(
+        &token,
+        None,
+        state.banned_token_store.clone(),
+        state.session_store.clone(),
+        &state.keyring,
+    )
+    .await
+    .map_err(|_| OAuthError::InvalidToken)?;
+
+    // A client's token for itself names no user.
+    if claims.is_client_token() {
+        return Err(OAuthError::InvalidToken);
+    }
+
+    let scopes: Vec<&str> = claims
+        .scope
+        .as_deref()
+        .unwrap_or_default()
+        .split(' ')
+        .collect();
+
+    if !scopes.contains(&"openid") {
+        return Err(OAuthError::InvalidToken);
+    }
+
+    let include_email = scopes.contains(&"email");
+
+    Ok(Json(UserinfoResponse {
+        email: include_email.then(|| claims.sub.clone()),
+        email_verified: include_email.then_some(true),
+        sub: claims.sub,
+    }))
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UserinfoResponse {
+    pub sub: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
+}
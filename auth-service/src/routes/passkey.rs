@@ -0,0 +1,369 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use webauthn_rs::prelude::{
+    AuthenticationResult, Passkey, PasskeyAuthentication, PublicKeyCredential,
+    RegisterPublicKeyCredential, RequestChallengeResponse,
+};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, AuthMethod, Email, LoginAttemptId, PasskeyCeremonyStoreError,
+        PasskeyStoreError, UserStoreError,
+    },
+    utils::{
+        auth::{AuthenticatedUser, ClientInfo},
+        webauthn::{decoy_passkey, user_handle, webauthn},
+    },
+};
+
+use super::verify_2fa::{check_login_attempt, issue_cookies};
+
+#[tracing::instrument(name = "Start passkey registration", skip_all)]
+pub async fn start_passkey_registration(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let webauthn = webauthn().map_err(AuthAPIError::UnexpectedError)?;
+
+    // Stops the user's authenticator from creating a second passkey for the same account.
+    let existing = state
+        .passkey_store
+        .read()
+        .await
+        .get_passkeys(&user.email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?
+        .iter()
+        .map(|passkey| passkey.cred_id().clone())
+        .collect::<Vec<_>>();
+
+    let email = user.email.as_ref().expose_secret();
+    let (options, registration) = webauthn
+        .start_passkey_registration(user_handle(&user.email), email, email, Some(existing))
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    state
+        .passkey_ceremony_store
+        .write()
+        .await
+        .add_registration(&user.email, registration)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok((StatusCode::OK, Json(options)))
+}
+
+#[tracing::instrument(name = "Finish passkey registration", skip_all)]
+pub async fn finish_passkey_registration(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(credential): Json<RegisterPublicKeyCredential>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let webauthn = webauthn().map_err(AuthAPIError::UnexpectedError)?;
+
+    let registration = match state
+        .passkey_ceremony_store
+        .write()
+        .await
+        .take_registration(&user.email)
+        .await
+    {
+        Ok(registration) => registration,
+        Err(PasskeyCeremonyStoreError::CeremonyNotFound) => {
+            return Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let passkey = webauthn
+        .finish_passkey_registration(&credential, &registration)
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    match state
+        .passkey_store
+        .write()
+        .await
+        .add_passkey(&user.email, &passkey)
+        .await
+    {
+        Ok(()) => Ok(StatusCode::CREATED),
+        Err(PasskeyStoreError::PasskeyAlreadyExists) => Err(AuthAPIError::IncorrectCredentials),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+/// Starts a passwordless login. The returned `loginAttemptId` identifies the ceremony when
+/// it's finished.
+///
+/// Unknown and unverified accounts, and accounts without a passkey, get a ceremony for a
+/// decoy passkey that can never be finished. The response is the same, and takes as long,
+/// so this route can't be used to find out which addresses are registered.
+#[tracing::instrument(name = "Start passkey login", skip_all)]
+pub async fn start_passkey_login(
+    State(state): State<AppState>,
+    Json(request): Json<StartPasskeyLoginRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let verified = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user.verified,
+        Err(UserStoreError::UserNotFound) => false,
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    // Looked up for every account, so the decoy costs as much as the real thing.
+    let mut passkeys = get_passkeys(&state, &email).await?;
+    if !verified {
+        passkeys.clear();
+    }
+    if passkeys.is_empty() {
+        passkeys.push(decoy_passkey(&email).map_err(AuthAPIError::UnexpectedError)?);
+    }
+
+    let login_attempt_id = LoginAttemptId::default();
+    let options = start_ceremony(&state, &email, &login_attempt_id, &passkeys).await?;
+
+    let response = Json(StartPasskeyLoginResponse {
+        login_attempt_id: login_attempt_id.as_ref().expose_secret().to_owned(),
+        options,
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[tracing::instrument(name = "Finish passkey login", skip_all)]
+pub async fn finish_passkey_login(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(request): Json<FinishPasskeyLoginRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let login_attempt_id = match LoginAttemptId::parse(request.login_attempt_id)
+        .map_err(|_| AuthAPIError::InvalidCredentials)
+    {
+        Ok(login_attempt_id) => login_attempt_id,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let email = match finish_authentication(&state, &login_attempt_id, &request.credential).await {
+        Ok(email) => email,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    // A passkey already proves possession and user verification, so it skips the second
+    // factor that a password login would need.
+    issue_cookies(jar, &state, &email, &[AuthMethod::Passkey], &client).await
+}
+
+/// Starts a passkey ceremony as the second factor of a password login.
+#[tracing::instrument(name = "Start passkey 2FA", skip_all)]
+pub async fn start_passkey_2fa(
+    State(state): State<AppState>,
+    Json(request): Json<StartPasskey2FARequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+    let login_attempt_id = LoginAttemptId::parse(request.login_attempt_id)
+        .map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    check_login_attempt(&state, &email, &login_attempt_id).await?;
+
+    let options = start_authentication(&state, &email, &login_attempt_id).await?;
+
+    Ok((StatusCode::OK, Json(options)))
+}
+
+#[tracing::instrument(name = "Finish passkey 2FA", skip_all)]
+pub async fn finish_passkey_2fa(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(request): Json<FinishPasskey2FARequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let email = match Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials) {
+        Ok(email) => email,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let login_attempt_id = match LoginAttemptId::parse(request.login_attempt_id)
+        .map_err(|_| AuthAPIError::InvalidCredentials)
+    {
+        Ok(login_attempt_id) => login_attempt_id,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let first_factor = match check_login_attempt(&state, &email, &login_attempt_id).await {
+        Ok(first_factor) => first_factor,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    match finish_authentication(&state, &login_attempt_id, &request.credential).await {
+        Ok(authenticated) if authenticated == email => {}
+        Ok(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+        Err(e) => return (jar, Err(e)),
+    }
+
+    if let Err(e) = state
+        .two_fa_code_store
+        .write()
+        .await
+        .remove_code(&email)
+        .await
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    issue_cookies(
+        jar,
+        &state,
+        &email,
+        &[first_factor, AuthMethod::Passkey, AuthMethod::MultiFactor],
+        &client,
+    )
+    .await
+}
+
+async fn start_authentication(
+    state: &AppState,
+    email: &Email,
+    login_attempt_id: &LoginAttemptId,
+) -> Result<RequestChallengeResponse, AuthAPIError> {
+    let passkeys = get_passkeys(state, email).await?;
+
+    if passkeys.is_empty() {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    start_ceremony(state, email, login_attempt_id, &passkeys).await
+}
+
+async fn get_passkeys(state: &AppState, email: &Email) -> Result<Vec<Passkey>, AuthAPIError> {
+    state
+        .passkey_store
+        .read()
+        .await
+        .get_passkeys(email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
+
+/// Starts a ceremony for one of `passkeys` and keeps it for `finish_authentication`.
+async fn start_ceremony(
+    state: &AppState,
+    email: &Email,
+    login_attempt_id: &LoginAttemptId,
+    passkeys: &[Passkey],
+) -> Result<RequestChallengeResponse, AuthAPIError> {
+    let webauthn = webauthn().map_err(AuthAPIError::UnexpectedError)?;
+
+    let (options, authentication) = webauthn
+        .start_passkey_authentication(passkeys)
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    state
+        .passkey_ceremony_store
+        .write()
+        .await
+        .add_authentication(login_attempt_id, email, authentication)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(options)
+}
+
+/// Verifies the assertion for a ceremony started by `start_authentication` and returns the
+/// user it authenticated. Each ceremony can only be finished once.
+async fn finish_authentication(
+    state: &AppState,
+    login_attempt_id: &LoginAttemptId,
+    credential: &PublicKeyCredential,
+) -> Result<Email, AuthAPIError> {
+    let webauthn = webauthn().map_err(AuthAPIError::UnexpectedError)?;
+
+    let (email, authentication): (Email, PasskeyAuthentication) = match state
+        .passkey_ceremony_store
+        .write()
+        .await
+        .take_authentication(login_attempt_id)
+        .await
+    {
+        Ok(ceremony) => ceremony,
+        Err(PasskeyCeremonyStoreError::CeremonyNotFound) => {
+            return Err(AuthAPIError::IncorrectCredentials)
+        }
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let result = webauthn
+        .finish_passkey_authentication(credential, &authentication)
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    update_passkey(state, &email, &result).await?;
+
+    Ok(email)
+}
+
+/// Persists the sign counter and backup state reported by the authenticator.
+async fn update_passkey(
+    state: &AppState,
+    email: &Email,
+    result: &AuthenticationResult,
+) -> Result<(), AuthAPIError> {
+    if !result.needs_update() {
+        return Ok(());
+    }
+
+    let mut passkey_store = state.passkey_store.write().await;
+
+    let passkeys = passkey_store
+        .get_passkeys(email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    for mut passkey in passkeys {
+        if passkey.update_credential(result) == Some(true) {
+            passkey_store
+                .update_passkey(&passkey)
+                .await
+                .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct StartPasskeyLoginRequest {
+    pub email: SecretString,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct StartPasskeyLoginResponse {
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: String,
+    pub options: RequestChallengeResponse,
+}
+
+#[derive(Deserialize)]
+pub struct FinishPasskeyLoginRequest {
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: SecretString,
+    pub credential: PublicKeyCredential,
+}
+
+#[derive(Deserialize)]
+pub struct StartPasskey2FARequest {
+    pub email: SecretString,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: SecretString,
+}
+
+#[derive(Deserialize)]
+pub struct FinishPasskey2FARequest {
+    pub email: SecretString,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: SecretString,
+    pub credential: PublicKeyCredential,
+}
@@ -0,0 +1,143 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, Password, UserStoreError},
+    utils::{
+        auth::{ban_purpose_tokens, generate_purpose_token, validate_purpose_token, TokenPurpose},
+        constants::AUTH_SERVICE_URL,
+        email::send_in_background,
+    },
+};
+
+use super::logout::end_all_sessions;
+
+#[tracing::instrument(name = "Request password reset", skip_all)]
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    Json(request): Json<PasswordResetRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    // Answer the same way whether or not the account exists, so this route can't be used
+    // to find out which addresses are registered.
+    let response = Json(PasswordResetResponse {
+        message: "If the account exists, a password reset link has been sent".to_owned(),
+    });
+
+    match state.user_store.read().await.get_user(&email).await {
+        Ok(_) => {}
+        Err(UserStoreError::UserNotFound) => return Ok((StatusCode::OK, response)),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    let token = generate_purpose_token(&email, TokenPurpose::PasswordReset, &state.keyring)
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    let link = format!(
+        "{}/?reset_token={}",
+        AUTH_SERVICE_URL.as_str(),
+        token.expose_secret()
+    );
+
+    let email_client = state.email_client.clone();
+    send_in_background(async move {
+        email_client
+            .send_email(
+                &email,
+                "Reset your password",
+                &format!(
+                    "Use the link below to choose a new password. It expires in 10 minutes and can only be used once.\n\n{}",
+                    link
+                ),
+            )
+            .await
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[tracing::instrument(name = "Confirm password reset", skip_all)]
+pub async fn confirm_password_reset(
+    State(state): State<AppState>,
+    Json(request): Json<PasswordResetConfirmRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let password =
+        Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let claims = validate_purpose_token(
+        &request.token,
+        TokenPurpose::PasswordReset,
+        state.banned_token_store.clone(),
+        &state.keyring,
+    )
+    .await
+    .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let email = Email::parse(SecretString::new(claims.sub.into_boxed_str()))
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    // Consuming the token is a single check-and-set, so of two requests racing to use the
+    // same link only one gets past here.
+    let consumed = state
+        .banned_token_store
+        .write()
+        .await
+        .consume_token(request.token)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    if !consumed {
+        return Err(AuthAPIError::InvalidToken);
+    }
+
+    match state
+        .user_store
+        .write()
+        .await
+        .update_password(&email, password)
+        .await
+    {
+        Ok(()) => {}
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    // Any other link sent out before now would reset the new password again.
+    ban_purpose_tokens(
+        &email,
+        TokenPurpose::PasswordReset,
+        state.banned_token_store.clone(),
+    )
+    .await
+    .map_err(AuthAPIError::UnexpectedError)?;
+
+    // Whoever knew the old password may still be logged in.
+    end_all_sessions(&state, &email)
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    let response = Json(PasswordResetResponse {
+        message: "Password updated successfully!".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: SecretString,
+}
+
+#[derive(Deserialize)]
+pub struct PasswordResetConfirmRequest {
+    pub token: SecretString,
+    pub password: SecretString,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct PasswordResetResponse {
+    pub message: String,
+}
@@ -0,0 +1,159 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{Duration, Utc};
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, PersonalAccessToken, PersonalAccessTokenRecord, PersonalAccessTokenStoreError,
+    },
+    utils::auth::AuthenticatedUser,
+};
+
+use super::oauth_clients::is_scope_token;
+
+const MAX_TOKEN_NAME_LENGTH: usize = 100;
+
+/// Creates a personal access token for the logged in user. The token itself is only ever
+/// part of this response; afterwards just its hash is kept.
+#[tracing::instrument(name = "Create personal access token", skip_all)]
+pub async fn create_personal_access_token(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(request): Json<CreatePersonalAccessTokenRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let name = request.name.trim().to_owned();
+    if name.is_empty()
+        || name.chars().count() > MAX_TOKEN_NAME_LENGTH
+        || !request.scopes.iter().all(|scope| is_scope_token(scope))
+    {
+        return Err(AuthAPIError::InvalidCredentials);
+    }
+
+    let created_at = Utc::now();
+    let expires_at = match request.expires_in_days {
+        Some(days) => Some(
+            Duration::try_days(days.into())
+                .filter(|ttl| *ttl > Duration::zero())
+                .and_then(|ttl| created_at.checked_add_signed(ttl))
+                .ok_or(AuthAPIError::InvalidCredentials)?,
+        ),
+        None => None,
+    };
+
+    let token = PersonalAccessToken::default();
+    let record = PersonalAccessTokenRecord {
+        id: Uuid::new_v4(),
+        email: user.email,
+        name,
+        scopes: request.scopes,
+        created_at,
+        expires_at,
+        last_used_at: None,
+    };
+
+    state
+        .personal_access_token_store
+        .write()
+        .await
+        .add_token(&token, record.clone())
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let response = PersonalAccessTokenResponse {
+        token: Some(token.as_ref().expose_secret().to_owned()),
+        ..record.into()
+    };
+
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+/// Lists the logged in user's personal access tokens, without the tokens themselves.
+#[tracing::instrument(name = "List personal access tokens", skip_all)]
+pub async fn list_personal_access_tokens(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let tokens = state
+        .personal_access_token_store
+        .read()
+        .await
+        .get_tokens(&user.email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let response: Vec<PersonalAccessTokenResponse> = tokens
+        .into_iter()
+        .map(PersonalAccessTokenResponse::from)
+        .collect();
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[tracing::instrument(name = "Revoke personal access token", skip_all)]
+pub async fn revoke_personal_access_token(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Path(id): Path<Uuid>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    match state
+        .personal_access_token_store
+        .write()
+        .await
+        .revoke_token(&user.email, id)
+        .await
+    {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(PersonalAccessTokenStoreError::TokenNotFound) => {
+            Err(AuthAPIError::PersonalAccessTokenNotFound)
+        }
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatePersonalAccessTokenRequest {
+    pub name: String,
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    /// Omitted for a token that never expires.
+    pub expires_in_days: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PersonalAccessTokenResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: String,
+    pub expires_at: Option<String>,
+    pub last_used_at: Option<String>,
+    /// Only set when the token is created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl From<PersonalAccessTokenRecord> for PersonalAccessTokenResponse {
+    fn from(record: PersonalAccessTokenRecord) -> Self {
+        Self {
+            id: record.id,
+            name: record.name,
+            scopes: record.scopes,
+            created_at: record.created_at.to_rfc3339(),
+            expires_at: record.expires_at.map(|expires_at| expires_at.to_rfc3339()),
+            last_used_at: record
+                .last_used_at
+                .map(|last_used_at| last_used_at.to_rfc3339()),
+            token: None,
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use axum_extra::extract::CookieJar;
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthAPIError, AuthMethod, Email, LoginAttemptId, RecoveryCode, RecoveryCodeStoreError,
+        RECOVERY_CODE_COUNT,
+    },
+    services::data_stores::postgres_user_store::compute_password_hash,
+    utils::auth::{AuthenticatedUser, ClientInfo},
+};
+
+use super::verify_2fa::{check_login_attempt, issue_cookies};
+
+/// Replaces the user's recovery codes with a fresh set. The codes are only ever shown in
+/// this response.
+#[tracing::instrument(name = "Regenerate recovery codes", skip_all)]
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let recovery_codes = generate_recovery_codes(&state, &user.email).await?;
+
+    Ok((
+        StatusCode::OK,
+        Json(RecoveryCodesResponse { recovery_codes }),
+    ))
+}
+
+/// Completes a 2FA login with a recovery code instead of the second factor.
+#[tracing::instrument(name = "Verify 2FA with recovery code", skip_all)]
+pub async fn verify_2fa_recovery_code(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(request): Json<VerifyRecoveryCodeRequest>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let email = match Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials) {
+        Ok(email) => email,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let login_attempt_id = match LoginAttemptId::parse(request.login_attempt_id)
+        .map_err(|_| AuthAPIError::InvalidCredentials)
+    {
+        Ok(login_attempt_id) => login_attempt_id,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let recovery_code = match RecoveryCode::parse(request.recovery_code)
+        .map_err(|_| AuthAPIError::InvalidCredentials)
+    {
+        Ok(recovery_code) => recovery_code,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let first_factor = match check_login_attempt(&state, &email, &login_attempt_id).await {
+        Ok(first_factor) => first_factor,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let mut recovery_code_store = state.recovery_code_store.write().await;
+
+    match recovery_code_store
+        .consume_code(&email, &recovery_code)
+        .await
+    {
+        Ok(()) => {}
+        Err(RecoveryCodeStoreError::CodeNotFound) => {
+            return (jar, Err(AuthAPIError::IncorrectCredentials))
+        }
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    }
+
+    let remaining = match recovery_code_store.count_unused_codes(&email).await {
+        Ok(remaining) => remaining,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    drop(recovery_code_store);
+
+    if let Err(e) = state
+        .two_fa_code_store
+        .write()
+        .await
+        .remove_code(&email)
+        .await
+    {
+        return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+    }
+
+    // The code is already spent, so failing the login over a delivery problem would only
+    // cost the user another one.
+    if let Err(e) = send_recovery_code_used_email(&state, &email, remaining).await {
+        tracing::warn!("failed to send recovery code notification: {:?}", e);
+    }
+
+    issue_cookies(
+        jar,
+        &state,
+        &email,
+        &[
+            first_factor,
+            AuthMethod::OneTimeCode,
+            AuthMethod::MultiFactor,
+        ],
+        &client,
+    )
+    .await
+}
+
+/// Replaces all of `email`'s recovery codes and returns the new ones for display.
+#[tracing::instrument(name = "Generate recovery codes", skip_all)]
+pub(crate) async fn generate_recovery_codes(
+    state: &AppState,
+    email: &Email,
+) -> Result<Vec<String>, AuthAPIError> {
+    let codes = (0..RECOVERY_CODE_COUNT)
+        .map(|_| RecoveryCode::default())
+        .collect::<Vec<_>>();
+
+    state
+        .recovery_code_store
+        .write()
+        .await
+        .set_codes(email, &codes)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    Ok(codes
+        .iter()
+        .map(|code| code.as_ref().expose_secret().to_owned())
+        .collect())
+}
+
+/// Codes for a signup whose email is already registered. They are hashed like real codes,
+/// so the signup takes as long, but never stored.
+#[tracing::instrument(name = "Generate decoy recovery codes", skip_all)]
+pub(crate) async fn generate_decoy_recovery_codes() -> Result<Vec<String>, AuthAPIError> {
+    let codes = (0..RECOVERY_CODE_COUNT)
+        .map(|_| RecoveryCode::default())
+        .collect::<Vec<_>>();
+
+    for code in &codes {
+        compute_password_hash(code.as_ref().to_owned())
+            .await
+            .map_err(AuthAPIError::UnexpectedError)?;
+    }
+
+    Ok(codes
+        .iter()
+        .map(|code| code.as_ref().expose_secret().to_owned())
+        .collect())
+}
+
+/// Called whenever 2FA is turned on. Users who still have unused codes keep them, so
+/// switching methods doesn't invalidate codes they've already written down.
+#[tracing::instrument(name = "Generate missing recovery codes", skip_all)]
+pub(crate) async fn generate_recovery_codes_if_missing(
+    state: &AppState,
+    email: &Email,
+) -> Result<Option<Vec<String>>, AuthAPIError> {
+    let unused = state
+        .recovery_code_store
+        .read()
+        .await
+        .count_unused_codes(email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    if unused > 0 {
+        return Ok(None);
+    }
+
+    generate_recovery_codes(state, email).await.map(Some)
+}
+
+#[tracing::instrument(name = "Send recovery code used email", skip_all)]
+async fn send_recovery_code_used_email(
+    state: &AppState,
+    email: &Email,
+    remaining: usize,
+) -> color_eyre::eyre::Result<()> {
+    state
+        .email_client
+        .send_email(
+            email,
+            "A recovery code was used",
+            &format!(
+                "One of your recovery codes was just used to sign in. You have {} left.\n\nIf this wasn't you, reset your password and generate new recovery codes.",
+                remaining
+            ),
+        )
+        .await
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RecoveryCodesResponse {
+    #[serde(rename = "recoveryCodes")]
+    pub recovery_codes: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct VerifyRecoveryCodeRequest {
+    pub email: SecretString,
+    #[serde(rename = "loginAttemptId")]
+    pub login_attempt_id: SecretString,
+    #[serde(rename = "recoveryCode")]
+    pub recovery_code: SecretString,
+}
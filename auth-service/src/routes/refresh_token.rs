@@ -0,0 +1,97 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use secrecy::SecretString;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, RefreshToken, RefreshTokenStoreError, SessionStoreError},
+    utils::{
+        auth::{generate_auth_cookie, generate_refresh_cookie},
+        constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME},
+    },
+};
+
+#[tracing::instrument(name = "Refresh token", skip_all)]
+pub async fn refresh_token(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    let cookie = match jar.get(REFRESH_COOKIE_NAME) {
+        Some(cookie) => cookie,
+        None => return (jar, Err(AuthAPIError::MissingToken)),
+    };
+
+    let token = match RefreshToken::parse(SecretString::new(cookie.value().to_owned().into()))
+        .map_err(|_| AuthAPIError::InvalidToken)
+    {
+        Ok(token) => token,
+        Err(e) => return (jar, Err(e)),
+    };
+
+    let mut refresh_token_store = state.refresh_token_store.write().await;
+
+    let record = match refresh_token_store.consume_token(&token, None).await {
+        Ok(record) => record,
+        Err(RefreshTokenStoreError::TokenReused) => {
+            // A rotated token came back, so either the client or an attacker holds a stolen
+            // copy. We can't tell which, so the whole family is revoked and both must log in again.
+            tracing::warn!("refresh token reuse detected, revoking token family");
+            if let Err(e) = refresh_token_store.revoke_family(&token).await {
+                return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+            }
+            let jar = remove_auth_cookies(jar);
+            return (jar, Err(AuthAPIError::InvalidToken));
+        }
+        Err(RefreshTokenStoreError::TokenNotFound) => {
+            return (jar, Err(AuthAPIError::InvalidToken))
+        }
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    drop(refresh_token_store);
+
+    // The token family is the session, so a revoked session can't be refreshed either.
+    match state
+        .session_store
+        .write()
+        .await
+        .touch_session(record.family_id)
+        .await
+    {
+        Ok(()) => {}
+        Err(SessionStoreError::SessionNotFound) => {
+            let jar = remove_auth_cookies(jar);
+            return (jar, Err(AuthAPIError::InvalidToken));
+        }
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    }
+
+    let auth_cookie =
+        match generate_auth_cookie(&record.email, &record.amr, record.family_id, &state.keyring) {
+            Ok(cookie) => cookie,
+            Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
+        };
+
+    let refresh_cookie = match generate_refresh_cookie(
+        &record.email,
+        record.family_id,
+        &record.amr,
+        state.refresh_token_store.clone(),
+    )
+    .await
+    {
+        Ok(cookie) => cookie,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
+    };
+
+    let updated_jar = jar.add(auth_cookie).add(refresh_cookie);
+
+    (updated_jar, Ok(StatusCode::OK))
+}
+
+// The cookies are set for "/", while a removal cookie without a path would only cover
+// "/token".
+fn remove_auth_cookies(jar: CookieJar) -> CookieJar {
+    jar.remove(Cookie::build(JWT_COOKIE_NAME).path("/"))
+        .remove(Cookie::build(REFRESH_COOKIE_NAME).path("/"))
+}
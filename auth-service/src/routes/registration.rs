@@ -0,0 +1,336 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use secrecy::ExposeSecret;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        ClientSecret, GrantType, OAuthClient, OAuthClientStoreError, OAuthError, RegistrationToken,
+    },
+    utils::{auth::bearer_token, constants::AUTH_SERVICE_URL},
+};
+
+use super::{
+    oauth_clients::{is_scope_token, parse_redirect_uri},
+    oidc::SUPPORTED_SCOPES,
+};
+
+/// Clients registering themselves may only sign users in and keep them signed in. Anything
+/// acting on its own behalf is registered by an admin, who decides which scopes it gets.
+const REGISTRABLE_GRANT_TYPES: [GrantType; 3] = [
+    GrantType::AuthorizationCode,
+    GrantType::DeviceCode,
+    GrantType::RefreshToken,
+];
+
+const TOKEN_ENDPOINT_AUTH_METHOD: &str = "client_secret_basic";
+
+/// Dynamic client registration (RFC 7591). Takes an initial access token from
+/// `/admin/initial-access-tokens` as a bearer token, which is used up by a successful
+/// registration.
+#[tracing::instrument(name = "Register client", skip_all)]
+pub async fn register_client(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(metadata): Json<ClientMetadata>,
+) -> Result<impl IntoResponse, OAuthError> {
+    let initial_access_token = bearer_token(&headers).ok_or(OAuthError::InvalidToken)?;
+
+    // Metadata is checked first so a mistake in it doesn't use up the token.
+    let client = metadata.into_client(Uuid::new_v4().to_string())?;
+
+    let mut oauth_client_store = state.oauth_client_store.write().await;
+
+    match oauth_client_store
+        .use_initial_access_token(&initial_access_token)
+        .await
+    {
+        Ok(()) => {}
+        Err(OAuthClientStoreError::InvalidCredentials) => return Err(OAuthError::InvalidToken),
+        Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+    }
+
+    let client_secret = ClientSecret::default();
+    let registration_access_token = RegistrationToken::default();
+
+    oauth_client_store
+        .add_client(&client, &client_secret, Some(&registration_access_token))
+        .await
+        .map_err(|e| OAuthError::UnexpectedError(e.into()))?;
+
+    let mut response = ClientInformationResponse::from(client);
+    response.client_secret = Some(client_secret.as_ref().expose_secret().to_owned());
+    response.client_secret_expires_at = Some(0);
+    response.registration_access_token = Some(
+        registration_access_token
+            .as_ref()
+            .expose_secret()
+            .to_owned(),
+    );
+
+    Ok((
+        StatusCode::CREATED,
+        [
+            (header::CACHE_CONTROL, "no-store"),
+            (header::PRAGMA, "no-cache"),
+        ],
+        Json(response),
+    ))
+}
+
+/// Client read request (RFC 7592 section 2.1).
+#[tracing::instrument(name = "Get registered client", skip_all)]
+pub async fn get_registered_client(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(client_id): Path<String>,
+) -> Result<impl IntoResponse, OAuthError> {
+    let client = authenticate_registration(&state, &headers, &client_id).await?;
+
+    Ok((
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(ClientInformationResponse::from(client)),
+    ))
+}
+
+/// Client update request (RFC 7592 section 2.2). The metadata replaces what the client
+/// registered before, with omitted fields falling back to their defaults. The secret and
+/// the registration access token stay the same.
+#[tracing::instrument(name = "Update registered client", skip_all)]
+pub async fn update_registered_client(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(client_id): Path<String>,
+    Json(metadata): Json<ClientMetadata>,
+) -> Result<impl IntoResponse, OAuthError> {
+    authenticate_registration(&state, &headers, &client_id).await?;
+
+    if metadata
+        .client_id
+        .as_ref()
+        .is_some_and(|id| *id != client_id)
+    {
+        return Err(OAuthError::InvalidClientMetadata);
+    }
+
+    let client = metadata.into_client(client_id)?;
+
+    state
+        .oauth_client_store
+        .write()
+        .await
+        .update_client(&client)
+        .await
+        .map_err(|e| OAuthError::UnexpectedError(e.into()))?;
+
+    Ok((
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(ClientInformationResponse::from(client)),
+    ))
+}
+
+/// Client delete request (RFC 7592 section 2.3). Tokens already issued to the client stay
+/// valid until they expire.
+#[tracing::instrument(name = "Delete registered client", skip_all)]
+pub async fn delete_registered_client(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(client_id): Path<String>,
+) -> Result<impl IntoResponse, OAuthError> {
+    authenticate_registration(&state, &headers, &client_id).await?;
+
+    state
+        .oauth_client_store
+        .write()
+        .await
+        .delete_client(&client_id)
+        .await
+        .map_err(|e| OAuthError::UnexpectedError(e.into()))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Checks the registration access token for `client_id`. Unknown clients are answered
+/// like a wrong token, so the endpoint doesn't reveal which client ids exist (RFC 7592
+/// section 3).
+async fn authenticate_registration(
+    state: &AppState,
+    headers: &HeaderMap,
+    client_id: &str,
+) -> Result<OAuthClient, OAuthError> {
+    let token = bearer_token(headers).ok_or(OAuthError::InvalidToken)?;
+
+    match state
+        .oauth_client_store
+        .read()
+        .await
+        .validate_registration_access_token(client_id, &token)
+        .await
+    {
+        Ok(client) => Ok(client),
+        Err(OAuthClientStoreError::ClientNotFound | OAuthClientStoreError::InvalidCredentials) => {
+            Err(OAuthError::InvalidToken)
+        }
+        Err(e) => Err(OAuthError::UnexpectedError(e.into())),
+    }
+}
+
+/// Client metadata (RFC 7591 section 2). Fields this server has no use for are ignored.
+#[derive(Deserialize)]
+pub struct ClientMetadata {
+    /// Only sent with updates, where it must match the client being updated.
+    pub client_id: Option<String>,
+    pub client_name: Option<String>,
+    #[serde(default)]
+    pub redirect_uris: Vec<String>,
+    pub grant_types: Option<Vec<String>>,
+    pub response_types: Option<Vec<String>>,
+    pub scope: Option<String>,
+    pub token_endpoint_auth_method: Option<String>,
+}
+
+impl ClientMetadata {
+    /// Without `grant_types` the client signs users in with authorization codes, and without
+    /// a `scope` it gets all of the OpenID Connect scopes.
+    fn into_client(self, client_id: String) -> Result<OAuthClient, OAuthError> {
+        let grant_types = match self.grant_types {
+            Some(grant_types) => grant_types
+                .iter()
+                .map(|grant_type| GrantType::parse(grant_type))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| OAuthError::InvalidClientMetadata)?,
+            None => vec![GrantType::AuthorizationCode],
+        };
+
+        if grant_types.is_empty()
+            || !grant_types
+                .iter()
+                .all(|grant_type| REGISTRABLE_GRANT_TYPES.contains(grant_type))
+        {
+            return Err(OAuthError::InvalidClientMetadata);
+        }
+
+        let signs_users_in = grant_types.contains(&GrantType::AuthorizationCode);
+
+        // `code` is the only response type, and it belongs to the authorization code grant
+        // (RFC 7591 section 2.1).
+        if let Some(response_types) = &self.response_types {
+            if !response_types
+                .iter()
+                .all(|response_type| response_type == "code")
+                || (!response_types.is_empty() && !signs_users_in)
+            {
+                return Err(OAuthError::InvalidClientMetadata);
+            }
+        }
+
+        if self
+            .token_endpoint_auth_method
+            .as_deref()
+            .is_some_and(|method| method != TOKEN_ENDPOINT_AUTH_METHOD)
+        {
+            return Err(OAuthError::InvalidClientMetadata);
+        }
+
+        let scopes: Vec<String> = match &self.scope {
+            Some(scope) => scope
+                .split(' ')
+                .filter(|scope| !scope.is_empty())
+                .map(str::to_owned)
+                .collect(),
+            None => SUPPORTED_SCOPES
+                .iter()
+                .map(|scope| scope.to_string())
+                .collect(),
+        };
+
+        if scopes.is_empty()
+            || !scopes
+                .iter()
+                .all(|scope| is_scope_token(scope) && SUPPORTED_SCOPES.contains(&scope.as_str()))
+        {
+            return Err(OAuthError::InvalidClientMetadata);
+        }
+
+        // Unlike clients an admin registers, these must use TLS to get their codes back
+        // unless they run on the user's own machine (RFC 8252 section 7.3).
+        let redirect_uris_valid = self.redirect_uris.iter().all(|uri| {
+            parse_redirect_uri(uri).is_some_and(|url| {
+                url.scheme() == "https"
+                    || (url.scheme() == "http"
+                        && matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]")))
+            })
+        });
+
+        if !redirect_uris_valid || (signs_users_in && self.redirect_uris.is_empty()) {
+            return Err(OAuthError::InvalidRedirectUri);
+        }
+
+        Ok(OAuthClient {
+            client_id,
+            client_name: self.client_name,
+            redirect_uris: self.redirect_uris,
+            allowed_scopes: scopes,
+            allowed_grant_types: grant_types,
+        })
+    }
+}
+
+/// Client information response (RFC 7591 section 3.2.1). The secret and the registration
+/// access token are only returned on registration.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ClientInformationResponse {
+    pub client_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_secret: Option<String>,
+    /// Always `0`: secrets don't expire.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_secret_expires_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registration_access_token: Option<String>,
+    pub registration_client_uri: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_name: Option<String>,
+    pub redirect_uris: Vec<String>,
+    pub grant_types: Vec<GrantType>,
+    pub response_types: Vec<String>,
+    pub scope: String,
+    pub token_endpoint_auth_method: String,
+}
+
+impl From<OAuthClient> for ClientInformationResponse {
+    fn from(client: OAuthClient) -> Self {
+        let response_types = if client
+            .allowed_grant_types
+            .contains(&GrantType::AuthorizationCode)
+        {
+            vec!["code".to_owned()]
+        } else {
+            vec![]
+        };
+
+        Self {
+            registration_client_uri: format!(
+                "{}/register/{}",
+                AUTH_SERVICE_URL.as_str(),
+                client.client_id
+            ),
+            client_id: client.client_id,
+            client_secret: None,
+            client_secret_expires_at: None,
+            registration_access_token: None,
+            client_name: client.client_name,
+            redirect_uris: client.redirect_uris,
+            grant_types: client.allowed_grant_types,
+            response_types,
+            scope: client.allowed_scopes.join(" "),
+            token_endpoint_auth_method: TOKEN_ENDPOINT_AUTH_METHOD.to_owned(),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Form};
+use secrecy::SecretString;
+use serde::Deserialize;
+
+use crate::{
+    app_state::AppState,
+    domain::{OAuthError, RefreshToken},
+    utils::auth::{validate_jwt, AuthenticatedClient},
+};
+
+/// Token revocation (RFC 7009). A client can only revoke tokens that were issued to it.
+/// Access tokens are banned, and refresh tokens are revoked along with every token rotated
+/// from the same grant. Unknown, already invalid and other clients' tokens are accepted
+/// silently so callers can't probe which tokens exist.
+#[tracing::instrument(name = "Revoke token", skip_all)]
+pub async fn revoke(
+    State(state): State<AppState>,
+    AuthenticatedClient { client }: AuthenticatedClient,
+    Form(request): Form<RevokeRequest>,
+) -> Result<impl IntoResponse, OAuthError> {
+    let token = request.token.ok_or(OAuthError::InvalidRequest)?;
+
+    // The two kinds of token can be told apart by their shape, so `token_type_hint`
+    // (RFC 7009 section 2.1) is accepted but not needed. Refresh tokens in the cookies of
+    // first-party sessions have no client, so none can revoke them; users end those
+    // sessions with `/logout` or `/sessions` instead.
+    if let Ok(refresh_token) = RefreshToken::parse(token.clone()) {
+        state
+            .refresh_token_store
+            .write()
+            .await
+            .revoke_client_family(&refresh_token, &client.client_id)
+            .await
+            .map_err(|e| OAuthError::UnexpectedError(e.into()))?;
+
+        return Ok(StatusCode::OK);
+    }
+
+    // Only tokens we would still accept are banned, so the ban list can't be filled with
+    // junk.
+    let Ok(claims) = validate_jwt(
+        &token,
+        None,
+        state.banned_token_store.clone(),
+        state.session_store.clone(),
+        &state.keyring,
+    )
+    .await
+    else {
+        return Ok(StatusCode::OK);
+    };
+
+    // RFC 7009 section 2.1: the token must have been issued to the client revoking it.
+    if claims.client_id.as_deref() != Some(client.client_id.as_str()) {
+        return Ok(StatusCode::OK);
+    }
+
+    state
+        .banned_token_store
+        .write()
+        .await
+        .store_token(token)
+        .await
+        .map_err(|e| OAuthError::UnexpectedError(e.into()))?;
+
+    Ok(StatusCode::OK)
+}
+
+#[derive(Deserialize)]
+pub struct RevokeRequest {
+    pub token: Option<SecretString>,
+    pub token_type_hint: Option<String>,
+}
@@ -0,0 +1,114 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::{cookie::Cookie, CookieJar};
+use chrono::{Duration, Utc};
+use color_eyre::eyre::eyre;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, AuthMethod, Session, SessionStoreError},
+    utils::{
+        auth::{AuthenticatedUser, REFRESH_TOKEN_TTL_SECONDS},
+        constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME},
+    },
+};
+
+/// Lists where the logged in user is logged in, most recently used first.
+#[tracing::instrument(name = "List sessions", skip_all)]
+pub async fn list_sessions(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let sessions = state
+        .session_store
+        .read()
+        .await
+        .get_sessions(&user.email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    // A session whose refresh token has expired can't be used any more, even though its
+    // record is still around.
+    let expired_before = Duration::try_seconds(REFRESH_TOKEN_TTL_SECONDS)
+        .and_then(|ttl| Utc::now().checked_sub_signed(ttl))
+        .ok_or(AuthAPIError::UnexpectedError(eyre!(
+            "failed to compute session expiry"
+        )))?;
+
+    let response: Vec<SessionResponse> = sessions
+        .into_iter()
+        .filter(|session| session.last_seen_at > expired_before)
+        .map(|session| SessionResponse::new(session, user.session_id))
+        .collect();
+
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Logs the user out of one of their sessions. Revoking the current session also clears
+/// its cookies.
+#[tracing::instrument(name = "Revoke session", skip_all)]
+pub async fn revoke_session(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    user: AuthenticatedUser,
+    Path(id): Path<Uuid>,
+) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
+    match state
+        .session_store
+        .write()
+        .await
+        .revoke_session(&user.email, id)
+        .await
+    {
+        Ok(()) => {}
+        Err(SessionStoreError::SessionNotFound) => {
+            return (jar, Err(AuthAPIError::SessionNotFound))
+        }
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    }
+
+    // The cookies are set for "/", while a removal cookie without a path would only cover
+    // "/sessions".
+    let jar = if user.session_id == id {
+        jar.remove(Cookie::build(JWT_COOKIE_NAME).path("/"))
+            .remove(Cookie::build(REFRESH_COOKIE_NAME).path("/"))
+    } else {
+        jar
+    };
+
+    (jar, Ok(StatusCode::NO_CONTENT))
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionResponse {
+    pub id: Uuid,
+    pub created_at: String,
+    pub last_seen_at: String,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    /// Whether the user completed 2FA when logging in to this session.
+    pub two_factor: bool,
+    /// Whether this is the session the request was made with.
+    pub current: bool,
+}
+
+impl SessionResponse {
+    fn new(session: Session, current_session_id: Uuid) -> Self {
+        Self {
+            id: session.id,
+            created_at: session.created_at.to_rfc3339(),
+            last_seen_at: session.last_seen_at.to_rfc3339(),
+            ip: session.ip,
+            user_agent: session.user_agent,
+            two_factor: session.amr.contains(&AuthMethod::MultiFactor),
+            current: current_session_id == session.id,
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::AuthAPIError,
+    utils::{auth::AdminCaller, constants::SIGNING_KEY_ACTIVATION_DELAY_SECONDS},
+};
+
+/// Replaces the active signing key with a newly generated one. The new key is published
+/// straight away but only signs tokens once verifiers have had time to pick it up. Tokens
+/// signed by the old key keep verifying until they expire, so nobody is logged out.
+#[tracing::instrument(name = "Rotate signing key", skip_all)]
+pub async fn rotate_signing_key(
+    State(state): State<AppState>,
+    _admin: AdminCaller,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let kid = state
+        .keyring
+        .rotate(
+            &state.signing_key_store,
+            chrono::Duration::seconds(SIGNING_KEY_ACTIVATION_DELAY_SECONDS as i64),
+        )
+        .await
+        .map_err(AuthAPIError::UnexpectedError)?;
+
+    Ok((StatusCode::OK, Json(RotateSigningKeyResponse { kid })))
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RotateSigningKeyResponse {
+    pub kid: String,
+}
@@ -1,12 +1,22 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use color_eyre::eyre::Result;
 use secrecy::SecretString;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, Password, User},
+    domain::{AuthAPIError, Email, Password, User, UserStoreError},
+    utils::constants::AUTH_SERVICE_URL,
 };
 
+use super::{
+    recovery_codes::{generate_decoy_recovery_codes, generate_recovery_codes},
+    verify_email::send_verification_email,
+};
+
+/// Signing up with a registered email gets the same response as with a new one, and takes
+/// as long, so signups can't be used to find out which addresses are registered. The
+/// account's owner is emailed instead of being sent a verification link.
 #[tracing::instrument(name = "Signup", skip_all)]
 pub async fn signup(
     State(state): State<AppState>,
@@ -17,26 +27,68 @@ pub async fn signup(
     let password =
         Password::parse(request.password).map_err(|_| AuthAPIError::InvalidCredentials)?;
 
-    let mut user_store = state.user_store.write().await;
+    let user = User::new(email.clone(), password, request.requires_2fa);
 
-    if user_store.get_user(&email).await.is_ok() {
-        return Err(AuthAPIError::UserAlreadyExists);
-    }
+    let created = match state.user_store.write().await.add_user(user).await {
+        Ok(()) => true,
+        Err(UserStoreError::UserAlreadyExists) => false,
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
 
-    let user = User::new(email, password, request.requires_2fa);
+    // The account already exists at this point, so a delivery failure shouldn't fail the
+    // signup. The user can ask for another link through /verify-email/resend.
+    let sent = if created {
+        send_verification_email(&state, &email).await
+    } else {
+        send_account_exists_email(&state, &email).await
+    };
+    if let Err(e) = sent {
+        tracing::warn!("failed to send signup email: {:?}", e);
+    }
 
-    user_store
-        .add_user(user)
-        .await
-        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+    // Like the verification email, a failure here shouldn't undo the signup. New codes can
+    // be generated from /2fa/recovery-codes once logged in. Signups for a registered email
+    // get codes that are never stored.
+    let recovery_codes = if request.requires_2fa {
+        let codes = if created {
+            generate_recovery_codes(&state, &email).await
+        } else {
+            generate_decoy_recovery_codes().await
+        };
+        match codes {
+            Ok(codes) => Some(codes),
+            Err(e) => {
+                tracing::warn!("failed to generate recovery codes: {:?}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
 
     let response = Json(SignupResponse {
         message: "User created successfully!".to_string(),
+        recovery_codes,
     });
 
     Ok((StatusCode::CREATED, response))
 }
 
+#[tracing::instrument(name = "Send account exists email", skip_all)]
+async fn send_account_exists_email(state: &AppState, email: &Email) -> Result<()> {
+    state
+        .email_client
+        .send_email(
+            email,
+            "You already have an account",
+            &format!(
+                "Someone tried to sign up with this email address, but it already has an account. If it was you, log in instead, or reset your password if you've forgotten it. If it wasn't, you can ignore this email.\n\n{}",
+                AUTH_SERVICE_URL.as_str()
+            ),
+        )
+        .await
+}
+
 #[derive(Deserialize)]
 pub struct SignupRequest {
     pub email: SecretString,
@@ -48,4 +100,10 @@ pub struct SignupRequest {
 #[derive(Debug, Deserialize, PartialEq, Serialize)]
 pub struct SignupResponse {
     pub message: String,
+    #[serde(
+        rename = "recoveryCodes",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub recovery_codes: Option<Vec<String>>,
 }
@@ -0,0 +1,414 @@
+use axum::{extract::State, http::header, response::IntoResponse, Form, Json};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use uuid::Uuid;
+
+use crate::{
+    app_state::AppState,
+    domain::{
+        AuthMethod, AuthorizationCode, AuthorizationCodeStoreError, DeviceCode,
+        DeviceCodeStoreError, DeviceGrantStatus, Email, GrantType, OAuthClient, OAuthError,
+        RefreshToken, RefreshTokenClient, RefreshTokenStoreError,
+    },
+    utils::{
+        auth::{
+            first_party_audience, generate_access_token, generate_client_token, generate_id_token,
+            generate_refresh_token, hash_token, AuthenticatedClient, TOKEN_TTL_SECONDS,
+        },
+        constants::{DEVICE_CODE_POLL_INTERVAL_SECONDS, JWT_AUDIENCES},
+    },
+};
+
+/// Token endpoint (RFC 6749 section 3.2). Each client may only use the grant types it was
+/// registered with.
+#[tracing::instrument(name = "Token", skip_all)]
+pub async fn token(
+    State(state): State<AppState>,
+    AuthenticatedClient { client }: AuthenticatedClient,
+    Form(request): Form<TokenRequest>,
+) -> Result<impl IntoResponse, OAuthError> {
+    let grant_type = request
+        .grant_type
+        .as_deref()
+        .ok_or(OAuthError::InvalidRequest)?;
+    let grant_type = GrantType::parse(grant_type).map_err(|_| OAuthError::UnsupportedGrantType)?;
+
+    if !client.allowed_grant_types.contains(&grant_type) {
+        return Err(OAuthError::UnauthorizedClient);
+    }
+
+    let audience = requested_audience(request.resource.as_deref())?;
+
+    let response = match grant_type {
+        GrantType::AuthorizationCode => {
+            redeem_authorization_code(&state, &client, audience, request).await?
+        }
+        GrantType::ClientCredentials => issue_client_token(&state, &client, audience, request)?,
+        GrantType::DeviceCode => redeem_device_code(&state, &client, audience, request).await?,
+        GrantType::RefreshToken => redeem_refresh_token(&state, &client, audience, request).await?,
+    };
+
+    // RFC 6749 section 5.1: responses carrying tokens must not be cached.
+    Ok((
+        [
+            (header::CACHE_CONTROL, "no-store"),
+            (header::PRAGMA, "no-cache"),
+        ],
+        Json(response),
+    ))
+}
+
+/// Redeems an authorization code from `/authorize` for an access token and an ID token.
+/// The code must be presented by the client it was issued to, with the same redirect URI
+/// and the PKCE verifier for its challenge.
+async fn redeem_authorization_code(
+    state: &AppState,
+    client: &OAuthClient,
+    audience: &str,
+    request: TokenRequest,
+) -> Result<TokenResponse, OAuthError> {
+    let (Some(code), Some(redirect_uri), Some(code_verifier)) =
+        (request.code, request.redirect_uri, request.code_verifier)
+    else {
+        return Err(OAuthError::InvalidRequest);
+    };
+
+    let code = AuthorizationCode::parse(code).map_err(|_| OAuthError::InvalidGrant)?;
+
+    let grant = match state
+        .authorization_code_store
+        .write()
+        .await
+        .take_code(&code)
+        .await
+    {
+        Ok(grant) => grant,
+        Err(AuthorizationCodeStoreError::CodeNotFound) => return Err(OAuthError::InvalidGrant),
+        Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+    };
+
+    if grant.client_id != client.client_id
+        || grant.redirect_uri != redirect_uri
+        || !verify_code_challenge(&code_verifier, &grant.code_challenge)
+    {
+        return Err(OAuthError::InvalidGrant);
+    }
+
+    let access_token = generate_access_token(
+        &grant.email,
+        &grant.amr,
+        &grant.scope,
+        &client.client_id,
+        audience,
+        &state.keyring,
+    )
+    .map_err(OAuthError::UnexpectedError)?;
+    let id_token =
+        generate_id_token(&grant, &state.keyring).map_err(OAuthError::UnexpectedError)?;
+    let refresh_token = if client
+        .allowed_grant_types
+        .contains(&GrantType::RefreshToken)
+    {
+        Some(
+            issue_refresh_token(
+                state,
+                client,
+                Uuid::new_v4(),
+                &grant.email,
+                &grant.amr,
+                &grant.scope,
+            )
+            .await?,
+        )
+    } else {
+        None
+    };
+
+    Ok(TokenResponse {
+        access_token: access_token.expose_secret().to_owned(),
+        token_type: "Bearer".to_owned(),
+        expires_in: TOKEN_TTL_SECONDS,
+        id_token: Some(id_token.expose_secret().to_owned()),
+        refresh_token,
+        scope: grant.scope,
+    })
+}
+
+/// Issues a token to the client itself (RFC 6749 section 4.4).
+fn issue_client_token(
+    state: &AppState,
+    client: &OAuthClient,
+    audience: &str,
+    request: TokenRequest,
+) -> Result<TokenResponse, OAuthError> {
+    if !client.can_act_for_itself() {
+        return Err(OAuthError::UnauthorizedClient);
+    }
+
+    let scope = requested_scope(client, request.scope)?;
+
+    let access_token = generate_client_token(&client.client_id, &scope, audience, &state.keyring)
+        .map_err(OAuthError::UnexpectedError)?;
+
+    Ok(TokenResponse {
+        access_token: access_token.expose_secret().to_owned(),
+        token_type: "Bearer".to_owned(),
+        expires_in: TOKEN_TTL_SECONDS,
+        id_token: None,
+        refresh_token: None,
+        scope,
+    })
+}
+
+/// Collects the result of a device authorization request from `/device/code` (RFC 8628
+/// section 3.4). Until the user has decided the device is told to keep polling, and to
+/// slow down if it polls more often than the interval it was given.
+async fn redeem_device_code(
+    state: &AppState,
+    client: &OAuthClient,
+    audience: &str,
+    request: TokenRequest,
+) -> Result<TokenResponse, OAuthError> {
+    let device_code = request.device_code.ok_or(OAuthError::InvalidRequest)?;
+    let device_code = DeviceCode::parse(device_code).map_err(|_| OAuthError::InvalidGrant)?;
+
+    let polls = state
+        .rate_limit_store
+        .write()
+        .await
+        .hit(
+            &format!("device_poll:{}", hash_token(device_code.as_ref())),
+            DEVICE_CODE_POLL_INTERVAL_SECONDS,
+        )
+        .await
+        .map_err(|e| OAuthError::UnexpectedError(e.into()))?;
+
+    if polls > 1 {
+        return Err(OAuthError::SlowDown);
+    }
+
+    let grant = match state
+        .device_code_store
+        .write()
+        .await
+        .poll(&device_code)
+        .await
+    {
+        Ok(grant) => grant,
+        // Requests are dropped from the store when they expire, so an expired code can't be
+        // told apart from one that was never issued. Either way the device has to start over.
+        Err(DeviceCodeStoreError::GrantNotFound) => return Err(OAuthError::ExpiredToken),
+        Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+    };
+
+    if grant.client_id != client.client_id {
+        return Err(OAuthError::InvalidGrant);
+    }
+
+    let (email, amr) = match grant.status {
+        DeviceGrantStatus::Pending => return Err(OAuthError::AuthorizationPending),
+        DeviceGrantStatus::Denied => return Err(OAuthError::AccessDenied),
+        DeviceGrantStatus::Approved { email, amr } => (email, amr),
+    };
+
+    let access_token = generate_access_token(
+        &email,
+        &amr,
+        &grant.scope,
+        &client.client_id,
+        audience,
+        &state.keyring,
+    )
+    .map_err(OAuthError::UnexpectedError)?;
+    let refresh_token = if client
+        .allowed_grant_types
+        .contains(&GrantType::RefreshToken)
+    {
+        Some(issue_refresh_token(state, client, Uuid::new_v4(), &email, &amr, &grant.scope).await?)
+    } else {
+        None
+    };
+
+    Ok(TokenResponse {
+        access_token: access_token.expose_secret().to_owned(),
+        token_type: "Bearer".to_owned(),
+        expires_in: TOKEN_TTL_SECONDS,
+        id_token: None,
+        refresh_token,
+        scope: grant.scope,
+    })
+}
+
+/// Swaps a refresh token issued to the client for a new access token and refresh token
+/// (RFC 6749 section 6). The tokens keep the scope the user originally granted. Each
+/// refresh token can be used once; presenting one again revokes its whole family, as it
+/// does for session cookies.
+async fn redeem_refresh_token(
+    state: &AppState,
+    client: &OAuthClient,
+    audience: &str,
+    request: TokenRequest,
+) -> Result<TokenResponse, OAuthError> {
+    let token = request.refresh_token.ok_or(OAuthError::InvalidRequest)?;
+    let token = RefreshToken::parse(token).map_err(|_| OAuthError::InvalidGrant)?;
+
+    let mut refresh_token_store = state.refresh_token_store.write().await;
+
+    let record = match refresh_token_store
+        .consume_token(&token, Some(&client.client_id))
+        .await
+    {
+        Ok(record) => record,
+        Err(RefreshTokenStoreError::TokenReused) => {
+            tracing::warn!("refresh token reuse detected, revoking token family");
+            refresh_token_store
+                .revoke_family(&token)
+                .await
+                .map_err(|e| OAuthError::UnexpectedError(e.into()))?;
+            return Err(OAuthError::InvalidGrant);
+        }
+        Err(RefreshTokenStoreError::TokenNotFound) => return Err(OAuthError::InvalidGrant),
+        Err(e) => return Err(OAuthError::UnexpectedError(e.into())),
+    };
+
+    drop(refresh_token_store);
+
+    let scope = record
+        .client
+        .map(|client| client.scope)
+        .ok_or(OAuthError::InvalidGrant)?;
+
+    let access_token = generate_access_token(
+        &record.email,
+        &record.amr,
+        &scope,
+        &client.client_id,
+        audience,
+        &state.keyring,
+    )
+    .map_err(OAuthError::UnexpectedError)?;
+    let refresh_token = issue_refresh_token(
+        state,
+        client,
+        record.family_id,
+        &record.email,
+        &record.amr,
+        &scope,
+    )
+    .await?;
+
+    Ok(TokenResponse {
+        access_token: access_token.expose_secret().to_owned(),
+        token_type: "Bearer".to_owned(),
+        expires_in: TOKEN_TTL_SECONDS,
+        id_token: None,
+        refresh_token: Some(refresh_token),
+        scope,
+    })
+}
+
+/// Issues a refresh token for `email` that only `client` can redeem or revoke.
+async fn issue_refresh_token(
+    state: &AppState,
+    client: &OAuthClient,
+    family_id: Uuid,
+    email: &Email,
+    amr: &[AuthMethod],
+    scope: &str,
+) -> Result<String, OAuthError> {
+    let client = RefreshTokenClient {
+        client_id: client.client_id.clone(),
+        scope: scope.to_owned(),
+    };
+
+    let token = generate_refresh_token(
+        email,
+        family_id,
+        amr,
+        Some(client),
+        state.refresh_token_store.clone(),
+    )
+    .await
+    .map_err(OAuthError::UnexpectedError)?;
+
+    Ok(token.as_ref().expose_secret().to_owned())
+}
+
+/// Checks the scopes a client asked for against the ones it is allowed. Without a `scope`
+/// the client gets every scope it is allowed.
+pub(super) fn requested_scope(
+    client: &OAuthClient,
+    scope: Option<String>,
+) -> Result<String, OAuthError> {
+    let Some(scope) = scope else {
+        return Ok(client.allowed_scopes.join(" "));
+    };
+
+    let scopes: Vec<&str> = scope.split(' ').filter(|s| !s.is_empty()).collect();
+    if scopes.is_empty()
+        || !scopes
+            .iter()
+            .all(|scope| client.allowed_scopes.iter().any(|allowed| allowed == scope))
+    {
+        return Err(OAuthError::InvalidScope);
+    }
+
+    Ok(scopes.join(" "))
+}
+
+/// RFC 8707 section 2: a client names the service it wants a token for in `resource`,
+/// which must be one of the configured `JWT_AUDIENCES`. Without one the token is for the
+/// first-party audience.
+fn requested_audience(resource: Option<&str>) -> Result<&'static str, OAuthError> {
+    match resource {
+        None => Ok(first_party_audience()),
+        Some(resource) => JWT_AUDIENCES
+            .iter()
+            .find(|audience| *audience == resource)
+            .map(String::as_str)
+            .ok_or(OAuthError::InvalidTarget),
+    }
+}
+
+/// RFC 7636 section 4.6: the verifier's base64url SHA-256 digest must equal the challenge.
+fn verify_code_challenge(code_verifier: &SecretString, code_challenge: &str) -> bool {
+    let code_verifier = code_verifier.expose_secret();
+
+    // RFC 7636 section 4.1 bounds the verifier's length.
+    if !(43..=128).contains(&code_verifier.len()) {
+        return false;
+    }
+
+    let digest = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+
+    bool::from(digest.as_bytes().ct_eq(code_challenge.as_bytes()))
+}
+
+#[derive(Deserialize)]
+pub struct TokenRequest {
+    pub grant_type: Option<String>,
+    pub code: Option<SecretString>,
+    pub redirect_uri: Option<String>,
+    pub code_verifier: Option<SecretString>,
+    pub scope: Option<String>,
+    pub device_code: Option<SecretString>,
+    pub refresh_token: Option<SecretString>,
+    pub resource: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    /// Only for users signing in through `/authorize`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+    /// Only for users signing in to clients allowed the `refresh_token` grant.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    pub scope: String,
+}
@@ -0,0 +1,185 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::Utc;
+use secrecy::SecretString;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, TotpSecret, TotpSecretStoreError, TwoFACode, TwoFAMethod},
+    utils::{
+        auth::AuthenticatedUser,
+        totp::{build_enrollment, verify_code},
+    },
+};
+
+use super::recovery_codes::generate_recovery_codes_if_missing;
+
+/// Starts authenticator app enrollment with a fresh secret. Until it's confirmed, the
+/// user's current second factor (if any) keeps working.
+#[tracing::instrument(name = "Enroll TOTP", skip_all)]
+pub async fn enroll_totp(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let secret = TotpSecret::default();
+
+    let enrollment =
+        build_enrollment(&secret, &user.email).map_err(AuthAPIError::UnexpectedError)?;
+
+    state
+        .totp_secret_store
+        .write()
+        .await
+        .add_pending_secret(&user.email, secret)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let response = Json(EnrollTotpResponse {
+        otpauth_uri: enrollment.otpauth_uri,
+        qr_code_png: enrollment.qr_code_png,
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[tracing::instrument(name = "Confirm TOTP", skip_all)]
+pub async fn confirm_totp(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(request): Json<ConfirmTotpRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let code = TwoFACode::parse(request.code).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let mut totp_secret_store = state.totp_secret_store.write().await;
+
+    let secret = match totp_secret_store.get_pending_secret(&user.email).await {
+        Ok(secret) => secret,
+        Err(TotpSecretStoreError::SecretNotFound) => return Err(AuthAPIError::TotpNotEnrolled),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let step = verify_code(&secret, &code, current_time())
+        .map_err(AuthAPIError::UnexpectedError)?
+        .ok_or(AuthAPIError::IncorrectCredentials)?;
+
+    match totp_secret_store
+        .activate_pending_secret(&user.email, step)
+        .await
+    {
+        Ok(()) => {}
+        Err(TotpSecretStoreError::SecretNotFound) => return Err(AuthAPIError::TotpNotEnrolled),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    drop(totp_secret_store);
+
+    state
+        .user_store
+        .write()
+        .await
+        .set_two_fa_method(&user.email, TwoFAMethod::Totp)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let recovery_codes = generate_recovery_codes_if_missing(&state, &user.email).await?;
+
+    Ok((StatusCode::OK, Json(TwoFAMethodResponse { recovery_codes })))
+}
+
+#[tracing::instrument(name = "Set 2FA method", skip_all)]
+pub async fn set_two_fa_method(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(request): Json<SetTwoFAMethodRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    if request.method == TwoFAMethod::Totp {
+        match state
+            .totp_secret_store
+            .read()
+            .await
+            .get_secret(&user.email)
+            .await
+        {
+            Ok(_) => {}
+            Err(TotpSecretStoreError::SecretNotFound) => {
+                return Err(AuthAPIError::TotpNotEnrolled)
+            }
+            Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+        }
+    }
+
+    state
+        .user_store
+        .write()
+        .await
+        .set_two_fa_method(&user.email, request.method)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))?;
+
+    let recovery_codes = generate_recovery_codes_if_missing(&state, &user.email).await?;
+
+    Ok((StatusCode::OK, Json(TwoFAMethodResponse { recovery_codes })))
+}
+
+/// Checks a login's second factor against the user's authenticator app. Each code is
+/// accepted at most once.
+#[tracing::instrument(name = "Verify TOTP login code", skip_all)]
+pub(crate) async fn verify_totp_code(
+    state: &AppState,
+    email: &Email,
+    code: &TwoFACode,
+) -> Result<bool, AuthAPIError> {
+    let mut totp_secret_store = state.totp_secret_store.write().await;
+
+    let secret = match totp_secret_store.get_secret(email).await {
+        Ok(secret) => secret,
+        Err(TotpSecretStoreError::SecretNotFound) => return Ok(false),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    let Some(step) =
+        verify_code(&secret, code, current_time()).map_err(AuthAPIError::UnexpectedError)?
+    else {
+        return Ok(false);
+    };
+
+    match totp_secret_store.use_step(email, step).await {
+        Ok(()) => Ok(true),
+        Err(TotpSecretStoreError::StepAlreadyUsed) => Ok(false),
+        Err(e) => Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+}
+
+fn current_time() -> u64 {
+    Utc::now().timestamp().try_into().unwrap_or_default()
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct EnrollTotpResponse {
+    #[serde(rename = "otpauthUri")]
+    pub otpauth_uri: String,
+    #[serde(rename = "qrCodePng")]
+    pub qr_code_png: String,
+}
+
+/// Returned when 2FA gets turned on. `recoveryCodes` is only present the first time, or
+/// after the previous codes have all been used.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TwoFAMethodResponse {
+    #[serde(
+        rename = "recoveryCodes",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub recovery_codes: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+pub struct ConfirmTotpRequest {
+    pub code: SecretString,
+}
+
+#[derive(Deserialize)]
+pub struct SetTwoFAMethodRequest {
+    pub method: TwoFAMethod,
+}
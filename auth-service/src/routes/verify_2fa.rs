@@ -1,17 +1,27 @@
 use crate::{
     app_state::AppState,
-    domain::{AuthAPIError, Email, LoginAttemptId, TwoFACode},
-    utils::auth::generate_auth_cookie,
+    domain::{
+        AuthAPIError, AuthMethod, Email, FailedAttempts, LoginAttemptId, TwoFACode, TwoFAMethod,
+    },
+    routes::verify_totp_code,
+    utils::auth::{start_session, ClientInfo},
 };
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 use axum_extra::extract::CookieJar;
+use chrono::{DateTime, Duration, Utc};
 use secrecy::SecretString;
 use serde::Deserialize;
 
+// A 6-digit code can't be guessed in this many tries; the login has to start over after.
+const MAX_FAILED_ATTEMPTS: u32 = 5;
+// After each wrong code the next one is turned away for twice as long as after the last.
+const BACKOFF_BASE_SECONDS: i64 = 1;
+
 #[tracing::instrument(name = "Verify 2FA", skip_all)]
 pub async fn verify_2fa(
     State(state): State<AppState>,
     jar: CookieJar,
+    client: ClientInfo,
     Json(request): Json<Verify2FARequest>,
 ) -> (CookieJar, Result<impl IntoResponse, AuthAPIError>) {
     let email = match Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials) {
@@ -32,6 +42,11 @@ pub async fn verify_2fa(
             Err(e) => return (jar, Err(e)),
         };
 
+    let two_fa_method = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user.two_fa_method,
+        Err(_) => return (jar, Err(AuthAPIError::IncorrectCredentials)),
+    };
+
     let mut two_fa_code_store = state.two_fa_code_store.write().await;
 
     let (stored_login_attempt_id, stored_two_fa_code) = match two_fa_code_store
@@ -45,22 +60,124 @@ pub async fn verify_2fa(
         Err(e) => return (jar, Err(e)),
     };
 
-    if login_attempt_id != stored_login_attempt_id || two_fa_code != stored_two_fa_code {
+    // A wrong login attempt id doesn't count as a failure, or anyone knowing the address
+    // could make its owner's code unusable.
+    if login_attempt_id != stored_login_attempt_id {
+        return (jar, Err(AuthAPIError::IncorrectCredentials));
+    }
+
+    let failed_attempts = match two_fa_code_store
+        .get_failed_attempts(&login_attempt_id)
+        .await
+    {
+        Ok(failed_attempts) => failed_attempts,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
+    if retry_at(&failed_attempts).is_some_and(|retry_at| Utc::now() < retry_at) {
+        return (jar, Err(AuthAPIError::TooManyAttempts));
+    }
+
+    let code_is_valid = match two_fa_method {
+        TwoFAMethod::Email => two_fa_code == stored_two_fa_code,
+        TwoFAMethod::Totp => match verify_totp_code(&state, &email, &two_fa_code).await {
+            Ok(valid) => valid,
+            Err(e) => return (jar, Err(e)),
+        },
+    };
+
+    if !code_is_valid {
+        let failed_attempts = match two_fa_code_store
+            .record_failed_attempt(&login_attempt_id, Utc::now())
+            .await
+        {
+            Ok(failed_attempts) => failed_attempts,
+            Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+        };
+
+        if failed_attempts.count >= MAX_FAILED_ATTEMPTS {
+            if let Err(e) = two_fa_code_store.remove_code(&email).await {
+                return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
+            }
+            return (jar, Err(AuthAPIError::TooManyAttempts));
+        }
+
         return (jar, Err(AuthAPIError::IncorrectCredentials));
     }
 
+    let first_factor = match two_fa_code_store.get_first_factor(&email).await {
+        Ok(first_factor) => first_factor,
+        Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e.into()))),
+    };
+
     if let Err(e) = two_fa_code_store.remove_code(&email).await {
         return (jar, Err(AuthAPIError::UnexpectedError(e.into())));
     }
 
-    let auth_cookie = match generate_auth_cookie(&email) {
-        Ok(cookie) => cookie,
+    drop(two_fa_code_store);
+
+    issue_cookies(
+        jar,
+        &state,
+        &email,
+        &[
+            first_factor,
+            AuthMethod::OneTimeCode,
+            AuthMethod::MultiFactor,
+        ],
+        &client,
+    )
+    .await
+}
+
+/// When the next code may be tried after `failed_attempts`, if there's a wait at all.
+fn retry_at(failed_attempts: &FailedAttempts) -> Option<DateTime<Utc>> {
+    let last_failed_at = failed_attempts.last_failed_at?;
+    let backoff = BACKOFF_BASE_SECONDS << failed_attempts.count.saturating_sub(1).min(16);
+
+    Some(last_failed_at + Duration::seconds(backoff))
+}
+
+/// Makes sure `login_attempt_id` is the pending 2FA login for `email`, and returns the
+/// factor that login was started with.
+pub(crate) async fn check_login_attempt(
+    state: &AppState,
+    email: &Email,
+    login_attempt_id: &LoginAttemptId,
+) -> Result<AuthMethod, AuthAPIError> {
+    let two_fa_code_store = state.two_fa_code_store.read().await;
+
+    let (stored_login_attempt_id, _) = two_fa_code_store
+        .get_code(email)
+        .await
+        .map_err(|_| AuthAPIError::IncorrectCredentials)?;
+
+    if *login_attempt_id != stored_login_attempt_id {
+        return Err(AuthAPIError::IncorrectCredentials);
+    }
+
+    two_fa_code_store
+        .get_first_factor(email)
+        .await
+        .map_err(|e| AuthAPIError::UnexpectedError(e.into()))
+}
+
+/// Logs `email` in once every factor has been checked. `amr` lists the factors.
+pub(crate) async fn issue_cookies(
+    jar: CookieJar,
+    state: &AppState,
+    email: &Email,
+    amr: &[AuthMethod],
+    client: &ClientInfo,
+) -> (CookieJar, Result<StatusCode, AuthAPIError>) {
+    let (auth_cookie, refresh_cookie) = match start_session(state, email, amr, client).await {
+        Ok(cookies) => cookies,
         Err(e) => return (jar, Err(AuthAPIError::UnexpectedError(e))),
     };
 
-    let updated_jar = jar.add(auth_cookie);
+    let updated_jar = jar.add(auth_cookie).add(refresh_cookie);
 
-    (updated_jar, Ok(StatusCode::OK.into_response()))
+    (updated_jar, Ok(StatusCode::OK))
 }
 
 #[derive(Deserialize)]
@@ -0,0 +1,108 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use color_eyre::eyre::Result;
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    app_state::AppState,
+    domain::{AuthAPIError, Email, UserStoreError},
+    utils::{
+        auth::{generate_purpose_token, validate_purpose_token, TokenPurpose},
+        constants::AUTH_SERVICE_URL,
+        email::send_in_background,
+    },
+};
+
+#[tracing::instrument(name = "Verify email", skip_all)]
+pub async fn verify_email(
+    State(state): State<AppState>,
+    Json(request): Json<VerifyEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let claims = validate_purpose_token(
+        &request.token,
+        TokenPurpose::EmailVerification,
+        state.banned_token_store.clone(),
+        &state.keyring,
+    )
+    .await
+    .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    let email = Email::parse(SecretString::new(claims.sub.into_boxed_str()))
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+    match state.user_store.write().await.mark_verified(&email).await {
+        Ok(()) => {}
+        Err(UserStoreError::UserNotFound) => return Err(AuthAPIError::InvalidToken),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    }
+
+    let response = Json(VerifyEmailResponse {
+        message: "Email verified successfully!".to_owned(),
+    });
+
+    Ok((StatusCode::OK, response))
+}
+
+#[tracing::instrument(name = "Resend verification email", skip_all)]
+pub async fn resend_verification_email(
+    State(state): State<AppState>,
+    Json(request): Json<ResendVerificationEmailRequest>,
+) -> Result<impl IntoResponse, AuthAPIError> {
+    let email = Email::parse(request.email).map_err(|_| AuthAPIError::InvalidCredentials)?;
+
+    let response = Json(VerifyEmailResponse {
+        message: "If the account exists and is unverified, a verification link has been sent"
+            .to_owned(),
+    });
+
+    let user = match state.user_store.read().await.get_user(&email).await {
+        Ok(user) => user,
+        Err(UserStoreError::UserNotFound) => return Ok((StatusCode::OK, response)),
+        Err(e) => return Err(AuthAPIError::UnexpectedError(e.into())),
+    };
+
+    if !user.verified {
+        let state = state.clone();
+        send_in_background(async move { send_verification_email(&state, &email).await });
+    }
+
+    Ok((StatusCode::OK, response))
+}
+
+#[tracing::instrument(name = "Send verification email", skip_all)]
+pub(crate) async fn send_verification_email(state: &AppState, email: &Email) -> Result<()> {
+    let token = generate_purpose_token(email, TokenPurpose::EmailVerification, &state.keyring)?;
+
+    let link = format!(
+        "{}/?verify_token={}",
+        AUTH_SERVICE_URL.as_str(),
+        token.expose_secret()
+    );
+
+    state
+        .email_client
+        .send_email(
+            email,
+            "Verify your email",
+            &format!(
+                "Use the link below to confirm your email address. It expires in 24 hours.\n\n{}",
+                link
+            ),
+        )
+        .await
+}
+
+#[derive(Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: SecretString,
+}
+
+#[derive(Deserialize)]
+pub struct ResendVerificationEmailRequest {
+    pub email: SecretString,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct VerifyEmailResponse {
+    pub message: String,
+}
@@ -2,21 +2,46 @@ use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
 
 use crate::{app_state::AppState, domain::AuthAPIError, utils::auth::validate_token};
 use secrecy::SecretString;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 #[tracing::instrument(name = "Verify token", skip_all)]
 pub async fn verify_token(
     State(state): State<AppState>,
     Json(request): Json<VerifyTokenRequest>,
 ) -> Result<impl IntoResponse, AuthAPIError> {
-    validate_token(&request.token, state.banned_token_store.clone())
-        .await
-        .map_err(|_| AuthAPIError::InvalidToken)?;
+    let verified = validate_token(
+        &request.token,
+        request.audience.as_deref(),
+        state.banned_token_store.clone(),
+        state.session_store.clone(),
+        state.personal_access_token_store.clone(),
+        &state.keyring,
+    )
+    .await
+    .map_err(|_| AuthAPIError::InvalidToken)?;
 
-    Ok(StatusCode::OK)
+    let response = VerifyTokenResponse {
+        sub: verified.sub,
+        scope: verified.scope,
+    };
+
+    Ok((StatusCode::OK, Json(response)))
 }
 
 #[derive(Deserialize)]
 pub struct VerifyTokenRequest {
+    /// An access token or a personal access token, as the caller received it in a
+    /// `Bearer` authorization header.
     pub token: SecretString,
+    /// The service the caller expects the token to be issued for. Defaults to any of the
+    /// configured audiences.
+    pub audience: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct VerifyTokenResponse {
+    pub sub: String,
+    /// Space-separated, as in the `scope` claim of an access token.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
 }
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use chrono::Utc;
+use uuid::Uuid;
+
+use crate::domain::{
+    data_stores::{Session, SessionStore, SessionStoreError},
+    email::Email,
+};
+
+#[derive(Default)]
+pub struct HashmapSessionStore {
+    sessions: HashMap<Uuid, Session>,
+}
+
+#[async_trait::async_trait]
+impl SessionStore for HashmapSessionStore {
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        self.sessions.insert(session.id, session);
+        Ok(())
+    }
+
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        let mut sessions: Vec<Session> = self
+            .sessions
+            .values()
+            .filter(|session| session.email == *email)
+            .cloned()
+            .collect();
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.last_seen_at));
+        Ok(sessions)
+    }
+
+    async fn contains_session(&self, id: Uuid) -> Result<bool, SessionStoreError> {
+        Ok(self.sessions.contains_key(&id))
+    }
+
+    async fn touch_session(&mut self, id: Uuid) -> Result<(), SessionStoreError> {
+        match self.sessions.get_mut(&id) {
+            Some(session) => {
+                session.last_seen_at = Utc::now();
+                Ok(())
+            }
+            None => Err(SessionStoreError::SessionNotFound),
+        }
+    }
+
+    async fn revoke_session(&mut self, email: &Email, id: Uuid) -> Result<(), SessionStoreError> {
+        match self.sessions.get(&id) {
+            Some(session) if session.email == *email => {
+                self.sessions.remove(&id);
+                Ok(())
+            }
+            _ => Err(SessionStoreError::SessionNotFound),
+        }
+    }
+
+    async fn revoke_all_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError> {
+        self.sessions.retain(|_, session| session.email != *email);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::SecretString;
+
+    use super::*;
+    use crate::domain::AuthMethod;
+
+    fn session(email: &str) -> Session {
+        let now = Utc::now();
+        Session {
+            id: Uuid::new_v4(),
+            email: Email::parse(SecretString::new(email.to_owned().into_boxed_str())).unwrap(),
+            created_at: now,
+            last_seen_at: now,
+            ip: None,
+            user_agent: None,
+            amr: vec![AuthMethod::Password],
+        }
+    }
+
+    #[tokio::test]
+    async fn get_sessions_only_returns_sessions_of_user() {
+        let mut store = HashmapSessionStore::default();
+        let bob = session("bob@example.com");
+        let alice = session("alice@example.com");
+
+        store.add_session(bob.clone()).await.unwrap();
+        store.add_session(alice).await.unwrap();
+
+        assert_eq!(store.get_sessions(&bob.email).await, Ok(vec![bob]));
+    }
+
+    #[tokio::test]
+    async fn revoke_session_only_revokes_own_session() {
+        let mut store = HashmapSessionStore::default();
+        let bob = session("bob@example.com");
+        let alice = session("alice@example.com");
+
+        store.add_session(bob.clone()).await.unwrap();
+
+        assert_eq!(
+            store.revoke_session(&alice.email, bob.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+        assert_eq!(store.contains_session(bob.id).await, Ok(true));
+
+        assert_eq!(store.revoke_session(&bob.email, bob.id).await, Ok(()));
+        assert_eq!(store.contains_session(bob.id).await, Ok(false));
+        assert_eq!(
+            store.touch_session(bob.id).await,
+            Err(SessionStoreError::SessionNotFound)
+        );
+    }
+}
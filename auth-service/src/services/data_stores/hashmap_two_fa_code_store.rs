@@ -1,15 +1,18 @@
 use std::collections::HashMap;
 
+use chrono::{DateTime, Utc};
+use secrecy::ExposeSecret;
+
 use crate::domain::{
-    data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
+    data_stores::{FailedAttempts, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
     email::Email,
+    AuthMethod,
 };
 
-use secrecy::SecretString;
-
 #[derive(Default)]
 pub struct HashmapTwoFACodeStore {
-    codes: HashMap<Email, (LoginAttemptId, TwoFACode)>,
+    codes: HashMap<Email, (LoginAttemptId, TwoFACode, AuthMethod)>,
+    failed_attempts: HashMap<String, FailedAttempts>,
 }
 
 #[async_trait::async_trait]
@@ -19,8 +22,10 @@ impl TwoFACodeStore for HashmapTwoFACodeStore {
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
+        first_factor: AuthMethod,
     ) -> Result<(), TwoFACodeStoreError> {
-        self.codes.insert(email, (login_attempt_id, code));
+        self.codes
+            .insert(email, (login_attempt_id, code, first_factor));
         Ok(())
     }
 
@@ -36,14 +41,48 @@ impl TwoFACodeStore for HashmapTwoFACodeStore {
         email: &Email,
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
         match self.codes.get(email) {
-            Some(code) => Ok(code.clone()),
+            Some((login_attempt_id, code, _)) => Ok((login_attempt_id.clone(), code.clone())),
+            None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
+        }
+    }
+
+    async fn get_first_factor(&self, email: &Email) -> Result<AuthMethod, TwoFACodeStoreError> {
+        match self.codes.get(email) {
+            Some((_, _, first_factor)) => Ok(*first_factor),
             None => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
         }
     }
+
+    async fn record_failed_attempt(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+        failed_at: DateTime<Utc>,
+    ) -> Result<FailedAttempts, TwoFACodeStoreError> {
+        let failed_attempts = self
+            .failed_attempts
+            .entry(login_attempt_id.as_ref().expose_secret().to_owned())
+            .or_default();
+        failed_attempts.count += 1;
+        failed_attempts.last_failed_at = Some(failed_at);
+        Ok(failed_attempts.clone())
+    }
+
+    async fn get_failed_attempts(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<FailedAttempts, TwoFACodeStoreError> {
+        Ok(self
+            .failed_attempts
+            .get(login_attempt_id.as_ref().expose_secret())
+            .cloned()
+            .unwrap_or_default())
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use secrecy::SecretString;
+
     use super::*;
 
     #[tokio::test]
@@ -59,12 +98,20 @@ mod tests {
         let login_attempt_id = LoginAttemptId::default();
         let code = TwoFACode::default();
         let result = store
-            .add_code(email.clone(), login_attempt_id.clone(), code.clone())
+            .add_code(
+                email.clone(),
+                login_attempt_id.clone(),
+                code.clone(),
+                AuthMethod::Password,
+            )
             .await;
 
         assert!(result.is_ok());
         assert!(store.codes.len() == 1);
-        assert_eq!(store.codes.get(&email), Some(&(login_attempt_id, code)));
+        assert_eq!(
+            store.codes.get(&email),
+            Some(&(login_attempt_id, code, AuthMethod::Password))
+        );
     }
 
     #[tokio::test]
@@ -78,17 +125,21 @@ mod tests {
         let login_attempt_id = LoginAttemptId::default();
         let code = TwoFACode::default();
 
-        store
-            .codes
-            .insert(email.clone(), (login_attempt_id.clone(), code.clone()));
+        store.codes.insert(
+            email.clone(),
+            (login_attempt_id.clone(), code.clone(), AuthMethod::Password),
+        );
 
         assert!(store.codes.len() == 1);
-        assert_eq!(store.codes.get(&email), Some(&(login_attempt_id, code)));
+        assert_eq!(
+            store.codes.get(&email),
+            Some(&(login_attempt_id, code, AuthMethod::Password))
+        );
 
         let result = store.remove_code(&email).await;
 
         assert!(result.is_ok());
-        assert!(store.codes.get(&email).is_none());
+        assert!(!store.codes.contains_key(&email));
     }
 
     #[tokio::test]
@@ -118,9 +169,10 @@ mod tests {
         let login_attempt_id = LoginAttemptId::default();
         let code = TwoFACode::default();
 
-        store
-            .codes
-            .insert(email.clone(), (login_attempt_id.clone(), code.clone()));
+        store.codes.insert(
+            email.clone(),
+            (login_attempt_id.clone(), code.clone(), AuthMethod::Password),
+        );
 
         let result = store.get_code(&email).await;
 
@@ -140,4 +192,41 @@ mod tests {
 
         assert_eq!(result, Err(TwoFACodeStoreError::LoginAttemptIdNotFound));
     }
+
+    #[tokio::test]
+    async fn record_failed_attempt_counts_per_login_attempt() {
+        let mut store = HashmapTwoFACodeStore::default();
+
+        let login_attempt_id = LoginAttemptId::default();
+        let other_login_attempt_id = LoginAttemptId::default();
+
+        assert_eq!(
+            store.get_failed_attempts(&login_attempt_id).await,
+            Ok(FailedAttempts::default())
+        );
+
+        let first_failed_at = Utc::now();
+        let second_failed_at = first_failed_at + chrono::Duration::seconds(1);
+        store
+            .record_failed_attempt(&login_attempt_id, first_failed_at)
+            .await
+            .unwrap();
+        let result = store
+            .record_failed_attempt(&login_attempt_id, second_failed_at)
+            .await;
+
+        let expected = FailedAttempts {
+            count: 2,
+            last_failed_at: Some(second_failed_at),
+        };
+        assert_eq!(result, Ok(expected.clone()));
+        assert_eq!(
+            store.get_failed_attempts(&login_attempt_id).await,
+            Ok(expected)
+        );
+        assert_eq!(
+            store.get_failed_attempts(&other_login_attempt_id).await,
+            Ok(FailedAttempts::default())
+        );
+    }
 }
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use crate::domain::{Email, Password, User, UserStore, UserStoreError};
+use crate::domain::{Email, Password, TwoFAMethod, User, UserStore, UserStoreError};
 
 #[derive(Default)]
 pub struct HashmapUserStore {
@@ -44,6 +44,45 @@ impl UserStore for HashmapUserStore {
             None => Err(UserStoreError::UserNotFound),
         }
     }
+
+    async fn update_password(
+        &mut self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.password = password;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn mark_verified(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.verified = true;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
+
+    async fn set_two_fa_method(
+        &mut self,
+        email: &Email,
+        method: TwoFAMethod,
+    ) -> Result<(), UserStoreError> {
+        match self.users.get_mut(email) {
+            Some(user) => {
+                user.requires_2fa = true;
+                user.two_fa_method = method;
+                Ok(())
+            }
+            None => Err(UserStoreError::UserNotFound),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -63,6 +102,8 @@ mod tests {
             password: Password::parse(SecretString::new("password".to_string().into_boxed_str()))
                 .unwrap(),
             requires_2fa: false,
+            verified: false,
+            two_fa_method: TwoFAMethod::Email,
         };
 
         // Test adding a new user
@@ -87,6 +128,8 @@ mod tests {
             password: Password::parse(SecretString::new("password".to_owned().into_boxed_str()))
                 .unwrap(),
             requires_2fa: false,
+            verified: false,
+            two_fa_method: TwoFAMethod::Email,
         };
 
         // Test getting a user that exists
@@ -121,6 +164,8 @@ mod tests {
             email: email.clone(),
             password: password.clone(),
             requires_2fa: false,
+            verified: false,
+            two_fa_method: TwoFAMethod::Email,
         };
 
         // Test validating a user that exists with correct password
@@ -149,4 +194,105 @@ mod tests {
 
         assert_eq!(result, Err(UserStoreError::UserNotFound));
     }
+
+    #[tokio::test]
+    async fn test_update_password() {
+        let mut user_store = HashmapUserStore::default();
+        let email = Email::parse(SecretString::new(
+            "test@example.com".to_owned().into_boxed_str(),
+        ))
+        .unwrap();
+        let password =
+            Password::parse(SecretString::new("password".to_owned().into_boxed_str())).unwrap();
+        let new_password = Password::parse(SecretString::new(
+            "newpassword".to_owned().into_boxed_str(),
+        ))
+        .unwrap();
+
+        // Test updating the password of a user that doesn't exist
+        let result = user_store
+            .update_password(&email, new_password.clone())
+            .await;
+        assert_eq!(result, Err(UserStoreError::UserNotFound));
+
+        // Test updating the password of a user that exists
+        user_store.users.insert(
+            email.clone(),
+            User {
+                email: email.clone(),
+                password: password.clone(),
+                requires_2fa: false,
+                verified: false,
+                two_fa_method: TwoFAMethod::Email,
+            },
+        );
+        let result = user_store
+            .update_password(&email, new_password.clone())
+            .await;
+        assert_eq!(result, Ok(()));
+
+        assert_eq!(
+            user_store.validate_user(&email, &password).await,
+            Err(UserStoreError::InvalidCredentials)
+        );
+        assert_eq!(user_store.validate_user(&email, &new_password).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_mark_verified() {
+        let mut user_store = HashmapUserStore::default();
+        let email = Email::parse(SecretString::new(
+            "test@example.com".to_owned().into_boxed_str(),
+        ))
+        .unwrap();
+        let password =
+            Password::parse(SecretString::new("password".to_owned().into_boxed_str())).unwrap();
+
+        // Test verifying a user that doesn't exist
+        let result = user_store.mark_verified(&email).await;
+        assert_eq!(result, Err(UserStoreError::UserNotFound));
+
+        // Test verifying a user that exists
+        user_store
+            .add_user(User::new(email.clone(), password, false))
+            .await
+            .unwrap();
+        assert!(!user_store.get_user(&email).await.unwrap().verified);
+
+        let result = user_store.mark_verified(&email).await;
+        assert_eq!(result, Ok(()));
+        assert!(user_store.get_user(&email).await.unwrap().verified);
+    }
+
+    #[tokio::test]
+    async fn test_set_two_fa_method() {
+        let mut user_store = HashmapUserStore::default();
+        let email = Email::parse(SecretString::new(
+            "test@example.com".to_owned().into_boxed_str(),
+        ))
+        .unwrap();
+        let password =
+            Password::parse(SecretString::new("password".to_owned().into_boxed_str())).unwrap();
+
+        // Test changing the method of a user that doesn't exist
+        let result = user_store
+            .set_two_fa_method(&email, TwoFAMethod::Totp)
+            .await;
+        assert_eq!(result, Err(UserStoreError::UserNotFound));
+
+        // Test changing the method of a user without 2FA turns it on
+        user_store
+            .add_user(User::new(email.clone(), password, false))
+            .await
+            .unwrap();
+
+        let result = user_store
+            .set_two_fa_method(&email, TwoFAMethod::Totp)
+            .await;
+        assert_eq!(result, Ok(()));
+
+        let user = user_store.get_user(&email).await.unwrap();
+        assert!(user.requires_2fa);
+        assert_eq!(user.two_fa_method, TwoFAMethod::Totp);
+    }
 }
@@ -1,11 +1,13 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use crate::domain::{BannedTokenStore, BannedTokenStoreError};
+use chrono::{DateTime, Utc};
 use secrecy::{ExposeSecret, SecretString};
 
 #[derive(Default)]
 pub struct HashsetBannedTokenStore {
     tokens: HashSet<String>,
+    not_before: HashMap<String, DateTime<Utc>>,
 }
 
 #[async_trait::async_trait]
@@ -19,6 +21,27 @@ impl BannedTokenStore for HashsetBannedTokenStore {
     async fn contains_token(&self, token: &SecretString) -> Result<bool, BannedTokenStoreError> {
         Ok(self.tokens.contains(token.expose_secret()))
     }
+
+    async fn consume_token(&mut self, token: SecretString) -> Result<bool, BannedTokenStoreError> {
+        Ok(self.tokens.insert(token.expose_secret().to_string()))
+    }
+
+    async fn ban_tokens_before(
+        &mut self,
+        subject: &str,
+        not_before: DateTime<Utc>,
+    ) -> Result<(), BannedTokenStoreError> {
+        self.not_before.insert(subject.to_owned(), not_before);
+
+        Ok(())
+    }
+
+    async fn get_tokens_not_before(
+        &self,
+        subject: &str,
+    ) -> Result<Option<DateTime<Utc>>, BannedTokenStoreError> {
+        Ok(self.not_before.get(subject).copied())
+    }
 }
 
 #[cfg(test)]
@@ -70,4 +93,45 @@ mod tests {
             .await
             .unwrap());
     }
+
+    #[tokio::test]
+    async fn consume_token_only_succeeds_once() {
+        let mut token_store = HashsetBannedTokenStore::default();
+
+        let token = SecretString::new("some token value".to_owned().into_boxed_str());
+
+        assert!(token_store.consume_token(token.clone()).await.unwrap());
+        assert!(token_store.contains_token(&token).await.unwrap());
+        assert!(!token_store.consume_token(token).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn ban_tokens_before_replaces_earlier_cut_off() {
+        let mut token_store = HashsetBannedTokenStore::default();
+
+        assert_eq!(
+            token_store.get_tokens_not_before("subject").await.unwrap(),
+            None
+        );
+
+        let first = Utc::now();
+        let second = first + chrono::Duration::seconds(10);
+        token_store
+            .ban_tokens_before("subject", first)
+            .await
+            .unwrap();
+        token_store
+            .ban_tokens_before("subject", second)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            token_store.get_tokens_not_before("subject").await.unwrap(),
+            Some(second)
+        );
+        assert_eq!(
+            token_store.get_tokens_not_before("other").await.unwrap(),
+            None
+        );
+    }
 }
@@ -1,13 +1,49 @@
+pub mod hashmap_session_store;
 pub mod hashmap_two_fa_code_store;
 pub mod hashmap_user_store;
 pub mod hashset_banned_token_store;
+pub mod postgres_oauth_client_store;
+pub mod postgres_passkey_store;
+pub mod postgres_personal_access_token_store;
+pub mod postgres_recovery_code_store;
+pub mod postgres_refresh_token_store;
+pub mod postgres_session_store;
+pub mod postgres_signing_key_store;
+pub mod postgres_totp_secret_store;
+pub mod postgres_user_identity_store;
 pub mod postgres_user_store;
+pub mod redis_authorization_code_store;
 pub mod redis_banned_token_store;
+pub mod redis_device_code_store;
+pub mod redis_federated_login_store;
+pub mod redis_login_lockout_store;
+pub mod redis_magic_link_store;
+pub mod redis_passkey_ceremony_store;
+pub mod redis_rate_limit_store;
+pub mod redis_refresh_token_store;
 pub mod redis_two_fa_code_store;
 
+pub use hashmap_session_store::*;
 pub use hashmap_two_fa_code_store::*;
 pub use hashmap_user_store::*;
 pub use hashset_banned_token_store::*;
+pub use postgres_oauth_client_store::*;
+pub use postgres_passkey_store::*;
+pub use postgres_personal_access_token_store::*;
+pub use postgres_recovery_code_store::*;
+pub use postgres_refresh_token_store::*;
+pub use postgres_session_store::*;
+pub use postgres_signing_key_store::*;
+pub use postgres_totp_secret_store::*;
+pub use postgres_user_identity_store::*;
 pub use postgres_user_store::*;
+pub use redis_authorization_code_store::*;
 pub use redis_banned_token_store::*;
+pub use redis_device_code_store::*;
+pub use redis_federated_login_store::*;
+pub use redis_login_lockout_store::*;
+pub use redis_magic_link_store::*;
+pub use redis_passkey_ceremony_store::*;
+pub use redis_rate_limit_store::*;
+pub use redis_refresh_token_store::*;
 pub use redis_two_fa_code_store::*;
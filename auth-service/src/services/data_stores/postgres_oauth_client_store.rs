@@ -0,0 +1,317 @@
+use chrono::{DateTime, Utc};
+use secrecy::SecretString;
+use sqlx::PgPool;
+use subtle::ConstantTimeEq;
+
+use crate::{
+    domain::data_stores::{
+        ClientSecret, GrantType, OAuthClient, OAuthClientStore, OAuthClientStoreError,
+        RegistrationToken,
+    },
+    utils::auth::hash_token,
+};
+
+pub struct PostgresOAuthClientStore {
+    pool: PgPool,
+}
+
+impl PostgresOAuthClientStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl OAuthClientStore for PostgresOAuthClientStore {
+    #[tracing::instrument(name = "Adding OAuth client to PostgreSQL", skip_all)]
+    async fn add_client(
+        &mut self,
+        client: &OAuthClient,
+        client_secret: &ClientSecret,
+        registration_access_token: Option<&RegistrationToken>,
+    ) -> Result<(), OAuthClientStoreError> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO oauth_clients
+                (client_id, client_secret_hash, client_name, redirect_uris, allowed_scopes,
+                allowed_grant_types, registration_access_token_hash)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (client_id) DO NOTHING
+            "#,
+            client.client_id,
+            hash_token(client_secret.as_ref()),
+            client.client_name,
+            &client.redirect_uris,
+            &client.allowed_scopes,
+            &grant_type_names(&client.allowed_grant_types) as &[String],
+            registration_access_token.map(|token| hash_token(token.as_ref()))
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| OAuthClientStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(OAuthClientStoreError::ClientAlreadyExists);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving OAuth client from PostgreSQL", skip_all)]
+    async fn get_client(&self, client_id: &str) -> Result<OAuthClient, OAuthClientStoreError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT client_id, client_name, redirect_uris, allowed_scopes, allowed_grant_types
+            FROM oauth_clients
+            WHERE client_id = $1
+            "#,
+            client_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| OAuthClientStoreError::UnexpectedError(e.into()))?
+        .ok_or(OAuthClientStoreError::ClientNotFound)?;
+
+        Ok(OAuthClient {
+            client_id: row.client_id,
+            client_name: row.client_name,
+            redirect_uris: row.redirect_uris,
+            allowed_scopes: row.allowed_scopes,
+            allowed_grant_types: parse_grant_types(&row.allowed_grant_types)?,
+        })
+    }
+
+    #[tracing::instrument(name = "Listing OAuth clients in PostgreSQL", skip_all)]
+    async fn list_clients(&self) -> Result<Vec<OAuthClient>, OAuthClientStoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT client_id, client_name, redirect_uris, allowed_scopes, allowed_grant_types
+            FROM oauth_clients
+            ORDER BY created_at, client_id
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| OAuthClientStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(OAuthClient {
+                    client_id: row.client_id,
+                    client_name: row.client_name,
+                    redirect_uris: row.redirect_uris,
+                    allowed_scopes: row.allowed_scopes,
+                    allowed_grant_types: parse_grant_types(&row.allowed_grant_types)?,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Updating OAuth client in PostgreSQL", skip_all)]
+    async fn update_client(&mut self, client: &OAuthClient) -> Result<(), OAuthClientStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE oauth_clients
+            SET client_name = $2, redirect_uris = $3, allowed_scopes = $4,
+                allowed_grant_types = $5
+            WHERE client_id = $1
+            "#,
+            client.client_id,
+            client.client_name,
+            &client.redirect_uris,
+            &client.allowed_scopes,
+            &grant_type_names(&client.allowed_grant_types) as &[String]
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| OAuthClientStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(OAuthClientStoreError::ClientNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Updating OAuth client secret in PostgreSQL", skip_all)]
+    async fn update_secret(
+        &mut self,
+        client_id: &str,
+        client_secret: &ClientSecret,
+    ) -> Result<(), OAuthClientStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE oauth_clients
+            SET client_secret_hash = $2
+            WHERE client_id = $1
+            "#,
+            client_id,
+            hash_token(client_secret.as_ref())
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| OAuthClientStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(OAuthClientStoreError::ClientNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Deleting OAuth client from PostgreSQL", skip_all)]
+    async fn delete_client(&mut self, client_id: &str) -> Result<(), OAuthClientStoreError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM oauth_clients
+            WHERE client_id = $1
+            "#,
+            client_id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| OAuthClientStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(OAuthClientStoreError::ClientNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Validating OAuth client in PostgreSQL", skip_all)]
+    async fn validate_client(
+        &self,
+        client_id: &str,
+        client_secret: &SecretString,
+    ) -> Result<OAuthClient, OAuthClientStoreError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT client_id, client_secret_hash, client_name, redirect_uris, allowed_scopes,
+                allowed_grant_types
+            FROM oauth_clients
+            WHERE client_id = $1
+            "#,
+            client_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| OAuthClientStoreError::UnexpectedError(e.into()))?
+        .ok_or(OAuthClientStoreError::ClientNotFound)?;
+
+        let hash = hash_token(client_secret);
+
+        if !bool::from(hash.as_bytes().ct_eq(row.client_secret_hash.as_bytes())) {
+            return Err(OAuthClientStoreError::InvalidCredentials);
+        }
+
+        Ok(OAuthClient {
+            client_id: row.client_id,
+            client_name: row.client_name,
+            redirect_uris: row.redirect_uris,
+            allowed_scopes: row.allowed_scopes,
+            allowed_grant_types: parse_grant_types(&row.allowed_grant_types)?,
+        })
+    }
+
+    #[tracing::instrument(name = "Validating registration access token in PostgreSQL", skip_all)]
+    async fn validate_registration_access_token(
+        &self,
+        client_id: &str,
+        registration_access_token: &SecretString,
+    ) -> Result<OAuthClient, OAuthClientStoreError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT client_id, client_name, redirect_uris, allowed_scopes, allowed_grant_types,
+                registration_access_token_hash
+            FROM oauth_clients
+            WHERE client_id = $1
+            "#,
+            client_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| OAuthClientStoreError::UnexpectedError(e.into()))?
+        .ok_or(OAuthClientStoreError::ClientNotFound)?;
+
+        // Clients registered through the admin API have no registration access token.
+        let Some(expected_hash) = row.registration_access_token_hash else {
+            return Err(OAuthClientStoreError::InvalidCredentials);
+        };
+
+        let hash = hash_token(registration_access_token);
+
+        if !bool::from(hash.as_bytes().ct_eq(expected_hash.as_bytes())) {
+            return Err(OAuthClientStoreError::InvalidCredentials);
+        }
+
+        Ok(OAuthClient {
+            client_id: row.client_id,
+            client_name: row.client_name,
+            redirect_uris: row.redirect_uris,
+            allowed_scopes: row.allowed_scopes,
+            allowed_grant_types: parse_grant_types(&row.allowed_grant_types)?,
+        })
+    }
+
+    #[tracing::instrument(name = "Adding initial access token to PostgreSQL", skip_all)]
+    async fn add_initial_access_token(
+        &mut self,
+        initial_access_token: &RegistrationToken,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), OAuthClientStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO initial_access_tokens (token_hash, expires_at)
+            VALUES ($1, $2)
+            "#,
+            hash_token(initial_access_token.as_ref()),
+            expires_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| OAuthClientStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Using initial access token in PostgreSQL", skip_all)]
+    async fn use_initial_access_token(
+        &mut self,
+        initial_access_token: &SecretString,
+    ) -> Result<(), OAuthClientStoreError> {
+        // Deleting the token is what uses it up, so two registrations racing for the same
+        // token can't both succeed.
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM initial_access_tokens
+            WHERE token_hash = $1 AND expires_at > NOW()
+            "#,
+            hash_token(initial_access_token)
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| OAuthClientStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(OAuthClientStoreError::InvalidCredentials);
+        }
+
+        Ok(())
+    }
+}
+
+fn grant_type_names(grant_types: &[GrantType]) -> Vec<String> {
+    grant_types
+        .iter()
+        .map(|grant_type| grant_type.as_str().to_owned())
+        .collect()
+}
+
+fn parse_grant_types(names: &[String]) -> Result<Vec<GrantType>, OAuthClientStoreError> {
+    names
+        .iter()
+        .map(|name| GrantType::parse(name))
+        .collect::<Result<_, _>>()
+        .map_err(OAuthClientStoreError::UnexpectedError)
+}
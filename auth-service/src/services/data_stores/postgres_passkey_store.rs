@@ -0,0 +1,110 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use secrecy::ExposeSecret;
+use sqlx::{types::Json, PgPool};
+use webauthn_rs::prelude::{Credential, Passkey};
+
+use crate::domain::{
+    data_stores::{PasskeyStore, PasskeyStoreError},
+    Email,
+};
+
+pub struct PostgresPasskeyStore {
+    pool: PgPool,
+}
+
+impl PostgresPasskeyStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl PasskeyStore for PostgresPasskeyStore {
+    #[tracing::instrument(name = "Adding passkey to PostgreSQL", skip_all)]
+    async fn add_passkey(
+        &mut self,
+        email: &Email,
+        passkey: &Passkey,
+    ) -> Result<(), PasskeyStoreError> {
+        let credential = Credential::from(passkey.clone());
+
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO passkeys (credential_id, email, public_key, sign_count, transports, credential)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (credential_id) DO NOTHING
+            "#,
+            credential_id(passkey),
+            email.as_ref().expose_secret(),
+            Json(&credential.cred) as _,
+            i64::from(credential.counter),
+            credential.transports.as_ref().map(Json) as _,
+            Json(&credential) as _
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(PasskeyStoreError::PasskeyAlreadyExists);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving passkeys from PostgreSQL", skip_all)]
+    async fn get_passkeys(&self, email: &Email) -> Result<Vec<Passkey>, PasskeyStoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT sign_count, credential AS "credential: Json<Credential>"
+            FROM passkeys
+            WHERE email = $1
+            ORDER BY created_at
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter()
+            .map(|row| {
+                let mut credential = row.credential.0;
+                credential.counter = from_db_counter(row.sign_count)?;
+                Ok(Passkey::from(credential))
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Updating passkey in PostgreSQL", skip_all)]
+    async fn update_passkey(&mut self, passkey: &Passkey) -> Result<(), PasskeyStoreError> {
+        let credential = Credential::from(passkey.clone());
+
+        // Sign counters only move forward, so a stale write can't roll one back.
+        sqlx::query!(
+            r#"
+            UPDATE passkeys
+            SET sign_count = GREATEST(sign_count, $2), credential = $3
+            WHERE credential_id = $1
+            "#,
+            credential_id(passkey),
+            i64::from(credential.counter),
+            Json(&credential) as _
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| PasskeyStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+}
+
+fn credential_id(passkey: &Passkey) -> String {
+    URL_SAFE_NO_PAD.encode(passkey.cred_id())
+}
+
+fn from_db_counter(sign_count: i64) -> Result<u32, PasskeyStoreError> {
+    sign_count
+        .try_into()
+        .map_err(|e: std::num::TryFromIntError| PasskeyStoreError::UnexpectedError(e.into()))
+}
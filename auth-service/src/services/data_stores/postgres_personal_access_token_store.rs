@@ -0,0 +1,187 @@
+use chrono::{DateTime, Utc};
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    domain::{
+        data_stores::{
+            PersonalAccessToken, PersonalAccessTokenRecord, PersonalAccessTokenStore,
+            PersonalAccessTokenStoreError,
+        },
+        Email,
+    },
+    utils::auth::hash_token,
+};
+
+pub struct PostgresPersonalAccessTokenStore {
+    pool: PgPool,
+}
+
+impl PostgresPersonalAccessTokenStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl PersonalAccessTokenStore for PostgresPersonalAccessTokenStore {
+    #[tracing::instrument(name = "Adding personal access token to PostgreSQL", skip_all)]
+    async fn add_token(
+        &mut self,
+        token: &PersonalAccessToken,
+        record: PersonalAccessTokenRecord,
+    ) -> Result<(), PersonalAccessTokenStoreError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO personal_access_tokens
+                (id, email, name, token_hash, scopes, created_at, expires_at, last_used_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            record.id,
+            record.email.as_ref().expose_secret(),
+            record.name,
+            hash_token(token.as_ref()),
+            &record.scopes as &[String],
+            record.created_at,
+            record.expires_at,
+            record.last_used_at
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| PersonalAccessTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving personal access tokens from PostgreSQL", skip_all)]
+    async fn get_tokens(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<PersonalAccessTokenRecord>, PersonalAccessTokenStoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, email, name, scopes, created_at, expires_at, last_used_at
+            FROM personal_access_tokens
+            WHERE email = $1
+            ORDER BY created_at
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| PersonalAccessTokenStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter()
+            .map(|row| {
+                to_record(
+                    row.id,
+                    row.email,
+                    row.name,
+                    row.scopes,
+                    row.created_at,
+                    row.expires_at,
+                    row.last_used_at,
+                )
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Using personal access token in PostgreSQL", skip_all)]
+    async fn use_token(
+        &mut self,
+        token: &PersonalAccessToken,
+    ) -> Result<PersonalAccessTokenRecord, PersonalAccessTokenStoreError> {
+        let row = sqlx::query!(
+            r#"
+            UPDATE personal_access_tokens
+            SET last_used_at = NOW()
+            WHERE token_hash = $1 AND (expires_at IS NULL OR expires_at > NOW())
+            RETURNING id, email, name, scopes, created_at, expires_at, last_used_at
+            "#,
+            hash_token(token.as_ref())
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| PersonalAccessTokenStoreError::UnexpectedError(e.into()))?
+        .ok_or(PersonalAccessTokenStoreError::TokenNotFound)?;
+
+        to_record(
+            row.id,
+            row.email,
+            row.name,
+            row.scopes,
+            row.created_at,
+            row.expires_at,
+            row.last_used_at,
+        )
+    }
+
+    #[tracing::instrument(name = "Revoking personal access token in PostgreSQL", skip_all)]
+    async fn revoke_token(
+        &mut self,
+        email: &Email,
+        id: Uuid,
+    ) -> Result<(), PersonalAccessTokenStoreError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM personal_access_tokens
+            WHERE id = $1 AND email = $2
+            "#,
+            id,
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| PersonalAccessTokenStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(PersonalAccessTokenStoreError::TokenNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        name = "Revoking all personal access tokens of a user in PostgreSQL",
+        skip_all
+    )]
+    async fn revoke_all_tokens(
+        &mut self,
+        email: &Email,
+    ) -> Result<(), PersonalAccessTokenStoreError> {
+        sqlx::query!(
+            r#"
+            DELETE FROM personal_access_tokens
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| PersonalAccessTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+}
+
+fn to_record(
+    id: Uuid,
+    email: String,
+    name: String,
+    scopes: Vec<String>,
+    created_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+    last_used_at: Option<DateTime<Utc>>,
+) -> Result<PersonalAccessTokenRecord, PersonalAccessTokenStoreError> {
+    Ok(PersonalAccessTokenRecord {
+        id,
+        email: Email::parse(SecretString::new(email.into_boxed_str()))
+            .map_err(PersonalAccessTokenStoreError::UnexpectedError)?,
+        name,
+        scopes,
+        created_at,
+        expires_at,
+        last_used_at,
+    })
+}
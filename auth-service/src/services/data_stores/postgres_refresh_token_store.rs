@@ -0,0 +1,193 @@
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::PgPool;
+
+use crate::{
+    domain::{
+        data_stores::{
+            RefreshToken, RefreshTokenClient, RefreshTokenRecord, RefreshTokenStore,
+            RefreshTokenStoreError,
+        },
+        AuthMethod, Email,
+    },
+    utils::auth::hash_token,
+};
+
+pub struct PostgresRefreshTokenStore {
+    pool: PgPool,
+}
+
+impl PostgresRefreshTokenStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for PostgresRefreshTokenStore {
+    #[tracing::instrument(name = "Adding refresh token to PostgreSQL", skip_all)]
+    async fn add_token(
+        &mut self,
+        token: &RefreshToken,
+        record: RefreshTokenRecord,
+    ) -> Result<(), RefreshTokenStoreError> {
+        let amr: Vec<String> = record
+            .amr
+            .iter()
+            .map(|method| method.as_str().to_owned())
+            .collect();
+
+        let client_id = record
+            .client
+            .as_ref()
+            .map(|client| client.client_id.as_str());
+        let scope = record.client.as_ref().map(|client| client.scope.as_str());
+
+        sqlx::query!(
+            r#"
+            INSERT INTO refresh_tokens (token_hash, family_id, email, expires_at, amr, client_id, scope)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            hash_token(token.as_ref()),
+            record.family_id,
+            record.email.as_ref().expose_secret(),
+            record.expires_at,
+            &amr as &[String],
+            client_id,
+            scope
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Consuming refresh token in PostgreSQL", skip_all)]
+    async fn consume_token(
+        &mut self,
+        token: &RefreshToken,
+        client_id: Option<&str>,
+    ) -> Result<RefreshTokenRecord, RefreshTokenStoreError> {
+        let token_hash = hash_token(token.as_ref());
+
+        // Flipping `used` in the same statement that reads the row makes concurrent
+        // refreshes with the same token race safely: only one of them gets a row back.
+        let consumed = sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+            SET used = TRUE
+            WHERE token_hash = $1
+                AND client_id IS NOT DISTINCT FROM $2
+                AND used = FALSE
+                AND revoked = FALSE
+                AND expires_at > NOW()
+            RETURNING email, family_id, expires_at, amr, client_id, scope
+            "#,
+            token_hash,
+            client_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+        if let Some(row) = consumed {
+            return Ok(RefreshTokenRecord {
+                email: Email::parse(SecretString::new(row.email.into_boxed_str()))
+                    .map_err(RefreshTokenStoreError::UnexpectedError)?,
+                family_id: row.family_id,
+                expires_at: row.expires_at,
+                amr: row
+                    .amr
+                    .iter()
+                    .map(|method| AuthMethod::parse(method))
+                    .collect::<Result<_, _>>()
+                    .map_err(RefreshTokenStoreError::UnexpectedError)?,
+                client: row.client_id.map(|client_id| RefreshTokenClient {
+                    client_id,
+                    scope: row.scope.unwrap_or_default(),
+                }),
+            });
+        }
+
+        let already_used = sqlx::query!(
+            r#"
+            SELECT used
+            FROM refresh_tokens
+            WHERE token_hash = $1
+                AND client_id IS NOT DISTINCT FROM $2
+                AND revoked = FALSE
+                AND expires_at > NOW()
+            "#,
+            token_hash,
+            client_id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?
+        .is_some_and(|row| row.used);
+
+        if already_used {
+            Err(RefreshTokenStoreError::TokenReused)
+        } else {
+            Err(RefreshTokenStoreError::TokenNotFound)
+        }
+    }
+
+    #[tracing::instrument(name = "Revoking refresh token family in PostgreSQL", skip_all)]
+    async fn revoke_family(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError> {
+        sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+            SET revoked = TRUE
+            WHERE family_id = (SELECT family_id FROM refresh_tokens WHERE token_hash = $1)
+            "#,
+            hash_token(token.as_ref())
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Revoking client refresh token family in PostgreSQL", skip_all)]
+    async fn revoke_client_family(
+        &mut self,
+        token: &RefreshToken,
+        client_id: &str,
+    ) -> Result<(), RefreshTokenStoreError> {
+        sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+            SET revoked = TRUE
+            WHERE family_id = (
+                SELECT family_id FROM refresh_tokens WHERE token_hash = $1 AND client_id = $2
+            )
+            "#,
+            hash_token(token.as_ref()),
+            client_id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Revoking all refresh tokens of a user in PostgreSQL", skip_all)]
+    async fn revoke_all_tokens(&mut self, email: &Email) -> Result<(), RefreshTokenStoreError> {
+        sqlx::query!(
+            r#"
+            UPDATE refresh_tokens
+            SET revoked = TRUE
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| RefreshTokenStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,180 @@
+use chrono::{DateTime, Utc};
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::{
+    data_stores::{Session, SessionStore, SessionStoreError},
+    AuthMethod, Email,
+};
+
+pub struct PostgresSessionStore {
+    pool: PgPool,
+}
+
+impl PostgresSessionStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl SessionStore for PostgresSessionStore {
+    #[tracing::instrument(name = "Adding session to PostgreSQL", skip_all)]
+    async fn add_session(&mut self, session: Session) -> Result<(), SessionStoreError> {
+        let amr: Vec<String> = session
+            .amr
+            .iter()
+            .map(|method| method.as_str().to_owned())
+            .collect();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO sessions (id, email, created_at, last_seen_at, ip, user_agent, amr)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            session.id,
+            session.email.as_ref().expose_secret(),
+            session.created_at,
+            session.last_seen_at,
+            session.ip,
+            session.user_agent,
+            &amr as &[String]
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving sessions from PostgreSQL", skip_all)]
+    async fn get_sessions(&self, email: &Email) -> Result<Vec<Session>, SessionStoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT id, email, created_at, last_seen_at, ip, user_agent, amr
+            FROM sessions
+            WHERE email = $1
+            ORDER BY last_seen_at DESC
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter()
+            .map(|row| {
+                to_session(
+                    row.id,
+                    row.email,
+                    row.created_at,
+                    row.last_seen_at,
+                    row.ip,
+                    row.user_agent,
+                    row.amr,
+                )
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Checking session in PostgreSQL", skip_all)]
+    async fn contains_session(&self, id: Uuid) -> Result<bool, SessionStoreError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT id
+            FROM sessions
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+
+        Ok(row.is_some())
+    }
+
+    #[tracing::instrument(name = "Touching session in PostgreSQL", skip_all)]
+    async fn touch_session(&mut self, id: Uuid) -> Result<(), SessionStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE sessions
+            SET last_seen_at = NOW()
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(SessionStoreError::SessionNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Revoking session in PostgreSQL", skip_all)]
+    async fn revoke_session(&mut self, email: &Email, id: Uuid) -> Result<(), SessionStoreError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM sessions
+            WHERE id = $1 AND email = $2
+            "#,
+            id,
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(SessionStoreError::SessionNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Revoking all sessions of a user in PostgreSQL", skip_all)]
+    async fn revoke_all_sessions(&mut self, email: &Email) -> Result<(), SessionStoreError> {
+        sqlx::query!(
+            r#"
+            DELETE FROM sessions
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SessionStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+}
+
+fn to_session(
+    id: Uuid,
+    email: String,
+    created_at: DateTime<Utc>,
+    last_seen_at: DateTime<Utc>,
+    ip: Option<String>,
+    user_agent: Option<String>,
+    amr: Vec<String>,
+) -> Result<Session, SessionStoreError> {
+    Ok(Session {
+        id,
+        email: Email::parse(SecretString::new(email.into_boxed_str()))
+            .map_err(SessionStoreError::UnexpectedError)?,
+        created_at,
+        last_seen_at,
+        ip,
+        user_agent,
+        amr: amr
+            .iter()
+            .map(|method| AuthMethod::parse(method))
+            .collect::<Result<_, _>>()
+            .map_err(SessionStoreError::UnexpectedError)?,
+    })
+}
@@ -0,0 +1,161 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::Context;
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::PgPool;
+
+use crate::{
+    domain::data_stores::{SigningKeyRecord, SigningKeyStore, SigningKeyStoreError},
+    utils::{
+        constants::JWT_KEY_ENCRYPTION_KEY,
+        encryption::{decrypt, encrypt},
+    },
+};
+
+pub struct PostgresSigningKeyStore {
+    pool: PgPool,
+}
+
+impl PostgresSigningKeyStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl SigningKeyStore for PostgresSigningKeyStore {
+    #[tracing::instrument(name = "Adding signing key to PostgreSQL", skip_all)]
+    async fn add_key(
+        &mut self,
+        kid: &str,
+        private_key_pem: &SecretString,
+        activates_at: DateTime<Utc>,
+    ) -> Result<bool, SigningKeyStoreError> {
+        let encrypted = encrypt(
+            &JWT_KEY_ENCRYPTION_KEY,
+            private_key_pem.expose_secret().as_bytes(),
+        )
+        .wrap_err("failed to encrypt signing key")
+        .map_err(SigningKeyStoreError::UnexpectedError)?;
+
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| SigningKeyStoreError::UnexpectedError(e.into()))?;
+
+        sqlx::query!(
+            r#"
+            UPDATE signing_keys
+            SET retired_at = $1
+            WHERE retired_at IS NULL
+            "#,
+            activates_at
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| SigningKeyStoreError::UnexpectedError(e.into()))?;
+
+        // Instances starting together with a new key file all try to add it. Only the first
+        // does; the others must leave the key it retired alone, so they roll back.
+        let inserted = sqlx::query!(
+            r#"
+            INSERT INTO signing_keys (kid, private_key, activates_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT DO NOTHING
+            "#,
+            kid,
+            encrypted,
+            activates_at
+        )
+        .execute(&mut *transaction)
+        .await
+        .map_err(|e| SigningKeyStoreError::UnexpectedError(e.into()))?
+        .rows_affected()
+            == 1;
+
+        if !inserted {
+            transaction
+                .rollback()
+                .await
+                .map_err(|e| SigningKeyStoreError::UnexpectedError(e.into()))?;
+            return Ok(false);
+        }
+
+        transaction
+            .commit()
+            .await
+            .map_err(|e| SigningKeyStoreError::UnexpectedError(e.into()))?;
+
+        Ok(true)
+    }
+
+    #[tracing::instrument(name = "Checking for signing key in PostgreSQL", skip_all)]
+    async fn contains_key(&self, kid: &str) -> Result<bool, SigningKeyStoreError> {
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS(SELECT 1 FROM signing_keys WHERE kid = $1) AS "exists!"
+            "#,
+            kid
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| SigningKeyStoreError::UnexpectedError(e.into()))?;
+
+        Ok(exists)
+    }
+
+    #[tracing::instrument(name = "Retrieving signing keys from PostgreSQL", skip_all)]
+    async fn get_keys(
+        &self,
+        retired_after: DateTime<Utc>,
+    ) -> Result<Vec<SigningKeyRecord>, SigningKeyStoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT kid, private_key AS "private_key!", activates_at, retired_at
+            FROM signing_keys
+            WHERE private_key IS NOT NULL AND (retired_at IS NULL OR retired_at > $1)
+            ORDER BY activates_at DESC
+            "#,
+            retired_after
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| SigningKeyStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter()
+            .map(|row| {
+                let pem = decrypt(&JWT_KEY_ENCRYPTION_KEY, &row.private_key)
+                    .and_then(|pem| Ok(String::from_utf8(pem)?))
+                    .wrap_err(format!("failed to decrypt signing key {}", row.kid))
+                    .map_err(SigningKeyStoreError::UnexpectedError)?;
+
+                Ok(SigningKeyRecord {
+                    kid: row.kid,
+                    private_key_pem: SecretString::new(pem.into_boxed_str()),
+                    activates_at: row.activates_at,
+                    retired_at: row.retired_at,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Removing retired signing keys from PostgreSQL", skip_all)]
+    async fn remove_retired_keys(
+        &mut self,
+        retired_before: DateTime<Utc>,
+    ) -> Result<(), SigningKeyStoreError> {
+        sqlx::query!(
+            r#"
+            UPDATE signing_keys
+            SET private_key = NULL
+            WHERE retired_at <= $1
+            "#,
+            retired_before
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| SigningKeyStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+use crate::{
+    domain::{
+        data_stores::{TotpSecret, TotpSecretStore, TotpSecretStoreError},
+        Email,
+    },
+    utils::totp::{decrypt_secret, encrypt_secret},
+};
+
+pub struct PostgresTotpSecretStore {
+    pool: PgPool,
+}
+
+impl PostgresTotpSecretStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl TotpSecretStore for PostgresTotpSecretStore {
+    #[tracing::instrument(name = "Adding pending TOTP secret to PostgreSQL", skip_all)]
+    async fn add_pending_secret(
+        &mut self,
+        email: &Email,
+        secret: TotpSecret,
+    ) -> Result<(), TotpSecretStoreError> {
+        let encrypted = encrypt_secret(&secret).map_err(TotpSecretStoreError::UnexpectedError)?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO totp_secrets (email, pending_secret)
+            VALUES ($1, $2)
+            ON CONFLICT (email) DO UPDATE SET pending_secret = EXCLUDED.pending_secret
+            "#,
+            email.as_ref().expose_secret(),
+            encrypted
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| TotpSecretStoreError::UnexpectedError(e.into()))?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving pending TOTP secret from PostgreSQL", skip_all)]
+    async fn get_pending_secret(&self, email: &Email) -> Result<TotpSecret, TotpSecretStoreError> {
+        let encrypted = sqlx::query!(
+            r#"
+            SELECT pending_secret
+            FROM totp_secrets
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| TotpSecretStoreError::UnexpectedError(e.into()))?
+        .and_then(|row| row.pending_secret)
+        .ok_or(TotpSecretStoreError::SecretNotFound)?;
+
+        decrypt_secret(&encrypted).map_err(TotpSecretStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Activating pending TOTP secret in PostgreSQL", skip_all)]
+    async fn activate_pending_secret(
+        &mut self,
+        email: &Email,
+        step: u64,
+    ) -> Result<(), TotpSecretStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE totp_secrets
+            SET secret = pending_secret, pending_secret = NULL, last_used_step = $2
+            WHERE email = $1 AND pending_secret IS NOT NULL
+            "#,
+            email.as_ref().expose_secret(),
+            to_db_step(step)?
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| TotpSecretStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(TotpSecretStoreError::SecretNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving TOTP secret from PostgreSQL", skip_all)]
+    async fn get_secret(&self, email: &Email) -> Result<TotpSecret, TotpSecretStoreError> {
+        let encrypted = sqlx::query!(
+            r#"
+            SELECT secret
+            FROM totp_secrets
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| TotpSecretStoreError::UnexpectedError(e.into()))?
+        .and_then(|row| row.secret)
+        .ok_or(TotpSecretStoreError::SecretNotFound)?;
+
+        decrypt_secret(&encrypted).map_err(TotpSecretStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Recording used TOTP step in PostgreSQL", skip_all)]
+    async fn use_step(&mut self, email: &Email, step: u64) -> Result<(), TotpSecretStoreError> {
+        // The comparison and the update happen in one statement, so two requests racing
+        // with the same code can't both succeed.
+        let result = sqlx::query!(
+            r#"
+            UPDATE totp_secrets
+            SET last_used_step = $2
+            WHERE email = $1
+              AND secret IS NOT NULL
+              AND (last_used_step IS NULL OR last_used_step < $2)
+            "#,
+            email.as_ref().expose_secret(),
+            to_db_step(step)?
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| TotpSecretStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(TotpSecretStoreError::StepAlreadyUsed);
+        }
+
+        Ok(())
+    }
+}
+
+fn to_db_step(step: u64) -> Result<i64, TotpSecretStoreError> {
+    step.try_into()
+        .map_err(|e: std::num::TryFromIntError| TotpSecretStoreError::UnexpectedError(e.into()))
+}
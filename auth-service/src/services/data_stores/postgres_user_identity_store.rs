@@ -0,0 +1,138 @@
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::PgPool;
+
+use crate::domain::{
+    data_stores::{UserIdentity, UserIdentityStore, UserIdentityStoreError},
+    Email,
+};
+
+pub struct PostgresUserIdentityStore {
+    pool: PgPool,
+}
+
+impl PostgresUserIdentityStore {
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait::async_trait]
+impl UserIdentityStore for PostgresUserIdentityStore {
+    #[tracing::instrument(name = "Adding user identity to PostgreSQL", skip_all)]
+    async fn add_identity(
+        &mut self,
+        email: &Email,
+        provider: &str,
+        subject: &str,
+    ) -> Result<(), UserIdentityStoreError> {
+        let result = sqlx::query!(
+            r#"
+            INSERT INTO user_identities (provider, subject, email)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (provider, subject) DO NOTHING
+            "#,
+            provider,
+            subject,
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserIdentityStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserIdentityStoreError::IdentityAlreadyLinked);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving user identity from PostgreSQL", skip_all)]
+    async fn get_identity(
+        &self,
+        provider: &str,
+        subject: &str,
+    ) -> Result<UserIdentity, UserIdentityStoreError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT provider, subject, email, created_at
+            FROM user_identities
+            WHERE provider = $1 AND subject = $2
+            "#,
+            provider,
+            subject
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| UserIdentityStoreError::UnexpectedError(e.into()))?
+        .ok_or(UserIdentityStoreError::IdentityNotFound)?;
+
+        Ok(UserIdentity {
+            provider: row.provider,
+            subject: row.subject,
+            email: parse_email(row.email)?,
+            created_at: row.created_at,
+        })
+    }
+
+    #[tracing::instrument(name = "Retrieving user identities from PostgreSQL", skip_all)]
+    async fn get_identities(
+        &self,
+        email: &Email,
+    ) -> Result<Vec<UserIdentity>, UserIdentityStoreError> {
+        let rows = sqlx::query!(
+            r#"
+            SELECT provider, subject, email, created_at
+            FROM user_identities
+            WHERE email = $1
+            ORDER BY created_at
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| UserIdentityStoreError::UnexpectedError(e.into()))?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(UserIdentity {
+                    provider: row.provider,
+                    subject: row.subject,
+                    email: parse_email(row.email)?,
+                    created_at: row.created_at,
+                })
+            })
+            .collect()
+    }
+
+    #[tracing::instrument(name = "Removing user identity from PostgreSQL", skip_all)]
+    async fn remove_identity(
+        &mut self,
+        email: &Email,
+        provider: &str,
+        subject: &str,
+    ) -> Result<(), UserIdentityStoreError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM user_identities
+            WHERE provider = $1 AND subject = $2 AND email = $3
+            "#,
+            provider,
+            subject,
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserIdentityStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserIdentityStoreError::IdentityNotFound);
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_email(email: String) -> Result<Email, UserIdentityStoreError> {
+    Email::parse(SecretString::new(email.into_boxed_str()))
+        .map_err(UserIdentityStoreError::UnexpectedError)
+}
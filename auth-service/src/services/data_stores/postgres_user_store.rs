@@ -10,7 +10,7 @@ use sqlx::PgPool;
 
 use crate::domain::{
     data_stores::{UserStore, UserStoreError},
-    Email, Password, User,
+    Email, Password, TwoFAMethod, User,
 };
 
 pub struct PostgresUserStore {
@@ -31,19 +31,28 @@ impl UserStore for PostgresUserStore {
             .await
             .map_err(UserStoreError::UnexpectedError)?;
 
-        sqlx::query!(
+        // The password is hashed before finding out whether the email is taken, so signing up
+        // with a registered email takes as long as with a new one.
+        let result = sqlx::query!(
             r#"
-            INSERT INTO users (email, password_hash, requires_2fa)
-            VALUES ($1, $2, $3)
+            INSERT INTO users (email, password_hash, requires_2fa, verified, two_fa_method)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (email) DO NOTHING
             "#,
             user.email.as_ref().expose_secret(),
             &password_hash.expose_secret(),
-            user.requires_2fa
+            user.requires_2fa,
+            user.verified,
+            user.two_fa_method.as_str()
         )
         .execute(&self.pool)
         .await
         .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
 
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserAlreadyExists);
+        }
+
         Ok(())
     }
 
@@ -51,7 +60,7 @@ impl UserStore for PostgresUserStore {
     async fn get_user(&self, email: &Email) -> Result<User, UserStoreError> {
         sqlx::query!(
             r#"
-            SELECT email, password_hash, requires_2fa
+            SELECT email, password_hash, requires_2fa, verified, two_fa_method
             FROM users
             WHERE email = $1
             "#,
@@ -70,6 +79,9 @@ impl UserStore for PostgresUserStore {
                 password: Password::from_password_hash(parsed_hash)
                     .map_err(UserStoreError::UnexpectedError)?,
                 requires_2fa: row.requires_2fa,
+                verified: row.verified,
+                two_fa_method: TwoFAMethod::parse(&row.two_fa_method)
+                    .map_err(UserStoreError::UnexpectedError)?,
             })
         })
         .ok_or(UserStoreError::UserNotFound)?
@@ -81,19 +93,114 @@ impl UserStore for PostgresUserStore {
         email: &Email,
         password: &Password,
     ) -> Result<(), UserStoreError> {
-        let user = self.get_user(email).await?;
+        let user = match self.get_user(email).await {
+            Ok(user) => Some(user),
+            Err(UserStoreError::UserNotFound) => None,
+            Err(e) => return Err(e),
+        };
+
+        // Unknown users are checked against a dummy hash, so they cost as much as known ones
+        // and the time taken doesn't give away which emails are registered.
+        let expected_password_hash = match &user {
+            Some(user) => user.password.as_ref().to_owned(),
+            None => SecretString::from(DUMMY_PASSWORD_HASH),
+        };
 
-        verify_password_hash(
-            user.password.as_ref().to_owned(),
-            password.as_ref().to_owned(),
+        let result =
+            verify_password_hash(expected_password_hash, password.as_ref().to_owned()).await;
+
+        match (user, result) {
+            (None, _) => Err(UserStoreError::UserNotFound),
+            (Some(_), Ok(())) => Ok(()),
+            (Some(_), Err(_)) => Err(UserStoreError::InvalidCredentials),
+        }
+    }
+
+    #[tracing::instrument(name = "Updating user password in PostgreSQL", skip_all)]
+    async fn update_password(
+        &mut self,
+        email: &Email,
+        password: Password,
+    ) -> Result<(), UserStoreError> {
+        let password_hash = compute_password_hash(password.as_ref().to_owned())
+            .await
+            .map_err(UserStoreError::UnexpectedError)?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET password_hash = $2
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret(),
+            &password_hash.expose_secret()
         )
+        .execute(&self.pool)
         .await
-        .map_err(|_| UserStoreError::InvalidCredentials)
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Marking user as verified in PostgreSQL", skip_all)]
+    async fn mark_verified(&mut self, email: &Email) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET verified = TRUE
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Setting user 2FA method in PostgreSQL", skip_all)]
+    async fn set_two_fa_method(
+        &mut self,
+        email: &Email,
+        method: TwoFAMethod,
+    ) -> Result<(), UserStoreError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET requires_2fa = TRUE, two_fa_method = $2
+            WHERE email = $1
+            "#,
+            email.as_ref().expose_secret(),
+            method.as_str()
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| UserStoreError::UnexpectedError(e.into()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(UserStoreError::UserNotFound);
+        }
+
+        Ok(())
     }
 }
 
+// The hash of a random password nobody knows, made with the same parameters as
+// `compute_password_hash` so verifying against it takes as long as against a real one.
+const DUMMY_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=15000,t=2,p=1$vA+I9Ga3w1xuzlNGdUkC/A$xGNtPffBzWZ71OyvnMr7IhTXVvdYAMGamz9W0/770js";
+
 #[tracing::instrument(name = "Verify password hash", skip_all)]
-async fn verify_password_hash(
+pub(crate) async fn verify_password_hash(
     expected_password_hash: SecretString,
     password_candidate: SecretString,
 ) -> Result<()> {
@@ -117,7 +224,7 @@ async fn verify_password_hash(
 }
 
 #[tracing::instrument(name = "Computing password hash", skip_all)]
-async fn compute_password_hash(password: SecretString) -> Result<SecretString> {
+pub(crate) async fn compute_password_hash(password: SecretString) -> Result<SecretString> {
     let current_span: tracing::Span = tracing::Span::current();
 
     let result = tokio::task::spawn_blocking(move || {
@@ -138,3 +245,33 @@ async fn compute_password_hash(password: SecretString) -> Result<SecretString> {
 
     result?
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn dummy_password_hash_should_cost_as_much_as_real_one() {
+        let password_hash = compute_password_hash(SecretString::from("password123"))
+            .await
+            .unwrap();
+
+        let real = PasswordHash::new(password_hash.expose_secret()).unwrap();
+        let dummy = PasswordHash::new(DUMMY_PASSWORD_HASH).unwrap();
+
+        assert_eq!(dummy.algorithm, real.algorithm);
+        assert_eq!(dummy.version, real.version);
+        assert_eq!(dummy.params, real.params);
+    }
+
+    #[tokio::test]
+    async fn dummy_password_hash_should_not_match_any_password() {
+        let result = verify_password_hash(
+            SecretString::from(DUMMY_PASSWORD_HASH),
+            SecretString::from("password123"),
+        )
+        .await;
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,115 @@
+use crate::domain::{
+    data_stores::{
+        AuthorizationCode, AuthorizationCodeStore, AuthorizationCodeStoreError, AuthorizationGrant,
+    },
+    AuthMethod, Email,
+};
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+pub struct RedisAuthorizationCodeStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisAuthorizationCodeStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl AuthorizationCodeStore for RedisAuthorizationCodeStore {
+    #[tracing::instrument(name = "Storing authorization code in Redis", skip_all)]
+    async fn add_code(
+        &mut self,
+        code: &AuthorizationCode,
+        grant: AuthorizationGrant,
+    ) -> Result<(), AuthorizationCodeStoreError> {
+        let value = serde_json::to_string(&StoredGrant::from(grant))
+            .wrap_err("failed to serialize authorization grant")
+            .map_err(AuthorizationCodeStoreError::UnexpectedError)?;
+
+        self.conn
+            .write()
+            .await
+            .set_ex(get_key(code), value, AUTHORIZATION_CODE_TTL_SECONDS)
+            .wrap_err("failed to set authorization code in Redis")
+            .map_err(AuthorizationCodeStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Taking authorization code from Redis", skip_all)]
+    async fn take_code(
+        &mut self,
+        code: &AuthorizationCode,
+    ) -> Result<AuthorizationGrant, AuthorizationCodeStoreError> {
+        let value: Option<String> = self
+            .conn
+            .write()
+            .await
+            .get_del(get_key(code))
+            .wrap_err("failed to get authorization code from Redis")
+            .map_err(AuthorizationCodeStoreError::UnexpectedError)?;
+
+        let value = value.ok_or(AuthorizationCodeStoreError::CodeNotFound)?;
+
+        let stored: StoredGrant = serde_json::from_str(&value)
+            .wrap_err("failed to deserialize authorization grant")
+            .map_err(AuthorizationCodeStoreError::UnexpectedError)?;
+
+        let email = Email::parse(SecretString::new(stored.email.into_boxed_str()))
+            .map_err(AuthorizationCodeStoreError::UnexpectedError)?;
+
+        Ok(AuthorizationGrant {
+            client_id: stored.client_id,
+            redirect_uri: stored.redirect_uri,
+            email,
+            scope: stored.scope,
+            nonce: stored.nonce,
+            code_challenge: stored.code_challenge,
+            amr: stored.amr,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredGrant {
+    client_id: String,
+    redirect_uri: String,
+    email: String,
+    scope: String,
+    nonce: Option<String>,
+    code_challenge: String,
+    amr: Vec<AuthMethod>,
+}
+
+impl From<AuthorizationGrant> for StoredGrant {
+    fn from(grant: AuthorizationGrant) -> Self {
+        Self {
+            client_id: grant.client_id,
+            redirect_uri: grant.redirect_uri,
+            email: grant.email.as_ref().expose_secret().to_owned(),
+            scope: grant.scope,
+            nonce: grant.nonce,
+            code_challenge: grant.code_challenge,
+            amr: grant.amr,
+        }
+    }
+}
+
+// RFC 6749 section 4.1.2 recommends codes live no longer than 10 minutes; a client
+// redeems its code straight after the redirect, so one minute is plenty.
+const AUTHORIZATION_CODE_TTL_SECONDS: u64 = 60;
+const AUTHORIZATION_CODE_PREFIX: &str = "authorization_code:";
+
+fn get_key(code: &AuthorizationCode) -> String {
+    format!(
+        "{}{}",
+        AUTHORIZATION_CODE_PREFIX,
+        code.as_ref().expose_secret()
+    )
+}
@@ -1,12 +1,13 @@
-use color_eyre::eyre::Context;
-use redis::{Commands, Connection};
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Context};
+use redis::{Commands, Connection, ExistenceCheck, SetExpiry, SetOptions};
 use secrecy::{ExposeSecret, SecretString};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
 use crate::{
     domain::data_stores::{BannedTokenStore, BannedTokenStoreError},
-    utils::auth::TOKEN_TTL_SECONDS,
+    utils::auth::{MAX_TOKEN_TTL_SECONDS, TOKEN_TTL_SECONDS},
 };
 
 pub struct RedisBannedTokenStore {
@@ -57,6 +58,80 @@ impl BannedTokenStore for RedisBannedTokenStore {
 
         Ok(is_banned)
     }
+
+    #[tracing::instrument(name = "Consuming JWT in Redis", skip_all)]
+    async fn consume_token(&mut self, token: SecretString) -> Result<bool, BannedTokenStoreError> {
+        let token_key = get_key(token.expose_secret());
+
+        let ttl: u64 = TOKEN_TTL_SECONDS
+            .try_into()
+            .wrap_err("failed to cast TOKEN_TTL_SECONDS to u64")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        // NX makes the check and the ban one step, so of two requests racing to use the
+        // same token only one sees it as new.
+        let banned: bool = self
+            .conn
+            .write()
+            .await
+            .set_options(
+                &token_key,
+                true,
+                SetOptions::default()
+                    .conditional_set(ExistenceCheck::NX)
+                    .with_expiration(SetExpiry::EX(ttl)),
+            )
+            .wrap_err("failed to consume token in Redis")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(banned)
+    }
+
+    #[tracing::instrument(name = "Storing token cut-off in Redis", skip_all)]
+    async fn ban_tokens_before(
+        &mut self,
+        subject: &str,
+        not_before: DateTime<Utc>,
+    ) -> Result<(), BannedTokenStoreError> {
+        // Tokens issued before the cut-off have all expired once the longest-lived one
+        // could have.
+        let ttl: u64 = MAX_TOKEN_TTL_SECONDS
+            .try_into()
+            .wrap_err("failed to cast MAX_TOKEN_TTL_SECONDS to u64")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        let _: () = self
+            .conn
+            .write()
+            .await
+            .set_ex(get_not_before_key(subject), not_before.timestamp(), ttl)
+            .wrap_err("failed to set token cut-off in Redis")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Retrieving token cut-off from Redis", skip_all)]
+    async fn get_tokens_not_before(
+        &self,
+        subject: &str,
+    ) -> Result<Option<DateTime<Utc>>, BannedTokenStoreError> {
+        let not_before: Option<i64> = self
+            .conn
+            .write()
+            .await
+            .get(get_not_before_key(subject))
+            .wrap_err("failed to get token cut-off from Redis")
+            .map_err(BannedTokenStoreError::UnexpectedError)?;
+
+        not_before
+            .map(|timestamp| {
+                DateTime::from_timestamp(timestamp, 0)
+                    .ok_or_else(|| eyre!("invalid token cut-off: {}", timestamp))
+                    .map_err(BannedTokenStoreError::UnexpectedError)
+            })
+            .transpose()
+    }
 }
 
 // We are using a key prefix to prevent collisions and organize data!
@@ -65,3 +140,9 @@ const BANNED_TOKEN_KEY_PREFIX: &str = "banned_token:";
 fn get_key(token: &str) -> String {
     format!("{}{}", BANNED_TOKEN_KEY_PREFIX, token)
 }
+
+const NOT_BEFORE_KEY_PREFIX: &str = "tokens_not_before:";
+
+fn get_not_before_key(subject: &str) -> String {
+    format!("{}{}", NOT_BEFORE_KEY_PREFIX, subject)
+}
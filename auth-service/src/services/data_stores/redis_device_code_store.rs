@@ -0,0 +1,219 @@
+use crate::{
+    domain::{
+        data_stores::{
+            DeviceCode, DeviceCodeStore, DeviceCodeStoreError, DeviceGrant, DeviceGrantStatus,
+            UserCode,
+        },
+        AuthMethod, Email,
+    },
+    utils::constants::DEVICE_CODE_TTL_SECONDS,
+};
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection, ExistenceCheck, SetExpiry, SetOptions};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+pub struct RedisDeviceCodeStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisDeviceCodeStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+// The grant is stored under its device code, and the user code points at the device code.
+// The user code is deleted once the user has decided, so it can't be used again.
+#[async_trait::async_trait]
+impl DeviceCodeStore for RedisDeviceCodeStore {
+    #[tracing::instrument(name = "Storing device grant in Redis", skip_all)]
+    async fn add_grant(
+        &mut self,
+        device_code: &DeviceCode,
+        user_code: &UserCode,
+        grant: DeviceGrant,
+    ) -> Result<(), DeviceCodeStoreError> {
+        let value = serialize(grant)?;
+
+        let mut conn = self.conn.write().await;
+
+        // User codes are short enough to collide, unlike device codes.
+        let created: bool = conn
+            .set_options(
+                get_user_code_key(user_code),
+                device_code.as_ref().expose_secret(),
+                SetOptions::default()
+                    .conditional_set(ExistenceCheck::NX)
+                    .with_expiration(SetExpiry::EX(DEVICE_CODE_TTL_SECONDS)),
+            )
+            .wrap_err("failed to set user code in Redis")
+            .map_err(DeviceCodeStoreError::UnexpectedError)?;
+
+        if !created {
+            return Err(DeviceCodeStoreError::UserCodeAlreadyExists);
+        }
+
+        conn.set_ex(
+            get_device_code_key(device_code),
+            value,
+            DEVICE_CODE_TTL_SECONDS,
+        )
+        .wrap_err("failed to set device grant in Redis")
+        .map_err(DeviceCodeStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Retrieving device grant from Redis", skip_all)]
+    async fn get_grant(&self, user_code: &UserCode) -> Result<DeviceGrant, DeviceCodeStoreError> {
+        let mut conn = self.conn.write().await;
+
+        let device_code: Option<String> = conn
+            .get(get_user_code_key(user_code))
+            .wrap_err("failed to get user code from Redis")
+            .map_err(DeviceCodeStoreError::UnexpectedError)?;
+
+        let device_code = device_code.ok_or(DeviceCodeStoreError::GrantNotFound)?;
+
+        let value: Option<String> = conn
+            .get(device_code_key(&device_code))
+            .wrap_err("failed to get device grant from Redis")
+            .map_err(DeviceCodeStoreError::UnexpectedError)?;
+
+        deserialize(&value.ok_or(DeviceCodeStoreError::GrantNotFound)?)
+    }
+
+    #[tracing::instrument(name = "Deciding device grant in Redis", skip_all)]
+    async fn decide(
+        &mut self,
+        user_code: &UserCode,
+        status: DeviceGrantStatus,
+    ) -> Result<(), DeviceCodeStoreError> {
+        let mut conn = self.conn.write().await;
+
+        let device_code: Option<String> = conn
+            .get_del(get_user_code_key(user_code))
+            .wrap_err("failed to get user code from Redis")
+            .map_err(DeviceCodeStoreError::UnexpectedError)?;
+
+        let key = device_code_key(&device_code.ok_or(DeviceCodeStoreError::GrantNotFound)?);
+
+        let value: Option<String> = conn
+            .get(&key)
+            .wrap_err("failed to get device grant from Redis")
+            .map_err(DeviceCodeStoreError::UnexpectedError)?;
+
+        let mut grant = deserialize(&value.ok_or(DeviceCodeStoreError::GrantNotFound)?)?;
+        grant.status = status;
+
+        // The device keeps the rest of its original expiry to collect the result.
+        conn.set_options(
+            &key,
+            serialize(grant)?,
+            SetOptions::default()
+                .conditional_set(ExistenceCheck::XX)
+                .with_expiration(SetExpiry::KEEPTTL),
+        )
+        .wrap_err("failed to update device grant in Redis")
+        .map_err(DeviceCodeStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Polling device grant in Redis", skip_all)]
+    async fn poll(
+        &mut self,
+        device_code: &DeviceCode,
+    ) -> Result<DeviceGrant, DeviceCodeStoreError> {
+        let key = get_device_code_key(device_code);
+
+        let mut conn = self.conn.write().await;
+
+        let value: Option<String> = conn
+            .get(&key)
+            .wrap_err("failed to get device grant from Redis")
+            .map_err(DeviceCodeStoreError::UnexpectedError)?;
+
+        let grant = deserialize(&value.ok_or(DeviceCodeStoreError::GrantNotFound)?)?;
+
+        if grant.status != DeviceGrantStatus::Pending {
+            let _: () = conn
+                .del(&key)
+                .wrap_err("failed to delete device grant from Redis")
+                .map_err(DeviceCodeStoreError::UnexpectedError)?;
+        }
+
+        Ok(grant)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredGrant {
+    client_id: String,
+    scope: String,
+    status: StoredStatus,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum StoredStatus {
+    Pending,
+    Approved { email: String, amr: Vec<AuthMethod> },
+    Denied,
+}
+
+fn serialize(grant: DeviceGrant) -> Result<String, DeviceCodeStoreError> {
+    let status = match grant.status {
+        DeviceGrantStatus::Pending => StoredStatus::Pending,
+        DeviceGrantStatus::Approved { email, amr } => StoredStatus::Approved {
+            email: email.as_ref().expose_secret().to_owned(),
+            amr,
+        },
+        DeviceGrantStatus::Denied => StoredStatus::Denied,
+    };
+
+    serde_json::to_string(&StoredGrant {
+        client_id: grant.client_id,
+        scope: grant.scope,
+        status,
+    })
+    .wrap_err("failed to serialize device grant")
+    .map_err(DeviceCodeStoreError::UnexpectedError)
+}
+
+fn deserialize(value: &str) -> Result<DeviceGrant, DeviceCodeStoreError> {
+    let stored: StoredGrant = serde_json::from_str(value)
+        .wrap_err("failed to deserialize device grant")
+        .map_err(DeviceCodeStoreError::UnexpectedError)?;
+
+    let status = match stored.status {
+        StoredStatus::Pending => DeviceGrantStatus::Pending,
+        StoredStatus::Approved { email, amr } => DeviceGrantStatus::Approved {
+            email: Email::parse(SecretString::new(email.into_boxed_str()))
+                .map_err(DeviceCodeStoreError::UnexpectedError)?,
+            amr,
+        },
+        StoredStatus::Denied => DeviceGrantStatus::Denied,
+    };
+
+    Ok(DeviceGrant {
+        client_id: stored.client_id,
+        scope: stored.scope,
+        status,
+    })
+}
+
+const DEVICE_CODE_PREFIX: &str = "device_code:";
+const USER_CODE_PREFIX: &str = "user_code:";
+
+fn get_device_code_key(device_code: &DeviceCode) -> String {
+    device_code_key(device_code.as_ref().expose_secret())
+}
+
+fn device_code_key(device_code: &str) -> String {
+    format!("{}{}", DEVICE_CODE_PREFIX, device_code)
+}
+
+fn get_user_code_key(user_code: &UserCode) -> String {
+    format!("{}{}", USER_CODE_PREFIX, user_code.as_ref().expose_secret())
+}
@@ -0,0 +1,110 @@
+use crate::domain::{
+    data_stores::{
+        FederatedLogin, FederatedLoginState, FederatedLoginStore, FederatedLoginStoreError,
+    },
+    Email,
+};
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+pub struct RedisFederatedLoginStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisFederatedLoginStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl FederatedLoginStore for RedisFederatedLoginStore {
+    #[tracing::instrument(name = "Storing federated login in Redis", skip_all)]
+    async fn add_login(
+        &mut self,
+        state: &FederatedLoginState,
+        login: FederatedLogin,
+    ) -> Result<(), FederatedLoginStoreError> {
+        let value = serde_json::to_string(&StoredLogin::from(login))
+            .wrap_err("failed to serialize federated login")
+            .map_err(FederatedLoginStoreError::UnexpectedError)?;
+
+        self.conn
+            .write()
+            .await
+            .set_ex(get_key(state), value, FEDERATED_LOGIN_TTL_SECONDS)
+            .wrap_err("failed to set federated login in Redis")
+            .map_err(FederatedLoginStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Taking federated login from Redis", skip_all)]
+    async fn take_login(
+        &mut self,
+        state: &FederatedLoginState,
+    ) -> Result<FederatedLogin, FederatedLoginStoreError> {
+        let value: Option<String> = self
+            .conn
+            .write()
+            .await
+            .get_del(get_key(state))
+            .wrap_err("failed to get federated login from Redis")
+            .map_err(FederatedLoginStoreError::UnexpectedError)?;
+
+        let value = value.ok_or(FederatedLoginStoreError::LoginNotFound)?;
+
+        let stored: StoredLogin = serde_json::from_str(&value)
+            .wrap_err("failed to deserialize federated login")
+            .map_err(FederatedLoginStoreError::UnexpectedError)?;
+
+        let link_to = stored
+            .link_to
+            .map(|email| Email::parse(SecretString::new(email.into_boxed_str())))
+            .transpose()
+            .map_err(FederatedLoginStoreError::UnexpectedError)?;
+
+        Ok(FederatedLogin {
+            nonce: stored.nonce,
+            code_verifier: SecretString::new(stored.code_verifier.into_boxed_str()),
+            return_to: stored.return_to,
+            link_to,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredLogin {
+    nonce: String,
+    code_verifier: String,
+    return_to: Option<String>,
+    link_to: Option<String>,
+}
+
+impl From<FederatedLogin> for StoredLogin {
+    fn from(login: FederatedLogin) -> Self {
+        Self {
+            nonce: login.nonce,
+            code_verifier: login.code_verifier.expose_secret().to_owned(),
+            return_to: login.return_to,
+            link_to: login
+                .link_to
+                .map(|email| email.as_ref().expose_secret().to_owned()),
+        }
+    }
+}
+
+// Long enough to log in at the upstream provider, including a second factor there.
+const FEDERATED_LOGIN_TTL_SECONDS: u64 = 600; // 10 minutes
+const FEDERATED_LOGIN_PREFIX: &str = "federated_login:";
+
+fn get_key(state: &FederatedLoginState) -> String {
+    format!(
+        "{}{}",
+        FEDERATED_LOGIN_PREFIX,
+        state.as_ref().expose_secret()
+    )
+}
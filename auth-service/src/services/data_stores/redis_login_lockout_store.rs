@@ -0,0 +1,184 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Context};
+use redis::{Commands, Connection};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::domain::data_stores::{Lockout, LoginLockoutStore, LoginLockoutStoreError};
+
+pub struct RedisLoginLockoutStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisLoginLockoutStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl LoginLockoutStore for RedisLoginLockoutStore {
+    #[tracing::instrument(name = "Recording failed login in Redis", skip_all)]
+    async fn record_failure(
+        &mut self,
+        key: &str,
+        window_seconds: u64,
+    ) -> Result<u64, LoginLockoutStoreError> {
+        let key = get_failures_key(key);
+        let now = Utc::now().timestamp_millis();
+
+        let mut conn = self.conn.write().await;
+
+        // A sorted set of failure times makes the window slide: failures drop out one by
+        // one as they age, instead of all at once when a fixed window ends.
+        let _: () = conn
+            .zrembyscore(&key, "-inf", window_start(now, window_seconds) - 1)
+            .wrap_err("failed to drop old failed logins in Redis")
+            .map_err(LoginLockoutStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .zadd(&key, Uuid::new_v4().to_string(), now)
+            .wrap_err("failed to record failed login in Redis")
+            .map_err(LoginLockoutStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .expire(&key, window_seconds as i64)
+            .wrap_err("failed to set expiry of failed logins in Redis")
+            .map_err(LoginLockoutStoreError::UnexpectedError)?;
+
+        conn.zcard(&key)
+            .wrap_err("failed to count failed logins in Redis")
+            .map_err(LoginLockoutStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Counting failed logins in Redis", skip_all)]
+    async fn count_failures(
+        &self,
+        key: &str,
+        window_seconds: u64,
+    ) -> Result<u64, LoginLockoutStoreError> {
+        let now = Utc::now().timestamp_millis();
+
+        self.conn
+            .write()
+            .await
+            .zcount(
+                get_failures_key(key),
+                window_start(now, window_seconds),
+                "+inf",
+            )
+            .wrap_err("failed to count failed logins in Redis")
+            .map_err(LoginLockoutStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Clearing failed logins in Redis", skip_all)]
+    async fn clear_failures(&mut self, key: &str) -> Result<(), LoginLockoutStoreError> {
+        self.conn
+            .write()
+            .await
+            .del(get_failures_key(key))
+            .wrap_err("failed to clear failed logins in Redis")
+            .map_err(LoginLockoutStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Storing login lockout in Redis", skip_all)]
+    async fn lock(
+        &mut self,
+        key: &str,
+        until: DateTime<Utc>,
+    ) -> Result<(), LoginLockoutStoreError> {
+        let ttl: u64 = (until - Utc::now())
+            .num_seconds()
+            .max(1)
+            .try_into()
+            .wrap_err("failed to cast lockout duration to u64")
+            .map_err(LoginLockoutStoreError::UnexpectedError)?;
+
+        let mut conn = self.conn.write().await;
+
+        let _: () = conn
+            .set_ex(get_lock_key(key), until.timestamp(), ttl)
+            .wrap_err("failed to set login lockout in Redis")
+            .map_err(LoginLockoutStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .incr(get_lockouts_key(key), 1)
+            .wrap_err("failed to count login lockout in Redis")
+            .map_err(LoginLockoutStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .expire(get_lockouts_key(key), LOCKOUT_MEMORY_SECONDS)
+            .wrap_err("failed to set expiry of login lockouts in Redis")
+            .map_err(LoginLockoutStoreError::UnexpectedError)?;
+
+        conn.del(get_failures_key(key))
+            .wrap_err("failed to clear failed logins in Redis")
+            .map_err(LoginLockoutStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Retrieving login lockout from Redis", skip_all)]
+    async fn get_lockout(&self, key: &str) -> Result<Lockout, LoginLockoutStoreError> {
+        let mut conn = self.conn.write().await;
+
+        let locked_until: Option<i64> = conn
+            .get(get_lock_key(key))
+            .wrap_err("failed to get login lockout from Redis")
+            .map_err(LoginLockoutStoreError::UnexpectedError)?;
+
+        let lockouts: Option<u32> = conn
+            .get(get_lockouts_key(key))
+            .wrap_err("failed to get login lockouts from Redis")
+            .map_err(LoginLockoutStoreError::UnexpectedError)?;
+
+        let locked_until = locked_until
+            .map(|timestamp| {
+                DateTime::from_timestamp(timestamp, 0)
+                    .ok_or_else(|| eyre!("invalid login lockout: {}", timestamp))
+                    .map_err(LoginLockoutStoreError::UnexpectedError)
+            })
+            .transpose()?;
+
+        Ok(Lockout {
+            locked_until,
+            lockouts: lockouts.unwrap_or_default(),
+        })
+    }
+
+    #[tracing::instrument(name = "Removing login lockout from Redis", skip_all)]
+    async fn unlock(&mut self, key: &str) -> Result<(), LoginLockoutStoreError> {
+        self.conn
+            .write()
+            .await
+            .del(&[
+                get_lock_key(key),
+                get_lockouts_key(key),
+                get_failures_key(key),
+            ])
+            .wrap_err("failed to remove login lockout from Redis")
+            .map_err(LoginLockoutStoreError::UnexpectedError)
+    }
+}
+
+const LOCKOUT_MEMORY_SECONDS: i64 = 86_400; // 1 day
+
+// Failures are scored by when they happened, in milliseconds.
+fn window_start(now: i64, window_seconds: u64) -> i64 {
+    now - window_seconds as i64 * 1000
+}
+
+const LOGIN_FAILURES_PREFIX: &str = "login_failures:";
+const LOGIN_LOCK_PREFIX: &str = "login_lock:";
+const LOGIN_LOCKOUTS_PREFIX: &str = "login_lockouts:";
+
+fn get_failures_key(key: &str) -> String {
+    format!("{}{}", LOGIN_FAILURES_PREFIX, key)
+}
+
+fn get_lock_key(key: &str) -> String {
+    format!("{}{}", LOGIN_LOCK_PREFIX, key)
+}
+
+fn get_lockouts_key(key: &str) -> String {
+    format!("{}{}", LOGIN_LOCKOUTS_PREFIX, key)
+}
@@ -0,0 +1,71 @@
+use crate::domain::{
+    data_stores::{LoginAttemptId, MagicLinkStore, MagicLinkStoreError},
+    Email,
+};
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use secrecy::{ExposeSecret, SecretString};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+pub struct RedisMagicLinkStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisMagicLinkStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl MagicLinkStore for RedisMagicLinkStore {
+    #[tracing::instrument(name = "Storing magic link login attempt in Redis", skip_all)]
+    async fn add_login_attempt(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+        email: &Email,
+    ) -> Result<(), MagicLinkStoreError> {
+        self.conn
+            .write()
+            .await
+            .set_ex(
+                get_key(login_attempt_id),
+                email.as_ref().expose_secret(),
+                TEN_MINUTES_IN_SECONDS,
+            )
+            .wrap_err("failed to set magic link login attempt in Redis")
+            .map_err(MagicLinkStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Taking magic link login attempt from Redis", skip_all)]
+    async fn take_login_attempt(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<Email, MagicLinkStoreError> {
+        let email: Option<String> = self
+            .conn
+            .write()
+            .await
+            .get_del(get_key(login_attempt_id))
+            .wrap_err("failed to get magic link login attempt from Redis")
+            .map_err(MagicLinkStoreError::UnexpectedError)?;
+
+        let email = email.ok_or(MagicLinkStoreError::LoginAttemptNotFound)?;
+
+        Email::parse(SecretString::new(email.into_boxed_str()))
+            .map_err(MagicLinkStoreError::UnexpectedError)
+    }
+}
+
+const TEN_MINUTES_IN_SECONDS: u64 = 600;
+const MAGIC_LINK_PREFIX: &str = "magic_link:";
+
+fn get_key(login_attempt_id: &LoginAttemptId) -> String {
+    format!(
+        "{}{}",
+        MAGIC_LINK_PREFIX,
+        login_attempt_id.as_ref().expose_secret()
+    )
+}
@@ -0,0 +1,141 @@
+use crate::domain::{
+    data_stores::{LoginAttemptId, PasskeyCeremonyStore, PasskeyCeremonyStoreError},
+    Email,
+};
+
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use webauthn_rs::prelude::{PasskeyAuthentication, PasskeyRegistration};
+
+pub struct RedisPasskeyCeremonyStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisPasskeyCeremonyStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl PasskeyCeremonyStore for RedisPasskeyCeremonyStore {
+    #[tracing::instrument(name = "Storing passkey registration in Redis", skip_all)]
+    async fn add_registration(
+        &mut self,
+        email: &Email,
+        state: PasskeyRegistration,
+    ) -> Result<(), PasskeyCeremonyStoreError> {
+        let value = serde_json::to_string(&state)
+            .wrap_err("failed to serialize passkey registration")
+            .map_err(PasskeyCeremonyStoreError::UnexpectedError)?;
+
+        self.conn
+            .write()
+            .await
+            .set_ex(get_registration_key(email), value, CEREMONY_TTL_SECONDS)
+            .wrap_err("failed to set passkey registration in Redis")
+            .map_err(PasskeyCeremonyStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Taking passkey registration from Redis", skip_all)]
+    async fn take_registration(
+        &mut self,
+        email: &Email,
+    ) -> Result<PasskeyRegistration, PasskeyCeremonyStoreError> {
+        let value: Option<String> = self
+            .conn
+            .write()
+            .await
+            .get_del(get_registration_key(email))
+            .wrap_err("failed to get passkey registration from Redis")
+            .map_err(PasskeyCeremonyStoreError::UnexpectedError)?;
+
+        let value = value.ok_or(PasskeyCeremonyStoreError::CeremonyNotFound)?;
+
+        serde_json::from_str(&value)
+            .wrap_err("failed to deserialize passkey registration")
+            .map_err(PasskeyCeremonyStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Storing passkey authentication in Redis", skip_all)]
+    async fn add_authentication(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+        email: &Email,
+        state: PasskeyAuthentication,
+    ) -> Result<(), PasskeyCeremonyStoreError> {
+        let value = serde_json::to_string(&StoredAuthentication {
+            email: email.as_ref().expose_secret().to_owned(),
+            state,
+        })
+        .wrap_err("failed to serialize passkey authentication")
+        .map_err(PasskeyCeremonyStoreError::UnexpectedError)?;
+
+        self.conn
+            .write()
+            .await
+            .set_ex(
+                get_authentication_key(login_attempt_id),
+                value,
+                CEREMONY_TTL_SECONDS,
+            )
+            .wrap_err("failed to set passkey authentication in Redis")
+            .map_err(PasskeyCeremonyStoreError::UnexpectedError)
+    }
+
+    #[tracing::instrument(name = "Taking passkey authentication from Redis", skip_all)]
+    async fn take_authentication(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<(Email, PasskeyAuthentication), PasskeyCeremonyStoreError> {
+        let value: Option<String> = self
+            .conn
+            .write()
+            .await
+            .get_del(get_authentication_key(login_attempt_id))
+            .wrap_err("failed to get passkey authentication from Redis")
+            .map_err(PasskeyCeremonyStoreError::UnexpectedError)?;
+
+        let value = value.ok_or(PasskeyCeremonyStoreError::CeremonyNotFound)?;
+
+        let stored: StoredAuthentication = serde_json::from_str(&value)
+            .wrap_err("failed to deserialize passkey authentication")
+            .map_err(PasskeyCeremonyStoreError::UnexpectedError)?;
+
+        let email = Email::parse(SecretString::new(stored.email.into_boxed_str()))
+            .map_err(PasskeyCeremonyStoreError::UnexpectedError)?;
+
+        Ok((email, stored.state))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredAuthentication {
+    email: String,
+    state: PasskeyAuthentication,
+}
+
+// Matches the timeout webauthn-rs puts in the challenges it issues.
+const CEREMONY_TTL_SECONDS: u64 = 300;
+const PASSKEY_REGISTRATION_PREFIX: &str = "passkey_registration:";
+const PASSKEY_AUTHENTICATION_PREFIX: &str = "passkey_authentication:";
+
+fn get_registration_key(email: &Email) -> String {
+    format!(
+        "{}{}",
+        PASSKEY_REGISTRATION_PREFIX,
+        email.as_ref().expose_secret()
+    )
+}
+
+fn get_authentication_key(login_attempt_id: &LoginAttemptId) -> String {
+    format!(
+        "{}{}",
+        PASSKEY_AUTHENTICATION_PREFIX,
+        login_attempt_id.as_ref().expose_secret()
+    )
+}
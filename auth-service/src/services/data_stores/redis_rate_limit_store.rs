@@ -0,0 +1,93 @@
+use chrono::Utc;
+use color_eyre::eyre::Context;
+use redis::{Commands, Connection, ExistenceCheck, SetExpiry, SetOptions};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+use crate::domain::{
+    data_stores::{RateLimitStore, RateLimitStoreError},
+    RateLimitDecision, RateLimitQuota,
+};
+
+pub struct RedisRateLimitStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisRateLimitStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl RateLimitStore for RedisRateLimitStore {
+    #[tracing::instrument(name = "Recording rate limit hit in Redis", skip_all)]
+    async fn hit(&mut self, key: &str, window_seconds: u64) -> Result<u64, RateLimitStoreError> {
+        let key = get_key(key);
+
+        let mut conn = self.conn.write().await;
+
+        // The first hit opens the window; later hits must not push its end further out.
+        // Creating the key with its expiry and counting run as one transaction, so a
+        // counter can never be left behind without an expiry.
+        let (count,): (u64,) = redis::pipe()
+            .atomic()
+            .set_options(
+                &key,
+                0,
+                SetOptions::default()
+                    .conditional_set(ExistenceCheck::NX)
+                    .with_expiration(SetExpiry::EX(window_seconds)),
+            )
+            .ignore()
+            .incr(&key, 1)
+            .query(&mut *conn)
+            .wrap_err("failed to increment rate limit counter in Redis")
+            .map_err(RateLimitStoreError::UnexpectedError)?;
+
+        Ok(count)
+    }
+
+    #[tracing::instrument(name = "Acquiring rate limit allowance in Redis", skip_all)]
+    async fn acquire(
+        &mut self,
+        key: &str,
+        quota: &RateLimitQuota,
+    ) -> Result<RateLimitDecision, RateLimitStoreError> {
+        let key = get_gcra_key(key);
+
+        let mut conn = self.conn.write().await;
+
+        // WATCH turns the read and write below into a check-and-set: if another replica
+        // writes the key in between, EXEC does nothing and the check runs again. Replicas
+        // compare TATs against their own clocks, so these must be kept in sync.
+        redis::transaction(&mut *conn, &[&key], |conn, pipe| {
+            let tat: Option<i64> = conn.get(&key)?;
+            let now = Utc::now().timestamp_millis();
+
+            let (decision, new_tat) = quota.check(tat, now);
+
+            match new_tat {
+                Some(new_tat) => pipe
+                    .pset_ex(&key, new_tat, (new_tat - now) as u64)
+                    .ignore()
+                    .query::<Option<()>>(conn)
+                    .map(|result| result.map(|_| decision)),
+                None => Ok(Some(decision)),
+            }
+        })
+        .wrap_err("failed to update rate limit in Redis")
+        .map_err(RateLimitStoreError::UnexpectedError)
+    }
+}
+
+const RATE_LIMIT_PREFIX: &str = "rate_limit:";
+const GCRA_PREFIX: &str = "rate_limit_tat:";
+
+fn get_key(key: &str) -> String {
+    format!("{}{}", RATE_LIMIT_PREFIX, key)
+}
+
+fn get_gcra_key(key: &str) -> String {
+    format!("{}{}", GCRA_PREFIX, key)
+}
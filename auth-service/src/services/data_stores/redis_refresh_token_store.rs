@@ -0,0 +1,291 @@
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Context};
+use redis::{Commands, Connection};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+use crate::{
+    domain::{
+        data_stores::{
+            RefreshToken, RefreshTokenClient, RefreshTokenRecord, RefreshTokenStore,
+            RefreshTokenStoreError,
+        },
+        AuthMethod, Email,
+    },
+    utils::auth::hash_token,
+};
+
+pub struct RedisRefreshTokenStore {
+    conn: Arc<RwLock<Connection>>,
+}
+
+impl RedisRefreshTokenStore {
+    pub fn new(conn: Arc<RwLock<Connection>>) -> Self {
+        Self { conn }
+    }
+}
+
+#[async_trait::async_trait]
+impl RefreshTokenStore for RedisRefreshTokenStore {
+    #[tracing::instrument(name = "Storing refresh token in Redis", skip_all)]
+    async fn add_token(
+        &mut self,
+        token: &RefreshToken,
+        record: RefreshTokenRecord,
+    ) -> Result<(), RefreshTokenStoreError> {
+        let ttl = seconds_until(record.expires_at)?;
+        let token_key = get_token_key(token);
+        let family_key = get_family_key(&record.family_id);
+        let user_key = get_user_key(&record.email);
+
+        let entry = serde_json::to_string(&StoredRefreshToken::new(&record, false))
+            .wrap_err("failed to serialize refresh token")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        let mut conn = self.conn.write().await;
+
+        let _: () = redis::pipe()
+            .atomic()
+            .set_ex(&token_key, entry, ttl)
+            .sadd(&family_key, &token_key)
+            .expire(&family_key, ttl as i64)
+            .sadd(&user_key, &family_key)
+            .expire(&user_key, ttl as i64)
+            .query(&mut *conn)
+            .wrap_err("failed to store refresh token in Redis")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Consuming refresh token in Redis", skip_all)]
+    async fn consume_token(
+        &mut self,
+        token: &RefreshToken,
+        client_id: Option<&str>,
+    ) -> Result<RefreshTokenRecord, RefreshTokenStoreError> {
+        let token_key = get_token_key(token);
+
+        let mut conn = self.conn.write().await;
+
+        // WATCH turns the read and the marking below into a check-and-set: if a concurrent
+        // refresh consumes the token in between, EXEC does nothing and the check runs again,
+        // this time seeing the token as used.
+        redis::transaction(&mut *conn, &[&token_key], |conn, pipe| {
+            let value: Option<String> = conn.get(&token_key)?;
+
+            let (record, entry, ttl) = match mark_used(value, client_id) {
+                Ok(marked) => marked,
+                Err(e) => return Ok(Some(Err(e))),
+            };
+
+            pipe.set_ex(&token_key, entry, ttl)
+                .ignore()
+                .query::<Option<()>>(conn)
+                .map(|result| result.map(|_| Ok(record)))
+        })
+        .wrap_err("failed to consume refresh token in Redis")
+        .map_err(RefreshTokenStoreError::UnexpectedError)?
+    }
+
+    #[tracing::instrument(name = "Revoking refresh token family in Redis", skip_all)]
+    async fn revoke_family(&mut self, token: &RefreshToken) -> Result<(), RefreshTokenStoreError> {
+        self.revoke_family_if(token, |_| true).await
+    }
+
+    #[tracing::instrument(name = "Revoking client refresh token family in Redis", skip_all)]
+    async fn revoke_client_family(
+        &mut self,
+        token: &RefreshToken,
+        client_id: &str,
+    ) -> Result<(), RefreshTokenStoreError> {
+        self.revoke_family_if(token, |stored| {
+            stored.client_id.as_deref() == Some(client_id)
+        })
+        .await
+    }
+
+    #[tracing::instrument(name = "Revoking all refresh tokens of a user in Redis", skip_all)]
+    async fn revoke_all_tokens(&mut self, email: &Email) -> Result<(), RefreshTokenStoreError> {
+        let user_key = get_user_key(email);
+
+        let mut conn = self.conn.write().await;
+
+        let family_keys: Vec<String> = conn
+            .smembers(&user_key)
+            .wrap_err("failed to get refresh token families from Redis")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        let mut keys = vec![user_key];
+        for family_key in family_keys {
+            let token_keys: Vec<String> = conn
+                .smembers(&family_key)
+                .wrap_err("failed to get refresh token family from Redis")
+                .map_err(RefreshTokenStoreError::UnexpectedError)?;
+            keys.extend(token_keys);
+            keys.push(family_key);
+        }
+
+        let _: () = conn
+            .del(keys)
+            .wrap_err("failed to delete refresh tokens from Redis")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+impl RedisRefreshTokenStore {
+    // Deletes the family `token` belongs to if its stored entry passes `check`.
+    async fn revoke_family_if(
+        &mut self,
+        token: &RefreshToken,
+        check: impl Fn(&StoredRefreshToken) -> bool + Send,
+    ) -> Result<(), RefreshTokenStoreError> {
+        let token_key = get_token_key(token);
+
+        let mut conn = self.conn.write().await;
+
+        let value: Option<String> = conn
+            .get(&token_key)
+            .wrap_err("failed to get refresh token from Redis")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        let Some(value) = value else {
+            return Ok(());
+        };
+
+        let stored: StoredRefreshToken = serde_json::from_str(&value)
+            .wrap_err("failed to deserialize refresh token")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        if !check(&stored) {
+            return Ok(());
+        }
+
+        let family_key = get_family_key(&stored.family_id);
+
+        let mut keys: Vec<String> = conn
+            .smembers(&family_key)
+            .wrap_err("failed to get refresh token family from Redis")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+        keys.push(token_key);
+        keys.push(family_key);
+
+        let _: () = conn
+            .del(keys)
+            .wrap_err("failed to delete refresh token family from Redis")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StoredRefreshToken {
+    email: String,
+    family_id: Uuid,
+    expires_at: i64,
+    used: bool,
+    #[serde(default)]
+    amr: Vec<AuthMethod>,
+    #[serde(default)]
+    client_id: Option<String>,
+    #[serde(default)]
+    scope: Option<String>,
+}
+
+impl StoredRefreshToken {
+    fn new(record: &RefreshTokenRecord, used: bool) -> Self {
+        Self {
+            email: record.email.as_ref().expose_secret().to_owned(),
+            family_id: record.family_id,
+            expires_at: record.expires_at.timestamp(),
+            used,
+            amr: record.amr.clone(),
+            client_id: record
+                .client
+                .as_ref()
+                .map(|client| client.client_id.clone()),
+            scope: record.client.as_ref().map(|client| client.scope.clone()),
+        }
+    }
+
+    fn into_record(self) -> Result<RefreshTokenRecord, RefreshTokenStoreError> {
+        Ok(RefreshTokenRecord {
+            email: Email::parse(SecretString::new(self.email.into_boxed_str()))
+                .map_err(RefreshTokenStoreError::UnexpectedError)?,
+            family_id: self.family_id,
+            expires_at: DateTime::from_timestamp(self.expires_at, 0)
+                .ok_or(eyre!("invalid refresh token expiry"))
+                .map_err(RefreshTokenStoreError::UnexpectedError)?,
+            amr: self.amr,
+            client: self.client_id.map(|client_id| RefreshTokenClient {
+                client_id,
+                scope: self.scope.unwrap_or_default(),
+            }),
+        })
+    }
+}
+
+// Returns the record of a stored, unused token issued to `client_id`, with the entry
+// marking it used and that entry's TTL.
+fn mark_used(
+    value: Option<String>,
+    client_id: Option<&str>,
+) -> Result<(RefreshTokenRecord, String, u64), RefreshTokenStoreError> {
+    let stored: StoredRefreshToken = match value {
+        Some(value) => serde_json::from_str(&value)
+            .wrap_err("failed to deserialize refresh token")
+            .map_err(RefreshTokenStoreError::UnexpectedError)?,
+        None => return Err(RefreshTokenStoreError::TokenNotFound),
+    };
+
+    if stored.client_id.as_deref() != client_id {
+        return Err(RefreshTokenStoreError::TokenNotFound);
+    }
+
+    if stored.used {
+        return Err(RefreshTokenStoreError::TokenReused);
+    }
+
+    let record = stored.into_record()?;
+    let ttl = seconds_until(record.expires_at)?;
+
+    let entry = serde_json::to_string(&StoredRefreshToken::new(&record, true))
+        .wrap_err("failed to serialize refresh token")
+        .map_err(RefreshTokenStoreError::UnexpectedError)?;
+
+    Ok((record, entry, ttl))
+}
+
+fn seconds_until(expires_at: DateTime<Utc>) -> Result<u64, RefreshTokenStoreError> {
+    (expires_at - Utc::now())
+        .num_seconds()
+        .try_into()
+        .wrap_err("refresh token has already expired")
+        .map_err(RefreshTokenStoreError::UnexpectedError)
+}
+
+const REFRESH_TOKEN_PREFIX: &str = "refresh_token:";
+const REFRESH_TOKEN_FAMILY_PREFIX: &str = "refresh_token_family:";
+const REFRESH_TOKEN_USER_PREFIX: &str = "refresh_token_user:";
+
+fn get_token_key(token: &RefreshToken) -> String {
+    format!("{}{}", REFRESH_TOKEN_PREFIX, hash_token(token.as_ref()))
+}
+
+fn get_family_key(family_id: &Uuid) -> String {
+    format!("{}{}", REFRESH_TOKEN_FAMILY_PREFIX, family_id)
+}
+
+fn get_user_key(email: &Email) -> String {
+    format!(
+        "{}{}",
+        REFRESH_TOKEN_USER_PREFIX,
+        email.as_ref().expose_secret()
+    )
+}
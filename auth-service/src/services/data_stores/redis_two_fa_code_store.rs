@@ -1,13 +1,14 @@
 use crate::domain::{
-    data_stores::{LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
-    Email,
+    data_stores::{FailedAttempts, LoginAttemptId, TwoFACode, TwoFACodeStore, TwoFACodeStoreError},
+    AuthMethod, Email,
 };
 
-use color_eyre::eyre::Context;
+use chrono::{DateTime, Utc};
+use color_eyre::eyre::{eyre, Context};
 use redis::{Commands, Connection};
 use secrecy::{ExposeSecret, SecretString};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 use tokio::sync::RwLock;
 
 pub struct RedisTwoFACodeStore {
@@ -28,12 +29,14 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
         email: Email,
         login_attempt_id: LoginAttemptId,
         code: TwoFACode,
+        first_factor: AuthMethod,
     ) -> Result<(), TwoFACodeStoreError> {
         let key = get_key(&email);
 
         let raw_tuple = TwoFATuple(
             login_attempt_id.as_ref().expose_secret().to_string(),
             code.as_ref().expose_secret().to_string(),
+            first_factor,
         );
 
         let tuple = serde_json::to_string(&raw_tuple)
@@ -65,30 +68,108 @@ impl TwoFACodeStore for RedisTwoFACodeStore {
         &self,
         email: &Email,
     ) -> Result<(LoginAttemptId, TwoFACode), TwoFACodeStoreError> {
-        let key = get_key(email);
+        let data = self.get_tuple(email).await?;
 
-        match self.conn.write().await.get::<_, String>(&key) {
-            Ok(value) => {
-                let data: TwoFATuple = serde_json::from_str(&value)
-                    .wrap_err("failed to deserialize 2FA tuple")
-                    .map_err(TwoFACodeStoreError::UnexpectedError)?;
+        let login_attempt_id = LoginAttemptId::parse(SecretString::new(data.0.into_boxed_str()))
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
-                let login_attempt_id =
-                    LoginAttemptId::parse(SecretString::new(data.0.into_boxed_str()))
-                        .map_err(TwoFACodeStoreError::UnexpectedError)?;
+        let email_code = TwoFACode::parse(SecretString::new(data.1.into_boxed_str()))
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
 
-                let email_code = TwoFACode::parse(SecretString::new(data.1.into_boxed_str()))
-                    .map_err(TwoFACodeStoreError::UnexpectedError)?;
+        Ok((login_attempt_id, email_code))
+    }
+
+    #[tracing::instrument(name = "Retrieving 2FA first factor from Redis", skip_all)]
+    async fn get_first_factor(&self, email: &Email) -> Result<AuthMethod, TwoFACodeStoreError> {
+        Ok(self.get_tuple(email).await?.2)
+    }
+
+    #[tracing::instrument(name = "Recording failed 2FA attempt in Redis", skip_all)]
+    async fn record_failed_attempt(
+        &mut self,
+        login_attempt_id: &LoginAttemptId,
+        failed_at: DateTime<Utc>,
+    ) -> Result<FailedAttempts, TwoFACodeStoreError> {
+        let key = get_failed_attempts_key(login_attempt_id);
 
-                Ok((login_attempt_id, email_code))
-            }
+        let mut conn = self.conn.write().await;
+
+        let count: u32 = conn
+            .hincr(&key, COUNT_FIELD, 1)
+            .wrap_err("failed to increment failed 2FA attempts in Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        let _: () = conn
+            .hset(&key, LAST_FAILED_AT_FIELD, failed_at.timestamp_millis())
+            .wrap_err("failed to set last failed 2FA attempt in Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        // The count outlives the code by at most its TTL.
+        if count == 1 {
+            let _: () = conn
+                .expire(&key, TEN_MINUTES_IN_SECONDS as i64)
+                .wrap_err("failed to set expiry of failed 2FA attempts in Redis")
+                .map_err(TwoFACodeStoreError::UnexpectedError)?;
+        }
+
+        Ok(FailedAttempts {
+            count,
+            last_failed_at: Some(failed_at),
+        })
+    }
+
+    #[tracing::instrument(name = "Retrieving failed 2FA attempts from Redis", skip_all)]
+    async fn get_failed_attempts(
+        &self,
+        login_attempt_id: &LoginAttemptId,
+    ) -> Result<FailedAttempts, TwoFACodeStoreError> {
+        let fields: HashMap<String, i64> = self
+            .conn
+            .write()
+            .await
+            .hgetall(get_failed_attempts_key(login_attempt_id))
+            .wrap_err("failed to get failed 2FA attempts from Redis")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?;
+
+        let count = fields
+            .get(COUNT_FIELD)
+            .map(|&count| u32::try_from(count))
+            .transpose()
+            .wrap_err("invalid failed 2FA attempt count")
+            .map_err(TwoFACodeStoreError::UnexpectedError)?
+            .unwrap_or_default();
+
+        let last_failed_at = fields
+            .get(LAST_FAILED_AT_FIELD)
+            .map(|&millis| {
+                DateTime::from_timestamp_millis(millis)
+                    .ok_or_else(|| eyre!("invalid last failed 2FA attempt: {}", millis))
+                    .map_err(TwoFACodeStoreError::UnexpectedError)
+            })
+            .transpose()?;
+
+        Ok(FailedAttempts {
+            count,
+            last_failed_at,
+        })
+    }
+}
+
+impl RedisTwoFACodeStore {
+    async fn get_tuple(&self, email: &Email) -> Result<TwoFATuple, TwoFACodeStoreError> {
+        let key = get_key(email);
+
+        match self.conn.write().await.get::<_, String>(&key) {
+            Ok(value) => serde_json::from_str(&value)
+                .wrap_err("failed to deserialize 2FA tuple")
+                .map_err(TwoFACodeStoreError::UnexpectedError),
             Err(_) => Err(TwoFACodeStoreError::LoginAttemptIdNotFound),
         }
     }
 }
 
 #[derive(Serialize, Deserialize)]
-struct TwoFATuple(pub String, pub String);
+struct TwoFATuple(pub String, pub String, pub AuthMethod);
 
 const TEN_MINUTES_IN_SECONDS: u64 = 600;
 const TWO_FA_CODE_PREFIX: &str = "two_fa_code:";
@@ -96,3 +177,15 @@ const TWO_FA_CODE_PREFIX: &str = "two_fa_code:";
 fn get_key(email: &Email) -> String {
     format!("{}{}", TWO_FA_CODE_PREFIX, email.as_ref().expose_secret())
 }
+
+const FAILED_ATTEMPTS_PREFIX: &str = "two_fa_failed_attempts:";
+const COUNT_FIELD: &str = "count";
+const LAST_FAILED_AT_FIELD: &str = "last_failed_at";
+
+fn get_failed_attempts_key(login_attempt_id: &LoginAttemptId) -> String {
+    format!(
+        "{}{}",
+        FAILED_ATTEMPTS_PREFIX,
+        login_attempt_id.as_ref().expose_secret()
+    )
+}
@@ -1,7 +1,9 @@
 pub mod data_stores;
 pub mod mock_email_client;
+pub mod oidc_identity_provider;
 pub mod postmark_email_client;
 
 pub use data_stores::*;
 pub use mock_email_client::*;
+pub use oidc_identity_provider::*;
 pub use postmark_email_client::*;
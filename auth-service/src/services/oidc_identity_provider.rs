@@ -0,0 +1,346 @@
+use color_eyre::eyre::{eyre, Context};
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use reqwest::{Client, StatusCode, Url};
+use secrecy::{ExposeSecret, SecretString};
+use serde::Deserialize;
+use tokio::sync::OnceCell;
+
+use crate::domain::{Email, IdentityProvider, IdentityProviderError, UpstreamIdentity};
+
+/// Logs users in through an OpenID Connect provider with the authorization code flow,
+/// authenticating with `client_secret_basic`. The provider's metadata is discovered on
+/// first use (OpenID Connect Discovery 1.0).
+pub struct OidcIdentityProvider {
+    http_client: Client,
+    issuer: String,
+    client_id: String,
+    client_secret: SecretString,
+    metadata: OnceCell<ProviderMetadata>,
+}
+
+impl OidcIdentityProvider {
+    pub fn new(
+        issuer: String,
+        client_id: String,
+        client_secret: SecretString,
+        http_client: Client,
+    ) -> Self {
+        Self {
+            http_client,
+            issuer,
+            client_id,
+            client_secret,
+            metadata: OnceCell::new(),
+        }
+    }
+
+    async fn metadata(&self) -> Result<&ProviderMetadata, IdentityProviderError> {
+        self.metadata
+            .get_or_try_init(|| async {
+                let url = format!(
+                    "{}/.well-known/openid-configuration",
+                    self.issuer.trim_end_matches('/')
+                );
+
+                let metadata: ProviderMetadata = self
+                    .http_client
+                    .get(url)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .wrap_err("failed to fetch upstream provider metadata")?
+                    .json()
+                    .await
+                    .wrap_err("failed to parse upstream provider metadata")?;
+
+                // OpenID Connect Discovery 1.0 section 4.3.
+                if metadata.issuer != self.issuer {
+                    return Err(eyre!(
+                        "upstream provider metadata is for issuer {}",
+                        metadata.issuer
+                    ));
+                }
+
+                Ok(metadata)
+            })
+            .await
+            .map_err(IdentityProviderError::UnexpectedError)
+    }
+
+    /// The provider's keys are fetched for every login, so keys it rotates in are picked up
+    /// straight away.
+    async fn validate_id_token(
+        &self,
+        id_token: &str,
+        jwks_uri: &str,
+        nonce: &str,
+    ) -> Result<UpstreamIdentity, IdentityProviderError> {
+        let header = decode_header(id_token)
+            .wrap_err("malformed ID token header")
+            .map_err(IdentityProviderError::InvalidIdToken)?;
+
+        // Only asymmetric algorithms, so a token can't be "signed" with a public key used
+        // as an HMAC secret.
+        if !SUPPORTED_ALGORITHMS.contains(&header.alg) {
+            return Err(IdentityProviderError::InvalidIdToken(eyre!(
+                "unsupported ID token algorithm {:?}",
+                header.alg
+            )));
+        }
+
+        let jwks: JwkSet = self
+            .http_client
+            .get(jwks_uri)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .wrap_err("failed to fetch upstream JWKS")
+            .map_err(IdentityProviderError::UnexpectedError)?
+            .json()
+            .await
+            .wrap_err("failed to parse upstream JWKS")
+            .map_err(IdentityProviderError::UnexpectedError)?;
+
+        let jwk = match &header.kid {
+            Some(kid) => jwks.find(kid),
+            None if jwks.keys.len() == 1 => jwks.keys.first(),
+            None => None,
+        }
+        .ok_or_else(|| {
+            IdentityProviderError::InvalidIdToken(eyre!("ID token was not signed by a known key"))
+        })?;
+
+        let key = DecodingKey::from_jwk(jwk)
+            .wrap_err("failed to use upstream JWK")
+            .map_err(IdentityProviderError::InvalidIdToken)?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[self.issuer.as_str()]);
+        validation.set_audience(&[self.client_id.as_str()]);
+        validation.set_required_spec_claims(&["exp", "iss", "aud", "sub"]);
+
+        let claims = decode::<IdTokenClaims>(id_token, &key, &validation)
+            .wrap_err("failed to validate ID token")
+            .map_err(IdentityProviderError::InvalidIdToken)?
+            .claims;
+
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(IdentityProviderError::InvalidIdToken(eyre!(
+                "ID token nonce does not match"
+            )));
+        }
+
+        let email = claims
+            .email
+            .map(|email| Email::parse(SecretString::new(email.into_boxed_str())))
+            .transpose()
+            .map_err(IdentityProviderError::InvalidIdToken)?;
+
+        Ok(UpstreamIdentity {
+            subject: claims.sub,
+            email,
+            email_verified: claims.email_verified.unwrap_or(false),
+            amr: claims.amr.unwrap_or_default(),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl IdentityProvider for OidcIdentityProvider {
+    fn issuer(&self) -> &str {
+        &self.issuer
+    }
+
+    #[tracing::instrument(name = "Building upstream authorization URL", skip_all)]
+    async fn authorization_url(
+        &self,
+        state: &SecretString,
+        nonce: &str,
+        code_challenge: &str,
+        redirect_uri: &str,
+    ) -> Result<String, IdentityProviderError> {
+        let metadata = self.metadata().await?;
+
+        let mut url = Url::parse(&metadata.authorization_endpoint)
+            .wrap_err("invalid upstream authorization endpoint")
+            .map_err(IdentityProviderError::UnexpectedError)?;
+
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.client_id)
+            .append_pair("redirect_uri", redirect_uri)
+            .append_pair("scope", UPSTREAM_SCOPE)
+            .append_pair("state", state.expose_secret())
+            .append_pair("nonce", nonce)
+            .append_pair("code_challenge", code_challenge)
+            .append_pair("code_challenge_method", "S256");
+
+        Ok(url.into())
+    }
+
+    #[tracing::instrument(name = "Authenticating with upstream provider", skip_all)]
+    async fn authenticate(
+        &self,
+        code: &SecretString,
+        code_verifier: &SecretString,
+        redirect_uri: &str,
+        nonce: &str,
+    ) -> Result<UpstreamIdentity, IdentityProviderError> {
+        let metadata = self.metadata().await?;
+
+        let response = self
+            .http_client
+            .post(&metadata.token_endpoint)
+            .basic_auth(&self.client_id, Some(self.client_secret.expose_secret()))
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code.expose_secret()),
+                ("redirect_uri", redirect_uri),
+                ("code_verifier", code_verifier.expose_secret()),
+            ])
+            .send()
+            .await
+            .wrap_err("failed to reach upstream token endpoint")
+            .map_err(IdentityProviderError::UnexpectedError)?;
+
+        // RFC 6749 section 5.2: a code that is wrong, expired or already used is a 400.
+        if response.status() == StatusCode::BAD_REQUEST {
+            return Err(IdentityProviderError::InvalidGrant);
+        }
+
+        let tokens: TokenResponse = response
+            .error_for_status()
+            .wrap_err("upstream token endpoint failed")
+            .map_err(IdentityProviderError::UnexpectedError)?
+            .json()
+            .await
+            .wrap_err("failed to parse upstream token response")
+            .map_err(IdentityProviderError::UnexpectedError)?;
+
+        self.validate_id_token(&tokens.id_token, &metadata.jwks_uri, nonce)
+            .await
+    }
+}
+
+const UPSTREAM_SCOPE: &str = "openid email";
+
+const SUPPORTED_ALGORITHMS: [Algorithm; 8] = [
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::EdDSA,
+];
+
+#[derive(Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Deserialize)]
+struct IdTokenClaims {
+    sub: String,
+    nonce: Option<String>,
+    email: Option<String>,
+    email_verified: Option<bool>,
+    amr: Option<Vec<String>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn provider(issuer: String) -> OidcIdentityProvider {
+        OidcIdentityProvider::new(
+            issuer,
+            "client-id".to_owned(),
+            SecretString::new("client-secret".to_owned().into_boxed_str()),
+            Client::new(),
+        )
+    }
+
+    async fn mount_metadata(mock_server: &MockServer, issuer: &str) {
+        let uri = mock_server.uri();
+
+        Mock::given(method("GET"))
+            .and(path("/.well-known/openid-configuration"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "issuer": issuer,
+                "authorization_endpoint": format!("{}/authorize", uri),
+                "token_endpoint": format!("{}/token", uri),
+                "jwks_uri": format!("{}/jwks", uri),
+            })))
+            .expect(1)
+            .mount(mock_server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn authorization_url_asks_for_a_code_with_pkce() {
+        let mock_server = MockServer::start().await;
+        mount_metadata(&mock_server, &mock_server.uri()).await;
+        let provider = provider(mock_server.uri());
+        let state = SecretString::new("state".to_owned().into_boxed_str());
+
+        let url = provider
+            .authorization_url(&state, "nonce", "challenge", "https://rp.example.com/cb")
+            .await
+            .expect("Failed to build authorization URL");
+
+        // Metadata is only fetched once.
+        provider
+            .authorization_url(&state, "nonce", "challenge", "https://rp.example.com/cb")
+            .await
+            .expect("Failed to build authorization URL");
+
+        let url = Url::parse(&url).unwrap();
+        let query: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+
+        assert_eq!(url.path(), "/authorize");
+        for (name, value) in [
+            ("response_type", "code"),
+            ("client_id", "client-id"),
+            ("redirect_uri", "https://rp.example.com/cb"),
+            ("state", "state"),
+            ("nonce", "nonce"),
+            ("code_challenge", "challenge"),
+            ("code_challenge_method", "S256"),
+        ] {
+            assert!(
+                query.contains(&(name.to_owned(), value.to_owned())),
+                "Missing {}",
+                name
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn metadata_for_another_issuer_is_rejected() {
+        let mock_server = MockServer::start().await;
+        mount_metadata(&mock_server, "https://idp.example.com").await;
+        let provider = provider(mock_server.uri());
+        let state = SecretString::new("state".to_owned().into_boxed_str());
+
+        let outcome = provider
+            .authorization_url(&state, "nonce", "challenge", "https://rp.example.com/cb")
+            .await;
+
+        assert!(matches!(
+            outcome,
+            Err(IdentityProviderError::UnexpectedError(_))
+        ));
+    }
+}
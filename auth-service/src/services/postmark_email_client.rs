@@ -32,7 +32,7 @@ impl PostmarkEmailClient {
 impl EmailClient for PostmarkEmailClient {
     #[tracing::instrument(name = "Sending email", skip_all)]
     async fn send_email(&self, recipient: &Email, subject: &str, content: &str) -> Result<()> {
-        let base = Url::parse(&self.base_url.as_str())?;
+        let base = Url::parse(self.base_url.as_str())?;
         let url = base.join("/email")?;
 
         let request_body = SendEmailRequest {
@@ -116,7 +116,7 @@ mod tests {
     impl wiremock::Match for SendEmailBodyMatcher {
         fn matches(&self, request: &Request) -> bool {
             let result: Result<serde_json::Value, _> =
-                serde_json::from_slice(&request.body.as_slice());
+                serde_json::from_slice(request.body.as_slice());
             if let Ok(body) = result {
                 body.get("From").is_some()
                     && body.get("To").is_some()
@@ -146,7 +146,7 @@ mod tests {
             .await;
 
         let outcome = email_client
-            .send_email(&email(), &subject().as_str(), &content().as_str())
+            .send_email(&email(), subject().as_str(), content().as_str())
             .await;
 
         assert!(outcome.is_ok());
@@ -163,7 +163,7 @@ mod tests {
             .await;
 
         let outcome = email_client
-            .send_email(&email(), &subject().as_str(), &content().as_str())
+            .send_email(&email(), subject().as_str(), content().as_str())
             .await;
 
         assert!(outcome.is_err());
@@ -181,7 +181,7 @@ mod tests {
             .await;
 
         let outcome = email_client
-            .send_email(&email(), &subject().as_str(), &content().as_str())
+            .send_email(&email(), subject().as_str(), content().as_str())
             .await;
 
         assert!(outcome.is_err());
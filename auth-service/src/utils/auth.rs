@@ -1,20 +1,89 @@
-use axum_extra::extract::cookie::{Cookie, SameSite};
+use axum::{
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header, request::Parts, HeaderMap},
+};
+use axum_extra::extract::{
+    cookie::{Cookie, SameSite},
+    CookieJar,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
 use chrono::Utc;
 use color_eyre::eyre::{eyre, Context, ContextCompat, Result};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Validation};
+use jsonwebtoken::{decode, decode_header, encode, Header, Validation};
 use secrecy::{ExposeSecret, SecretString};
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{convert::Infallible, net::SocketAddr};
+use subtle::ConstantTimeEq;
+use uuid::Uuid;
 
-use crate::{app_state::BannedTokenStoreType, domain::email::Email};
+use crate::{
+    app_state::{
+        AppState, BannedTokenStoreType, PersonalAccessTokenStoreType, RefreshTokenStoreType,
+        SessionStoreType,
+    },
+    domain::{
+        email::Email, AuthAPIError, AuthMethod, AuthorizationGrant, LoginAttemptId, OAuthClient,
+        OAuthClientStoreError, OAuthError, PersonalAccessToken, RefreshToken, RefreshTokenClient,
+        RefreshTokenRecord, Session,
+    },
+};
 
-use super::constants::{JWT_COOKIE_NAME, JWT_SECRET};
+use super::{
+    constants::{ADMIN_API_TOKEN, JWT_AUDIENCES, JWT_COOKIE_NAME, JWT_ISSUER, REFRESH_COOKIE_NAME},
+    signing_key::Keyring,
+};
 
+/// `amr` lists how the user proved who they are and ends up in the token's `amr` claim.
+/// `session_id` ends up in the `sid` claim, so the token stops working once the session is
+/// revoked.
 #[tracing::instrument(name = "Generate auth cookie", skip_all)]
-pub fn generate_auth_cookie(email: &Email) -> Result<Cookie<'static>> {
-    let token = generate_auth_token(email)?;
+pub fn generate_auth_cookie(
+    email: &Email,
+    amr: &[AuthMethod],
+    session_id: Uuid,
+    keyring: &Keyring,
+) -> Result<Cookie<'static>> {
+    let token = generate_auth_token(email, amr, session_id, keyring)?;
     Ok(create_auth_cookie(token))
 }
 
+/// Starts a session for `email` once every login factor has been checked, and returns the
+/// auth and refresh cookies for it. The session shares its id with the refresh token
+/// family, so refreshing keeps the session going.
+#[tracing::instrument(name = "Start session", skip_all)]
+pub async fn start_session(
+    state: &AppState,
+    email: &Email,
+    amr: &[AuthMethod],
+    client: &ClientInfo,
+) -> Result<(Cookie<'static>, Cookie<'static>)> {
+    let now = Utc::now();
+    let session = Session {
+        id: Uuid::new_v4(),
+        email: email.clone(),
+        created_at: now,
+        last_seen_at: now,
+        ip: client.ip.clone(),
+        user_agent: client.user_agent.clone(),
+        amr: amr.to_vec(),
+    };
+    let session_id = session.id;
+
+    state
+        .session_store
+        .write()
+        .await
+        .add_session(session)
+        .await?;
+
+    let auth_cookie = generate_auth_cookie(email, amr, session_id, &state.keyring)?;
+    let refresh_cookie =
+        generate_refresh_cookie(email, session_id, amr, state.refresh_token_store.clone()).await?;
+
+    Ok((auth_cookie, refresh_cookie))
+}
+
 #[tracing::instrument(name = "Create auth cookie", skip_all)]
 fn create_auth_cookie(token: SecretString) -> Cookie<'static> {
     let cookie = Cookie::build((JWT_COOKIE_NAME, token.expose_secret().to_string()))
@@ -26,6 +95,34 @@ fn create_auth_cookie(token: SecretString) -> Cookie<'static> {
     cookie
 }
 
+/// Issues a new refresh token and wraps it in a cookie. Pass the `family_id` of the token
+/// being rotated, or the id of the new session on a fresh login. The `amr` is kept with
+/// the token so access tokens issued on refresh report the original login methods.
+#[tracing::instrument(name = "Generate refresh cookie", skip_all)]
+pub async fn generate_refresh_cookie(
+    email: &Email,
+    family_id: Uuid,
+    amr: &[AuthMethod],
+    refresh_token_store: RefreshTokenStoreType,
+) -> Result<Cookie<'static>> {
+    let token = generate_refresh_token(email, family_id, amr, None, refresh_token_store).await?;
+    Ok(create_refresh_cookie(token))
+}
+
+#[tracing::instrument(name = "Create refresh cookie", skip_all)]
+fn create_refresh_cookie(token: RefreshToken) -> Cookie<'static> {
+    let cookie = Cookie::build((
+        REFRESH_COOKIE_NAME,
+        token.as_ref().expose_secret().to_string(),
+    ))
+    .path("/")
+    .http_only(true)
+    .same_site(SameSite::Lax)
+    .build();
+
+    cookie
+}
+
 #[derive(Debug)]
 pub enum GenerateTokenError {
     TokenError(jsonwebtoken::errors::Error),
@@ -34,33 +131,199 @@ pub enum GenerateTokenError {
 
 pub const TOKEN_TTL_SECONDS: i64 = 600; // 10 minutes
 
+/// No JWT is issued with a longer lifetime than this.
+pub const MAX_TOKEN_TTL_SECONDS: i64 = 86_400; // 24 hours
+
+/// Issues a bearer access token limited to `scope` and `audience`, for an OAuth client
+/// acting on the user's behalf.
+#[tracing::instrument(name = "Generate access token", skip_all)]
+pub fn generate_access_token(
+    email: &Email,
+    amr: &[AuthMethod],
+    scope: &str,
+    client_id: &str,
+    audience: &str,
+    keyring: &Keyring,
+) -> Result<SecretString> {
+    let claims = access_claims(
+        email.as_ref().expose_secret(),
+        amr,
+        Some(scope),
+        Some(client_id),
+        audience,
+    )?;
+    create_token(&claims, keyring)
+}
+
+/// Issues a bearer access token limited to `scope` and `audience` for an OAuth client
+/// acting on its own behalf. The client is the token's subject, so it can never pass for a
+/// user.
+#[tracing::instrument(name = "Generate client token", skip_all)]
+pub fn generate_client_token(
+    client_id: &str,
+    scope: &str,
+    audience: &str,
+    keyring: &Keyring,
+) -> Result<SecretString> {
+    let claims = access_claims(client_id, &[], Some(scope), Some(client_id), audience)?;
+    create_token(&claims, keyring)
+}
+
 #[tracing::instrument(name = "Generate auth token", skip_all)]
-fn generate_auth_token(email: &Email) -> Result<SecretString> {
-    let delta = chrono::Duration::try_seconds(TOKEN_TTL_SECONDS)
-        .wrap_err("failed to create 10 minute time delta")?;
+fn generate_auth_token(
+    email: &Email,
+    amr: &[AuthMethod],
+    session_id: Uuid,
+    keyring: &Keyring,
+) -> Result<SecretString> {
+    let claims = Claims {
+        sid: Some(session_id.to_string()),
+        ..access_claims(
+            email.as_ref().expose_secret(),
+            amr,
+            None,
+            None,
+            first_party_audience(),
+        )?
+    };
+    create_token(&claims, keyring)
+}
+
+// Each token is issued for a single audience, so a service handed a token can't pass it
+// on to another service.
+fn access_claims(
+    sub: &str,
+    amr: &[AuthMethod],
+    scope: Option<&str>,
+    client_id: Option<&str>,
+    audience: &str,
+) -> Result<Claims> {
+    let (iat, exp) = token_lifetime(TOKEN_TTL_SECONDS)?;
+
+    Ok(Claims {
+        iss: JWT_ISSUER.clone(),
+        sub: sub.to_owned(),
+        aud: vec![audience.to_owned()],
+        exp,
+        nbf: iat,
+        iat,
+        jti: Uuid::new_v4().to_string(),
+        amr: amr.to_vec(),
+        scope: scope.map(str::to_owned),
+        client_id: client_id.map(str::to_owned),
+        sid: None,
+    })
+}
+
+/// Issues the OpenID Connect ID token for a redeemed authorization code. It is addressed
+/// to the client itself, so it is never accepted as an access token.
+#[tracing::instrument(name = "Generate ID token", skip_all)]
+pub fn generate_id_token(grant: &AuthorizationGrant, keyring: &Keyring) -> Result<SecretString> {
+    let (iat, exp) = token_lifetime(TOKEN_TTL_SECONDS)?;
+    let email = grant.email.as_ref().expose_secret();
+    let include_email = grant.scope.split(' ').any(|scope| scope == "email");
+
+    let claims = IdTokenClaims {
+        iss: JWT_ISSUER.clone(),
+        sub: email.to_owned(),
+        aud: grant.client_id.clone(),
+        exp,
+        iat,
+        nonce: grant.nonce.clone(),
+        amr: grant.amr.clone(),
+        email: include_email.then(|| email.to_owned()),
+        // Users can't log in before verifying their email, so anyone we issue tokens for
+        // has.
+        email_verified: include_email.then_some(true),
+    };
+
+    create_token(&claims, keyring)
+}
 
-    let exp = Utc::now()
+/// Returns the `iat` and `exp` timestamps for a token that is valid for `ttl_seconds`
+/// from now.
+fn token_lifetime(ttl_seconds: i64) -> Result<(usize, usize)> {
+    let now = Utc::now();
+
+    let delta = chrono::Duration::try_seconds(ttl_seconds).wrap_err(format!(
+        "failed to create {} second time delta",
+        ttl_seconds
+    ))?;
+
+    let exp = now
         .checked_add_signed(delta)
-        .ok_or(eyre!("failed to add 10 minutes to current time"))?
+        .ok_or(eyre!("failed to add token TTL to current time"))?
         .timestamp();
 
+    let iat: usize = now
+        .timestamp()
+        .try_into()
+        .wrap_err("failed to cast iat time to usize")?;
+
     let exp: usize = exp.try_into().wrap_err(format!(
         "failed to cast exp time to usize. exp time: {}",
         exp
     ))?;
 
-    let sub = email.as_ref().expose_secret().to_owned();
+    Ok((iat, exp))
+}
+
+pub const REFRESH_TOKEN_TTL_SECONDS: i64 = 2_592_000; // 30 days
+
+/// Issues a new refresh token in the family `family_id`. Tokens for an OAuth client name
+/// it as `client`, so only that client can redeem or revoke them; session cookies have none.
+#[tracing::instrument(name = "Generate refresh token", skip_all)]
+pub async fn generate_refresh_token(
+    email: &Email,
+    family_id: Uuid,
+    amr: &[AuthMethod],
+    client: Option<RefreshTokenClient>,
+    refresh_token_store: RefreshTokenStoreType,
+) -> Result<RefreshToken> {
+    let delta = chrono::Duration::try_seconds(REFRESH_TOKEN_TTL_SECONDS)
+        .wrap_err("failed to create 30 day time delta")?;
+
+    let expires_at = Utc::now()
+        .checked_add_signed(delta)
+        .ok_or(eyre!("failed to add 30 days to current time"))?;
+
+    let token = RefreshToken::default();
 
-    let claims = Claims { sub, exp };
+    let record = RefreshTokenRecord {
+        email: email.clone(),
+        family_id,
+        expires_at,
+        amr: amr.to_vec(),
+        client,
+    };
 
-    create_token(&claims)
+    refresh_token_store
+        .write()
+        .await
+        .add_token(&token, record)
+        .await?;
+
+    Ok(token)
 }
 
-#[tracing::instrument(name = "Validate token", skip_all)]
-pub async fn validate_token(
+/// Opaque tokens are only ever stored as a SHA-256 digest so a leaked table can't be replayed.
+pub fn hash_token(token: &SecretString) -> String {
+    format!("{:x}", Sha256::digest(token.expose_secret().as_bytes()))
+}
+
+/// Validates an access token. With no `audience` the token must be issued for one of the
+/// configured `JWT_AUDIENCES`; otherwise it must be issued for `audience`, which has to be
+/// one of them. A token that belongs to a session is only valid while the session is.
+#[tracing::instrument(name = "Validate JWT", skip_all)]
+pub async fn validate_jwt(
     token: &SecretString,
+    audience: Option<&str>,
     banned_token_store: BannedTokenStoreType,
+    session_store: SessionStoreType,
+    keyring: &Keyring,
 ) -> Result<Claims> {
+    let audiences = expected_audiences(audience)?;
+
     match banned_token_store.read().await.contains_token(token).await {
         Ok(value) => {
             if value {
@@ -70,30 +333,528 @@ pub async fn validate_token(
         Err(e) => return Err(e.into()),
     }
 
-    decode::<Claims>(
-        token.expose_secret(),
-        &DecodingKey::from_secret(JWT_SECRET.expose_secret().as_bytes()),
-        &Validation::default(),
+    let claims =
+        decode_token::<Claims>(token, &audiences, keyring).wrap_err("failed to decode token")?;
+
+    // Set by "log out everywhere": every token the subject held at that moment is banned.
+    // `iat` only has whole seconds, so tokens issued during the cut-off's second are too.
+    if let Some(not_before) = banned_token_store
+        .read()
+        .await
+        .get_tokens_not_before(&claims.sub)
+        .await?
+    {
+        if claims.iat as i64 <= not_before.timestamp() {
+            return Err(eyre!("token was issued before the subject's cut-off"));
+        }
+    }
+
+    if let Some(sid) = &claims.sid {
+        let session_id = Uuid::parse_str(sid).wrap_err("invalid session id")?;
+        if !session_store
+            .read()
+            .await
+            .contains_session(session_id)
+            .await?
+        {
+            return Err(eyre!("session is revoked"));
+        }
+    }
+
+    Ok(claims)
+}
+
+/// Validates the token of an auth cookie. Only the tokens logins put there are accepted:
+/// first-party tokens that belong to a session. An OAuth access token a client copies into
+/// the cookie is turned away, so it can't act as the user's login.
+#[tracing::instrument(name = "Validate session JWT", skip_all)]
+pub async fn validate_session_jwt(
+    token: &SecretString,
+    banned_token_store: BannedTokenStoreType,
+    session_store: SessionStoreType,
+    keyring: &Keyring,
+) -> Result<(Claims, Uuid)> {
+    let claims = validate_jwt(
+        token,
+        Some(first_party_audience()),
+        banned_token_store,
+        session_store,
+        keyring,
     )
-    .map(|data| data.claims)
-    .wrap_err("failed to decode token")
+    .await?;
+
+    if claims.client_id.is_some() {
+        return Err(eyre!("token was issued to an OAuth client"));
+    }
+    // `validate_jwt` has already checked that the session id parses.
+    let session_id = claims
+        .sid
+        .as_deref()
+        .and_then(|sid| Uuid::parse_str(sid).ok())
+        .ok_or_else(|| eyre!("token does not belong to a session"))?;
+
+    Ok((claims, session_id))
 }
 
-#[tracing::instrument(name = "Create token", skip_all)]
-fn create_token(claims: &Claims) -> Result<SecretString> {
-    encode(
-        &jsonwebtoken::Header::default(),
-        &claims,
-        &EncodingKey::from_secret(JWT_SECRET.expose_secret().as_bytes()),
+/// The audience of the tokens in auth cookies, and of OAuth tokens issued without a
+/// `resource`: the first of the configured `JWT_AUDIENCES`.
+pub fn first_party_audience() -> &'static str {
+    &JWT_AUDIENCES[0]
+}
+
+fn expected_audiences(audience: Option<&str>) -> Result<Vec<&str>> {
+    match audience {
+        Some(audience) if JWT_AUDIENCES.iter().any(|known| known == audience) => Ok(vec![audience]),
+        Some(audience) => Err(eyre!("unknown audience: {}", audience)),
+        None => Ok(JWT_AUDIENCES.iter().map(String::as_str).collect()),
+    }
+}
+
+/// Validates a bearer token a service was handed: either an access token, checked as by
+/// `validate_jwt`, or a personal access token, which is good for any configured audience.
+#[tracing::instrument(name = "Validate token", skip_all)]
+pub async fn validate_token(
+    token: &SecretString,
+    audience: Option<&str>,
+    banned_token_store: BannedTokenStoreType,
+    session_store: SessionStoreType,
+    personal_access_token_store: PersonalAccessTokenStoreType,
+    keyring: &Keyring,
+) -> Result<VerifiedToken> {
+    let Ok(personal_access_token) = PersonalAccessToken::parse(token.clone()) else {
+        let claims =
+            validate_jwt(token, audience, banned_token_store, session_store, keyring).await?;
+        return Ok(VerifiedToken {
+            sub: claims.sub,
+            scope: claims.scope,
+        });
+    };
+
+    expected_audiences(audience)?;
+    let record = personal_access_token_store
+        .write()
+        .await
+        .use_token(&personal_access_token)
+        .await?;
+
+    Ok(VerifiedToken {
+        sub: record.email.as_ref().expose_secret().to_owned(),
+        scope: (!record.scopes.is_empty()).then(|| record.scopes.join(" ")),
+    })
+}
+
+/// Who a token accepted by `validate_token` belongs to and what it may be used for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifiedToken {
+    pub sub: String,
+    pub scope: Option<String>,
+}
+
+/// Extracts the user behind the JWT cookie, rejecting the request with
+/// `AuthAPIError::MissingToken` or `AuthAPIError::InvalidToken` otherwise.
+pub struct AuthenticatedUser {
+    pub email: Email,
+    /// How the user logged in to this session.
+    pub amr: Vec<AuthMethod>,
+    pub session_id: Uuid,
+}
+
+impl FromRequestParts<AppState> for AuthenticatedUser {
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let jar = CookieJar::from_headers(&parts.headers);
+
+        let cookie = jar.get(JWT_COOKIE_NAME).ok_or(AuthAPIError::MissingToken)?;
+        let token = SecretString::new(cookie.value().to_owned().into_boxed_str());
+
+        let (claims, session_id) = validate_session_jwt(
+            &token,
+            state.banned_token_store.clone(),
+            state.session_store.clone(),
+            &state.keyring,
+        )
+        .await
+        .map_err(|_| AuthAPIError::InvalidToken)?;
+
+        let email = Email::parse(SecretString::new(claims.sub.into_boxed_str()))
+            .map_err(|_| AuthAPIError::InvalidToken)?;
+
+        Ok(Self {
+            email,
+            amr: claims.amr,
+            session_id,
+        })
+    }
+}
+
+/// Where a request came from, as recorded for new sessions.
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+// Enough for any real browser's user agent; longer values are cut off.
+const MAX_USER_AGENT_LENGTH: usize = 512;
+
+impl<S: Send + Sync> FromRequestParts<S> for ClientInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(address)| address.ip().to_string());
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(MAX_USER_AGENT_LENGTH).collect());
+
+        Ok(Self { ip, user_agent })
+    }
+}
+
+/// Guards admin routes. Callers must send `Authorization: Bearer <ADMIN_API_TOKEN>`; when
+/// no admin token is configured every request is rejected with
+/// `AuthAPIError::InvalidToken`.
+pub struct AdminCaller;
+
+impl FromRequestParts<AppState> for AdminCaller {
+    type Rejection = AuthAPIError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let token = bearer_token(&parts.headers).ok_or(AuthAPIError::MissingToken)?;
+
+        let admin_token = ADMIN_API_TOKEN.as_ref().ok_or(AuthAPIError::InvalidToken)?;
+
+        if bool::from(
+            token
+                .expose_secret()
+                .as_bytes()
+                .ct_eq(admin_token.expose_secret().as_bytes()),
+        ) {
+            Ok(Self)
+        } else {
+            Err(AuthAPIError::InvalidToken)
+        }
+    }
+}
+
+/// Reads a bearer token (RFC 6750 section 2.1) from the `Authorization` header.
+pub fn bearer_token(headers: &HeaderMap) -> Option<SecretString> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|value| SecretString::new(value.to_owned().into_boxed_str()))
+}
+
+/// Returns the user name and password of an `Authorization: Basic` header.
+pub fn basic_credentials(headers: &HeaderMap) -> Option<(String, SecretString)> {
+    let credentials = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "))
+        .and_then(|value| STANDARD.decode(value).ok())
+        .and_then(|value| String::from_utf8(value).ok())?;
+
+    let (user, password) = credentials.split_once(':')?;
+
+    Some((
+        user.to_owned(),
+        SecretString::new(password.to_owned().into_boxed_str()),
+    ))
+}
+
+/// Authenticates an OAuth client from its HTTP Basic credentials (RFC 6749 section 2.3.1),
+/// rejecting the request with `OAuthError::InvalidClient` otherwise.
+pub struct AuthenticatedClient {
+    pub client: OAuthClient,
+}
+
+impl FromRequestParts<AppState> for AuthenticatedClient {
+    type Rejection = OAuthError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let (client_id, client_secret) =
+            basic_credentials(&parts.headers).ok_or(OAuthError::InvalidClient)?;
+
+        match state
+            .oauth_client_store
+            .read()
+            .await
+            .validate_client(&client_id, &client_secret)
+            .await
+        {
+            Ok(client) => Ok(Self { client }),
+            Err(OAuthClientStoreError::ClientNotFound)
+            | Err(OAuthClientStoreError::InvalidCredentials) => Err(OAuthError::InvalidClient),
+            Err(e) => Err(OAuthError::UnexpectedError(e.into())),
+        }
+    }
+}
+
+/// Single-purpose tokens that are emailed to users (e.g. password reset links).
+/// The purpose travels in the `aud` claim, so `validate_jwt` never accepts one of these
+/// as an access token and one purpose can't be swapped for another.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenPurpose {
+    PasswordReset,
+    EmailVerification,
+    MagicLink,
+    AccountUnlock,
+}
+
+impl TokenPurpose {
+    fn audience(&self) -> &'static str {
+        match self {
+            TokenPurpose::PasswordReset => "password-reset",
+            TokenPurpose::EmailVerification => "email-verification",
+            TokenPurpose::MagicLink => "magic-link",
+            TokenPurpose::AccountUnlock => "account-unlock",
+        }
+    }
+
+    fn ttl_seconds(&self) -> i64 {
+        match self {
+            // Used reset tokens are banned to make them single-use, and banned tokens are
+            // only remembered for TOKEN_TTL_SECONDS, so a reset token must not outlive that.
+            TokenPurpose::PasswordReset => TOKEN_TTL_SECONDS,
+            // Verifying twice is harmless, so these don't need banning and can live longer.
+            TokenPurpose::EmailVerification => MAX_TOKEN_TTL_SECONDS,
+            // Magic links are made single-use by their login attempt, which Redis keeps for
+            // the same 10 minutes.
+            TokenPurpose::MagicLink => TOKEN_TTL_SECONDS,
+            // Unlocking twice is harmless too, and a link should last as long as the
+            // longest lockout.
+            TokenPurpose::AccountUnlock => MAX_TOKEN_TTL_SECONDS,
+        }
+    }
+}
+
+#[tracing::instrument(name = "Generate purpose token", skip_all)]
+pub fn generate_purpose_token(
+    email: &Email,
+    purpose: TokenPurpose,
+    keyring: &Keyring,
+) -> Result<SecretString> {
+    let jti = Uuid::new_v4().to_string();
+    create_token(&purpose_claims(email, purpose, jti)?, keyring)
+}
+
+/// Magic link tokens carry the login attempt they were issued for in `jti`, so redeeming
+/// one can be tied to a single pending login.
+#[tracing::instrument(name = "Generate magic link token", skip_all)]
+pub fn generate_magic_link_token(
+    email: &Email,
+    login_attempt_id: &LoginAttemptId,
+    keyring: &Keyring,
+) -> Result<SecretString> {
+    let jti = login_attempt_id.as_ref().expose_secret().to_owned();
+    create_token(
+        &purpose_claims(email, TokenPurpose::MagicLink, jti)?,
+        keyring,
     )
-    .map(|value: String| SecretString::new(value.into_boxed_str()))
-    .wrap_err("failed to create token")
+}
+
+fn purpose_claims(email: &Email, purpose: TokenPurpose, jti: String) -> Result<PurposeClaims> {
+    let (iat, exp) = token_lifetime(purpose.ttl_seconds())?;
+
+    Ok(PurposeClaims {
+        iss: JWT_ISSUER.clone(),
+        sub: email.as_ref().expose_secret().to_owned(),
+        aud: purpose.audience().to_owned(),
+        exp,
+        nbf: iat,
+        iat,
+        jti,
+    })
+}
+
+#[tracing::instrument(name = "Validate purpose token", skip_all)]
+pub async fn validate_purpose_token(
+    token: &SecretString,
+    purpose: TokenPurpose,
+    banned_token_store: BannedTokenStoreType,
+    keyring: &Keyring,
+) -> Result<PurposeClaims> {
+    if banned_token_store
+        .read()
+        .await
+        .contains_token(token)
+        .await?
+    {
+        return Err(eyre!("token is banned"));
+    }
+
+    let claims = decode_token::<PurposeClaims>(token, &[purpose.audience()], keyring)
+        .wrap_err("failed to decode purpose token")?;
+
+    // Set by `ban_purpose_tokens`; `iat` only has whole seconds, as for access tokens.
+    if let Some(not_before) = banned_token_store
+        .read()
+        .await
+        .get_tokens_not_before(&purpose_subject(purpose, &claims.sub))
+        .await?
+    {
+        if claims.iat as i64 <= not_before.timestamp() {
+            return Err(eyre!("token was issued before the subject's cut-off"));
+        }
+    }
+
+    Ok(claims)
+}
+
+/// Bans every `purpose` token issued to `email` so far, e.g. the other reset links once the
+/// password has been reset. The cut-off is kept apart from the one for access tokens, so
+/// logging out everywhere leaves emailed links alone.
+#[tracing::instrument(name = "Ban purpose tokens", skip_all)]
+pub async fn ban_purpose_tokens(
+    email: &Email,
+    purpose: TokenPurpose,
+    banned_token_store: BannedTokenStoreType,
+) -> Result<()> {
+    banned_token_store
+        .write()
+        .await
+        .ban_tokens_before(
+            &purpose_subject(purpose, email.as_ref().expose_secret()),
+            Utc::now(),
+        )
+        .await?;
+
+    Ok(())
+}
+
+fn purpose_subject(purpose: TokenPurpose, subject: &str) -> String {
+    format!("{}:{}", purpose.audience(), subject)
+}
+
+#[tracing::instrument(name = "Create token", skip_all)]
+fn create_token<T: Serialize>(claims: &T, keyring: &Keyring) -> Result<SecretString> {
+    let key = keyring.signing_key();
+
+    let mut header = Header::new(key.algorithm());
+    header.kid = Some(key.kid().to_owned());
+
+    encode(&header, &claims, key.encoding_key())
+        .map(|value: String| SecretString::new(value.into_boxed_str()))
+        .wrap_err("failed to create token")
+}
+
+/// Checks the signature against the key named by the token's `kid`, which may be a retired
+/// key, then the registered claims: `iss` must be ours, `aud` must name one of `audiences`,
+/// and the token must be inside its `nbf`..`exp` window and not issued in the future.
+fn decode_token<T: TokenClaims>(
+    token: &SecretString,
+    audiences: &[&str],
+    keyring: &Keyring,
+) -> Result<T> {
+    let header = decode_header(token.expose_secret()).wrap_err("malformed token header")?;
+    let key = header
+        .kid
+        .and_then(|kid| keyring.verification_key(&kid))
+        .ok_or(eyre!("token was not signed by a known key"))?;
+
+    let mut validation = Validation::new(key.algorithm());
+    validation.set_issuer(&[JWT_ISSUER.as_str()]);
+    validation.set_audience(audiences);
+    validation.set_required_spec_claims(&["exp", "nbf", "iss", "aud", "sub"]);
+    validation.validate_nbf = true;
+
+    let data = decode::<T>(token.expose_secret(), key.decoding_key(), &validation)?;
+
+    let latest_iat = Utc::now().timestamp() as u64 + validation.leeway;
+    if data.claims.issued_at() as u64 > latest_iat {
+        return Err(eyre!("token was issued in the future"));
+    }
+
+    Ok(data.claims)
+}
+
+/// Claims types `decode_token` can check beyond what `jsonwebtoken` validates itself.
+trait TokenClaims: DeserializeOwned {
+    fn issued_at(&self) -> usize;
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
+    pub iss: String,
     pub sub: String,
+    pub aud: Vec<String>,
     pub exp: usize,
+    pub nbf: usize,
+    pub iat: usize,
+    pub jti: String,
+    #[serde(default)]
+    pub amr: Vec<AuthMethod>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+    /// The OAuth client the token was issued to (RFC 9068), if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    /// The login session an auth cookie token belongs to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sid: Option<String>,
+}
+
+impl Claims {
+    /// Whether the token was issued to a client for itself (client_credentials) rather than
+    /// for a user. As in RFC 9068 its subject is then the client's own id, which never
+    /// parses as the email address a user's subject is.
+    pub fn is_client_token(&self) -> bool {
+        self.client_id.as_deref() == Some(self.sub.as_str())
+    }
+}
+
+impl TokenClaims for Claims {
+    fn issued_at(&self) -> usize {
+        self.iat
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PurposeClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: String,
+    pub exp: usize,
+    pub nbf: usize,
+    pub iat: usize,
+    pub jti: String,
+}
+
+impl TokenClaims for PurposeClaims {
+    fn issued_at(&self) -> usize {
+        self.iat
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub aud: String,
+    pub exp: usize,
+    pub iat: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(default)]
+    pub amr: Vec<AuthMethod>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email_verified: Option<bool>,
 }
 
 #[cfg(test)]
@@ -101,17 +862,57 @@ mod tests {
     use std::sync::Arc;
     use tokio::sync::RwLock;
 
-    use crate::services::hashset_banned_token_store::HashsetBannedTokenStore;
+    use crate::{
+        domain::BannedTokenStore,
+        services::{
+            hashmap_session_store::HashmapSessionStore,
+            hashset_banned_token_store::HashsetBannedTokenStore,
+        },
+        utils::signing_key::SigningKey,
+    };
 
     use super::*;
 
+    fn test_keyring() -> Keyring {
+        Keyring::new(SigningKey::generate().unwrap())
+    }
+
+    fn empty_session_store() -> SessionStoreType {
+        Arc::new(RwLock::new(HashmapSessionStore::default()))
+    }
+
+    /// Returns a store holding one session of `email`, and the session's id.
+    async fn test_session(email: &Email) -> (SessionStoreType, Uuid) {
+        let session_store = empty_session_store();
+        let session = Session {
+            id: Uuid::new_v4(),
+            email: email.clone(),
+            created_at: Utc::now(),
+            last_seen_at: Utc::now(),
+            ip: None,
+            user_agent: None,
+            amr: vec![AuthMethod::Password],
+        };
+        let session_id = session.id;
+        session_store
+            .write()
+            .await
+            .add_session(session)
+            .await
+            .unwrap();
+        (session_store, session_id)
+    }
+
     #[tokio::test]
     async fn test_generate_auth_cookie() {
+        let keyring = test_keyring();
         let email = Email::parse(SecretString::new(
             "test@example.com".to_owned().into_boxed_str(),
         ))
         .unwrap();
-        let cookie = generate_auth_cookie(&email).unwrap();
+        let cookie =
+            generate_auth_cookie(&email, &[AuthMethod::Password], Uuid::new_v4(), &keyring)
+                .unwrap();
         assert_eq!(cookie.name(), JWT_COOKIE_NAME);
         assert_eq!(cookie.value().split('.').count(), 3);
         assert_eq!(cookie.path(), Some("/"));
@@ -130,26 +931,54 @@ mod tests {
         assert_eq!(cookie.same_site(), Some(SameSite::Lax));
     }
 
+    #[tokio::test]
+    async fn test_create_refresh_cookie() {
+        let token = RefreshToken::default();
+        let cookie = create_refresh_cookie(token.clone());
+        assert_eq!(cookie.name(), REFRESH_COOKIE_NAME);
+        assert_eq!(cookie.value(), token.as_ref().expose_secret());
+        assert_eq!(cookie.path(), Some("/"));
+        assert_eq!(cookie.http_only(), Some(true));
+        assert_eq!(cookie.same_site(), Some(SameSite::Lax));
+    }
+
+    #[test]
+    fn test_hash_token_is_deterministic_and_hides_token() {
+        let token = SecretString::new("some token value".to_owned().into_boxed_str());
+        let hash = hash_token(&token);
+        assert_eq!(hash, hash_token(&token));
+        assert_eq!(hash.len(), 64);
+        assert!(!hash.contains(token.expose_secret()));
+    }
+
     #[tokio::test]
     async fn test_generate_auth_token() {
+        let keyring = test_keyring();
         let email = Email::parse(SecretString::new(
             "test@example.com".to_owned().into_boxed_str(),
         ))
         .unwrap();
-        let result = generate_auth_token(&email).unwrap();
+        let result =
+            generate_auth_token(&email, &[AuthMethod::Password], Uuid::new_v4(), &keyring).unwrap();
         assert_eq!(result.expose_secret().split('.').count(), 3);
     }
 
     #[tokio::test]
-    async fn test_validate_token_with_valid_token() {
+    async fn test_validate_jwt_with_valid_token() {
+        let keyring = test_keyring();
         let email = Email::parse(SecretString::new(
             "test@example.com".to_owned().into_boxed_str(),
         ))
         .unwrap();
-        let token = generate_auth_token(&email).unwrap();
+        let (session_store, session_id) = test_session(&email).await;
+        let token =
+            generate_auth_token(&email, &[AuthMethod::Password], session_id, &keyring).unwrap();
         let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
-        let result = validate_token(&token, banned_token_store).await.unwrap();
+        let result = validate_jwt(&token, None, banned_token_store, session_store, &keyring)
+            .await
+            .unwrap();
         assert_eq!(result.sub, "test@example.com");
+        assert_eq!(result.sid, Some(session_id.to_string()));
 
         let exp = Utc::now()
             .checked_add_signed(chrono::Duration::try_minutes(9).expect("valid duration"))
@@ -160,10 +989,361 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_validate_token_with_invalid_token() {
+    async fn test_validate_purpose_token_with_valid_token() {
+        let keyring = test_keyring();
+        let email = Email::parse(SecretString::new(
+            "test@example.com".to_owned().into_boxed_str(),
+        ))
+        .unwrap();
+        let token = generate_purpose_token(&email, TokenPurpose::PasswordReset, &keyring).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let result = validate_purpose_token(
+            &token,
+            TokenPurpose::PasswordReset,
+            banned_token_store,
+            &keyring,
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.sub, "test@example.com");
+    }
+
+    #[tokio::test]
+    async fn test_purpose_token_is_not_an_access_token() {
+        let keyring = test_keyring();
+        let email = Email::parse(SecretString::new(
+            "test@example.com".to_owned().into_boxed_str(),
+        ))
+        .unwrap();
+        let token = generate_purpose_token(&email, TokenPurpose::PasswordReset, &keyring).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        assert!(validate_jwt(
+            &token,
+            None,
+            banned_token_store,
+            empty_session_store(),
+            &keyring
+        )
+        .await
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_access_token_is_not_a_purpose_token() {
+        let keyring = test_keyring();
+        let email = Email::parse(SecretString::new(
+            "test@example.com".to_owned().into_boxed_str(),
+        ))
+        .unwrap();
+        let token =
+            generate_auth_token(&email, &[AuthMethod::Password], Uuid::new_v4(), &keyring).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let result = validate_purpose_token(
+            &token,
+            TokenPurpose::PasswordReset,
+            banned_token_store,
+            &keyring,
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_purpose_tokens_are_not_interchangeable() {
+        let keyring = test_keyring();
+        let email = Email::parse(SecretString::new(
+            "test@example.com".to_owned().into_boxed_str(),
+        ))
+        .unwrap();
+        let token = generate_purpose_token(&email, TokenPurpose::PasswordReset, &keyring).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let result = validate_purpose_token(
+            &token,
+            TokenPurpose::EmailVerification,
+            banned_token_store,
+            &keyring,
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_magic_link_token_carries_login_attempt_id() {
+        let keyring = test_keyring();
+        let email = Email::parse(SecretString::new(
+            "test@example.com".to_owned().into_boxed_str(),
+        ))
+        .unwrap();
+        let login_attempt_id = LoginAttemptId::default();
+        let token = generate_magic_link_token(&email, &login_attempt_id, &keyring).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let result = validate_purpose_token(
+            &token,
+            TokenPurpose::MagicLink,
+            banned_token_store,
+            &keyring,
+        )
+        .await
+        .unwrap();
+        assert_eq!(result.sub, "test@example.com");
+        assert_eq!(result.jti, login_attempt_id.as_ref().expose_secret());
+    }
+
+    fn test_claims() -> Claims {
+        let now = Utc::now().timestamp() as usize;
+        Claims {
+            iss: JWT_ISSUER.clone(),
+            sub: "test@example.com".to_owned(),
+            aud: JWT_AUDIENCES.clone(),
+            exp: now + 600,
+            nbf: now,
+            iat: now,
+            jti: Uuid::new_v4().to_string(),
+            amr: vec![AuthMethod::Password],
+            scope: None,
+            client_id: None,
+            sid: None,
+        }
+    }
+
+    async fn validate_claims(claims: &Claims, audience: Option<&str>) -> Result<Claims> {
+        let keyring = test_keyring();
+        let token = create_token(claims, &keyring).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        validate_jwt(
+            &token,
+            audience,
+            banned_token_store,
+            empty_session_store(),
+            &keyring,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_access_token_carries_registered_claims() {
+        let keyring = test_keyring();
+        let email = Email::parse(SecretString::new(
+            "test@example.com".to_owned().into_boxed_str(),
+        ))
+        .unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let (session_store, session_id) = test_session(&email).await;
+        let first =
+            generate_auth_token(&email, &[AuthMethod::Password], session_id, &keyring).unwrap();
+        let second =
+            generate_auth_token(&email, &[AuthMethod::Password], session_id, &keyring).unwrap();
+        let first = validate_jwt(
+            &first,
+            None,
+            banned_token_store.clone(),
+            session_store.clone(),
+            &keyring,
+        )
+        .await
+        .unwrap();
+        let second = validate_jwt(&second, None, banned_token_store, session_store, &keyring)
+            .await
+            .unwrap();
+        assert_eq!(first.iss, *JWT_ISSUER);
+        assert_eq!(first.aud, vec![first_party_audience().to_owned()]);
+        assert_eq!(first.nbf, first.iat);
+        assert_eq!(first.exp, first.iat + TOKEN_TTL_SECONDS as usize);
+        assert_eq!(first.amr, vec![AuthMethod::Password]);
+        assert_ne!(first.jti, second.jti);
+    }
+
+    #[tokio::test]
+    async fn test_validate_jwt_checks_audience() {
+        let audience = JWT_AUDIENCES[0].clone();
+        assert!(validate_claims(&test_claims(), Some(&audience))
+            .await
+            .is_ok());
+        assert!(validate_claims(&test_claims(), Some("unknown-service"))
+            .await
+            .is_err());
+
+        let mut claims = test_claims();
+        claims.aud = vec!["other-service".to_owned()];
+        assert!(validate_claims(&claims, None).await.is_err());
+    }
+
+    #[test]
+    fn test_access_token_is_only_valid_for_its_audience() {
+        let keyring = test_keyring();
+        let email = Email::parse(SecretString::new(
+            "test@example.com".to_owned().into_boxed_str(),
+        ))
+        .unwrap();
+        let token = generate_access_token(
+            &email,
+            &[AuthMethod::Password],
+            "openid",
+            "client",
+            "service-a",
+            &keyring,
+        )
+        .unwrap();
+
+        let claims = decode_token::<Claims>(&token, &["service-a"], &keyring).unwrap();
+        assert_eq!(claims.aud, vec!["service-a".to_owned()]);
+        assert!(decode_token::<Claims>(&token, &["service-b"], &keyring).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_jwt_rejects_foreign_issuer() {
+        let mut claims = test_claims();
+        claims.iss = "https://issuer.example.com".to_owned();
+        assert!(validate_claims(&claims, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_jwt_rejects_token_not_yet_valid() {
+        let mut claims = test_claims();
+        claims.nbf += 3600;
+        assert!(validate_claims(&claims, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_jwt_rejects_token_issued_in_future() {
+        let mut claims = test_claims();
+        claims.iat += 3600;
+        assert!(validate_claims(&claims, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_jwt_with_invalid_token() {
+        let keyring = test_keyring();
         let token = SecretString::new("invalid token".to_owned().into_boxed_str());
         let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
-        let result = validate_token(&token, banned_token_store).await;
+        let result = validate_jwt(
+            &token,
+            None,
+            banned_token_store,
+            empty_session_store(),
+            &keyring,
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_jwt_signed_by_unknown_key() {
+        let email = Email::parse(SecretString::new(
+            "test@example.com".to_owned().into_boxed_str(),
+        ))
+        .unwrap();
+        let (session_store, session_id) = test_session(&email).await;
+        let token =
+            generate_auth_token(&email, &[AuthMethod::Password], session_id, &test_keyring())
+                .unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let result = validate_jwt(
+            &token,
+            None,
+            banned_token_store,
+            session_store,
+            &test_keyring(),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_validate_jwt_rejects_revoked_session() {
+        let keyring = test_keyring();
+        let email = Email::parse(SecretString::new(
+            "test@example.com".to_owned().into_boxed_str(),
+        ))
+        .unwrap();
+        let (session_store, session_id) = test_session(&email).await;
+        let token =
+            generate_auth_token(&email, &[AuthMethod::Password], session_id, &keyring).unwrap();
+        session_store
+            .write()
+            .await
+            .revoke_session(&email, session_id)
+            .await
+            .unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        let result = validate_jwt(&token, None, banned_token_store, session_store, &keyring).await;
         assert!(result.is_err());
     }
+
+    #[tokio::test]
+    async fn test_validate_session_jwt_only_accepts_session_tokens() {
+        let keyring = test_keyring();
+        let email = Email::parse(SecretString::new(
+            "test@example.com".to_owned().into_boxed_str(),
+        ))
+        .unwrap();
+        let (session_store, session_id) = test_session(&email).await;
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+
+        let token =
+            generate_auth_token(&email, &[AuthMethod::Password], session_id, &keyring).unwrap();
+        let (_, sid) = validate_session_jwt(
+            &token,
+            banned_token_store.clone(),
+            session_store.clone(),
+            &keyring,
+        )
+        .await
+        .unwrap();
+        assert_eq!(sid, session_id);
+
+        let token = generate_access_token(
+            &email,
+            &[AuthMethod::Password],
+            "openid",
+            "client",
+            first_party_audience(),
+            &keyring,
+        )
+        .unwrap();
+        assert!(
+            validate_session_jwt(&token, banned_token_store, session_store, &keyring)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_validate_jwt_rejects_token_issued_before_cut_off() {
+        let keyring = test_keyring();
+        let email = Email::parse(SecretString::new(
+            "test@example.com".to_owned().into_boxed_str(),
+        ))
+        .unwrap();
+        let (session_store, session_id) = test_session(&email).await;
+        let token =
+            generate_auth_token(&email, &[AuthMethod::Password], session_id, &keyring).unwrap();
+        let banned_token_store = Arc::new(RwLock::new(HashsetBannedTokenStore::default()));
+        banned_token_store
+            .write()
+            .await
+            .ban_tokens_before("test@example.com", Utc::now())
+            .await
+            .unwrap();
+        let result = validate_jwt(
+            &token,
+            None,
+            banned_token_store.clone(),
+            session_store.clone(),
+            &keyring,
+        )
+        .await;
+        assert!(result.is_err());
+
+        // The cut-off only applies to its own subject.
+        let other = Email::parse(SecretString::new(
+            "other@example.com".to_owned().into_boxed_str(),
+        ))
+        .unwrap();
+        let (session_store, session_id) = test_session(&other).await;
+        let token =
+            generate_auth_token(&other, &[AuthMethod::Password], session_id, &keyring).unwrap();
+        let result = validate_jwt(&token, None, banned_token_store, session_store, &keyring).await;
+        assert!(result.is_ok());
+    }
 }
@@ -5,19 +5,112 @@ use std::env as std_env;
 
 // Define a lazily evaluated static. lazy_static is needed because std_env::var is not a const function.
 lazy_static! {
+    pub static ref ADMIN_API_TOKEN: Option<SecretString> = set_admin_api_token();
+    pub static ref AUTH_SERVICE_URL: String = set_auth_service_url();
     pub static ref DATABASE_URL: SecretString = set_db_url();
-    pub static ref JWT_SECRET: SecretString = set_token();
+    pub static ref JWT_AUDIENCES: Vec<String> = set_jwt_audiences();
+    pub static ref JWT_ISSUER: String = set_jwt_issuer();
+    pub static ref JWT_KEY_ENCRYPTION_KEY: SecretString = set_jwt_key_encryption_key();
+    pub static ref JWT_SIGNING_KEY_PATH: String = set_jwt_signing_key_path();
+    pub static ref LOGIN_FAILURE_WINDOW_SECONDS: u64 = positive_env_var(
+        env::LOGIN_FAILURE_WINDOW_SECONDS_ENV_VAR,
+        DEFAULT_LOGIN_FAILURE_WINDOW_SECONDS
+    );
+    pub static ref LOGIN_IP_FAILURE_LIMIT: u64 = positive_env_var(
+        env::LOGIN_IP_FAILURE_LIMIT_ENV_VAR,
+        DEFAULT_LOGIN_IP_FAILURE_LIMIT
+    );
+    pub static ref LOGIN_LOCKOUT_SECONDS: u64 = positive_env_var(
+        env::LOGIN_LOCKOUT_SECONDS_ENV_VAR,
+        DEFAULT_LOGIN_LOCKOUT_SECONDS
+    );
+    pub static ref LOGIN_LOCKOUT_THRESHOLD: u64 = positive_env_var(
+        env::LOGIN_LOCKOUT_THRESHOLD_ENV_VAR,
+        DEFAULT_LOGIN_LOCKOUT_THRESHOLD
+    );
     pub static ref POSTMARK_AUTH_TOKEN: SecretString = set_postmark_auth_token();
     pub static ref REDIS_HOST_NAME: String = set_redis_host();
+    pub static ref TOTP_DRIFT_STEPS: u8 = set_totp_drift_steps();
+    pub static ref TOTP_ENCRYPTION_KEY: SecretString = set_totp_encryption_key();
+    pub static ref UPSTREAM_OIDC_CLIENT_ID: Option<String> =
+        optional_env_var(env::UPSTREAM_OIDC_CLIENT_ID_ENV_VAR);
+    pub static ref UPSTREAM_OIDC_CLIENT_SECRET: Option<SecretString> =
+        optional_env_var(env::UPSTREAM_OIDC_CLIENT_SECRET_ENV_VAR)
+            .map(|secret| SecretString::new(secret.into_boxed_str()));
+    pub static ref UPSTREAM_OIDC_ISSUER: Option<String> =
+        optional_env_var(env::UPSTREAM_OIDC_ISSUER_ENV_VAR);
 }
 
-fn set_token() -> SecretString {
+fn set_jwt_signing_key_path() -> String {
     dotenv().ok(); // Load environment variables
-    let secret = std_env::var(env::JWT_SECRET_ENV_VAR).expect("JWT_SECRET must be set.");
-    if secret.is_empty() {
-        panic!("JWT_SECRET must not be empty.");
+    let path =
+        std_env::var(env::JWT_SIGNING_KEY_PATH_ENV_VAR).expect("JWT_SIGNING_KEY_PATH must be set.");
+    if path.is_empty() {
+        panic!("JWT_SIGNING_KEY_PATH must not be empty.");
+    }
+    path
+}
+
+fn set_jwt_key_encryption_key() -> SecretString {
+    dotenv().ok();
+    let key = std_env::var(env::JWT_KEY_ENCRYPTION_KEY_ENV_VAR)
+        .expect("JWT_KEY_ENCRYPTION_KEY must be set.");
+    if key.is_empty() {
+        panic!("JWT_KEY_ENCRYPTION_KEY must not be empty.");
+    }
+    SecretString::new(key.into_boxed_str())
+}
+
+// Tokens are issued by, and only accepted from, this issuer.
+fn set_jwt_issuer() -> String {
+    dotenv().ok();
+    std_env::var(env::JWT_ISSUER_ENV_VAR)
+        .ok()
+        .filter(|issuer| !issuer.is_empty())
+        .unwrap_or_else(|| AUTH_SERVICE_URL.clone())
+}
+
+// The client services access tokens are issued for, as a comma-separated list. The first
+// is the first-party app that auth cookies are for.
+fn set_jwt_audiences() -> Vec<String> {
+    dotenv().ok();
+    let audiences: Vec<String> = std_env::var(env::JWT_AUDIENCES_ENV_VAR)
+        .unwrap_or(DEFAULT_JWT_AUDIENCE.to_owned())
+        .split(',')
+        .map(|audience| audience.trim().to_owned())
+        .filter(|audience| !audience.is_empty())
+        .collect();
+    if audiences.is_empty() {
+        panic!("JWT_AUDIENCES must name at least one audience.");
+    }
+    audiences
+}
+
+// Admin routes are disabled unless a token is configured.
+fn set_admin_api_token() -> Option<SecretString> {
+    dotenv().ok();
+    std_env::var(env::ADMIN_API_TOKEN_ENV_VAR)
+        .ok()
+        .filter(|token| !token.is_empty())
+        .map(|token| SecretString::new(token.into_boxed_str()))
+}
+
+// Unset and empty variables both count as not configured.
+fn optional_env_var(name: &str) -> Option<String> {
+    dotenv().ok();
+    std_env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+fn positive_env_var(name: &str, default: u64) -> u64 {
+    dotenv().ok();
+    match std_env::var(name) {
+        Ok(value) => value
+            .parse()
+            .ok()
+            .filter(|value| *value > 0)
+            .unwrap_or_else(|| panic!("{} must be a positive integer.", name)),
+        Err(_) => default,
     }
-    SecretString::new(secret.into_boxed_str())
 }
 
 fn set_db_url() -> SecretString {
@@ -34,6 +127,17 @@ fn set_redis_host() -> String {
     std_env::var(env::REDIS_HOST_NAME_ENV_VAR).unwrap_or(DEFAULT_REDIS_HOSTNAME.to_owned())
 }
 
+// The public URL emailed links, the default issuer and the passkey relying party are built
+// from. There is no sensible default: a wrong one only shows up as broken links in production.
+fn set_auth_service_url() -> String {
+    dotenv().ok();
+    let url = std_env::var(env::AUTH_SERVICE_URL_ENV_VAR).expect("AUTH_SERVICE_URL must be set.");
+    if url.is_empty() {
+        panic!("AUTH_SERVICE_URL must not be empty.");
+    }
+    url
+}
+
 fn set_postmark_auth_token() -> SecretString {
     dotenv().ok();
     SecretString::new(
@@ -43,18 +147,83 @@ fn set_postmark_auth_token() -> SecretString {
     )
 }
 
+fn set_totp_encryption_key() -> SecretString {
+    dotenv().ok();
+    let key =
+        std_env::var(env::TOTP_ENCRYPTION_KEY_ENV_VAR).expect("TOTP_ENCRYPTION_KEY must be set.");
+    if key.is_empty() {
+        panic!("TOTP_ENCRYPTION_KEY must not be empty.");
+    }
+    SecretString::new(key.into_boxed_str())
+}
+
+fn set_totp_drift_steps() -> u8 {
+    dotenv().ok();
+    std_env::var(env::TOTP_DRIFT_STEPS_ENV_VAR)
+        .map(|steps| {
+            steps
+                .parse()
+                .expect("TOTP_DRIFT_STEPS must be a small non-negative integer.")
+        })
+        .unwrap_or(DEFAULT_TOTP_DRIFT_STEPS)
+}
+
 pub mod env {
+    pub const ADMIN_API_TOKEN_ENV_VAR: &str = "ADMIN_API_TOKEN";
+    pub const AUTH_SERVICE_URL_ENV_VAR: &str = "AUTH_SERVICE_URL";
     pub const DATABASE_URL_ENV_VAR: &str = "DATABASE_URL";
-    pub const JWT_SECRET_ENV_VAR: &str = "JWT_SECRET";
+    pub const JWT_AUDIENCES_ENV_VAR: &str = "JWT_AUDIENCES";
+    pub const JWT_ISSUER_ENV_VAR: &str = "JWT_ISSUER";
+    pub const JWT_KEY_ENCRYPTION_KEY_ENV_VAR: &str = "JWT_KEY_ENCRYPTION_KEY";
+    pub const JWT_SIGNING_KEY_PATH_ENV_VAR: &str = "JWT_SIGNING_KEY_PATH";
+    pub const LOGIN_FAILURE_WINDOW_SECONDS_ENV_VAR: &str = "LOGIN_FAILURE_WINDOW_SECONDS";
+    pub const LOGIN_IP_FAILURE_LIMIT_ENV_VAR: &str = "LOGIN_IP_FAILURE_LIMIT";
+    pub const LOGIN_LOCKOUT_SECONDS_ENV_VAR: &str = "LOGIN_LOCKOUT_SECONDS";
+    pub const LOGIN_LOCKOUT_THRESHOLD_ENV_VAR: &str = "LOGIN_LOCKOUT_THRESHOLD";
     pub const POSTMARK_AUTH_TOKEN_ENV_VAR: &str = "POSTMARK_AUTH_TOKEN";
     pub const REDIS_HOST_NAME_ENV_VAR: &str = "REDIS_HOST_NAME";
+    pub const TOTP_DRIFT_STEPS_ENV_VAR: &str = "TOTP_DRIFT_STEPS";
+    pub const TOTP_ENCRYPTION_KEY_ENV_VAR: &str = "TOTP_ENCRYPTION_KEY";
+    pub const UPSTREAM_OIDC_CLIENT_ID_ENV_VAR: &str = "UPSTREAM_OIDC_CLIENT_ID";
+    pub const UPSTREAM_OIDC_CLIENT_SECRET_ENV_VAR: &str = "UPSTREAM_OIDC_CLIENT_SECRET";
+    pub const UPSTREAM_OIDC_ISSUER_ENV_VAR: &str = "UPSTREAM_OIDC_ISSUER";
 }
 
 pub const DEFAULT_REDIS_HOSTNAME: &str = "127.0.0.1";
+pub const DEFAULT_JWT_AUDIENCE: &str = "app-service";
 pub const JWT_COOKIE_NAME: &str = "jwt";
+pub const REFRESH_COOKIE_NAME: &str = "refresh_token";
+pub const FEDERATED_LOGIN_COOKIE_NAME: &str = "federated_login";
+pub const DEFAULT_TOTP_DRIFT_STEPS: u8 = 1;
+pub const TOTP_ISSUER: &str = "Auth Service";
+pub const WEBAUTHN_RP_NAME: &str = "Auth Service";
+// RFC 8628 section 3.2: how long a device code is valid for and how often the device may
+// poll `/token` with it.
+pub const DEVICE_CODE_TTL_SECONDS: u64 = 600; // 10 minutes
+pub const DEVICE_CODE_POLL_INTERVAL_SECONDS: u64 = 5;
+// Failed logins are counted over a sliding window. An account is locked once it reaches the
+// threshold, and an address is turned away once it reaches its limit, which is set high
+// enough for many users behind one NAT.
+pub const DEFAULT_LOGIN_FAILURE_WINDOW_SECONDS: u64 = 900; // 15 minutes
+pub const DEFAULT_LOGIN_LOCKOUT_THRESHOLD: u64 = 5;
+pub const DEFAULT_LOGIN_IP_FAILURE_LIMIT: u64 = 100;
+// The first lockout of an account lasts this long, and each further one within a day
+// twice as long as the one before, up to a day.
+pub const DEFAULT_LOGIN_LOCKOUT_SECONDS: u64 = 900; // 15 minutes
+pub const MAX_LOGIN_LOCKOUT_SECONDS: u64 = 86_400; // 1 day
+
+// How long verifiers may cache `/.well-known/jwks.json`. A rotated in key is published for
+// long enough that every instance has reloaded it and every cached JWKS lists it before it
+// signs anything.
+pub const JWKS_MAX_AGE_SECONDS: u64 = 300; // 5 minutes
+pub const SIGNING_KEY_ACTIVATION_DELAY_SECONDS: u64 =
+    prod::KEYRING_REFRESH_INTERVAL.as_secs() + JWKS_MAX_AGE_SECONDS;
 
 pub mod prod {
+    use std::time::Duration;
+
     pub const APP_ADDRESS: &str = "0.0.0.0:3000";
+    pub const KEYRING_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
     pub mod email_client {
         use std::time::Duration;
 
@@ -62,6 +231,11 @@ pub mod prod {
         pub const SENDER: &str = "bogdan@codeiron.io";
         pub const TIMEOUT: Duration = std::time::Duration::from_secs(10);
     }
+    pub mod identity_provider {
+        use std::time::Duration;
+
+        pub const TIMEOUT: Duration = std::time::Duration::from_secs(10);
+    }
 }
 
 pub mod test {
@@ -72,4 +246,11 @@ pub mod test {
         pub const SENDER: &str = "test@email.com";
         pub const TIMEOUT: Duration = std::time::Duration::from_millis(200);
     }
+    pub mod identity_provider {
+        use std::time::Duration;
+
+        pub const CLIENT_ID: &str = "auth-service";
+        pub const CLIENT_SECRET: &str = "upstream-secret";
+        pub const TIMEOUT: Duration = std::time::Duration::from_millis(200);
+    }
 }
@@ -0,0 +1,21 @@
+use std::future::Future;
+
+use color_eyre::eyre::Result;
+use tracing::Instrument;
+
+/// Sends an email without waiting for it. For routes that must answer the same way whether
+/// or not the recipient has an account: neither the time a delivery takes nor its failure
+/// shows in the response. Failures are only logged.
+pub fn send_in_background<F>(send: F)
+where
+    F: Future<Output = Result<()>> + Send + 'static,
+{
+    tokio::spawn(
+        async move {
+            if let Err(e) = send.await {
+                tracing::warn!("failed to send email: {:?}", e);
+            }
+        }
+        .in_current_span(),
+    );
+}
@@ -0,0 +1,50 @@
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use color_eyre::eyre::{eyre, Context, Result};
+use secrecy::{ExposeSecret, SecretString};
+
+const NONCE_BYTES: usize = 12;
+
+/// Encrypts `plaintext` with AES-256-GCM under `key`, a base64-encoded 32-byte key. The
+/// output is the base64 encoding of the nonce followed by the ciphertext.
+pub fn encrypt(key: &SecretString, plaintext: &[u8]) -> Result<String> {
+    let cipher = build_cipher(key)?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| eyre!("failed to encrypt"))?;
+
+    let mut payload = nonce.to_vec();
+    payload.extend(ciphertext);
+
+    Ok(STANDARD.encode(payload))
+}
+
+/// Reverses `encrypt`.
+pub fn decrypt(key: &SecretString, encrypted: &str) -> Result<Vec<u8>> {
+    let payload = STANDARD
+        .decode(encrypted)
+        .wrap_err("ciphertext is not valid base64")?;
+
+    if payload.len() < NONCE_BYTES {
+        return Err(eyre!("ciphertext is too short"));
+    }
+    let (nonce, ciphertext) = payload.split_at(NONCE_BYTES);
+    let nonce: [u8; NONCE_BYTES] = nonce.try_into()?;
+
+    build_cipher(key)?
+        .decrypt(&Nonce::from(nonce), ciphertext)
+        .map_err(|_| eyre!("failed to decrypt"))
+}
+
+fn build_cipher(key: &SecretString) -> Result<Aes256Gcm> {
+    let key = STANDARD
+        .decode(key.expose_secret())
+        .wrap_err("encryption key is not valid base64")?;
+
+    Aes256Gcm::new_from_slice(&key).map_err(|_| eyre!("encryption key must be 32 bytes"))
+}
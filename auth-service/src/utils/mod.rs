@@ -1,3 +1,9 @@
 pub mod auth;
 pub mod constants;
+pub mod email;
+pub mod encryption;
+pub mod rate_limit;
+pub mod signing_key;
+pub mod totp;
 pub mod tracing;
+pub mod webauthn;
@@ -0,0 +1,245 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderName, HeaderValue, StatusCode},
+    middleware::{from_fn_with_state, Next},
+    response::{IntoResponse, Response},
+    routing::MethodRouter,
+};
+use secrecy::{ExposeSecret, SecretString};
+use serde::Deserialize;
+
+use crate::{
+    app_state::{AppState, RateLimitStoreType},
+    domain::{AuthAPIError, Email, RateLimitDecision, RateLimitQuota},
+    utils::auth::{basic_credentials, ClientInfo},
+};
+
+/// What a policy counts requests by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitKey {
+    /// The address the request came from.
+    Ip,
+    /// The `email` field of the JSON body, as the `Email` the route parses from it.
+    Email,
+    /// The client id of an `Authorization: Basic` header.
+    ClientId,
+}
+
+/// A route's rate limit. Counters are kept per policy name, so two routes only share
+/// their allowance if their policies have the same name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitPolicy {
+    pub name: &'static str,
+    pub key: RateLimitKey,
+    pub quota: RateLimitQuota,
+}
+
+/// The policies of the rate limited routes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimits {
+    pub signup: RateLimitPolicy,
+    /// Password and passkey logins alike.
+    pub login: RateLimitPolicy,
+    /// Finishing a passkey login, whose body names the login attempt but not the account.
+    pub passkey_login: RateLimitPolicy,
+    /// Every way of completing a second factor.
+    pub verify_2fa: RateLimitPolicy,
+    pub unlock: RateLimitPolicy,
+    pub magic_link: RateLimitPolicy,
+    pub password_reset: RateLimitPolicy,
+    pub verify_email_resend: RateLimitPolicy,
+    pub token: RateLimitPolicy,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        Self {
+            signup: RateLimitPolicy {
+                name: "signup",
+                key: RateLimitKey::Ip,
+                quota: RateLimitQuota::new(20, 3600),
+            },
+            // Failed logins also lock accounts and addresses out, but that only starts after
+            // the password has been hashed; this keeps the hashing itself in check.
+            login: RateLimitPolicy {
+                name: "login",
+                key: RateLimitKey::Email,
+                quota: RateLimitQuota::new(10, 60),
+            },
+            passkey_login: RateLimitPolicy {
+                name: "passkey-login",
+                key: RateLimitKey::Ip,
+                quota: RateLimitQuota::new(30, 60),
+            },
+            verify_2fa: RateLimitPolicy {
+                name: "verify-2fa",
+                key: RateLimitKey::Email,
+                quota: RateLimitQuota::new(10, 60),
+            },
+            // Unlock tokens can't be guessed, so this only bounds the work of checking them.
+            unlock: RateLimitPolicy {
+                name: "unlock",
+                key: RateLimitKey::Ip,
+                quota: RateLimitQuota::new(30, 60),
+            },
+            // The routes that send email are limited by recipient, so they can't be used to
+            // flood someone's inbox.
+            magic_link: RateLimitPolicy {
+                name: "magic-link",
+                key: RateLimitKey::Email,
+                quota: RateLimitQuota::new(5, 3600),
+            },
+            password_reset: RateLimitPolicy {
+                name: "password-reset",
+                key: RateLimitKey::Email,
+                quota: RateLimitQuota::new(5, 3600),
+            },
+            verify_email_resend: RateLimitPolicy {
+                name: "verify-email-resend",
+                key: RateLimitKey::Email,
+                quota: RateLimitQuota::new(3, 3600),
+            },
+            // Device flow clients poll every few seconds from each device, so a client needs
+            // room for many at once.
+            token: RateLimitPolicy {
+                name: "token",
+                key: RateLimitKey::ClientId,
+                quota: RateLimitQuota::new(600, 60),
+            },
+        }
+    }
+}
+
+/// Limits `method_router` to `policy`, with the counters kept in `store`.
+pub fn rate_limited(
+    method_router: MethodRouter<AppState>,
+    store: &RateLimitStoreType,
+    policy: RateLimitPolicy,
+) -> MethodRouter<AppState> {
+    method_router.layer(from_fn_with_state(
+        RateLimiter {
+            store: store.clone(),
+            policy,
+        },
+        rate_limit,
+    ))
+}
+
+#[derive(Clone)]
+struct RateLimiter {
+    store: RateLimitStoreType,
+    policy: RateLimitPolicy,
+}
+
+// Far more than any body the rate limited routes accept.
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+// Requests with nothing to count them by are let through: the route turns them away
+// before doing any real work.
+async fn rate_limit(
+    State(limiter): State<RateLimiter>,
+    client: ClientInfo,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (request, subject) = match limiter.policy.key {
+        RateLimitKey::Ip => (request, client.ip),
+        RateLimitKey::ClientId => {
+            let client_id = basic_credentials(request.headers()).map(|(client_id, _)| client_id);
+            (request, client_id)
+        }
+        RateLimitKey::Email => {
+            // The body can only be read once, so it is put back for the route to read.
+            let (parts, body) = request.into_parts();
+            let Ok(bytes) = to_bytes(body, MAX_BODY_BYTES).await else {
+                return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+            };
+            // Keyed like the lockouts and stores, which tell addresses apart exactly as
+            // given. A body without a valid address is turned away by the route.
+            let email = serde_json::from_slice::<EmailField>(&bytes)
+                .ok()
+                .and_then(|field| Email::parse(field.email).ok())
+                .map(|email| email.as_ref().expose_secret().to_owned());
+            (Request::from_parts(parts, Body::from(bytes)), email)
+        }
+    };
+
+    let Some(subject) = subject else {
+        return next.run(request).await;
+    };
+
+    let key = format!(
+        "{}:{}:{}",
+        limiter.policy.name,
+        key_kind(limiter.policy.key),
+        subject
+    );
+
+    let decision = match limiter
+        .store
+        .write()
+        .await
+        .acquire(&key, &limiter.policy.quota)
+        .await
+    {
+        Ok(decision) => decision,
+        Err(e) => return AuthAPIError::UnexpectedError(e.into()).into_response(),
+    };
+
+    let mut response = if decision.allowed {
+        next.run(request).await
+    } else {
+        AuthAPIError::TooManyRequests.into_response()
+    };
+
+    set_rate_limit_headers(&mut response, &limiter.policy.quota, &decision);
+
+    response
+}
+
+// The fields of draft-ietf-httpapi-ratelimit-headers, with times in whole seconds.
+fn set_rate_limit_headers(
+    response: &mut Response,
+    quota: &RateLimitQuota,
+    decision: &RateLimitDecision,
+) {
+    let headers = response.headers_mut();
+
+    headers.insert(RATELIMIT_LIMIT, HeaderValue::from(decision.limit));
+    headers.insert(RATELIMIT_REMAINING, HeaderValue::from(decision.remaining));
+    headers.insert(
+        RATELIMIT_RESET,
+        HeaderValue::from(decision.reset_after_ms.div_ceil(1000)),
+    );
+    if let Ok(policy) =
+        HeaderValue::from_str(&format!("{};w={}", decision.limit, quota.period_seconds))
+    {
+        headers.insert(RATELIMIT_POLICY, policy);
+    }
+
+    if !decision.allowed {
+        headers.insert(
+            header::RETRY_AFTER,
+            HeaderValue::from(decision.retry_after_ms.div_ceil(1000)),
+        );
+    }
+}
+
+const RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+const RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+const RATELIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+const RATELIMIT_POLICY: HeaderName = HeaderName::from_static("ratelimit-policy");
+
+fn key_kind(key: RateLimitKey) -> &'static str {
+    match key {
+        RateLimitKey::Ip => "ip",
+        RateLimitKey::Email => "email",
+        RateLimitKey::ClientId => "client",
+    }
+}
+
+#[derive(Deserialize)]
+struct EmailField {
+    email: SecretString,
+}
@@ -0,0 +1,389 @@
+use std::sync::{Arc, PoisonError, RwLock};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::Utc;
+use color_eyre::eyre::{eyre, Context, ContextCompat, Result};
+use ed25519_dalek::pkcs8::{spki::der::pem::LineEnding, DecodePrivateKey, EncodePrivateKey};
+use jsonwebtoken::{
+    jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+        OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse,
+    },
+    Algorithm, DecodingKey, EncodingKey,
+};
+use rand::RngCore;
+use secrecy::{ExposeSecret, SecretString};
+use sha2::{Digest, Sha256};
+
+use crate::{app_state::SigningKeyStoreType, domain::SigningKeyRecord};
+
+use super::{auth::MAX_TOKEN_TTL_SECONDS, constants::JWT_SIGNING_KEY_PATH};
+
+/// The active signing key plus the keys that are only trusted: the next key until it
+/// activates, and retired keys whose tokens may still be live. Lookups are served from
+/// memory; the `SigningKeyStore` is only read when the keys change.
+pub struct Keyring {
+    keys: RwLock<Arc<Keys>>,
+}
+
+struct Keys {
+    active: Arc<SigningKey>,
+    verify_only: Vec<Arc<SigningKey>>,
+}
+
+impl Keyring {
+    /// A keyring holding just `key`, without a store behind it.
+    pub fn new(key: SigningKey) -> Self {
+        Self {
+            keys: RwLock::new(Arc::new(Keys {
+                active: Arc::new(key),
+                verify_only: Vec::new(),
+            })),
+        }
+    }
+
+    /// Loads the keyring from `store`. If the key at `JWT_SIGNING_KEY_PATH` hasn't been
+    /// seen before it becomes the active key at once, so deploying a new key file rotates
+    /// too. A key file that has been rotated away from is ignored, even once the key is
+    /// removed.
+    #[tracing::instrument(name = "Load keyring", skip_all)]
+    pub async fn load(store: &SigningKeyStoreType) -> Result<Self> {
+        let pem = std::fs::read(JWT_SIGNING_KEY_PATH.as_str())
+            .wrap_err(format!("failed to read {}", JWT_SIGNING_KEY_PATH.as_str()))?;
+        let file_key = SigningKey::from_pem(&pem)?;
+
+        // Another instance starting with the same key file may add it first, in which case
+        // this does nothing and the refresh below reads what it added.
+        if !store.read().await.contains_key(file_key.kid()).await? {
+            store
+                .write()
+                .await
+                .add_key(file_key.kid(), file_key.private_key_pem(), Utc::now())
+                .await?;
+        }
+
+        let keyring = Self::new(file_key);
+        keyring.refresh(store).await?;
+
+        Ok(keyring)
+    }
+
+    /// Re-reads the keys from `store`, dropping retired keys whose tokens have all expired.
+    /// A rotated in key starts signing at the first refresh after it activates.
+    #[tracing::instrument(name = "Refresh keyring", skip_all)]
+    pub async fn refresh(&self, store: &SigningKeyStoreType) -> Result<()> {
+        let records = store.read().await.get_keys(retired_cutoff()?).await?;
+
+        let now = Utc::now();
+        let mut active = None;
+        let mut verify_only = Vec::new();
+        for record in records {
+            let key = Arc::new(SigningKey::try_from(&record)?);
+            let is_active = record.activates_at <= now
+                && record.retired_at.is_none_or(|retired_at| retired_at > now);
+            // Records come newest first, so should two overlap the newer one signs.
+            match is_active && active.is_none() {
+                true => active = Some(key),
+                false => verify_only.push(key),
+            }
+        }
+
+        let keys = Keys {
+            active: active.wrap_err("no active signing key")?,
+            verify_only,
+        };
+
+        *self.keys.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(keys);
+
+        Ok(())
+    }
+
+    /// Generates a new Ed25519 key that becomes the active key `activation_delay` from now.
+    /// Until then it's only published, so that verifiers already trust it when the first
+    /// token signed with it reaches them. The old key is kept for verification until every
+    /// token it could have signed has expired. Returns the new key's `kid`.
+    #[tracing::instrument(name = "Rotate signing key", skip_all)]
+    pub async fn rotate(
+        &self,
+        store: &SigningKeyStoreType,
+        activation_delay: chrono::Duration,
+    ) -> Result<String> {
+        let key = SigningKey::generate()?;
+        let activates_at = Utc::now()
+            .checked_add_signed(activation_delay)
+            .ok_or(eyre!("failed to add activation delay to current time"))?;
+
+        let mut store_guard = store.write().await;
+        if !store_guard
+            .add_key(key.kid(), key.private_key_pem(), activates_at)
+            .await?
+        {
+            return Err(eyre!("another signing key was added at the same time"));
+        }
+        store_guard.remove_retired_keys(retired_cutoff()?).await?;
+        drop(store_guard);
+
+        self.refresh(store).await?;
+
+        Ok(key.kid().to_owned())
+    }
+
+    /// The key new tokens are signed with.
+    pub fn signing_key(&self) -> Arc<SigningKey> {
+        self.keys().active.clone()
+    }
+
+    /// The key a token with header `kid` must verify against, if it's still trusted.
+    pub fn verification_key(&self, kid: &str) -> Option<Arc<SigningKey>> {
+        let keys = self.keys();
+        std::iter::once(&keys.active)
+            .chain(keys.verify_only.iter())
+            .find(|key| key.kid() == kid)
+            .cloned()
+    }
+
+    /// The public half of every trusted key, for `/.well-known/jwks.json`.
+    pub fn jwk_set(&self) -> JwkSet {
+        let keys = self.keys();
+        JwkSet {
+            keys: std::iter::once(&keys.active)
+                .chain(keys.verify_only.iter())
+                .map(|key| key.jwk().clone())
+                .collect(),
+        }
+    }
+
+    fn keys(&self) -> Arc<Keys> {
+        self.keys
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+// No token outlives MAX_TOKEN_TTL_SECONDS, so keys retired before then can't have signed
+// anything still valid.
+fn retired_cutoff() -> Result<chrono::DateTime<Utc>> {
+    let delta = chrono::Duration::try_seconds(MAX_TOKEN_TTL_SECONDS)
+        .wrap_err("failed to create max token TTL time delta")?;
+
+    Utc::now()
+        .checked_sub_signed(delta)
+        .ok_or(eyre!("failed to subtract max token TTL from current time"))
+}
+
+/// An Ed25519 (EdDSA) or RSA (RS256) private key, along with the public key and JWK that
+/// let others verify what it signs.
+pub struct SigningKey {
+    kid: String,
+    private_key_pem: SecretString,
+    algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    jwk: Jwk,
+}
+
+impl SigningKey {
+    /// Parses a PKCS#8 Ed25519 key, or a PKCS#1 or PKCS#8 RSA key. The `kid` is the key's
+    /// RFC 7638 thumbprint, so it stays the same for as long as the key does.
+    pub fn from_pem(pem: &[u8]) -> Result<Self> {
+        if let Ok(encoding_key) = EncodingKey::from_ed_pem(pem) {
+            let public_key = ed25519_dalek::SigningKey::from_pkcs8_der(encoding_key.inner())
+                .map_err(|e| eyre!("invalid Ed25519 private key: {}", e))?
+                .verifying_key();
+
+            let params = AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x: URL_SAFE_NO_PAD.encode(public_key.as_bytes()),
+            });
+
+            return Self::new(pem, Algorithm::EdDSA, encoding_key, params);
+        }
+
+        if let Ok(encoding_key) = EncodingKey::from_rsa_pem(pem) {
+            let params = Jwk::from_encoding_key(&encoding_key, Algorithm::RS256)
+                .wrap_err("invalid RSA private key")?
+                .algorithm;
+
+            return Self::new(pem, Algorithm::RS256, encoding_key, params);
+        }
+
+        Err(eyre!(
+            "JWT signing key must be an Ed25519 or RSA private key in PEM format"
+        ))
+    }
+
+    /// Generates a fresh Ed25519 key.
+    pub fn generate() -> Result<Self> {
+        let mut seed = [0u8; ed25519_dalek::SECRET_KEY_LENGTH];
+        rand::rng().fill_bytes(&mut seed);
+
+        let pem = ed25519_dalek::SigningKey::from_bytes(&seed)
+            .to_pkcs8_pem(LineEnding::LF)
+            .map_err(|e| eyre!("failed to encode Ed25519 private key: {}", e))?;
+
+        Self::from_pem(pem.as_bytes())
+    }
+
+    fn new(
+        pem: &[u8],
+        algorithm: Algorithm,
+        encoding_key: EncodingKey,
+        params: AlgorithmParameters,
+    ) -> Result<Self> {
+        let kid = thumbprint(&params)?;
+        let private_key_pem = String::from_utf8(pem.to_vec()).wrap_err("PEM is not UTF-8")?;
+
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: Some(match algorithm {
+                    Algorithm::EdDSA => KeyAlgorithm::EdDSA,
+                    _ => KeyAlgorithm::RS256,
+                }),
+                key_id: Some(kid.clone()),
+                ..Default::default()
+            },
+            algorithm: params,
+        };
+
+        let decoding_key =
+            DecodingKey::from_jwk(&jwk).wrap_err("failed to derive JWT verification key")?;
+
+        Ok(Self {
+            kid,
+            private_key_pem: SecretString::new(private_key_pem.into_boxed_str()),
+            algorithm,
+            encoding_key,
+            decoding_key,
+            jwk,
+        })
+    }
+
+    pub fn kid(&self) -> &str {
+        &self.kid
+    }
+
+    pub fn private_key_pem(&self) -> &SecretString {
+        &self.private_key_pem
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding_key
+    }
+
+    pub fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding_key
+    }
+
+    pub fn jwk(&self) -> &Jwk {
+        &self.jwk
+    }
+}
+
+impl TryFrom<&SigningKeyRecord> for SigningKey {
+    type Error = color_eyre::Report;
+
+    fn try_from(record: &SigningKeyRecord) -> Result<Self> {
+        let key = Self::from_pem(record.private_key_pem.expose_secret().as_bytes())?;
+        if key.kid() != record.kid {
+            return Err(eyre!("signing key {} does not match its kid", record.kid));
+        }
+        Ok(key)
+    }
+}
+
+// jsonwebtoken's own Jwk::thumbprint gets the member names wrong for Ed25519 keys, so the
+// RFC 7638 input (required members only, sorted, no whitespace) is built here.
+fn thumbprint(params: &AlgorithmParameters) -> Result<String> {
+    let input = match params {
+        AlgorithmParameters::OctetKeyPair(okp) => {
+            format!(r#"{{"crv":"Ed25519","kty":"OKP","x":"{}"}}"#, okp.x)
+        }
+        AlgorithmParameters::RSA(rsa) => {
+            format!(r#"{{"e":"{}","kty":"RSA","n":"{}"}}"#, rsa.e, rsa.n)
+        }
+        _ => return Err(eyre!("unsupported JWT signing key type")),
+    };
+
+    Ok(URL_SAFE_NO_PAD.encode(Sha256::digest(input.as_bytes())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ed25519_pem(seed: u8) -> String {
+        ed25519_dalek::SigningKey::from_bytes(&[seed; 32])
+            .to_pkcs8_pem(LineEnding::LF)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_ed25519_key_is_published_as_okp_jwk() {
+        let key = SigningKey::from_pem(ed25519_pem(1).as_bytes()).unwrap();
+        assert_eq!(key.algorithm(), Algorithm::EdDSA);
+        assert_eq!(key.jwk().common.key_id.as_deref(), Some(key.kid()));
+        assert!(matches!(
+            key.jwk().algorithm,
+            AlgorithmParameters::OctetKeyPair(_)
+        ));
+    }
+
+    #[test]
+    fn test_kid_is_stable_and_unique_per_key() {
+        let key = SigningKey::from_pem(ed25519_pem(1).as_bytes()).unwrap();
+        let same_key = SigningKey::from_pem(ed25519_pem(1).as_bytes()).unwrap();
+        let other_key = SigningKey::from_pem(ed25519_pem(2).as_bytes()).unwrap();
+        assert_eq!(key.kid(), same_key.kid());
+        assert_ne!(key.kid(), other_key.kid());
+    }
+
+    #[test]
+    fn test_generated_key_survives_pem_round_trip() {
+        let key = SigningKey::generate().unwrap();
+        let reloaded =
+            SigningKey::from_pem(key.private_key_pem().expose_secret().as_bytes()).unwrap();
+        assert_eq!(key.kid(), reloaded.kid());
+        assert_ne!(key.kid(), SigningKey::generate().unwrap().kid());
+    }
+
+    #[test]
+    fn test_keyring_only_trusts_its_own_keys() {
+        let key = SigningKey::generate().unwrap();
+        let kid = key.kid().to_owned();
+        let keyring = Keyring::new(key);
+        assert_eq!(keyring.signing_key().kid(), kid);
+        assert!(keyring.verification_key(&kid).is_some());
+        assert!(keyring
+            .verification_key(SigningKey::generate().unwrap().kid())
+            .is_none());
+        assert_eq!(keyring.jwk_set().keys.len(), 1);
+    }
+
+    #[test]
+    fn test_thumbprint_matches_rfc_8037_example() {
+        let params = AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+            key_type: OctetKeyPairType::OctetKeyPair,
+            curve: EllipticCurve::Ed25519,
+            x: "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo".to_owned(),
+        });
+        assert_eq!(
+            thumbprint(&params).unwrap(),
+            "kPrK_qmxVWaYVA9wwBF6Iuo3vVzz7TxHCTwXBygrS4k"
+        );
+    }
+
+    #[test]
+    fn test_rejects_non_key_pem() {
+        let pem = "-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----\n";
+        assert!(SigningKey::from_pem(pem.as_bytes()).is_err());
+        assert!(SigningKey::from_pem(b"not a pem file").is_err());
+    }
+}
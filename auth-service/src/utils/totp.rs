@@ -0,0 +1,206 @@
+use color_eyre::eyre::{eyre, Context, Result};
+use secrecy::{ExposeSecret, SecretString};
+use totp_rs::{Algorithm, TOTP};
+
+use crate::domain::{Email, TotpSecret, TwoFACode};
+
+use super::{
+    constants::{TOTP_DRIFT_STEPS, TOTP_ENCRYPTION_KEY, TOTP_ISSUER},
+    encryption::{decrypt, encrypt},
+};
+
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP_SECONDS: u64 = 30;
+
+/// What an authenticator app needs to start generating codes for `secret`.
+pub struct TotpEnrollment {
+    pub otpauth_uri: String,
+    /// Base64-encoded PNG of a QR code for `otpauth_uri`.
+    pub qr_code_png: String,
+}
+
+#[tracing::instrument(name = "Build TOTP enrollment", skip_all)]
+pub fn build_enrollment(secret: &TotpSecret, email: &Email) -> Result<TotpEnrollment> {
+    let totp = build_totp(secret, Some(email))?;
+
+    let qr_code_png = totp
+        .get_qr_base64()
+        .map_err(|e| eyre!("failed to render TOTP QR code: {}", e))?;
+
+    Ok(TotpEnrollment {
+        otpauth_uri: totp.get_url(),
+        qr_code_png,
+    })
+}
+
+/// Checks `code` against the steps within `TOTP_DRIFT_STEPS` of `time` (seconds since the
+/// epoch) and returns the step it matched, if any.
+#[tracing::instrument(name = "Verify TOTP code", skip_all)]
+pub fn verify_code(secret: &TotpSecret, code: &TwoFACode, time: u64) -> Result<Option<u64>> {
+    verify_code_within(secret, code, time, u64::from(*TOTP_DRIFT_STEPS))
+}
+
+fn verify_code_within(
+    secret: &TotpSecret,
+    code: &TwoFACode,
+    time: u64,
+    drift: u64,
+) -> Result<Option<u64>> {
+    let totp = build_totp(secret, None)?;
+    let current_step = time / TOTP_STEP_SECONDS;
+
+    let matched = (current_step.saturating_sub(drift)..=current_step + drift)
+        .find(|step| totp.check(code.as_ref().expose_secret(), step * TOTP_STEP_SECONDS));
+
+    Ok(matched)
+}
+
+/// Encrypts `secret` with AES-256-GCM under `TOTP_ENCRYPTION_KEY`.
+#[tracing::instrument(name = "Encrypt TOTP secret", skip_all)]
+pub fn encrypt_secret(secret: &TotpSecret) -> Result<String> {
+    encrypt_secret_with(&TOTP_ENCRYPTION_KEY, secret)
+}
+
+#[tracing::instrument(name = "Decrypt TOTP secret", skip_all)]
+pub fn decrypt_secret(encrypted: &str) -> Result<TotpSecret> {
+    decrypt_secret_with(&TOTP_ENCRYPTION_KEY, encrypted)
+}
+
+fn encrypt_secret_with(key: &SecretString, secret: &TotpSecret) -> Result<String> {
+    encrypt(key, secret.as_ref().expose_secret()).wrap_err("failed to encrypt TOTP secret")
+}
+
+fn decrypt_secret_with(key: &SecretString, encrypted: &str) -> Result<TotpSecret> {
+    let secret = decrypt(key, encrypted).wrap_err("failed to decrypt TOTP secret")?;
+
+    TotpSecret::parse(secret)
+}
+
+fn build_totp(secret: &TotpSecret, email: Option<&Email>) -> Result<TOTP> {
+    TOTP::new(
+        Algorithm::SHA1,
+        TOTP_DIGITS,
+        0,
+        TOTP_STEP_SECONDS,
+        secret.as_ref().expose_secret().to_vec(),
+        Some(TOTP_ISSUER.to_owned()),
+        email
+            .map(|email| email.as_ref().expose_secret().to_owned())
+            .unwrap_or_default(),
+    )
+    .wrap_err("failed to build TOTP")
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    use super::*;
+
+    const DRIFT: u64 = 1;
+    const TIME: u64 = 1_700_000_000;
+
+    // The RFC 6238 test secret, so codes are the same on every run.
+    fn test_secret() -> TotpSecret {
+        TotpSecret::parse(b"12345678901234567890".to_vec()).unwrap()
+    }
+
+    fn test_key() -> SecretString {
+        SecretString::new(STANDARD.encode([7u8; 32]).into_boxed_str())
+    }
+
+    fn code_at(secret: &TotpSecret, time: u64) -> TwoFACode {
+        let code = build_totp(secret, None).unwrap().generate(time);
+        TwoFACode::parse(SecretString::new(code.into_boxed_str())).unwrap()
+    }
+
+    #[test]
+    fn test_verify_code_returns_matched_step() {
+        let secret = test_secret();
+
+        let code = code_at(&secret, TIME);
+
+        assert_eq!(
+            verify_code_within(&secret, &code, TIME, DRIFT).unwrap(),
+            Some(TIME / TOTP_STEP_SECONDS)
+        );
+    }
+
+    #[test]
+    fn test_verify_code_accepts_codes_within_drift_window() {
+        let secret = test_secret();
+
+        let previous = code_at(&secret, TIME - TOTP_STEP_SECONDS);
+        let next = code_at(&secret, TIME + TOTP_STEP_SECONDS);
+
+        assert_eq!(
+            verify_code_within(&secret, &previous, TIME, DRIFT).unwrap(),
+            Some(TIME / TOTP_STEP_SECONDS - 1)
+        );
+        assert_eq!(
+            verify_code_within(&secret, &next, TIME, DRIFT).unwrap(),
+            Some(TIME / TOTP_STEP_SECONDS + 1)
+        );
+    }
+
+    #[test]
+    fn test_verify_code_rejects_codes_outside_drift_window() {
+        let secret = test_secret();
+
+        let stale = code_at(&secret, TIME - (DRIFT + 1) * TOTP_STEP_SECONDS);
+        let early = code_at(&secret, TIME + (DRIFT + 1) * TOTP_STEP_SECONDS);
+
+        assert_eq!(
+            verify_code_within(&secret, &stale, TIME, DRIFT).unwrap(),
+            None
+        );
+        assert_eq!(
+            verify_code_within(&secret, &early, TIME, DRIFT).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_encrypted_secret_round_trips() {
+        let secret = test_secret();
+        let key = test_key();
+
+        let encrypted = encrypt_secret_with(&key, &secret).unwrap();
+        let decrypted = decrypt_secret_with(&key, &encrypted).unwrap();
+
+        assert_eq!(
+            secret.as_ref().expose_secret(),
+            decrypted.as_ref().expose_secret()
+        );
+        assert!(!encrypted.contains(&STANDARD.encode(secret.as_ref().expose_secret())));
+    }
+
+    #[test]
+    fn test_tampered_secret_fails_to_decrypt() {
+        let secret = test_secret();
+        let key = test_key();
+
+        let mut payload = STANDARD
+            .decode(encrypt_secret_with(&key, &secret).unwrap())
+            .unwrap();
+        let last = payload.len() - 1;
+        payload[last] ^= 1;
+
+        assert!(decrypt_secret_with(&key, &STANDARD.encode(payload)).is_err());
+    }
+
+    #[test]
+    fn test_enrollment_uri_identifies_user() {
+        let secret = test_secret();
+        let email = Email::parse(SecretString::new(
+            "test@example.com".to_owned().into_boxed_str(),
+        ))
+        .unwrap();
+
+        let enrollment = build_enrollment(&secret, &email).unwrap();
+
+        assert!(enrollment.otpauth_uri.starts_with("otpauth://totp/"));
+        assert!(enrollment.otpauth_uri.contains("test%40example.com"));
+        assert!(!enrollment.qr_code_png.is_empty());
+    }
+}
@@ -0,0 +1,102 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use color_eyre::eyre::{eyre, Context, ContextCompat, Result};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use lazy_static::lazy_static;
+use secrecy::ExposeSecret;
+use sha2::Sha256;
+use webauthn_rs::prelude::{Passkey, Url, Uuid, Webauthn, WebauthnBuilder};
+
+use crate::domain::Email;
+
+use super::constants::{AUTH_SERVICE_URL, JWT_KEY_ENCRYPTION_KEY, WEBAUTHN_RP_NAME};
+
+lazy_static! {
+    // Built once from AUTH_SERVICE_URL. Errors are kept as strings because WebauthnError
+    // isn't Clone, and every request needs its own copy of the failure.
+    static ref WEBAUTHN: std::result::Result<Webauthn, String> =
+        build_webauthn().map_err(|e| format!("{:?}", e));
+}
+
+/// The relying party for passkey ceremonies. Its ID is the host of `AUTH_SERVICE_URL`, so
+/// passkeys only work on the origin the service is deployed at.
+pub fn webauthn() -> Result<&'static Webauthn> {
+    WEBAUTHN
+        .as_ref()
+        .map_err(|e| eyre!("failed to configure WebAuthn: {}", e))
+}
+
+/// WebAuthn wants a stable, opaque user handle. Deriving it from the email means we don't
+/// need to store one.
+pub fn user_handle(email: &Email) -> Uuid {
+    Uuid::new_v5(
+        &Uuid::NAMESPACE_URL,
+        email.as_ref().expose_secret().as_bytes(),
+    )
+}
+
+// A software passkey whose private key was thrown away once it was registered, so no
+// ceremony started with it can ever be finished.
+const DECOY_PASSKEY: &str = r#"{"cred":{"cred_id":"FB3hEZMw8RcPcSNqYDcU0h8QyFcgqKqNcXO4sABjSNk","cred":{"type_":"ES256","key":{"EC_EC2":{"curve":"SECP256R1","x":"WI7oQpc89KpLfWh4Z3DkgA4lIYiw3XDUuGOHVuwiMCE","y":"vsuxMKAt-VSjIqDk8MPKmbJyrQAn6PDtJ2iS8OL6oHM"}}},"counter":0,"transports":null,"user_verified":true,"backup_eligible":false,"backup_state":false,"registration_policy":"required","extensions":{"cred_protect":"Ignored","hmac_create_secret":"NotRequested","appid":"NotRequested","cred_props":"Ignored"},"attestation":{"data":"Self_","metadata":"None"},"attestation_format":"packed"}}"#;
+
+// Separates the key decoy credential ids are made with from any other use of the
+// encryption key (RFC 5869 section 3.2).
+const DECOY_PASSKEY_KEY_INFO: &[u8] = b"auth-service decoy passkey credential id";
+
+/// Stands in for the passkeys of an account that can't log in with one, so a login can be
+/// started for it like for any other. The credential id is an HMAC of the email under a
+/// key derived from `JWT_KEY_ENCRYPTION_KEY`: asking again gets the same id, as it would
+/// for a real passkey, and nobody without the key can tell it from one.
+pub fn decoy_passkey(email: &Email) -> Result<Passkey> {
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(None, JWT_KEY_ENCRYPTION_KEY.expose_secret().as_bytes())
+        .expand(DECOY_PASSKEY_KEY_INFO, &mut key)
+        .map_err(|_| eyre!("failed to derive decoy passkey key"))?;
+
+    let credential_id = Hmac::<Sha256>::new_from_slice(&key)
+        .wrap_err("invalid decoy passkey key")?
+        .chain_update(email.as_ref().expose_secret().as_bytes())
+        .finalize()
+        .into_bytes();
+
+    let mut passkey: serde_json::Value =
+        serde_json::from_str(DECOY_PASSKEY).wrap_err("invalid decoy passkey")?;
+    passkey["cred"]["cred_id"] = URL_SAFE_NO_PAD.encode(credential_id).into();
+
+    serde_json::from_value(passkey).wrap_err("invalid decoy passkey")
+}
+
+fn build_webauthn() -> Result<Webauthn> {
+    let origin = Url::parse(&AUTH_SERVICE_URL).wrap_err("AUTH_SERVICE_URL is not a valid URL")?;
+    let rp_id = origin
+        .domain()
+        .wrap_err("AUTH_SERVICE_URL must have a domain name")?
+        .to_owned();
+
+    WebauthnBuilder::new(&rp_id, &origin)
+        .wrap_err("invalid WebAuthn relying party")?
+        .rp_name(WEBAUTHN_RP_NAME)
+        .build()
+        .wrap_err("failed to build WebAuthn relying party")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use secrecy::SecretString;
+
+    fn email(address: &str) -> Email {
+        Email::parse(SecretString::new(address.to_owned().into_boxed_str())).unwrap()
+    }
+
+    #[test]
+    fn test_decoy_passkey_is_stable_per_email() {
+        let first = decoy_passkey(&email("test@example.com")).unwrap();
+        let second = decoy_passkey(&email("test@example.com")).unwrap();
+        let other = decoy_passkey(&email("other@example.com")).unwrap();
+
+        assert_eq!(first.cred_id(), second.cred_id());
+        assert_ne!(first.cred_id(), other.cred_id());
+    }
+}
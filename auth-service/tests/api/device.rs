@@ -0,0 +1,239 @@
+use std::time::Duration;
+
+use auth_service::{
+    routes::{DeviceAuthorizationResponse, DeviceRequestResponse, TokenResponse, UserinfoResponse},
+    utils::constants::DEVICE_CODE_POLL_INTERVAL_SECONDS,
+};
+
+use crate::helpers::{assert_oauth_error, get_random_email, TestApp};
+
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// Registers a CLI's client through the admin API and returns its id and secret.
+async fn create_device_client(app: &TestApp) -> (String, String) {
+    app.create_oauth_client(&[DEVICE_CODE_GRANT], &["openid", "email", "orders:read"])
+        .await
+}
+
+async fn start_device_flow(app: &TestApp, client: (&str, &str)) -> DeviceAuthorizationResponse {
+    let response = app
+        .post_device_code(Some(client), &[("scope", "openid email")])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<DeviceAuthorizationResponse>()
+        .await
+        .expect("Could not deserialize response body to DeviceAuthorizationResponse")
+}
+
+async fn poll(app: &TestApp, client: (&str, &str), device_code: &str) -> reqwest::Response {
+    app.post_token(
+        Some(client),
+        &[
+            ("grant_type", DEVICE_CODE_GRANT),
+            ("device_code", device_code),
+        ],
+    )
+    .await
+}
+
+#[tokio::test]
+async fn should_issue_token_once_the_user_approves() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = create_device_client(&app).await;
+    let client = (client_id.as_str(), client_secret.as_str());
+    let email = get_random_email();
+    app.signup_and_login(&email, false).await;
+
+    let device = start_device_flow(&app, client).await;
+
+    assert_eq!(device.user_code.len(), 9);
+    assert!(device.verification_uri.ends_with("/device"));
+    assert!(device
+        .verification_uri_complete
+        .ends_with(&format!("/device?user_code={}", device.user_code)));
+    assert_eq!(device.interval, DEVICE_CODE_POLL_INTERVAL_SECONDS);
+
+    assert_oauth_error(
+        poll(&app, client, &device.device_code).await,
+        400,
+        "authorization_pending",
+    )
+    .await;
+
+    // Polling again straight away is too fast.
+    assert_oauth_error(
+        poll(&app, client, &device.device_code).await,
+        400,
+        "slow_down",
+    )
+    .await;
+
+    // Users may type the code in lower case and without the dash.
+    let typed_code = device.user_code.replace('-', "").to_lowercase();
+
+    let response = app
+        .post_device_lookup(&serde_json::json!({ "userCode": typed_code }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let request = response
+        .json::<DeviceRequestResponse>()
+        .await
+        .expect("Could not deserialize response body to DeviceRequestResponse");
+
+    assert_eq!(request.client_id, client_id);
+    assert_eq!(request.scope, "openid email");
+
+    let body = serde_json::json!({ "userCode": typed_code, "approve": true });
+
+    let response = app.post_device_confirm(&body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Each user code can only be decided on once.
+    let response = app.post_device_confirm(&body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    tokio::time::sleep(Duration::from_secs(DEVICE_CODE_POLL_INTERVAL_SECONDS)).await;
+
+    let response = poll(&app, client, &device.device_code).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let tokens = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+
+    assert_eq!(tokens.scope, "openid email");
+    assert_eq!(tokens.id_token, None);
+
+    let userinfo = app
+        .get_userinfo(Some(&tokens.access_token))
+        .await
+        .json::<UserinfoResponse>()
+        .await
+        .expect("Could not deserialize response body to UserinfoResponse");
+
+    assert_eq!(userinfo.sub, email);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_access_denied_when_the_user_denies() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = create_device_client(&app).await;
+    let client = (client_id.as_str(), client_secret.as_str());
+    app.signup_and_login(&get_random_email(), false).await;
+
+    let device = start_device_flow(&app, client).await;
+
+    let response = app
+        .post_device_confirm(&serde_json::json!({
+            "userCode": device.user_code,
+            "approve": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_oauth_error(
+        poll(&app, client, &device.device_code).await,
+        400,
+        "access_denied",
+    )
+    .await;
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_a_logged_in_user_and_a_valid_user_code() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = create_device_client(&app).await;
+    let device = start_device_flow(&app, (&client_id, &client_secret)).await;
+
+    let response = app
+        .post_device_lookup(&serde_json::json!({ "userCode": device.user_code }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.signup_and_login(&get_random_email(), false).await;
+
+    let test_cases = [("BCDF-GHJ", 400), ("AEIO-UAEI", 400), ("BCDF-GHJK", 401)];
+
+    for (user_code, status) in test_cases {
+        let response = app
+            .post_device_lookup(&serde_json::json!({ "userCode": user_code }))
+            .await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            status,
+            "Failed for input: {}",
+            user_code
+        );
+    }
+
+    let response = app
+        .http_client
+        .get(format!("{}/device", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .expect("Failed to read response body")
+        .contains("device.js"));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_only_give_the_result_to_the_requesting_client() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = create_device_client(&app).await;
+    let (other_id, other_secret) = create_device_client(&app).await;
+    let (oidc_id, oidc_secret) = app.register_oauth_client().await;
+
+    let response = app
+        .post_device_code(Some((&oidc_id, &oidc_secret)), &[("scope", "openid")])
+        .await;
+
+    assert_oauth_error(response, 400, "unauthorized_client").await;
+
+    let response = app
+        .post_device_code(Some((&client_id, &client_secret)), &[("scope", "admin")])
+        .await;
+
+    assert_oauth_error(response, 400, "invalid_scope").await;
+
+    let device = start_device_flow(&app, (&client_id, &client_secret)).await;
+
+    assert_oauth_error(
+        poll(&app, (&other_id, &other_secret), &device.device_code).await,
+        400,
+        "invalid_grant",
+    )
+    .await;
+
+    let response = app.post_device_code(None, &[("scope", "openid")]).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
@@ -0,0 +1,619 @@
+use auth_service::{
+    domain::AuthMethod,
+    routes::{IdentityResponse, IntrospectResponse, LinkIdentityResponse},
+    utils::{
+        constants::{test, JWT_COOKIE_NAME},
+        signing_key::SigningKey,
+    },
+    ErrorResponse,
+};
+use jsonwebtoken::{encode, jwk::JwkSet, Header};
+use reqwest::Url;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{auth_token, get_random_email, TestApp};
+
+/// Publishes the mock upstream provider's metadata and its signing key.
+async fn mount_identity_provider(app: &TestApp, key: &SigningKey) {
+    let issuer = app.idp_server.uri();
+
+    Mock::given(path("/.well-known/openid-configuration"))
+        .and(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{}/authorize", issuer),
+            "token_endpoint": format!("{}/token", issuer),
+            "jwks_uri": format!("{}/jwks", issuer),
+        })))
+        .mount(&app.idp_server)
+        .await;
+
+    Mock::given(path("/jwks"))
+        .and(method("GET"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(JwkSet {
+            keys: vec![key.jwk().clone()],
+        }))
+        .mount(&app.idp_server)
+        .await;
+}
+
+/// Makes the upstream token endpoint answer the next code exchange with `id_token`.
+async fn mount_token_response(app: &TestApp, id_token: String) {
+    Mock::given(path("/token"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "access_token": "upstream-access-token",
+            "token_type": "Bearer",
+            "id_token": id_token,
+        })))
+        .up_to_n_times(1)
+        .mount(&app.idp_server)
+        .await;
+}
+
+fn id_token(key: &SigningKey, claims: serde_json::Value) -> String {
+    let mut header = Header::new(key.algorithm());
+    header.kid = Some(key.kid().to_owned());
+
+    encode(&header, &claims, key.encoding_key()).expect("Failed to sign ID token")
+}
+
+/// The claims of a valid ID token for `email`, which can be overridden per test.
+fn claims(app: &TestApp, email: &str, nonce: &str) -> serde_json::Value {
+    let now = chrono::Utc::now().timestamp();
+
+    serde_json::json!({
+        "iss": app.idp_server.uri(),
+        "aud": test::identity_provider::CLIENT_ID,
+        "sub": "upstream-user",
+        "iat": now,
+        "exp": now + 300,
+        "nonce": nonce,
+        "email": email,
+        "email_verified": true,
+    })
+}
+
+/// Starts a federated login, returning the `state` and `nonce` sent upstream.
+async fn start_login(app: &TestApp, return_to: Option<&str>) -> (String, String) {
+    let query: Vec<_> = return_to
+        .map(|url| ("return_to", url))
+        .into_iter()
+        .collect();
+    let response = app.get_federated_login(&query).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+
+    let location = response
+        .headers()
+        .get("location")
+        .expect("No redirect location")
+        .to_str()
+        .unwrap();
+
+    upstream_params(location)
+}
+
+/// Returns the `state` and `nonce` of a login at the upstream provider.
+fn upstream_params(location: &str) -> (String, String) {
+    let url = Url::parse(location).expect("Upstream login page is not a URL");
+
+    assert_eq!(url.path(), "/authorize");
+
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+            .unwrap_or_else(|| panic!("No {} in redirect", name))
+    };
+
+    assert_eq!(param("client_id"), test::identity_provider::CLIENT_ID);
+    assert_eq!(param("code_challenge_method"), "S256");
+
+    (param("state"), param("nonce"))
+}
+
+async fn introspect(app: &TestApp, token: &str) -> IntrospectResponse {
+    let (client_id, client_secret) = app.register_oauth_client().await;
+
+    app.introspect((&client_id, &client_secret), token).await
+}
+
+/// Links the upstream account `subject` to the logged in user.
+async fn link(app: &TestApp, key: &SigningKey, subject: &str) -> reqwest::Response {
+    let response = app
+        .post_federated_link(&serde_json::json!({ "password": "password123" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let url = response
+        .json::<LinkIdentityResponse>()
+        .await
+        .expect("Could not deserialize response body to LinkIdentityResponse")
+        .authorization_url;
+    let (state, nonce) = upstream_params(&url);
+
+    // Whatever address the upstream account has.
+    let mut claims = claims(app, "someone.else@example.com", &nonce);
+    claims["sub"] = subject.into();
+    claims["email_verified"] = false.into();
+    mount_token_response(app, id_token(key, claims)).await;
+
+    app.get_federated_callback(&[("code", "upstream-code"), ("state", &state)])
+        .await
+}
+
+#[tokio::test]
+async fn should_create_and_log_in_new_user() {
+    let mut app = TestApp::new().await;
+    let key = SigningKey::generate().unwrap();
+    mount_identity_provider(&app, &key).await;
+
+    let email = get_random_email();
+    let (state, nonce) = start_login(&app, Some("/authorize?client_id=app")).await;
+    mount_token_response(&app, id_token(&key, claims(&app, &email, &nonce))).await;
+
+    let response = app
+        .get_federated_callback(&[("code", "upstream-code"), ("state", &state)])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(
+        response.headers().get("location").unwrap(),
+        "/authorize?client_id=app"
+    );
+
+    let token = auth_token(&response);
+
+    assert_eq!(
+        introspect(&app, &token).await.amr,
+        Some(vec![AuthMethod::Federated])
+    );
+
+    // The code was exchanged with the PKCE verifier under the service's own credentials.
+    let exchange = app
+        .idp_server
+        .received_requests()
+        .await
+        .unwrap()
+        .into_iter()
+        .find(|request| request.url.path() == "/token")
+        .expect("The code was never exchanged");
+    let body = String::from_utf8(exchange.body).unwrap();
+
+    assert!(body.contains("code=upstream-code"));
+    assert!(body.contains("code_verifier="));
+    assert!(exchange.headers.get("authorization").is_some());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_log_in_existing_user() {
+    let mut app = TestApp::new().await;
+    let key = SigningKey::generate().unwrap();
+    mount_identity_provider(&app, &key).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let email = get_random_email();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&email).await;
+
+    // Returning anywhere but the consent pages would be an open redirect.
+    let (state, nonce) = start_login(&app, Some("https://evil.example.com/")).await;
+    let mut claims = claims(&app, &email, &nonce);
+    claims["amr"] = serde_json::json!(["pwd", "otp", "mfa"]);
+    mount_token_response(&app, id_token(&key, claims)).await;
+
+    let response = app
+        .get_federated_callback(&[("code", "upstream-code"), ("state", &state)])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("location").unwrap(), "/");
+
+    let token = auth_token(&response);
+
+    assert_eq!(
+        introspect(&app, &token).await.amr,
+        Some(vec![AuthMethod::Federated, AuthMethod::MultiFactor])
+    );
+
+    // The user's own password still works.
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_link_existing_user_with_2fa() {
+    let mut app = TestApp::new().await;
+    let key = SigningKey::generate().unwrap();
+    mount_identity_provider(&app, &key).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let email = get_random_email();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": true
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&email).await;
+
+    // Whatever the upstream provider asked for, it wasn't this account's second factor.
+    for amr in [
+        serde_json::json!(["pwd"]),
+        serde_json::json!(["pwd", "otp", "mfa"]),
+    ] {
+        let (state, nonce) = start_login(&app, None).await;
+        let mut claims = claims(&app, &email, &nonce);
+        claims["amr"] = amr;
+        mount_token_response(&app, id_token(&key, claims)).await;
+
+        let response = app
+            .get_federated_callback(&[("code", "upstream-code"), ("state", &state)])
+            .await;
+
+        assert_eq!(response.status().as_u16(), 403);
+        assert!(response
+            .cookies()
+            .all(|cookie| cookie.name() != JWT_COOKIE_NAME));
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Log in and link this identity to your account first"
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_replace_password_of_unverified_user() {
+    let mut app = TestApp::new().await;
+    let key = SigningKey::generate().unwrap();
+    mount_identity_provider(&app, &key).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Someone else signed up with the address before its owner arrived.
+    let email = get_random_email();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let (state, nonce) = start_login(&app, None).await;
+    mount_token_response(&app, id_token(&key, claims(&app, &email, &nonce))).await;
+
+    let response = app
+        .get_federated_callback(&[("code", "upstream-code"), ("state", &state)])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 303);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reject_invalid_id_token() {
+    let mut app = TestApp::new().await;
+    let key = SigningKey::generate().unwrap();
+    let other_key = SigningKey::generate().unwrap();
+    mount_identity_provider(&app, &key).await;
+
+    let email = get_random_email();
+
+    let test_cases = [
+        ("wrong nonce", "nonce", "replayed".into(), &key, 401),
+        ("wrong audience", "aud", "another-client".into(), &key, 401),
+        (
+            "wrong issuer",
+            "iss",
+            "https://evil.example.com".into(),
+            &key,
+            401,
+        ),
+        (
+            "unknown key",
+            "sub",
+            "upstream-user".into(),
+            &other_key,
+            401,
+        ),
+        (
+            "unverified email",
+            "email_verified",
+            false.into(),
+            &key,
+            403,
+        ),
+    ];
+
+    for (description, claim, value, signing_key, status) in test_cases {
+        let (state, nonce) = start_login(&app, None).await;
+        let mut claims = claims(&app, &email, &nonce);
+        claims[claim] = value;
+        mount_token_response(&app, id_token(signing_key, claims)).await;
+
+        let response = app
+            .get_federated_callback(&[("code", "upstream-code"), ("state", &state)])
+            .await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            status,
+            "Failed for: {}",
+            description
+        );
+        assert!(
+            response
+                .cookies()
+                .all(|cookie| cookie.name() != JWT_COOKIE_NAME),
+            "Logged in for: {}",
+            description
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reject_unknown_or_replayed_state() {
+    let mut app = TestApp::new().await;
+    let key = SigningKey::generate().unwrap();
+    mount_identity_provider(&app, &key).await;
+
+    let email = get_random_email();
+
+    // A state this browser didn't start a login for.
+    start_login(&app, None).await;
+
+    let response = app
+        .get_federated_callback(&[("code", "upstream-code"), ("state", "forged-state")])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Invalid auth token"
+    );
+
+    // The upstream provider refused the login.
+    let (state, _) = start_login(&app, None).await;
+
+    let response = app
+        .get_federated_callback(&[("error", "access_denied"), ("state", &state)])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // A state that was already used.
+    let (state, nonce) = start_login(&app, None).await;
+    mount_token_response(&app, id_token(&key, claims(&app, &email, &nonce))).await;
+
+    let response = app
+        .get_federated_callback(&[("code", "upstream-code"), ("state", &state)])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 303);
+
+    let response = app
+        .get_federated_callback(&[("code", "upstream-code"), ("state", &state)])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_keep_logging_in_linked_user() {
+    let mut app = TestApp::new().await;
+    let key = SigningKey::generate().unwrap();
+    mount_identity_provider(&app, &key).await;
+
+    let email = get_random_email();
+    let (state, nonce) = start_login(&app, None).await;
+    mount_token_response(&app, id_token(&key, claims(&app, &email, &nonce))).await;
+
+    let response = app
+        .get_federated_callback(&[("code", "upstream-code"), ("state", &state)])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 303);
+
+    // The upstream account changed its address, which the provider hasn't verified yet.
+    let (state, nonce) = start_login(&app, None).await;
+    let mut claims = claims(&app, &get_random_email(), &nonce);
+    claims["email_verified"] = false.into();
+    mount_token_response(&app, id_token(&key, claims)).await;
+
+    let response = app
+        .get_federated_callback(&[("code", "upstream-code"), ("state", &state)])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(
+        introspect(&app, &auth_token(&response)).await.sub,
+        Some(email)
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_link_and_unlink_identity() {
+    let mut app = TestApp::new().await;
+    let key = SigningKey::generate().unwrap();
+    mount_identity_provider(&app, &key).await;
+
+    let email = get_random_email();
+    app.signup_and_login(&email, false).await;
+
+    // Linking needs the password again.
+    let response = app
+        .post_federated_link(&serde_json::json!({ "password": "wrong-password" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = link(&app, &key, "work-account").await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("location").unwrap(), "/");
+
+    let response = app.get_federated_identities().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let identities = response
+        .json::<Vec<IdentityResponse>>()
+        .await
+        .expect("Could not deserialize response body to Vec<IdentityResponse>");
+
+    assert_eq!(identities.len(), 1);
+    assert_eq!(identities[0].provider, app.idp_server.uri());
+    assert_eq!(identities[0].subject, "work-account");
+
+    // The upstream account now logs in to the user, although its address differs.
+    let (state, nonce) = start_login(&app, None).await;
+    let mut claims = claims(&app, "someone.else@example.com", &nonce);
+    claims["sub"] = "work-account".into();
+    mount_token_response(&app, id_token(&key, claims)).await;
+
+    let response = app
+        .get_federated_callback(&[("code", "upstream-code"), ("state", &state)])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(
+        introspect(&app, &auth_token(&response)).await.sub,
+        Some(email.clone())
+    );
+
+    // Unlinking needs the password again too.
+    let unlink = |password: &'static str| {
+        serde_json::json!({
+            "provider": app.idp_server.uri(),
+            "subject": "work-account",
+            "password": password,
+        })
+    };
+
+    let response = app.post_federated_unlink(&unlink("wrong-password")).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_federated_unlink(&unlink("password123")).await;
+
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = app.post_federated_unlink(&unlink("password123")).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+
+    let identities = app
+        .get_federated_identities()
+        .await
+        .json::<Vec<IdentityResponse>>()
+        .await
+        .expect("Could not deserialize response body to Vec<IdentityResponse>");
+
+    assert!(identities.is_empty());
+
+    // The password still works after unlinking.
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_link_identity_of_another_user() {
+    let mut app = TestApp::new().await;
+    let key = SigningKey::generate().unwrap();
+    mount_identity_provider(&app, &key).await;
+
+    let email = get_random_email();
+    app.signup_and_login(&email, false).await;
+
+    let response = link(&app, &key, "shared-account").await;
+
+    assert_eq!(response.status().as_u16(), 303);
+
+    app.signup_and_login(&get_random_email(), false).await;
+
+    let response = link(&app, &key, "shared-account").await;
+
+    assert_eq!(response.status().as_u16(), 409);
+
+    app.clean_up().await;
+}
@@ -1,16 +1,47 @@
 use auth_service::{
-    app_state::{AppState, BannedTokenStoreType, TwoFACodeStoreType},
-    domain::Email,
+    app_state::{
+        AppState, BannedTokenStoreType, KeyringType, OAuthClientStoreType,
+        PersonalAccessTokenStoreType, SessionStoreType, SigningKeyStoreType, TwoFACodeStoreType,
+    },
+    domain::{ClientSecret, Email, GrantType, OAuthClient, RateLimitQuota},
     get_postgres_pool, get_redis_client,
+    routes::{
+        IntrospectResponse, OAuthClientSecretResponse, PersonalAccessTokenResponse,
+        TwoFactorAuthResponse,
+    },
     services::{
-        data_stores::postgres_user_store::PostgresUserStore,
-        postmark_email_client::PostmarkEmailClient, RedisBannedTokenStore, RedisTwoFACodeStore,
+        data_stores::{
+            postgres_oauth_client_store::PostgresOAuthClientStore,
+            postgres_passkey_store::PostgresPasskeyStore,
+            postgres_personal_access_token_store::PostgresPersonalAccessTokenStore,
+            postgres_recovery_code_store::PostgresRecoveryCodeStore,
+            postgres_refresh_token_store::PostgresRefreshTokenStore,
+            postgres_session_store::PostgresSessionStore,
+            postgres_signing_key_store::PostgresSigningKeyStore,
+            postgres_totp_secret_store::PostgresTotpSecretStore,
+            postgres_user_identity_store::PostgresUserIdentityStore,
+            postgres_user_store::PostgresUserStore,
+        },
+        oidc_identity_provider::OidcIdentityProvider,
+        postmark_email_client::PostmarkEmailClient,
+        RedisAuthorizationCodeStore, RedisBannedTokenStore, RedisDeviceCodeStore,
+        RedisFederatedLoginStore, RedisLoginLockoutStore, RedisMagicLinkStore,
+        RedisPasskeyCeremonyStore, RedisRateLimitStore, RedisTwoFACodeStore,
     },
-    utils::constants::{test, DATABASE_URL, DEFAULT_REDIS_HOSTNAME},
-    Application,
+    utils::{
+        constants::{
+            test, ADMIN_API_TOKEN, DATABASE_URL, DEFAULT_REDIS_HOSTNAME, JWT_COOKIE_NAME,
+            REFRESH_COOKIE_NAME,
+        },
+        rate_limit::RateLimits,
+        signing_key::Keyring,
+    },
+    Application, ErrorResponse,
 };
-use reqwest::{cookie::Jar, Client};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use reqwest::{cookie::Jar, Client, Url};
 use secrecy::{ExposeSecret, SecretString};
+use sha2::{Digest, Sha256};
 use sqlx::{
     postgres::{PgConnectOptions, PgConnection, PgPoolOptions},
     Connection, Executor, PgPool,
@@ -19,7 +50,12 @@ use std::str::FromStr;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use uuid::Uuid;
-use wiremock::MockServer;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+pub const TEST_REDIRECT_URI: &str = "https://client.example.com/callback";
+
+pub const CODE_VERIFIER: &str = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
 
 pub struct TestApp {
     pub address: String,
@@ -29,6 +65,12 @@ pub struct TestApp {
     pub db_name: String,
     pub email_server: MockServer,
     pub http_client: reqwest::Client,
+    pub idp_server: MockServer,
+    pub keyring: KeyringType,
+    pub oauth_client_store: OAuthClientStoreType,
+    pub personal_access_token_store: PersonalAccessTokenStoreType,
+    pub session_store: SessionStoreType,
+    pub signing_key_store: SigningKeyStoreType,
     pub two_fa_code_store: TwoFACodeStoreType,
 }
 
@@ -42,30 +84,111 @@ impl Drop for TestApp {
 
 impl TestApp {
     pub async fn new() -> Self {
+        Self::with_rate_limits(test_rate_limits()).await
+    }
+
+    pub async fn with_rate_limits(rate_limits: RateLimits) -> Self {
         let db_name = Uuid::new_v4().to_string();
         let pg_pool = configure_postgresql(&db_name).await;
 
-        let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool)));
+        let user_store = Arc::new(RwLock::new(PostgresUserStore::new(pg_pool.clone())));
+
+        let refresh_token_store =
+            Arc::new(RwLock::new(PostgresRefreshTokenStore::new(pg_pool.clone())));
+
+        let totp_secret_store =
+            Arc::new(RwLock::new(PostgresTotpSecretStore::new(pg_pool.clone())));
+
+        let passkey_store = Arc::new(RwLock::new(PostgresPasskeyStore::new(pg_pool.clone())));
+
+        let recovery_code_store =
+            Arc::new(RwLock::new(PostgresRecoveryCodeStore::new(pg_pool.clone())));
+
+        let signing_key_store: SigningKeyStoreType =
+            Arc::new(RwLock::new(PostgresSigningKeyStore::new(pg_pool.clone())));
+
+        let oauth_client_store: OAuthClientStoreType =
+            Arc::new(RwLock::new(PostgresOAuthClientStore::new(pg_pool.clone())));
+
+        let user_identity_store =
+            Arc::new(RwLock::new(PostgresUserIdentityStore::new(pg_pool.clone())));
+
+        let personal_access_token_store: PersonalAccessTokenStoreType = Arc::new(RwLock::new(
+            PostgresPersonalAccessTokenStore::new(pg_pool.clone()),
+        ));
+
+        let session_store: SessionStoreType =
+            Arc::new(RwLock::new(PostgresSessionStore::new(pg_pool)));
+
+        let keyring: KeyringType = Arc::new(
+            Keyring::load(&signing_key_store)
+                .await
+                .expect("Failed to load JWT signing keys"),
+        );
 
         let redis_conn = Arc::new(RwLock::new(configure_redis()));
 
         let banned_token_store =
             Arc::new(RwLock::new(RedisBannedTokenStore::new(redis_conn.clone())));
 
-        let two_fa_code_store = Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_conn)));
+        let two_fa_code_store =
+            Arc::new(RwLock::new(RedisTwoFACodeStore::new(redis_conn.clone())));
+
+        let rate_limit_store =
+            Arc::new(RwLock::new(RedisRateLimitStore::new(redis_conn.clone())));
+
+        let passkey_ceremony_store =
+            Arc::new(RwLock::new(RedisPasskeyCeremonyStore::new(redis_conn.clone())));
+
+        let magic_link_store =
+            Arc::new(RwLock::new(RedisMagicLinkStore::new(redis_conn.clone())));
+
+        let authorization_code_store = Arc::new(RwLock::new(RedisAuthorizationCodeStore::new(
+            redis_conn.clone(),
+        )));
+
+        let device_code_store =
+            Arc::new(RwLock::new(RedisDeviceCodeStore::new(redis_conn.clone())));
+
+        let federated_login_store =
+            Arc::new(RwLock::new(RedisFederatedLoginStore::new(redis_conn.clone())));
+
+        let login_lockout_store =
+            Arc::new(RwLock::new(RedisLoginLockoutStore::new(redis_conn)));
 
         let email_server = MockServer::start().await;
         let base_url = email_server.uri();
         let email_client = Arc::new(configure_postmark_email_client(base_url));
 
+        let idp_server = MockServer::start().await;
+        let identity_provider = Arc::new(configure_identity_provider(idp_server.uri()));
+
         let app_state = AppState::new(
             user_store,
             banned_token_store.clone(),
             two_fa_code_store.clone(),
+            refresh_token_store,
+            rate_limit_store,
+            totp_secret_store,
+            passkey_store,
+            passkey_ceremony_store,
+            recovery_code_store,
+            magic_link_store,
+            signing_key_store.clone(),
+            keyring.clone(),
+            oauth_client_store.clone(),
+            authorization_code_store,
+            device_code_store,
+            federated_login_store,
+            user_identity_store,
+            personal_access_token_store.clone(),
+            session_store.clone(),
+            login_lockout_store,
             email_client,
+            Some(identity_provider),
         );
 
-        let app = Application::build(app_state, test::APP_ADDRESS)
+        let app = Application::build(app_state, rate_limits, test::APP_ADDRESS)
             .await
             .expect("Failed to build app");
 
@@ -78,8 +201,10 @@ impl TestApp {
 
         let cookie_jar = Arc::new(Jar::default());
 
+        // Redirects are left for the tests to inspect rather than followed.
         let http_client = reqwest::Client::builder()
             .cookie_provider(cookie_jar.clone())
+            .redirect(reqwest::redirect::Policy::none())
             .build()
             .unwrap();
 
@@ -91,24 +216,50 @@ impl TestApp {
             db_name,
             email_server,
             http_client,
+            idp_server,
+            keyring,
+            oauth_client_store,
+            personal_access_token_store,
+            session_store,
+            signing_key_store,
             two_fa_code_store,
         }
     }
 
     pub async fn get_root(&self) -> reqwest::Response {
         self.http_client
-            .get(&format!("{}/", &self.address))
+            .get(format!("{}/", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_jwks(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/.well-known/jwks.json", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_rotate_signing_key(&self, admin_token: Option<&str>) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .post(format!("{}/admin/signing-keys/rotate", &self.address));
+
+        if let Some(admin_token) = admin_token {
+            request = request.bearer_auth(admin_token);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
     pub async fn post_signup<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/signup", &self.address))
+            .post(format!("{}/signup", &self.address))
             .json(body)
             .send()
             .await
@@ -120,7 +271,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.http_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
             .json(body)
             .send()
             .await
@@ -129,12 +280,40 @@ impl TestApp {
 
     pub async fn post_logout(&self) -> reqwest::Response {
         self.http_client
-            .post(&format!("{}/logout", &self.address))
+            .post(format!("{}/logout", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_logout_all(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/logout-all", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
+    pub async fn post_admin_logout_all<Body>(
+        &self,
+        admin_token: Option<&str>,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .post(format!("{}/admin/logout-all", &self.address))
+            .json(body);
+
+        if let Some(admin_token) = admin_token {
+            request = request.bearer_auth(admin_token);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
     pub async fn post_verify_2fa<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
@@ -159,49 +338,1093 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
-    pub async fn clean_up(&mut self) {
-        if self.clean_up_called {
-            return;
+    /// Registers a new OpenID Connect client redirecting to `TEST_REDIRECT_URI` and returns
+    /// its id and secret.
+    pub async fn register_oauth_client(&self) -> (String, String) {
+        self.register_oauth_client_with_grant_types(&[GrantType::AuthorizationCode])
+            .await
+    }
+
+    /// Registers a client through the admin API and returns its id and secret.
+    pub async fn create_oauth_client(
+        &self,
+        grant_types: &[&str],
+        scopes: &[&str],
+    ) -> (String, String) {
+        let response = self
+            .post_oauth_client(
+                Some(admin_token()),
+                &serde_json::json!({
+                    "allowedScopes": scopes,
+                    "allowedGrantTypes": grant_types
+                }),
+            )
+            .await;
+
+        assert_eq!(response.status().as_u16(), 201);
+
+        let response = response
+            .json::<OAuthClientSecretResponse>()
+            .await
+            .expect("Could not deserialize response body to OAuthClientSecretResponse");
+
+        (response.client_id, response.client_secret)
+    }
+
+    /// Like `register_oauth_client`, but allowed `grant_types` instead.
+    pub async fn register_oauth_client_with_grant_types(
+        &self,
+        grant_types: &[GrantType],
+    ) -> (String, String) {
+        let client = OAuthClient {
+            client_id: Uuid::new_v4().to_string(),
+            client_name: None,
+            redirect_uris: vec![TEST_REDIRECT_URI.to_owned()],
+            allowed_scopes: vec!["openid".to_owned(), "email".to_owned()],
+            allowed_grant_types: grant_types.to_vec(),
+        };
+        let client_secret = ClientSecret::default();
+
+        self.oauth_client_store
+            .write()
+            .await
+            .add_client(&client, &client_secret, None)
+            .await
+            .expect("Failed to register OAuth client");
+
+        (
+            client.client_id,
+            client_secret.as_ref().expose_secret().to_owned(),
+        )
+    }
+
+    pub async fn post_oauth_client<Body>(
+        &self,
+        admin_token: Option<&str>,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .post(format!("{}/admin/oauth-clients", &self.address))
+            .json(body);
+
+        if let Some(admin_token) = admin_token {
+            request = request.bearer_auth(admin_token);
         }
 
-        delete_database(&self.db_name).await;
+        request.send().await.expect("Failed to execute request.")
+    }
 
-        self.clean_up_called = true;
+    pub async fn get_oauth_clients(&self, admin_token: Option<&str>) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .get(format!("{}/admin/oauth-clients", &self.address));
+
+        if let Some(admin_token) = admin_token {
+            request = request.bearer_auth(admin_token);
+        }
+
+        request.send().await.expect("Failed to execute request.")
     }
-}
 
-pub fn get_random_email() -> String {
-    format!("{}@example.com", Uuid::new_v4())
-}
+    pub async fn get_oauth_client(
+        &self,
+        admin_token: Option<&str>,
+        client_id: &str,
+    ) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .get(format!("{}/admin/oauth-clients/{}", &self.address, client_id));
 
-async fn configure_postgresql(db_name: &str) -> PgPool {
-    let postgresql_conn_url = DATABASE_URL.to_owned();
+        if let Some(admin_token) = admin_token {
+            request = request.bearer_auth(admin_token);
+        }
 
-    configure_database(&postgresql_conn_url, db_name).await;
+        request.send().await.expect("Failed to execute request.")
+    }
 
-    let postgresql_conn_url_with_db = SecretString::new(
-        format!("{}/{}", postgresql_conn_url.expose_secret(), db_name).into_boxed_str(),
-    );
+    pub async fn put_oauth_client<Body>(
+        &self,
+        admin_token: Option<&str>,
+        client_id: &str,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .put(format!("{}/admin/oauth-clients/{}", &self.address, client_id))
+            .json(body);
 
-    get_postgres_pool(&postgresql_conn_url_with_db)
-        .await
-        .expect("Failed to create Postgres connection pool!")
-}
+        if let Some(admin_token) = admin_token {
+            request = request.bearer_auth(admin_token);
+        }
 
-fn configure_postmark_email_client(base_url: String) -> PostmarkEmailClient {
-    let postmark_auth_token = SecretString::new("auth_token".to_owned().into_boxed_str());
+        request.send().await.expect("Failed to execute request.")
+    }
 
-    let sender = Email::parse(SecretString::new(
-        test::email_client::SENDER.to_owned().into_boxed_str(),
-    ))
-    .unwrap();
+    pub async fn delete_oauth_client(
+        &self,
+        admin_token: Option<&str>,
+        client_id: &str,
+    ) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .delete(format!("{}/admin/oauth-clients/{}", &self.address, client_id));
 
-    let http_client = Client::builder()
-        .timeout(test::email_client::TIMEOUT)
-        .build()
-        .expect("Failed to build HTTP client");
+        if let Some(admin_token) = admin_token {
+            request = request.bearer_auth(admin_token);
+        }
 
-    PostmarkEmailClient::new(base_url, sender, postmark_auth_token, http_client)
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn post_rotate_oauth_client_secret(
+        &self,
+        admin_token: Option<&str>,
+        client_id: &str,
+    ) -> reqwest::Response {
+        let mut request = self.http_client.post(format!(
+            "{}/admin/oauth-clients/{}/secret",
+            &self.address, client_id
+        ));
+
+        if let Some(admin_token) = admin_token {
+            request = request.bearer_auth(admin_token);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn post_initial_access_token(&self, admin_token: Option<&str>) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .post(format!("{}/admin/initial-access-tokens", &self.address));
+
+        if let Some(admin_token) = admin_token {
+            request = request.bearer_auth(admin_token);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn post_register<Body>(&self, token: Option<&str>, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .post(format!("{}/register", &self.address))
+            .json(body);
+
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn get_registration(
+        &self,
+        token: Option<&str>,
+        client_id: &str,
+    ) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .get(format!("{}/register/{}", &self.address, client_id));
+
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn put_registration<Body>(
+        &self,
+        token: Option<&str>,
+        client_id: &str,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .put(format!("{}/register/{}", &self.address, client_id))
+            .json(body);
+
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn delete_registration(
+        &self,
+        token: Option<&str>,
+        client_id: &str,
+    ) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .delete(format!("{}/register/{}", &self.address, client_id));
+
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn get_openid_configuration(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/.well-known/openid-configuration", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_authorize<Query>(&self, query: &Query) -> reqwest::Response
+    where
+        Query: serde::Serialize,
+    {
+        self.http_client
+            .get(format!("{}/authorize", &self.address))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_federated_login<Query>(&self, query: &Query) -> reqwest::Response
+    where
+        Query: serde::Serialize,
+    {
+        self.http_client
+            .get(format!("{}/federated/login", &self.address))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_federated_callback<Query>(&self, query: &Query) -> reqwest::Response
+    where
+        Query: serde::Serialize,
+    {
+        self.http_client
+            .get(format!("{}/federated/callback", &self.address))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_federated_identities(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/federated/identities", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_federated_link<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/federated/link", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_federated_unlink<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/federated/unlink", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_personal_access_tokens(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/personal-access-tokens", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_personal_access_token<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/personal-access-tokens", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Creates a personal access token for the logged in user and returns it.
+    pub async fn create_personal_access_token(&self) -> String {
+        let response = self
+            .post_personal_access_token(&serde_json::json!({
+                "name": "CI deploys",
+                "scopes": ["deploy"]
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 201);
+
+        response
+            .json::<PersonalAccessTokenResponse>()
+            .await
+            .expect("Could not deserialize response body to PersonalAccessTokenResponse")
+            .token
+            .expect("No token in response")
+    }
+
+    pub async fn delete_personal_access_token(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/personal-access-tokens/{}", &self.address, id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_sessions(&self) -> reqwest::Response {
+        self.http_client
+            .get(format!("{}/sessions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn delete_session(&self, id: &str) -> reqwest::Response {
+        self.http_client
+            .delete(format!("{}/sessions/{}", &self.address, id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Posts `body` as a form, authenticating as `client` with HTTP Basic when given.
+    pub async fn post_token<Body>(
+        &self,
+        client: Option<(&str, &str)>,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .post(format!("{}/token", &self.address))
+            .form(body);
+
+        if let Some((client_id, client_secret)) = client {
+            request = request.basic_auth(client_id, Some(client_secret));
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn post_device_code<Body>(
+        &self,
+        client: Option<(&str, &str)>,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .post(format!("{}/device/code", &self.address))
+            .form(body);
+
+        if let Some((client_id, client_secret)) = client {
+            request = request.basic_auth(client_id, Some(client_secret));
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn post_device_lookup<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/device/lookup", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_device_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/device/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_userinfo(&self, access_token: Option<&str>) -> reqwest::Response {
+        let mut request = self
+            .http_client
+            .get(format!("{}/userinfo", &self.address));
+
+        if let Some(access_token) = access_token {
+            request = request.bearer_auth(access_token);
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn introspect(&self, client: (&str, &str), token: &str) -> IntrospectResponse {
+        let response = self
+            .post_introspect(Some(client), &[("token", token)])
+            .await;
+
+        assert_eq!(response.status().as_u16(), 200);
+
+        response
+            .json::<IntrospectResponse>()
+            .await
+            .expect("Could not deserialize response body to IntrospectResponse")
+    }
+
+    /// Posts `body` as a form, authenticating as `client` with HTTP Basic when given.
+    pub async fn post_introspect<Body>(
+        &self,
+        client: Option<(&str, &str)>,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .post(format!("{}/oauth/introspect", &self.address))
+            .form(body);
+
+        if let Some((client_id, client_secret)) = client {
+            request = request.basic_auth(client_id, Some(client_secret));
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    /// Posts `body` as a form, authenticating as `client` with HTTP Basic when given.
+    pub async fn post_revoke<Body>(
+        &self,
+        client: Option<(&str, &str)>,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        let mut request = self
+            .http_client
+            .post(format!("{}/oauth/revoke", &self.address))
+            .form(body);
+
+        if let Some((client_id, client_secret)) = client {
+            request = request.basic_auth(client_id, Some(client_secret));
+        }
+
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    pub async fn post_refresh_token(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/token/refresh", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset_request<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-reset/request", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_password_reset_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/password-reset/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_unlock_account<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/login/unlock", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-email", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_resend_verification_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-email/resend", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_totp_enroll(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/2fa/totp/enroll", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_totp_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/2fa/totp/confirm", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn put_two_fa_method<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .put(format!("{}/2fa/method", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_register_start(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/passkeys/register/start", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_register_finish<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/passkeys/register/finish", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_login_start<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/login/passkey/start", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_passkey_login_finish<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/login/passkey/finish", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_2fa_passkey_start<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-2fa/passkey/start", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_2fa_passkey_finish<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-2fa/passkey/finish", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_recovery_codes(&self) -> reqwest::Response {
+        self.http_client
+            .post(format!("{}/2fa/recovery-codes", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_verify_2fa_recovery<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/verify-2fa/recovery", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_magic_link<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/login/magic-link", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_magic_link_callback<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.http_client
+            .post(format!("{}/login/magic-link/callback", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Confirms `email` using the verification link sent to it on signup.
+    pub async fn verify_email(&self, email: &str) {
+        let token = self.get_link_param_from_email_to(email, "verify_token").await;
+
+        let response = self
+            .post_verify_email(&serde_json::json!({ "token": token }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    /// Signs up `email` with the password "password123" and verifies the address.
+    pub async fn signup(&self, email: &str, requires_2fa: bool) {
+        let response = self
+            .post_signup(&serde_json::json!({
+                "email": email,
+                "password": "password123",
+                "requires2FA": requires_2fa
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 201);
+
+        self.verify_email(email).await;
+    }
+
+    /// Logs in a user without 2FA who signed up with `signup`, leaving the session in the
+    /// cookie jar.
+    pub async fn login(&self, email: &str) -> reqwest::Response {
+        let response = self
+            .post_login(&serde_json::json!({
+                "email": email,
+                "password": "password123",
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 200);
+
+        response
+    }
+
+    /// Signs up and logs in `email`, entering the emailed code if the user has 2FA, and
+    /// returns the response that started the session.
+    pub async fn signup_and_login(&self, email: &str, requires_2fa: bool) -> reqwest::Response {
+        if !requires_2fa {
+            self.signup(email, false).await;
+            return self.login(email).await;
+        }
+
+        Mock::given(path("/email"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&self.email_server)
+            .await;
+
+        self.signup(email, true).await;
+
+        let response = self
+            .post_login(&serde_json::json!({
+                "email": email,
+                "password": "password123",
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 206);
+
+        let login_attempt_id = response
+            .json::<TwoFactorAuthResponse>()
+            .await
+            .expect("Could not deserialize response body to TwoFactorAuthResponse")
+            .login_attempt_id;
+
+        let (_, code) = self
+            .two_fa_code_store
+            .read()
+            .await
+            .get_code(&Email::parse(SecretString::new(email.into())).unwrap())
+            .await
+            .unwrap();
+
+        let response = self
+            .post_verify_2fa(&serde_json::json!({
+                "email": email,
+                "loginAttemptId": login_attempt_id,
+                "2FACode": code.as_ref().expose_secret(),
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 200);
+
+        response
+    }
+
+    /// Pulls the value of the `param` query parameter out of the link in the most recent
+    /// email received by the mock email server.
+    pub async fn get_link_param_from_last_email(&self, param: &str) -> String {
+        self.wait_for_sent_email(|emails| {
+            emails.last().and_then(|email| get_link_param(email, param))
+        })
+        .await
+        .expect("No email with the parameter was sent")
+    }
+
+    /// Pulls the value of the `param` query parameter out of the `n`th email, counting from
+    /// 1, whose link carries it; for routes that send several.
+    pub async fn get_link_param_from_nth_email(&self, n: usize, param: &str) -> String {
+        self.wait_for_sent_email(|emails| {
+            emails
+                .iter()
+                .filter_map(|email| get_link_param(email, param))
+                .nth(n - 1)
+        })
+        .await
+        .expect("No email with the parameter was sent")
+    }
+
+    /// Pulls the value of the `param` query parameter out of the most recent email sent to
+    /// `recipient` whose link carries it.
+    pub async fn get_link_param_from_email_to(&self, recipient: &str, param: &str) -> String {
+        self.wait_for_sent_email(|emails| {
+            emails
+                .iter()
+                .rev()
+                .filter(|email| email["To"].as_str() == Some(recipient))
+                .find_map(|email| get_link_param(email, param))
+        })
+        .await
+        .expect("No matching email was sent")
+    }
+
+    // Some routes send their emails in the background, so they can arrive just after the
+    // response.
+    async fn wait_for_sent_email<T>(
+        &self,
+        find: impl Fn(&[serde_json::Value]) -> Option<T>,
+    ) -> Option<T> {
+        for _ in 0..50 {
+            if let Some(found) = find(&self.get_sent_emails().await) {
+                return Some(found);
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+
+        None
+    }
+
+    /// Returns the bodies of all emails sent to `recipient` with the given subject.
+    pub async fn get_emails_to(&self, recipient: &str, subject: &str) -> Vec<String> {
+        self.get_sent_emails()
+            .await
+            .iter()
+            .filter(|email| {
+                email["To"].as_str() == Some(recipient) && email["Subject"].as_str() == Some(subject)
+            })
+            .filter_map(|email| email["TextBody"].as_str().map(str::to_owned))
+            .collect()
+    }
+
+    async fn get_sent_emails(&self) -> Vec<serde_json::Value> {
+        self.email_server
+            .received_requests()
+            .await
+            .expect("Request recording is disabled")
+            .iter()
+            .map(|request| {
+                serde_json::from_slice(&request.body).expect("Email body is not valid JSON")
+            })
+            .collect()
+    }
+
+    pub async fn clean_up(&mut self) {
+        if self.clean_up_called {
+            return;
+        }
+
+        delete_database(&self.db_name).await;
+
+        self.clean_up_called = true;
+    }
+}
+
+fn get_link_param(email: &serde_json::Value, param: &str) -> Option<String> {
+    let text = email["TextBody"].as_str()?;
+
+    let prefix = format!("{}=", param);
+    let start = text.find(&prefix)? + prefix.len();
+
+    text[start..]
+        .split(|c: char| c.is_whitespace() || c == '&')
+        .next()
+        .map(str::to_owned)
+}
+
+pub fn get_random_email() -> String {
+    format!("{}@example.com", Uuid::new_v4())
+}
+
+pub fn admin_token() -> &'static str {
+    ADMIN_API_TOKEN
+        .as_ref()
+        .expect("ADMIN_API_TOKEN must be set to run these tests")
+        .expose_secret()
+}
+
+/// The access token in the JWT cookie `response` sets.
+pub fn auth_token(response: &reqwest::Response) -> String {
+    response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned()
+}
+
+/// The refresh token in the refresh cookie `response` sets.
+pub fn refresh_token(response: &reqwest::Response) -> String {
+    response
+        .cookies()
+        .find(|cookie| cookie.name() == REFRESH_COOKIE_NAME)
+        .expect("No refresh cookie found")
+        .value()
+        .to_owned()
+}
+
+pub async fn assert_oauth_error(response: reqwest::Response, status: u16, error: &str) {
+    assert_eq!(response.status().as_u16(), status);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        error
+    );
+}
+
+/// Asserts that `response` clears both auth cookies for "/", the path they were set for.
+/// A removal cookie for any other path leaves them in the browser.
+pub fn assert_auth_cookies_removed(response: &reqwest::Response) {
+    for name in [JWT_COOKIE_NAME, REFRESH_COOKIE_NAME] {
+        let cookie = response
+            .cookies()
+            .find(|cookie| cookie.name() == name)
+            .unwrap_or_else(|| panic!("No removal cookie for {}", name));
+
+        assert!(cookie.value().is_empty());
+        assert_eq!(cookie.path(), Some("/"));
+    }
+}
+
+// Every test app is called from 127.0.0.1 and shares one Redis, so limits by address are
+// raised far enough that the whole suite never reaches them.
+pub fn code_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+/// A valid authorization request for `client_id`, for tests to tweak.
+pub fn authorize_request(client_id: &str) -> Vec<(&'static str, String)> {
+    vec![
+        ("response_type", "code".to_owned()),
+        ("client_id", client_id.to_owned()),
+        ("redirect_uri", TEST_REDIRECT_URI.to_owned()),
+        ("scope", "openid email".to_owned()),
+        ("state", "af0ifjsldkj".to_owned()),
+        ("nonce", "n-0S6_WzA2Mj".to_owned()),
+        ("code_challenge", code_challenge(CODE_VERIFIER)),
+        ("code_challenge_method", "S256".to_owned()),
+    ]
+}
+
+pub fn location(response: &reqwest::Response) -> Url {
+    assert_eq!(response.status().as_u16(), 303);
+
+    let location = response
+        .headers()
+        .get("location")
+        .and_then(|value| value.to_str().ok())
+        .expect("No location header");
+
+    Url::parse(location).expect("Location is not a URL")
+}
+
+pub fn query_param(url: &Url, name: &str) -> Option<String> {
+    url.query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+/// Runs `/authorize` for a logged in user and returns the code it redirects back with.
+pub async fn authorize(app: &TestApp, client_id: &str) -> String {
+    let response = app.get_authorize(&authorize_request(client_id)).await;
+    let callback = location(&response);
+
+    assert!(callback.as_str().starts_with(TEST_REDIRECT_URI));
+    assert_eq!(
+        query_param(&callback, "state").as_deref(),
+        Some("af0ifjsldkj")
+    );
+
+    query_param(&callback, "code").expect("No code in redirect")
+}
+
+pub fn token_request(code: &str) -> Vec<(&'static str, String)> {
+    vec![
+        ("grant_type", "authorization_code".to_owned()),
+        ("code", code.to_owned()),
+        ("redirect_uri", TEST_REDIRECT_URI.to_owned()),
+        ("code_verifier", CODE_VERIFIER.to_owned()),
+    ]
+}
+
+fn test_rate_limits() -> RateLimits {
+    let mut rate_limits = RateLimits::default();
+    rate_limits.signup.quota = RateLimitQuota::new(1_000_000, 3600);
+    rate_limits.passkey_login.quota = RateLimitQuota::new(1_000_000, 60);
+    rate_limits.unlock.quota = RateLimitQuota::new(1_000_000, 60);
+    rate_limits
+}
+
+async fn configure_postgresql(db_name: &str) -> PgPool {
+    let postgresql_conn_url = DATABASE_URL.to_owned();
+
+    configure_database(&postgresql_conn_url, db_name).await;
+
+    let postgresql_conn_url_with_db = SecretString::new(
+        format!("{}/{}", postgresql_conn_url.expose_secret(), db_name).into_boxed_str(),
+    );
+
+    get_postgres_pool(&postgresql_conn_url_with_db)
+        .await
+        .expect("Failed to create Postgres connection pool!")
+}
+
+fn configure_postmark_email_client(base_url: String) -> PostmarkEmailClient {
+    let postmark_auth_token = SecretString::new("auth_token".to_owned().into_boxed_str());
+
+    let sender = Email::parse(SecretString::new(
+        test::email_client::SENDER.to_owned().into_boxed_str(),
+    ))
+    .unwrap();
+
+    let http_client = Client::builder()
+        .timeout(test::email_client::TIMEOUT)
+        .build()
+        .expect("Failed to build HTTP client");
+
+    PostmarkEmailClient::new(base_url, sender, postmark_auth_token, http_client)
+}
+
+fn configure_identity_provider(issuer: String) -> OidcIdentityProvider {
+    let http_client = Client::builder()
+        .timeout(test::identity_provider::TIMEOUT)
+        .build()
+        .expect("Failed to build HTTP client");
+
+    OidcIdentityProvider::new(
+        issuer,
+        test::identity_provider::CLIENT_ID.to_owned(),
+        SecretString::new(test::identity_provider::CLIENT_SECRET.to_owned().into_boxed_str()),
+        http_client,
+    )
 }
 
 async fn configure_database(db_conn_string: &SecretString, db_name: &str) {
@@ -235,7 +1458,7 @@ async fn configure_database(db_conn_string: &SecretString, db_name: &str) {
 async fn delete_database(db_name: &str) {
     let postgresql_conn_url = DATABASE_URL.to_owned();
 
-    let connection_options = PgConnectOptions::from_str(&postgresql_conn_url.expose_secret())
+    let connection_options = PgConnectOptions::from_str(postgresql_conn_url.expose_secret())
         .expect("Failed to parse PostgreSQL connection string");
 
     let mut connection = PgConnection::connect_with(&connection_options)
@@ -0,0 +1,228 @@
+use auth_service::{
+    domain::AuthMethod,
+    routes::PersonalAccessTokenResponse,
+    utils::{auth::first_party_audience, constants::JWT_ISSUER},
+    ErrorResponse,
+};
+
+use crate::helpers::{assert_oauth_error, auth_token, get_random_email, refresh_token, TestApp};
+
+#[tokio::test]
+async fn should_describe_active_token() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = app.register_oauth_client().await;
+    let email = get_random_email();
+    let token = auth_token(&app.signup_and_login(&email, false).await);
+
+    let response = app.introspect((&client_id, &client_secret), &token).await;
+
+    assert!(response.active);
+    assert_eq!(response.sub, Some(email));
+    assert_eq!(response.aud, Some(vec![first_party_audience().to_owned()]));
+    assert_eq!(response.iss, Some(JWT_ISSUER.clone()));
+    assert_eq!(response.amr, Some(vec![AuthMethod::Password]));
+    assert_eq!(response.token_type.as_deref(), Some("Bearer"));
+    assert_eq!(response.scope, None);
+
+    let iat = response.iat.expect("No iat");
+    let exp = response.exp.expect("No exp");
+    assert!(exp > iat);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_describe_personal_access_token_until_revoked() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = app.register_oauth_client().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, false).await;
+
+    let response = app
+        .post_personal_access_token(&serde_json::json!({
+            "name": "CI deploys",
+            "scopes": ["deploy", "read:logs"],
+            "expiresInDays": 30
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let created = response
+        .json::<PersonalAccessTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to PersonalAccessTokenResponse");
+    let token = created.token.expect("No token in response");
+
+    let response = app.introspect((&client_id, &client_secret), &token).await;
+
+    assert!(response.active);
+    assert_eq!(response.sub, Some(email));
+    assert_eq!(response.scope.as_deref(), Some("deploy read:logs"));
+    assert_eq!(response.token_type.as_deref(), Some("Bearer"));
+    let expires_at = created.expires_at.expect("No expiry");
+    let expires_at = chrono::DateTime::parse_from_rfc3339(&expires_at).expect("Invalid expiry");
+    assert_eq!(response.exp, Some(expires_at.timestamp() as usize));
+
+    let response = app
+        .delete_personal_access_token(&created.id.to_string())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = app.introspect((&client_id, &client_secret), &token).await;
+
+    assert!(!response.active);
+    assert_eq!(response.sub, None);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_report_second_factor() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = app.register_oauth_client().await;
+    let token = auth_token(&app.signup_and_login(&get_random_email(), true).await);
+
+    let response = app.introspect((&client_id, &client_secret), &token).await;
+
+    assert!(response.active);
+    assert_eq!(
+        response.amr,
+        Some(vec![
+            AuthMethod::Password,
+            AuthMethod::OneTimeCode,
+            AuthMethod::MultiFactor
+        ])
+    );
+
+    // Refreshing the session keeps the methods the user originally logged in with.
+    let response = app.post_refresh_token().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let token = auth_token(&response);
+
+    let response = app.introspect((&client_id, &client_secret), &token).await;
+
+    assert!(response.active);
+    assert_eq!(
+        response.amr,
+        Some(vec![
+            AuthMethod::Password,
+            AuthMethod::OneTimeCode,
+            AuthMethod::MultiFactor
+        ])
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_report_banned_token_inactive() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = app.register_oauth_client().await;
+    let token = auth_token(&app.signup_and_login(&get_random_email(), false).await);
+
+    let response = app.post_logout().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.introspect((&client_id, &client_secret), &token).await;
+
+    assert!(!response.active);
+    assert_eq!(response.sub, None);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_report_unknown_tokens_inactive() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = app.register_oauth_client().await;
+
+    let email = get_random_email();
+    let response = app.signup_and_login(&email, false).await;
+
+    let refresh_token = refresh_token(&response);
+
+    // A purpose token is signed by us but is not an access token.
+    let verify_token = app
+        .get_link_param_from_email_to(&email, "verify_token")
+        .await;
+
+    let test_cases = ["", "invalid_token", &refresh_token, &verify_token];
+
+    for token in test_cases {
+        let response = app.introspect((&client_id, &client_secret), token).await;
+
+        assert!(!response.active, "Failed for input: {:?}", token);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_client_not_authenticated() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = app.register_oauth_client().await;
+    let token = auth_token(&app.signup_and_login(&get_random_email(), false).await);
+
+    let test_cases = [
+        None,
+        Some((client_id.as_str(), "wrong-secret")),
+        Some(("unknown-client", "wrong-secret")),
+    ];
+
+    for client in test_cases {
+        let response = app.post_introspect(client, &[("token", &token)]).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Failed for input: {:?}",
+            client
+        );
+        assert_eq!(
+            response
+                .headers()
+                .get("www-authenticate")
+                .and_then(|value| value.to_str().ok()),
+            Some("Basic")
+        );
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "invalid_client"
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_token_missing() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = app.register_oauth_client().await;
+
+    let response = app
+        .post_introspect(
+            Some((&client_id, &client_secret)),
+            &[("token_type_hint", "access_token")],
+        )
+        .await;
+
+    assert_oauth_error(response, 400, "invalid_request").await;
+
+    app.clean_up().await;
+}
@@ -0,0 +1,100 @@
+use auth_service::utils::constants::{DEFAULT_JWT_AUDIENCE, JWT_ISSUER};
+use jsonwebtoken::{
+    dangerous, decode, decode_header, encode, jwk::JwkSet, DecodingKey, EncodingKey, Header,
+    Validation,
+};
+
+use crate::helpers::{auth_token, get_random_email, TestApp};
+
+#[tokio::test]
+async fn jwks_should_verify_issued_tokens() {
+    let mut app = TestApp::new().await;
+
+    let token = auth_token(&app.signup_and_login(&get_random_email(), false).await);
+
+    let response = app.get_jwks().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let jwk_set = response
+        .json::<JwkSet>()
+        .await
+        .expect("Could not deserialize response body to JwkSet");
+
+    let header = decode_header(&token).expect("Token has no valid header");
+    let kid = header.kid.expect("Token has no kid");
+    let jwk = jwk_set
+        .find(&kid)
+        .expect("No published key matches the kid");
+
+    // This is what a downstream service does to check a token without calling us.
+    let mut validation = Validation::new(header.alg);
+    validation.set_audience(&[DEFAULT_JWT_AUDIENCE]);
+    validation.set_issuer(&[JWT_ISSUER.as_str()]);
+
+    let claims = decode::<serde_json::Value>(
+        &token,
+        &DecodingKey::from_jwk(jwk).expect("Published key is unusable"),
+        &validation,
+    )
+    .expect("Token did not verify against the published key")
+    .claims;
+
+    assert!(claims["sub"].is_string());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn jwks_should_not_publish_private_key_material() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_jwks().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<serde_json::Value>()
+        .await
+        .expect("Could not deserialize response body");
+
+    for key in body["keys"].as_array().expect("No keys published") {
+        assert!(key.get("d").is_none(), "Private key published: {}", key);
+        assert!(key.get("k").is_none(), "Symmetric key published: {}", key);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reject_hmac_signed_token() {
+    let mut app = TestApp::new().await;
+
+    let token = auth_token(&app.signup_and_login(&get_random_email(), false).await);
+
+    // Re-sign the real claims with a shared secret, keeping the real kid, the way tokens
+    // were signed before asymmetric keys.
+    let header = decode_header(&token).expect("Token has no valid header");
+    let claims = dangerous::insecure_decode::<serde_json::Value>(&token)
+        .expect("Could not read token claims")
+        .claims;
+
+    let forged_header = Header {
+        kid: header.kid,
+        ..Header::default()
+    };
+    let forged_token = encode(
+        &forged_header,
+        &claims,
+        &EncodingKey::from_secret(b"secret"),
+    )
+    .expect("Could not sign token");
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": forged_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
@@ -3,6 +3,7 @@ use auth_service::{
     domain::Email, routes::TwoFactorAuthResponse, utils::constants::JWT_COOKIE_NAME, ErrorResponse,
 };
 use secrecy::{ExposeSecret, SecretString};
+use std::time::{Duration, Instant};
 use wiremock::matchers::{method, path};
 use wiremock::{Mock, ResponseTemplate};
 
@@ -136,6 +137,52 @@ async fn should_return_401_if_incorrect_credentials() {
     app.clean_up().await;
 }
 
+#[tokio::test]
+async fn should_not_reveal_whether_account_exists() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    signup(&app, &email).await;
+
+    // The fastest of a few runs is the least disturbed by other tests running alongside.
+    // Three wrong passwords stay clear of the lockout.
+    let mut known = Duration::MAX;
+    let mut unknown = Duration::MAX;
+    for _ in 0..3 {
+        let started = Instant::now();
+        let known_response = login_with(&app, &email, "wrongpassword").await;
+        known = known.min(started.elapsed());
+
+        let started = Instant::now();
+        let unknown_response = login_with(&app, &get_random_email(), "wrongpassword").await;
+        unknown = unknown.min(started.elapsed());
+
+        assert_eq!(known_response.status().as_u16(), 401);
+        assert_eq!(unknown_response.status().as_u16(), 401);
+        assert_eq!(
+            known_response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            unknown_response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error
+        );
+    }
+
+    assert!(
+        unknown * 2 > known,
+        "unknown account took {:?}, known account {:?}",
+        unknown,
+        known
+    );
+
+    app.clean_up().await;
+}
+
 #[tokio::test]
 async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
     let mut app = TestApp::new().await;
@@ -152,6 +199,8 @@ async fn should_return_200_if_valid_credentials_and_2fa_disabled() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     let login_body = serde_json::json!({
         "email": random_email,
         "password": "password123",
@@ -188,6 +237,8 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
 
         assert_eq!(response.status().as_u16(), 201);
 
+        app.verify_email(&random_email).await;
+
         Mock::given(path("/email"))
             .and(method("POST"))
             .respond_with(ResponseTemplate::new(200))
@@ -226,3 +277,157 @@ async fn should_return_206_if_valid_credentials_and_2fa_enabled() {
 
     app.clean_up().await;
 }
+
+async fn signup(app: &TestApp, email: &str) {
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(email).await;
+}
+
+async fn login_with(app: &TestApp, email: &str, password: &str) -> reqwest::Response {
+    app.post_login(&serde_json::json!({
+        "email": email,
+        "password": password,
+    }))
+    .await
+}
+
+const LOCKED_SUBJECT: &str = "Your account has been locked";
+
+#[tokio::test]
+async fn should_lock_account_after_too_many_failed_logins() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    signup(&app, &email).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    for _ in 0..4 {
+        let response = login_with(&app, &email, "wrongpassword").await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = login_with(&app, &email, "wrongpassword").await;
+
+    assert_eq!(response.status().as_u16(), 423);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Account temporarily locked".to_owned()
+    );
+
+    // Even the right password is turned away while the account is locked.
+    let response = login_with(&app, &email, "password123").await;
+
+    assert_eq!(response.status().as_u16(), 423);
+
+    let token = app
+        .get_link_param_from_email_to(&email, "unlock_token")
+        .await;
+
+    assert_eq!(app.get_emails_to(&email, LOCKED_SUBJECT).await.len(), 1);
+
+    let response = app
+        .post_unlock_account(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = login_with(&app, &email, "password123").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_lock_unknown_account_without_sending_email() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    for _ in 0..4 {
+        let response = login_with(&app, &email, "wrongpassword").await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = login_with(&app, &email, "wrongpassword").await;
+
+    assert_eq!(response.status().as_u16(), 423);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_forget_failed_logins_after_successful_login() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    signup(&app, &email).await;
+
+    for _ in 0..4 {
+        let response = login_with(&app, &email, "wrongpassword").await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    let response = login_with(&app, &email, "password123").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = login_with(&app, &email, "wrongpassword").await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_unlock_token_invalid() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    signup(&app, &email).await;
+
+    // A token for another purpose doesn't unlock anything either.
+    let verify_token = app
+        .get_link_param_from_email_to(&email, "verify_token")
+        .await;
+
+    for token in ["invalid", verify_token.as_str()] {
+        let response = app
+            .post_unlock_account(&serde_json::json!({ "token": token }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    app.clean_up().await;
+}
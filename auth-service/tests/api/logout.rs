@@ -2,7 +2,7 @@ use auth_service::{utils::constants::JWT_COOKIE_NAME, ErrorResponse};
 use reqwest::Url;
 use secrecy::SecretString;
 
-use crate::helpers::{get_random_email, TestApp};
+use crate::helpers::{admin_token, auth_token, get_random_email, TestApp};
 
 #[tokio::test]
 async fn should_return_400_if_jwt_cookie_missing() {
@@ -71,6 +71,8 @@ async fn should_return_200_if_valid_jwt_cookie() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&email).await;
+
     let login_body = serde_json::json!({
         "email": email,
         "password": "password123",
@@ -128,6 +130,8 @@ async fn should_return_400_if_logout_called_twice_in_a_row() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&email).await;
+
     let login_body = serde_json::json!({
         "email": email,
         "password": "password123",
@@ -168,3 +172,164 @@ async fn should_return_400_if_logout_called_twice_in_a_row() {
 
     app.clean_up().await;
 }
+
+async fn assert_token_rejected(app: &TestApp, token: &str) {
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn logout_all_should_end_every_session() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    app.signup(&email, false).await;
+    // Logging in again replaces the first session's cookies, as if it were on another
+    // device.
+    let other_token = auth_token(&app.login(&email).await);
+    let current_token = auth_token(&app.login(&email).await);
+    let personal_access_token = app.create_personal_access_token().await;
+
+    let response = app.post_logout_all().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(auth_cookie.value().is_empty());
+
+    assert_token_rejected(&app, &other_token).await;
+    assert_token_rejected(&app, &current_token).await;
+    assert_token_rejected(&app, &personal_access_token).await;
+
+    let response = app.get_sessions().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn logout_all_should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_logout_all().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn logout_all_should_not_affect_other_users() {
+    let mut app = TestApp::new().await;
+
+    let first_email = get_random_email();
+    app.signup(&first_email, false).await;
+    let first_token = auth_token(&app.login(&first_email).await);
+
+    let second_email = get_random_email();
+    app.signup(&second_email, false).await;
+    app.login(&second_email).await;
+
+    let response = app.post_logout_all().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": first_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn admin_logout_all_should_end_every_session_of_user() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    app.signup(&email, false).await;
+    let token = auth_token(&app.login(&email).await);
+    let personal_access_token = app.create_personal_access_token().await;
+
+    let response = app
+        .post_admin_logout_all(Some(admin_token()), &serde_json::json!({ "email": email }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 204);
+
+    assert_token_rejected(&app, &token).await;
+    assert_token_rejected(&app, &personal_access_token).await;
+
+    // The user's own cookies are still set, but no longer work.
+    let response = app.post_refresh_token().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn admin_logout_all_should_return_404_if_user_unknown() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_admin_logout_all(
+            Some(admin_token()),
+            &serde_json::json!({ "email": get_random_email() }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "User not found".to_owned()
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn admin_logout_all_should_require_admin_token() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    app.signup(&email, false).await;
+    let token = auth_token(&app.login(&email).await);
+
+    let body = serde_json::json!({ "email": email });
+
+    let response = app.post_admin_logout_all(None, &body).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.post_admin_logout_all(Some("wrong-token"), &body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The caller's own auth cookie doesn't make them an admin.
+    let response = app.post_admin_logout_all(Some(&token), &body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
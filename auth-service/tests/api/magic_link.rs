@@ -0,0 +1,277 @@
+use auth_service::{
+    domain::AuthMethod,
+    routes::{SignupResponse, TwoFactorAuthResponse},
+    utils::constants::JWT_COOKIE_NAME,
+};
+use jsonwebtoken::dangerous;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{get_random_email, TestApp};
+
+const MAGIC_LINK_SUBJECT: &str = "Your login link";
+
+/// Signs up `email` and returns the recovery codes issued, if any.
+async fn signup(app: &TestApp, email: &str, requires_2fa: bool) -> Option<Vec<String>> {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": requires_2fa
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to SignupResponse")
+        .recovery_codes
+}
+
+async fn request_magic_link(app: &TestApp, email: &str) -> String {
+    let response = app
+        .post_magic_link(&serde_json::json!({ "email": email }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.get_link_param_from_email_to(email, "magic_token").await
+}
+
+#[tokio::test]
+async fn magic_link_should_log_user_in() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    signup(&app, &email, false).await;
+    app.verify_email(&email).await;
+
+    let token = request_magic_link(&app, &email).await;
+
+    let response = app
+        .post_magic_link_callback(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(!auth_cookie.value().is_empty());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn magic_link_should_verify_unverified_email() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    signup(&app, &email, false).await;
+
+    let token = request_magic_link(&app, &email).await;
+
+    let response = app
+        .post_magic_link_callback(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The password login is no longer blocked on verification either.
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn magic_link_should_only_work_once() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    signup(&app, &email, false).await;
+
+    let token = request_magic_link(&app, &email).await;
+
+    let response = app
+        .post_magic_link_callback(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_magic_link_callback(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn magic_link_should_chain_into_2fa() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    let recovery_codes = signup(&app, &email, true)
+        .await
+        .expect("No recovery codes issued");
+
+    let token = request_magic_link(&app, &email).await;
+
+    let response = app
+        .post_magic_link_callback(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+    assert!(response
+        .cookies()
+        .all(|cookie| cookie.name() != JWT_COOKIE_NAME));
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let response = app
+        .post_verify_2fa_recovery(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "recoveryCode": recovery_codes[0],
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The session still records that the first factor was the link.
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+    let claims = dangerous::insecure_decode::<serde_json::Value>(auth_cookie.value())
+        .expect("Could not read token claims")
+        .claims;
+
+    assert_eq!(
+        claims["amr"],
+        serde_json::json!([
+            AuthMethod::MagicLink,
+            AuthMethod::OneTimeCode,
+            AuthMethod::MultiFactor
+        ])
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_reveal_unknown_email() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+
+    let response = app
+        .post_magic_link(&serde_json::json!({ "email": email }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(app
+        .get_emails_to(&email, MAGIC_LINK_SUBJECT)
+        .await
+        .is_empty());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_email() {
+    let mut app = TestApp::new().await;
+
+    let test_cases = ["", "invalid_email", "@example.com"];
+
+    for email in test_cases {
+        let response = app
+            .post_magic_link(&serde_json::json!({ "email": email }))
+            .await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            email
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_429_after_too_many_requests() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+
+    for _ in 0..5 {
+        let response = app
+            .post_magic_link(&serde_json::json!({ "email": email }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    let response = app
+        .post_magic_link(&serde_json::json!({ "email": email }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn callback_should_return_401_if_invalid_token() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    signup(&app, &email, false).await;
+    app.verify_email(&email).await;
+
+    // A verification token is signed by us but issued for a different purpose.
+    let verify_token = app
+        .get_link_param_from_email_to(&email, "verify_token")
+        .await;
+
+    let test_cases = ["invalid_token".to_owned(), verify_token];
+
+    for token in test_cases {
+        let response = app
+            .post_magic_link_callback(&serde_json::json!({ "token": token }))
+            .await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Failed for input: {:?}",
+            token
+        );
+    }
+
+    app.clean_up().await;
+}
@@ -1,7 +1,26 @@
+mod device;
+mod federated;
 mod helpers;
+mod introspect;
+mod jwks;
 mod login;
 mod logout;
+mod magic_link;
+mod oauth_clients;
+mod oidc;
+mod passkey;
+mod rate_limit;
+mod password_reset;
+mod personal_access_tokens;
+mod recovery_codes;
+mod refresh_token;
+mod registration;
+mod revoke;
 mod root;
+mod sessions;
+mod signing_keys;
 mod signup;
+mod totp;
 mod verify_2fa;
+mod verify_email;
 mod verify_token;
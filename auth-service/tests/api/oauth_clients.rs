@@ -0,0 +1,446 @@
+use auth_service::{
+    domain::{ClientSecret, GrantType, OAuthClient},
+    routes::{IntrospectResponse, OAuthClientResponse, OAuthClientSecretResponse, TokenResponse},
+    utils::{auth::first_party_audience, constants::JWT_COOKIE_NAME},
+};
+use secrecy::ExposeSecret;
+
+use crate::helpers::{
+    admin_token, assert_oauth_error, get_random_email, TestApp, TEST_REDIRECT_URI,
+};
+
+/// Registers a backend job's client through the admin API and returns its id and secret.
+async fn create_service_client(app: &TestApp) -> (String, String) {
+    app.create_oauth_client(&["client_credentials"], &["orders:read", "orders:write"])
+        .await
+}
+
+async fn client_credentials(
+    app: &TestApp,
+    client: (&str, &str),
+    scope: Option<&str>,
+) -> reqwest::Response {
+    let mut request = vec![("grant_type", "client_credentials")];
+    if let Some(scope) = scope {
+        request.push(("scope", scope));
+    }
+
+    app.post_token(Some(client), &request).await
+}
+
+#[tokio::test]
+async fn should_manage_oauth_clients() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = create_service_client(&app).await;
+
+    let response = app.get_oauth_client(Some(admin_token()), &client_id).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let client = response
+        .json::<OAuthClientResponse>()
+        .await
+        .expect("Could not deserialize response body to OAuthClientResponse");
+
+    assert_eq!(client.allowed_scopes, ["orders:read", "orders:write"]);
+    assert_eq!(client.allowed_grant_types, [GrantType::ClientCredentials]);
+    assert!(client.redirect_uris.is_empty());
+
+    let response = app
+        .put_oauth_client(
+            Some(admin_token()),
+            &client_id,
+            &serde_json::json!({
+                "redirectUris": [TEST_REDIRECT_URI],
+                "allowedScopes": ["openid", "orders:read"],
+                "allowedGrantTypes": ["authorization_code", "client_credentials"]
+            }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.get_oauth_clients(Some(admin_token())).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let clients = response
+        .json::<Vec<OAuthClientResponse>>()
+        .await
+        .expect("Could not deserialize response body to Vec<OAuthClientResponse>");
+
+    assert_eq!(clients.len(), 1);
+    assert_eq!(clients[0].client_id, client_id);
+    assert_eq!(clients[0].redirect_uris, [TEST_REDIRECT_URI]);
+    assert_eq!(clients[0].allowed_scopes, ["openid", "orders:read"]);
+    assert_eq!(
+        clients[0].allowed_grant_types,
+        [GrantType::AuthorizationCode, GrantType::ClientCredentials]
+    );
+
+    let response = app
+        .delete_oauth_client(Some(admin_token()), &client_id)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = app.get_oauth_client(Some(admin_token()), &client_id).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app
+        .delete_oauth_client(Some(admin_token()), &client_id)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_for_invalid_client_metadata() {
+    let mut app = TestApp::new().await;
+
+    let test_cases = [
+        serde_json::json!({
+            "allowedScopes": ["orders:read"],
+            "allowedGrantTypes": []
+        }),
+        serde_json::json!({
+            "allowedScopes": ["openid"],
+            "allowedGrantTypes": ["authorization_code"]
+        }),
+        serde_json::json!({
+            "redirectUris": ["https://client.example.com/callback#fragment"],
+            "allowedScopes": ["openid"],
+            "allowedGrantTypes": ["authorization_code"]
+        }),
+        serde_json::json!({
+            "redirectUris": ["/callback"],
+            "allowedScopes": ["openid"],
+            "allowedGrantTypes": ["authorization_code"]
+        }),
+        serde_json::json!({
+            "allowedScopes": ["orders:read orders:write"],
+            "allowedGrantTypes": ["client_credentials"]
+        }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_oauth_client(Some(admin_token()), test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+
+    let response = app.get_oauth_clients(Some(admin_token())).await;
+    let clients = response
+        .json::<Vec<OAuthClientResponse>>()
+        .await
+        .expect("Could not deserialize response body to Vec<OAuthClientResponse>");
+
+    assert!(clients.is_empty());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_require_admin_token() {
+    let mut app = TestApp::new().await;
+
+    let body = serde_json::json!({
+        "allowedScopes": ["orders:read"],
+        "allowedGrantTypes": ["client_credentials"]
+    });
+
+    let response = app.post_oauth_client(None, &body).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .post_oauth_client(Some("not-the-admin-token"), &body)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.get_oauth_clients(Some("not-the-admin-token")).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_issue_scoped_token_for_client_credentials() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = create_service_client(&app).await;
+
+    let response =
+        client_credentials(&app, (&client_id, &client_secret), Some("orders:read")).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let tokens = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+
+    assert_eq!(tokens.token_type, "Bearer");
+    assert_eq!(tokens.scope, "orders:read");
+    assert_eq!(tokens.id_token, None);
+
+    // Protected services accept the token like any other.
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": tokens.access_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_introspect(
+            Some((&client_id, &client_secret)),
+            &[("token", tokens.access_token.as_str())],
+        )
+        .await
+        .json::<IntrospectResponse>()
+        .await
+        .expect("Could not deserialize response body to IntrospectResponse");
+
+    assert!(response.active);
+    assert_eq!(response.sub.as_deref(), Some(client_id.as_str()));
+    assert_eq!(response.client_id.as_deref(), Some(client_id.as_str()));
+    assert_eq!(response.scope.as_deref(), Some("orders:read"));
+    assert_eq!(response.amr, Some(vec![]));
+
+    // Without a scope the token gets everything the client is allowed.
+    let tokens = client_credentials(&app, (&client_id, &client_secret), None)
+        .await
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+
+    assert_eq!(tokens.scope, "orders:read orders:write");
+
+    // A service token is not a user session.
+    let response = app
+        .http_client
+        .post(format!("{}/2fa/recovery-codes", &app.address))
+        .header(
+            "Cookie",
+            format!("{}={}", JWT_COOKIE_NAME, tokens.access_token),
+        )
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_issue_token_for_requested_resource_only() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = create_service_client(&app).await;
+
+    let tokens = app
+        .post_token(
+            Some((&client_id, &client_secret)),
+            &[
+                ("grant_type", "client_credentials"),
+                ("resource", first_party_audience()),
+            ],
+        )
+        .await
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+
+    let response = app
+        .post_introspect(
+            Some((&client_id, &client_secret)),
+            &[("token", tokens.access_token.as_str())],
+        )
+        .await
+        .json::<IntrospectResponse>()
+        .await
+        .expect("Could not deserialize response body to IntrospectResponse");
+
+    assert_eq!(response.aud, Some(vec![first_party_audience().to_owned()]));
+
+    let response = app
+        .post_token(
+            Some((&client_id, &client_secret)),
+            &[
+                ("grant_type", "client_credentials"),
+                ("resource", "unknown-service"),
+            ],
+        )
+        .await;
+
+    assert_oauth_error(response, 400, "invalid_target").await;
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reject_scopes_the_client_is_not_allowed() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = create_service_client(&app).await;
+
+    for scope in ["admin", "orders:read admin", ""] {
+        let response = client_credentials(&app, (&client_id, &client_secret), Some(scope)).await;
+
+        assert_oauth_error(response, 400, "invalid_scope").await;
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reject_grant_types_the_client_is_not_allowed() {
+    let mut app = TestApp::new().await;
+
+    let (service_id, service_secret) = create_service_client(&app).await;
+    let (oidc_id, oidc_secret) = app.register_oauth_client().await;
+
+    let response = client_credentials(&app, (&oidc_id, &oidc_secret), None).await;
+
+    assert_oauth_error(response, 400, "unauthorized_client").await;
+
+    let response = app
+        .post_token(
+            Some((&service_id, &service_secret)),
+            &[
+                ("grant_type", "authorization_code"),
+                ("code", "code"),
+                ("redirect_uri", TEST_REDIRECT_URI),
+                ("code_verifier", "verifier"),
+            ],
+        )
+        .await;
+
+    assert_oauth_error(response, 400, "unauthorized_client").await;
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_let_client_pass_as_user() {
+    let mut app = TestApp::new().await;
+
+    // A client from before the registry, whose id happens to be an email address.
+    let client = OAuthClient {
+        client_id: get_random_email(),
+        client_name: None,
+        redirect_uris: vec![TEST_REDIRECT_URI.to_owned()],
+        allowed_scopes: vec!["openid".to_owned(), "email".to_owned()],
+        allowed_grant_types: vec![GrantType::AuthorizationCode, GrantType::ClientCredentials],
+    };
+    let client_secret = ClientSecret::default();
+
+    app.oauth_client_store
+        .write()
+        .await
+        .add_client(&client, &client_secret, None)
+        .await
+        .expect("Failed to register OAuth client");
+
+    let response = client_credentials(
+        &app,
+        (&client.client_id, client_secret.as_ref().expose_secret()),
+        None,
+    )
+    .await;
+
+    assert_oauth_error(response, 400, "unauthorized_client").await;
+
+    let response = app
+        .put_oauth_client(
+            Some(admin_token()),
+            &client.client_id,
+            &serde_json::json!({
+                "redirectUris": [TEST_REDIRECT_URI],
+                "allowedScopes": ["openid", "email"],
+                "allowedGrantTypes": ["authorization_code", "client_credentials"]
+            }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    // Even with the `openid` scope, a client's own token names no user.
+    let response = app
+        .post_oauth_client(
+            Some(admin_token()),
+            &serde_json::json!({
+                "allowedScopes": ["openid", "email"],
+                "allowedGrantTypes": ["client_credentials"]
+            }),
+        )
+        .await
+        .json::<OAuthClientSecretResponse>()
+        .await
+        .expect("Could not deserialize response body to OAuthClientSecretResponse");
+
+    let tokens = client_credentials(
+        &app,
+        (&response.client_id, &response.client_secret),
+        Some("openid email"),
+    )
+    .await
+    .json::<TokenResponse>()
+    .await
+    .expect("Could not deserialize response body to TokenResponse");
+
+    let response = app.get_userinfo(Some(&tokens.access_token)).await;
+
+    assert_oauth_error(response, 401, "invalid_token").await;
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn rotating_secret_should_invalidate_the_old_one() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, old_secret) = create_service_client(&app).await;
+
+    let response = app
+        .post_rotate_oauth_client_secret(Some(admin_token()), &client_id)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_secret = response
+        .json::<OAuthClientSecretResponse>()
+        .await
+        .expect("Could not deserialize response body to OAuthClientSecretResponse")
+        .client_secret;
+
+    assert_ne!(new_secret, old_secret);
+
+    let response = client_credentials(&app, (&client_id, &old_secret), None).await;
+
+    assert_oauth_error(response, 401, "invalid_client").await;
+
+    let response = client_credentials(&app, (&client_id, &new_secret), None).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_rotate_oauth_client_secret(Some(admin_token()), "unknown-client")
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+
+    app.clean_up().await;
+}
@@ -0,0 +1,501 @@
+use auth_service::{
+    domain::{AuthMethod, GrantType},
+    routes::{OpenIdConfiguration, TokenResponse, UserinfoResponse},
+    utils::constants::{JWT_COOKIE_NAME, JWT_ISSUER},
+};
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, DecodingKey, Validation};
+use reqwest::Url;
+
+use crate::helpers::{
+    assert_oauth_error, auth_token, authorize, authorize_request, get_random_email, location,
+    query_param, token_request, TestApp, TEST_REDIRECT_URI,
+};
+
+fn with(
+    request: &[(&'static str, String)],
+    name: &'static str,
+    value: Option<&str>,
+) -> Vec<(&'static str, String)> {
+    let mut request: Vec<_> = request
+        .iter()
+        .filter(|(key, _)| *key != name)
+        .cloned()
+        .collect();
+    if let Some(value) = value {
+        request.push((name, value.to_owned()));
+    }
+    request
+}
+
+#[tokio::test]
+async fn should_publish_openid_configuration() {
+    let mut app = TestApp::new().await;
+
+    let response = app.get_openid_configuration().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let configuration = response
+        .json::<OpenIdConfiguration>()
+        .await
+        .expect("Could not deserialize response body to OpenIdConfiguration");
+
+    assert_eq!(configuration.issuer, *JWT_ISSUER);
+    assert!(configuration.authorization_endpoint.ends_with("/authorize"));
+    assert!(configuration.token_endpoint.ends_with("/token"));
+    assert!(configuration.userinfo_endpoint.ends_with("/userinfo"));
+    assert!(configuration.jwks_uri.ends_with("/.well-known/jwks.json"));
+    assert_eq!(configuration.code_challenge_methods_supported, ["S256"]);
+    assert!(configuration
+        .scopes_supported
+        .contains(&"openid".to_owned()));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_complete_authorization_code_flow() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = app.register_oauth_client().await;
+    let email = get_random_email();
+    app.signup_and_login(&email, false).await;
+
+    let code = authorize(&app, &client_id).await;
+
+    let response = app
+        .post_token(Some((&client_id, &client_secret)), &token_request(&code))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .headers()
+            .get("cache-control")
+            .and_then(|value| value.to_str().ok()),
+        Some("no-store")
+    );
+
+    let tokens = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+
+    assert_eq!(tokens.token_type, "Bearer");
+    assert_eq!(tokens.scope, "openid email");
+    assert!(tokens.refresh_token.is_none());
+
+    // The client verifies the ID token against our published keys.
+    let jwk_set = app
+        .get_jwks()
+        .await
+        .json::<JwkSet>()
+        .await
+        .expect("Could not deserialize response body to JwkSet");
+
+    let id_token = tokens.id_token.expect("No ID token was issued");
+    let header = decode_header(&id_token).expect("ID token has no valid header");
+    let jwk = jwk_set
+        .find(&header.kid.expect("ID token has no kid"))
+        .expect("No published key matches the kid");
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_audience(&[&client_id]);
+    validation.set_issuer(&[JWT_ISSUER.as_str()]);
+
+    let claims = decode::<serde_json::Value>(
+        &id_token,
+        &DecodingKey::from_jwk(jwk).expect("Published key is unusable"),
+        &validation,
+    )
+    .expect("ID token did not verify against the published key")
+    .claims;
+
+    assert_eq!(claims["sub"], email);
+    assert_eq!(claims["nonce"], "n-0S6_WzA2Mj");
+    assert_eq!(claims["email"], email);
+    assert_eq!(claims["email_verified"], true);
+    assert_eq!(claims["amr"], serde_json::json!([AuthMethod::Password]));
+
+    let response = app.get_userinfo(Some(&tokens.access_token)).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let userinfo = response
+        .json::<UserinfoResponse>()
+        .await
+        .expect("Could not deserialize response body to UserinfoResponse");
+
+    assert_eq!(userinfo.sub, email);
+    assert_eq!(userinfo.email, Some(email));
+
+    // Codes are single use.
+    let response = app
+        .post_token(Some((&client_id, &client_secret)), &token_request(&code))
+        .await;
+
+    assert_oauth_error(response, 400, "invalid_grant").await;
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_rotate_refresh_tokens_for_clients_allowed_them() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = app
+        .register_oauth_client_with_grant_types(&[
+            GrantType::AuthorizationCode,
+            GrantType::RefreshToken,
+        ])
+        .await;
+    let email = get_random_email();
+    app.signup_and_login(&email, false).await;
+
+    let code = authorize(&app, &client_id).await;
+    let refresh_token = app
+        .post_token(Some((&client_id, &client_secret)), &token_request(&code))
+        .await
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse")
+        .refresh_token
+        .expect("No refresh token was issued");
+
+    let refresh = |token: String| {
+        vec![
+            ("grant_type", "refresh_token".to_owned()),
+            ("refresh_token", token),
+        ]
+    };
+
+    // Another client can't redeem it.
+    let (other_id, other_secret) = app
+        .register_oauth_client_with_grant_types(&[
+            GrantType::AuthorizationCode,
+            GrantType::RefreshToken,
+        ])
+        .await;
+    let response = app
+        .post_token(
+            Some((&other_id, &other_secret)),
+            &refresh(refresh_token.clone()),
+        )
+        .await;
+
+    assert_oauth_error(response, 400, "invalid_grant").await;
+
+    let response = app
+        .post_token(
+            Some((&client_id, &client_secret)),
+            &refresh(refresh_token.clone()),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let tokens = response
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+
+    assert_eq!(tokens.scope, "openid email");
+    assert!(tokens.id_token.is_none());
+
+    let response = app.get_userinfo(Some(&tokens.access_token)).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let userinfo = response
+        .json::<UserinfoResponse>()
+        .await
+        .expect("Could not deserialize response body to UserinfoResponse");
+
+    assert_eq!(userinfo.sub, email);
+
+    // Replaying the used token revokes the rotated one too.
+    let rotated = tokens.refresh_token.expect("No refresh token was issued");
+    let response = app
+        .post_token(Some((&client_id, &client_secret)), &refresh(refresh_token))
+        .await;
+
+    assert_oauth_error(response, 400, "invalid_grant").await;
+
+    let response = app
+        .post_token(Some((&client_id, &client_secret)), &refresh(rotated))
+        .await;
+
+    assert_oauth_error(response, 400, "invalid_grant").await;
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_send_user_to_login_first() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = app.register_oauth_client().await;
+
+    let response = app
+        .get_authorize(&with(
+            &authorize_request(&client_id),
+            "prompt",
+            Some("none"),
+        ))
+        .await;
+    let callback = location(&response);
+
+    assert_eq!(
+        query_param(&callback, "error").as_deref(),
+        Some("login_required")
+    );
+    assert_eq!(query_param(&callback, "code"), None);
+
+    let response = app.get_authorize(&authorize_request(&client_id)).await;
+    let login_page = location(&response);
+
+    assert_eq!(login_page.path(), "/");
+
+    let return_to = query_param(&login_page, "return_to").expect("No return_to");
+
+    assert!(return_to.starts_with("/authorize?"));
+
+    app.signup_and_login(&get_random_email(), false).await;
+
+    // Once logged in, the login page sends the user back to where they came from.
+    let response = app
+        .http_client
+        .get(format!("{}{}", &app.address, return_to))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let callback = location(&response);
+
+    assert!(callback.as_str().starts_with(TEST_REDIRECT_URI));
+    assert!(query_param(&callback, "code").is_some());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_without_redirecting_for_unknown_client_or_redirect_uri() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = app.register_oauth_client().await;
+    app.signup_and_login(&get_random_email(), false).await;
+
+    let request = authorize_request(&client_id);
+    let test_cases = [
+        with(&request, "client_id", None),
+        with(&request, "client_id", Some("unknown-client")),
+        with(&request, "redirect_uri", None),
+        with(
+            &request,
+            "redirect_uri",
+            Some("https://attacker.example.com/callback"),
+        ),
+    ];
+
+    for request in test_cases {
+        let response = app.get_authorize(&request).await;
+
+        assert!(
+            response.headers().get("location").is_none(),
+            "Failed for input: {:?}",
+            request
+        );
+        assert_oauth_error(response, 400, "invalid_request").await;
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_redirect_invalid_requests_back_with_error() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = app.register_oauth_client().await;
+    app.signup_and_login(&get_random_email(), false).await;
+
+    let request = authorize_request(&client_id);
+    let test_cases = [
+        (
+            with(&request, "response_type", Some("token")),
+            "unsupported_response_type",
+        ),
+        (with(&request, "scope", Some("email")), "invalid_scope"),
+        (with(&request, "code_challenge", None), "invalid_request"),
+        (
+            with(&request, "code_challenge_method", Some("plain")),
+            "invalid_request",
+        ),
+        (
+            with(&request, "code_challenge", Some("not-a-sha256-digest")),
+            "invalid_request",
+        ),
+    ];
+
+    for (request, error) in test_cases {
+        let response = app.get_authorize(&request).await;
+        let callback = location(&response);
+
+        assert!(callback.as_str().starts_with(TEST_REDIRECT_URI));
+        assert_eq!(
+            query_param(&callback, "error").as_deref(),
+            Some(error),
+            "Failed for input: {:?}",
+            request
+        );
+        assert_eq!(
+            query_param(&callback, "state").as_deref(),
+            Some("af0ifjsldkj")
+        );
+        assert_eq!(query_param(&callback, "code"), None);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reject_code_redeemed_with_wrong_details() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = app.register_oauth_client().await;
+    let (other_client_id, other_client_secret) = app.register_oauth_client().await;
+    app.signup_and_login(&get_random_email(), false).await;
+
+    let wrong_verifier = "x".repeat(43);
+    let test_cases = [
+        (
+            (other_client_id.as_str(), other_client_secret.as_str()),
+            None,
+        ),
+        (
+            (client_id.as_str(), client_secret.as_str()),
+            Some(("redirect_uri", "https://client.example.com/other")),
+        ),
+        (
+            (client_id.as_str(), client_secret.as_str()),
+            Some(("code_verifier", wrong_verifier.as_str())),
+        ),
+        (
+            (client_id.as_str(), client_secret.as_str()),
+            Some(("code", "not-a-code")),
+        ),
+    ];
+
+    for (client, change) in test_cases {
+        let code = authorize(&app, &client_id).await;
+
+        let mut request = token_request(&code);
+        if let Some((name, value)) = change {
+            request = with(&request, name, Some(value));
+        }
+
+        let response = app.post_token(Some(client), &request).await;
+
+        assert_oauth_error(response, 400, "invalid_grant").await;
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reject_bad_token_requests() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = app.register_oauth_client().await;
+    app.signup_and_login(&get_random_email(), false).await;
+
+    let code = authorize(&app, &client_id).await;
+    let request = token_request(&code);
+
+    let response = app.post_token(None, &request).await;
+
+    assert_oauth_error(response, 401, "invalid_client").await;
+
+    let test_cases = [
+        (
+            with(&request, "grant_type", Some("password")),
+            "unsupported_grant_type",
+        ),
+        (with(&request, "grant_type", None), "invalid_request"),
+        (with(&request, "code_verifier", None), "invalid_request"),
+    ];
+
+    for (request, error) in test_cases {
+        let response = app
+            .post_token(Some((&client_id, &client_secret)), &request)
+            .await;
+
+        assert_oauth_error(response, 400, error).await;
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn userinfo_should_only_accept_openid_access_tokens() {
+    let mut app = TestApp::new().await;
+
+    // The cookie's access token was never granted the `openid` scope.
+    let cookie_token = auth_token(&app.signup_and_login(&get_random_email(), false).await);
+
+    let test_cases = [None, Some("invalid_token"), Some(cookie_token.as_str())];
+
+    for token in test_cases {
+        let response = app.get_userinfo(token).await;
+
+        assert_eq!(
+            response
+                .headers()
+                .get("www-authenticate")
+                .and_then(|value| value.to_str().ok()),
+            Some(r#"Bearer error="invalid_token""#),
+            "Failed for input: {:?}",
+            token
+        );
+        assert_oauth_error(response, 401, "invalid_token").await;
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_accept_client_access_token_as_auth_cookie() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = app.register_oauth_client().await;
+    app.signup_and_login(&get_random_email(), false).await;
+
+    let code = authorize(&app, &client_id).await;
+    let tokens = app
+        .post_token(Some((&client_id, &client_secret)), &token_request(&code))
+        .await
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse");
+
+    // The client's token names the user, but it is no login of theirs.
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}={}; HttpOnly; SameSite=Lax; Secure; Path=/",
+            JWT_COOKIE_NAME, tokens.access_token
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app.get_sessions().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.get_authorize(&authorize_request(&client_id)).await;
+
+    assert_eq!(location(&response).path(), "/");
+
+    let response = app.post_logout().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
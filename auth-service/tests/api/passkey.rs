@@ -0,0 +1,390 @@
+use auth_service::{
+    routes::{StartPasskeyLoginResponse, TwoFactorAuthResponse},
+    utils::constants::{AUTH_SERVICE_URL, JWT_COOKIE_NAME},
+};
+use webauthn_authenticator_rs::{softpasskey::SoftPasskey, WebauthnAuthenticator};
+use webauthn_rs::prelude::{CreationChallengeResponse, RequestChallengeResponse, Url};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{get_random_email, TestApp};
+
+type Authenticator = WebauthnAuthenticator<SoftPasskey>;
+
+fn new_authenticator() -> Authenticator {
+    WebauthnAuthenticator::new(SoftPasskey::new(true))
+}
+
+fn origin() -> Url {
+    Url::parse(&AUTH_SERVICE_URL).expect("AUTH_SERVICE_URL is not a valid URL")
+}
+
+async fn register_passkey(app: &TestApp, authenticator: &mut Authenticator) {
+    let response = app.post_passkey_register_start().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let options = response
+        .json::<CreationChallengeResponse>()
+        .await
+        .expect("Could not deserialize response body to CreationChallengeResponse");
+
+    let credential = authenticator
+        .do_registration(origin(), options)
+        .expect("Software authenticator failed to register");
+
+    let response = app.post_passkey_register_finish(&credential).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+async fn start_passkey_login(app: &TestApp, email: &str) -> StartPasskeyLoginResponse {
+    let response = app
+        .post_passkey_login_start(&serde_json::json!({ "email": email }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<StartPasskeyLoginResponse>()
+        .await
+        .expect("Could not deserialize response body to StartPasskeyLoginResponse")
+}
+
+/// Turns on emailed 2FA codes for the logged-in user and starts a password login, which
+/// then stops at the second factor.
+async fn login_with_2fa(app: &TestApp, email: &str) -> TwoFactorAuthResponse {
+    let response = app
+        .put_two_fa_method(&serde_json::json!({ "method": "email" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+}
+
+#[tokio::test]
+async fn register_start_should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_passkey_register_start().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn register_finish_should_return_401_if_ceremony_replayed() {
+    let mut app = TestApp::new().await;
+    let mut authenticator = new_authenticator();
+
+    app.signup_and_login(&get_random_email(), false).await;
+
+    let response = app.post_passkey_register_start().await;
+    let options = response
+        .json::<CreationChallengeResponse>()
+        .await
+        .expect("Could not deserialize response body to CreationChallengeResponse");
+    let credential = authenticator
+        .do_registration(origin(), options)
+        .expect("Software authenticator failed to register");
+
+    let response = app.post_passkey_register_finish(&credential).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    // The ceremony was consumed by the first attempt.
+    let response = app.post_passkey_register_finish(&credential).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn passkey_login_should_set_auth_cookie() {
+    let mut app = TestApp::new().await;
+    let mut authenticator = new_authenticator();
+
+    let email = get_random_email();
+
+    app.signup_and_login(&email, false).await;
+    register_passkey(&app, &mut authenticator).await;
+
+    let body = start_passkey_login(&app, &email).await;
+    let credential = authenticator
+        .do_authentication(origin(), body.options)
+        .expect("Software authenticator failed to authenticate");
+
+    let response = app
+        .post_passkey_login_finish(&serde_json::json!({
+            "loginAttemptId": body.login_attempt_id,
+            "credential": credential,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(!auth_cookie.value().is_empty());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn passkey_login_should_work_repeatedly() {
+    let mut app = TestApp::new().await;
+    let mut authenticator = new_authenticator();
+
+    let email = get_random_email();
+
+    app.signup_and_login(&email, false).await;
+    register_passkey(&app, &mut authenticator).await;
+
+    // The sign counter goes up on every use, so this also checks it's saved.
+    for _ in 0..3 {
+        let body = start_passkey_login(&app, &email).await;
+        let credential = authenticator
+            .do_authentication(origin(), body.options)
+            .expect("Software authenticator failed to authenticate");
+
+        let response = app
+            .post_passkey_login_finish(&serde_json::json!({
+                "loginAttemptId": body.login_attempt_id,
+                "credential": credential,
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn passkey_login_should_return_401_if_ceremony_replayed() {
+    let mut app = TestApp::new().await;
+    let mut authenticator = new_authenticator();
+
+    let email = get_random_email();
+
+    app.signup_and_login(&email, false).await;
+    register_passkey(&app, &mut authenticator).await;
+
+    let body = start_passkey_login(&app, &email).await;
+    let credential = authenticator
+        .do_authentication(origin(), body.options)
+        .expect("Software authenticator failed to authenticate");
+
+    let request = serde_json::json!({
+        "loginAttemptId": body.login_attempt_id,
+        "credential": credential,
+    });
+
+    let response = app.post_passkey_login_finish(&request).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_passkey_login_finish(&request).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn passkey_login_should_return_401_if_challenge_does_not_match() {
+    let mut app = TestApp::new().await;
+    let mut authenticator = new_authenticator();
+
+    let email = get_random_email();
+
+    app.signup_and_login(&email, false).await;
+    register_passkey(&app, &mut authenticator).await;
+
+    let first = start_passkey_login(&app, &email).await;
+    let second = start_passkey_login(&app, &email).await;
+
+    // Sign the first challenge but submit it to the second ceremony.
+    let credential = authenticator
+        .do_authentication(origin(), first.options)
+        .expect("Software authenticator failed to authenticate");
+
+    let response = app
+        .post_passkey_login_finish(&serde_json::json!({
+            "loginAttemptId": second.login_attempt_id,
+            "credential": credential,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn passkey_login_start_should_answer_alike_for_accounts_without_passkeys() {
+    let mut app = TestApp::new().await;
+    let mut authenticator = new_authenticator();
+
+    let with_passkey = get_random_email();
+
+    app.signup_and_login(&with_passkey, false).await;
+    register_passkey(&app, &mut authenticator).await;
+
+    let without_passkey = get_random_email();
+
+    app.signup_and_login(&without_passkey, false).await;
+
+    let unverified = get_random_email();
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": unverified,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let real = start_passkey_login(&app, &with_passkey).await;
+    let credential = authenticator
+        .do_authentication(origin(), real.options.clone())
+        .expect("Software authenticator failed to authenticate");
+
+    let test_cases = [without_passkey, unverified, get_random_email()];
+
+    for email in test_cases.iter() {
+        let decoy = start_passkey_login(&app, email).await;
+
+        assert_eq!(
+            decoy.options.public_key.allow_credentials.len(),
+            real.options.public_key.allow_credentials.len(),
+            "Failed for {}",
+            email
+        );
+        assert_eq!(
+            decoy.options.public_key.user_verification, real.options.public_key.user_verification,
+            "Failed for {}",
+            email
+        );
+
+        // Asking again names the same credential, as it would for a real passkey.
+        let again = start_passkey_login(&app, email).await;
+
+        assert_eq!(
+            again.options.public_key.allow_credentials[0].id,
+            decoy.options.public_key.allow_credentials[0].id,
+            "Failed for {}",
+            email
+        );
+
+        // Nobody holds the decoy's key, so the ceremony can't be finished.
+        let response = app
+            .post_passkey_login_finish(&serde_json::json!({
+                "loginAttemptId": decoy.login_attempt_id,
+                "credential": credential,
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 401, "Failed for {}", email);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn passkey_should_complete_2fa() {
+    let mut app = TestApp::new().await;
+    let mut authenticator = new_authenticator();
+
+    let email = get_random_email();
+
+    app.signup_and_login(&email, false).await;
+    register_passkey(&app, &mut authenticator).await;
+
+    let body = login_with_2fa(&app, &email).await;
+
+    let response = app
+        .post_verify_2fa_passkey_start(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": body.login_attempt_id,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let options = response
+        .json::<RequestChallengeResponse>()
+        .await
+        .expect("Could not deserialize response body to RequestChallengeResponse");
+    let credential = authenticator
+        .do_authentication(origin(), options)
+        .expect("Software authenticator failed to authenticate");
+
+    let response = app
+        .post_verify_2fa_passkey_finish(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": body.login_attempt_id,
+            "credential": credential,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The login attempt is finished, so the emailed code can't be used anymore.
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": body.login_attempt_id,
+            "2FACode": "123456",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn passkey_2fa_start_should_return_401_if_login_attempt_id_is_wrong() {
+    let mut app = TestApp::new().await;
+    let mut authenticator = new_authenticator();
+
+    let email = get_random_email();
+
+    app.signup_and_login(&email, false).await;
+    register_passkey(&app, &mut authenticator).await;
+
+    login_with_2fa(&app, &email).await;
+
+    let response = app
+        .post_verify_2fa_passkey_start(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": uuid::Uuid::new_v4().to_string(),
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
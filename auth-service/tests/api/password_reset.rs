@@ -0,0 +1,409 @@
+use auth_service::{
+    routes::PasswordResetResponse,
+    utils::constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME},
+    ErrorResponse,
+};
+use reqwest::Url;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{auth_token, get_random_email, refresh_token, TestApp};
+
+async fn request_reset_token(app: &TestApp, email: &str) -> String {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": email }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.get_link_param_from_last_email("reset_token").await
+}
+
+#[tokio::test]
+async fn request_should_return_422_if_malformed_input() {
+    let mut app = TestApp::new().await;
+
+    let test_cases = [
+        serde_json::json!({}),
+        serde_json::json!({
+            "email": null,
+        }),
+        serde_json::json!({
+            "invalidfield": "invalidvalue",
+        }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_password_reset_request(test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            422,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn request_should_return_400_if_invalid_email() {
+    let mut app = TestApp::new().await;
+
+    let test_cases = ["", "notavalidemail.com", "@example.com"];
+
+    for test_case in test_cases {
+        let response = app
+            .post_password_reset_request(&serde_json::json!({ "email": test_case }))
+            .await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn request_should_return_200_without_sending_email_if_user_unknown() {
+    let mut app = TestApp::new().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_password_reset_request(&serde_json::json!({ "email": get_random_email() }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn request_should_return_same_response_for_known_and_unknown_users() {
+    let mut app = TestApp::new().await;
+
+    let known_email = get_random_email();
+    app.signup(&known_email, false).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let known = app
+        .post_password_reset_request(&serde_json::json!({ "email": known_email }))
+        .await;
+    let unknown = app
+        .post_password_reset_request(&serde_json::json!({ "email": get_random_email() }))
+        .await;
+
+    assert_eq!(known.status(), unknown.status());
+    assert_eq!(
+        known
+            .json::<PasswordResetResponse>()
+            .await
+            .expect("Could not deserialize response body to PasswordResetResponse"),
+        unknown
+            .json::<PasswordResetResponse>()
+            .await
+            .expect("Could not deserialize response body to PasswordResetResponse"),
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn confirm_should_return_422_if_malformed_input() {
+    let mut app = TestApp::new().await;
+
+    let test_cases = [
+        serde_json::json!({}),
+        serde_json::json!({
+            "token": "sometoken",
+        }),
+        serde_json::json!({
+            "password": "password123",
+        }),
+        serde_json::json!({
+            "token": null,
+            "password": null,
+        }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_password_reset_confirm(test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            422,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn confirm_should_return_400_if_invalid_password() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    app.signup(&email, false).await;
+    let token = request_reset_token(&app, &email).await;
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": token,
+            "password": "short",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn confirm_should_return_401_if_invalid_token() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": "invalid",
+            "password": "newpassword123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Invalid auth token".to_owned()
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn confirm_should_return_401_if_auth_token_used_as_reset_token() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    app.signup(&email, false).await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_token = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found")
+        .value()
+        .to_owned();
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": auth_token,
+            "password": "newpassword123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn confirm_should_update_password_and_revoke_sessions() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    let response = app.signup_and_login(&email, false).await;
+
+    let refresh_token = refresh_token(&response);
+    let auth_token = auth_token(&response);
+
+    let personal_access_token = app.create_personal_access_token().await;
+
+    let token = request_reset_token(&app, &email).await;
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": token,
+            "password": "newpassword123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // The old password no longer works, the new one does.
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "newpassword123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Sessions that existed before the reset are gone.
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}={}; HttpOnly; SameSite=Lax; Secure; Path=/",
+            REFRESH_COOKIE_NAME, refresh_token
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+
+    let response = app.post_refresh_token().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    for token in [auth_token, personal_access_token] {
+        let response = app
+            .post_verify_token(&serde_json::json!({ "token": token }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn confirm_should_return_401_if_token_used_twice() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    app.signup(&email, false).await;
+    let token = request_reset_token(&app, &email).await;
+
+    let body = serde_json::json!({
+        "token": token,
+        "password": "newpassword123",
+    });
+
+    let response = app.post_password_reset_confirm(&body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_password_reset_confirm(&body).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn confirm_should_accept_token_once_when_used_concurrently() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    app.signup(&email, false).await;
+    let token = request_reset_token(&app, &email).await;
+
+    let body = serde_json::json!({
+        "token": token,
+        "password": "newpassword123",
+    });
+
+    let (first, second) = tokio::join!(
+        app.post_password_reset_confirm(&body),
+        app.post_password_reset_confirm(&body)
+    );
+
+    let mut statuses = [first.status().as_u16(), second.status().as_u16()];
+    statuses.sort();
+
+    assert_eq!(statuses, [200, 401]);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn confirm_should_return_401_for_links_sent_before_reset() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    app.signup(&email, false).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    for _ in 0..2 {
+        let response = app
+            .post_password_reset_request(&serde_json::json!({ "email": email }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    let earlier_token = app.get_link_param_from_nth_email(1, "reset_token").await;
+    let token = app.get_link_param_from_nth_email(2, "reset_token").await;
+
+    assert_ne!(earlier_token, token);
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": token,
+            "password": "newpassword123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": earlier_token,
+            "password": "otherpassword123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
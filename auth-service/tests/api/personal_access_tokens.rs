@@ -0,0 +1,241 @@
+use auth_service::{
+    domain::{Email, PersonalAccessToken, PersonalAccessTokenRecord},
+    routes::{PersonalAccessTokenResponse, VerifyTokenResponse},
+    ErrorResponse,
+};
+use chrono::{Duration, Utc};
+use secrecy::{ExposeSecret, SecretString};
+use uuid::Uuid;
+
+use crate::helpers::{get_random_email, TestApp};
+
+async fn create_token(app: &TestApp, body: serde_json::Value) -> PersonalAccessTokenResponse {
+    let response = app.post_personal_access_token(&body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    response
+        .json::<PersonalAccessTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to PersonalAccessTokenResponse")
+}
+
+#[tokio::test]
+async fn should_accept_personal_access_token_until_revoked() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    app.signup_and_login(&email, false).await;
+
+    let created = create_token(
+        &app,
+        serde_json::json!({
+            "name": "CI deploys",
+            "scopes": ["deploy", "read:logs"],
+            "expiresInDays": 30
+        }),
+    )
+    .await;
+
+    let token = created.token.expect("No token in response");
+    assert!(token.starts_with("pat_"));
+    assert!(created.expires_at.is_some());
+    assert_eq!(created.last_used_at, None);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let verified = response
+        .json::<VerifyTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to VerifyTokenResponse");
+    assert_eq!(verified.sub, email);
+    assert_eq!(verified.scope.as_deref(), Some("deploy read:logs"));
+
+    // The token is never shown again, but its use is recorded.
+    let response = app.get_personal_access_tokens().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let tokens = response
+        .json::<Vec<PersonalAccessTokenResponse>>()
+        .await
+        .expect("Could not deserialize response body to Vec<PersonalAccessTokenResponse>");
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(tokens[0].id, created.id);
+    assert_eq!(tokens[0].name, "CI deploys");
+    assert_eq!(tokens[0].token, None);
+    assert!(tokens[0].last_used_at.is_some());
+
+    let response = app
+        .delete_personal_access_token(&created.id.to_string())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .delete_personal_access_token(&created.id.to_string())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Personal access token not found".to_owned()
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reject_expired_personal_access_token() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    app.signup_and_login(&email, false).await;
+
+    let token = PersonalAccessToken::default();
+    let created_at = Utc::now() - Duration::try_days(2).unwrap();
+    app.personal_access_token_store
+        .write()
+        .await
+        .add_token(
+            &token,
+            PersonalAccessTokenRecord {
+                id: Uuid::new_v4(),
+                email: Email::parse(SecretString::new(email.clone().into_boxed_str())).unwrap(),
+                name: "old".to_owned(),
+                scopes: vec![],
+                created_at,
+                expires_at: Some(created_at + Duration::try_days(1).unwrap()),
+                last_used_at: None,
+            },
+        )
+        .await
+        .unwrap();
+
+    let response = app
+        .post_verify_token(&serde_json::json!({
+            "token": token.as_ref().expose_secret()
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reject_personal_access_token_for_unknown_audience() {
+    let mut app = TestApp::new().await;
+
+    app.signup_and_login(&get_random_email(), false).await;
+
+    let token = create_token(&app, serde_json::json!({ "name": "scripts" }))
+        .await
+        .token
+        .expect("No token in response");
+
+    let response = app
+        .post_verify_token(&serde_json::json!({
+            "token": token,
+            "audience": "some-other-service"
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_input() {
+    let mut app = TestApp::new().await;
+
+    app.signup_and_login(&get_random_email(), false).await;
+
+    let test_cases = [
+        serde_json::json!({ "name": "" }),
+        serde_json::json!({ "name": "   " }),
+        serde_json::json!({ "name": "a".repeat(101) }),
+        serde_json::json!({ "name": "scripts", "scopes": ["read logs"] }),
+        serde_json::json!({ "name": "scripts", "scopes": [""] }),
+        serde_json::json!({ "name": "scripts", "expiresInDays": 0 }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.post_personal_access_token(test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_not_logged_in() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_personal_access_token(&serde_json::json!({ "name": "scripts" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.get_personal_access_tokens().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_revoke_token_of_another_user() {
+    let mut app = TestApp::new().await;
+
+    app.signup_and_login(&get_random_email(), false).await;
+
+    let created = create_token(&app, serde_json::json!({ "name": "scripts" })).await;
+    let token = created.token.expect("No token in response");
+
+    // Logging in replaces the first user's cookie.
+    app.signup_and_login(&get_random_email(), false).await;
+
+    let response = app
+        .delete_personal_access_token(&created.id.to_string())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app.get_personal_access_tokens().await;
+    let tokens = response
+        .json::<Vec<PersonalAccessTokenResponse>>()
+        .await
+        .expect("Could not deserialize response body to Vec<PersonalAccessTokenResponse>");
+    assert!(tokens.is_empty());
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
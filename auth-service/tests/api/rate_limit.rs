@@ -0,0 +1,218 @@
+use auth_service::{
+    domain::RateLimitQuota,
+    utils::rate_limit::{RateLimitPolicy, RateLimits},
+    ErrorResponse,
+};
+
+use uuid::Uuid;
+
+use crate::helpers::{get_random_email, TestApp};
+
+fn with_quota(policy: RateLimitPolicy, limit: u32) -> RateLimitPolicy {
+    RateLimitPolicy {
+        quota: RateLimitQuota::new(limit, 60),
+        ..policy
+    }
+}
+
+fn header<'a>(response: &'a reqwest::Response, name: &str) -> Option<&'a str> {
+    response
+        .headers()
+        .get(name)
+        .map(|value| value.to_str().expect("Header is not ASCII"))
+}
+
+async fn login(app: &TestApp, email: &str) -> reqwest::Response {
+    app.post_login(&serde_json::json!({
+        "email": email,
+        "password": "password123",
+    }))
+    .await
+}
+
+#[tokio::test]
+async fn should_return_429_once_login_limit_is_used_up() {
+    let mut rate_limits = RateLimits::default();
+    rate_limits.login = with_quota(rate_limits.login, 2);
+    let mut app = TestApp::with_rate_limits(rate_limits).await;
+
+    let email = get_random_email();
+
+    let response = login(&app, &email).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(header(&response, "ratelimit-limit"), Some("2"));
+    assert_eq!(header(&response, "ratelimit-remaining"), Some("1"));
+    assert_eq!(header(&response, "ratelimit-reset"), Some("30"));
+    assert_eq!(header(&response, "ratelimit-policy"), Some("2;w=60"));
+    assert_eq!(header(&response, "retry-after"), None);
+
+    let response = login(&app, &email).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(header(&response, "ratelimit-remaining"), Some("0"));
+
+    let response = login(&app, &email).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+    assert_eq!(header(&response, "ratelimit-remaining"), Some("0"));
+    assert_eq!(header(&response, "retry-after"), Some("30"));
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Too many requests".to_owned()
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_limit_login_by_email() {
+    let mut rate_limits = RateLimits::default();
+    rate_limits.login = with_quota(rate_limits.login, 1);
+    let mut app = TestApp::with_rate_limits(rate_limits).await;
+
+    let email = get_random_email();
+
+    let response = login(&app, &email).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = login(&app, &email).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    // Addresses are told apart as the stores and lockouts tell them apart, so the same
+    // address in other case is another account with its own allowance.
+    let response = login(&app, &email.to_uppercase()).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = login(&app, &get_random_email()).await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_share_verify_2fa_limit_across_second_factors() {
+    let mut rate_limits = RateLimits::default();
+    rate_limits.verify_2fa = with_quota(rate_limits.verify_2fa, 1);
+    let mut app = TestApp::with_rate_limits(rate_limits).await;
+
+    let email = get_random_email();
+    let login_attempt_id = Uuid::new_v4().to_string();
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": "123456",
+        }))
+        .await;
+
+    assert_ne!(response.status().as_u16(), 429);
+
+    let response = app
+        .post_verify_2fa_recovery(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "recoveryCode": "abcde-fghij",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    let response = app
+        .post_verify_2fa_passkey_start(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_limit_emails_sent_to_an_address() {
+    let mut rate_limits = RateLimits::default();
+    rate_limits.password_reset = with_quota(rate_limits.password_reset, 1);
+    let mut app = TestApp::with_rate_limits(rate_limits).await;
+
+    let email = get_random_email();
+    let request = serde_json::json!({ "email": email });
+
+    let response = app.post_password_reset_request(&request).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_password_reset_request(&request).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(header(&response, "retry-after").is_some());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_limit_unlock_attempts_by_address() {
+    let mut rate_limits = RateLimits::default();
+    // Every test app is called from 127.0.0.1, so this run of the test keeps its own
+    // counter.
+    rate_limits.unlock = RateLimitPolicy {
+        name: Box::leak(format!("unlock-{}", Uuid::new_v4()).into_boxed_str()),
+        ..with_quota(rate_limits.unlock, 1)
+    };
+    let mut app = TestApp::with_rate_limits(rate_limits).await;
+
+    let request = serde_json::json!({ "token": "invalid" });
+
+    let response = app.post_unlock_account(&request).await;
+
+    assert_ne!(response.status().as_u16(), 429);
+
+    let response = app.post_unlock_account(&request).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_limit_token_requests_by_client() {
+    let mut rate_limits = RateLimits::default();
+    rate_limits.token = with_quota(rate_limits.token, 1);
+    let mut app = TestApp::with_rate_limits(rate_limits).await;
+
+    let (client_id, client_secret) = app.register_oauth_client().await;
+    let request = serde_json::json!({ "grant_type": "client_credentials" });
+
+    let response = app
+        .post_token(Some((&client_id, &client_secret)), &request)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app
+        .post_token(Some((&client_id, &client_secret)), &request)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(header(&response, "retry-after").is_some());
+
+    let (other_client_id, other_client_secret) = app.register_oauth_client().await;
+
+    let response = app
+        .post_token(Some((&other_client_id, &other_client_secret)), &request)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
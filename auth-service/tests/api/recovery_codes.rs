@@ -0,0 +1,343 @@
+use std::collections::HashSet;
+
+use auth_service::{
+    routes::{RecoveryCodesResponse, SignupResponse, TwoFAMethodResponse, TwoFactorAuthResponse},
+    utils::constants::JWT_COOKIE_NAME,
+};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{get_random_email, TestApp};
+
+const RECOVERY_CODE_SUBJECT: &str = "A recovery code was used";
+
+/// Signs up a verified 2FA user and returns their email and recovery codes.
+async fn signup_with_2fa(app: &TestApp) -> (String, Vec<String>) {
+    let email = get_random_email();
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": true
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let body = response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to SignupResponse");
+
+    app.verify_email(&email).await;
+
+    (
+        email,
+        body.recovery_codes.expect("No recovery codes issued"),
+    )
+}
+
+async fn login_with_2fa(app: &TestApp, email: &str) -> String {
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id
+}
+
+#[tokio::test]
+async fn signup_should_issue_ten_distinct_codes() {
+    let mut app = TestApp::new().await;
+
+    let (_, codes) = signup_with_2fa(&app).await;
+
+    assert_eq!(codes.len(), 10);
+    assert_eq!(codes.iter().collect::<HashSet<_>>().len(), 10);
+
+    for code in codes.iter() {
+        assert_eq!(code.len(), 11, "Unexpected format: {}", code);
+        assert_eq!(code.as_bytes()[5], b'-', "Unexpected format: {}", code);
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn recovery_code_should_complete_login_and_notify_user() {
+    let mut app = TestApp::new().await;
+
+    let (email, codes) = signup_with_2fa(&app).await;
+    let login_attempt_id = login_with_2fa(&app, &email).await;
+
+    let response = app
+        .post_verify_2fa_recovery(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "recoveryCode": codes[0],
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(!auth_cookie.value().is_empty());
+
+    let notifications = app.get_emails_to(&email, RECOVERY_CODE_SUBJECT).await;
+    assert_eq!(notifications.len(), 1);
+    assert!(notifications[0].contains("You have 9 left"));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn recovery_code_should_be_accepted_without_dash_in_any_case() {
+    let mut app = TestApp::new().await;
+
+    let (email, codes) = signup_with_2fa(&app).await;
+    let login_attempt_id = login_with_2fa(&app, &email).await;
+
+    let response = app
+        .post_verify_2fa_recovery(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "recoveryCode": codes[0].replace('-', "").to_uppercase(),
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn recovery_code_should_only_work_once() {
+    let mut app = TestApp::new().await;
+
+    let (email, codes) = signup_with_2fa(&app).await;
+
+    let login_attempt_id = login_with_2fa(&app, &email).await;
+    let response = app
+        .post_verify_2fa_recovery(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "recoveryCode": codes[0],
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let login_attempt_id = login_with_2fa(&app, &email).await;
+    let response = app
+        .post_verify_2fa_recovery(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "recoveryCode": codes[0],
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_login_attempt_id_is_wrong() {
+    let mut app = TestApp::new().await;
+
+    let (email, codes) = signup_with_2fa(&app).await;
+    login_with_2fa(&app, &email).await;
+
+    let response = app
+        .post_verify_2fa_recovery(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": uuid::Uuid::new_v4().to_string(),
+            "recoveryCode": codes[0],
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The code wasn't spent on the failed attempt.
+    let login_attempt_id = login_with_2fa(&app, &email).await;
+    let response = app
+        .post_verify_2fa_recovery(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "recoveryCode": codes[0],
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_invalid_input() {
+    let mut app = TestApp::new().await;
+
+    let (email, _) = signup_with_2fa(&app).await;
+    let login_attempt_id = login_with_2fa(&app, &email).await;
+
+    let test_cases = ["", "abcde", "abcde-fghij-k", "00000-11111"];
+
+    for recovery_code in test_cases {
+        let response = app
+            .post_verify_2fa_recovery(&serde_json::json!({
+                "email": email,
+                "loginAttemptId": login_attempt_id,
+                "recoveryCode": recovery_code,
+            }))
+            .await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {:?}",
+            recovery_code
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn regenerating_should_invalidate_old_codes() {
+    let mut app = TestApp::new().await;
+
+    let (email, old_codes) = signup_with_2fa(&app).await;
+
+    // Log in with a recovery code to get the JWT cookie needed to regenerate.
+    let login_attempt_id = login_with_2fa(&app, &email).await;
+    let response = app
+        .post_verify_2fa_recovery(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "recoveryCode": old_codes[0],
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.post_recovery_codes().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_codes = response
+        .json::<RecoveryCodesResponse>()
+        .await
+        .expect("Could not deserialize response body to RecoveryCodesResponse")
+        .recovery_codes;
+
+    assert_eq!(new_codes.len(), 10);
+
+    let login_attempt_id = login_with_2fa(&app, &email).await;
+    let response = app
+        .post_verify_2fa_recovery(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "recoveryCode": old_codes[1],
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_verify_2fa_recovery(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "recoveryCode": new_codes[0],
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn regenerating_should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_recovery_codes().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn enabling_2fa_should_issue_codes_only_once() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&email).await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .put_two_fa_method(&serde_json::json!({ "method": "email" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<TwoFAMethodResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFAMethodResponse");
+
+    assert_eq!(body.recovery_codes.map(|codes| codes.len()), Some(10));
+
+    let response = app
+        .put_two_fa_method(&serde_json::json!({ "method": "email" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<TwoFAMethodResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFAMethodResponse");
+
+    assert!(body.recovery_codes.is_none());
+
+    app.clean_up().await;
+}
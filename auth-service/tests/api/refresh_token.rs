@@ -0,0 +1,146 @@
+use auth_service::{
+    utils::constants::{JWT_COOKIE_NAME, REFRESH_COOKIE_NAME},
+    ErrorResponse,
+};
+use reqwest::Url;
+
+use crate::helpers::{assert_auth_cookies_removed, get_random_email, refresh_token, TestApp};
+
+fn set_refresh_cookie(app: &TestApp, value: &str) {
+    app.cookie_jar.add_cookie_str(
+        &format!(
+            "{}={}; HttpOnly; SameSite=Lax; Secure; Path=/",
+            REFRESH_COOKIE_NAME, value
+        ),
+        &Url::parse("http://127.0.0.1").expect("Failed to parse URL"),
+    );
+}
+
+#[tokio::test]
+async fn should_return_400_if_refresh_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_refresh_token().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Missing auth token".to_owned()
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_invalid_refresh_token() {
+    let mut app = TestApp::new().await;
+
+    let test_cases = ["invalid", "dW5rbm93biByZWZyZXNoIHRva2VuLCAzMiBieXRlcyE"];
+
+    for test_case in test_cases {
+        set_refresh_cookie(&app, test_case);
+
+        let response = app.post_refresh_token().await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Failed for input: {:?}",
+            test_case
+        );
+
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "Invalid auth token".to_owned()
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_200_and_rotate_tokens_if_valid_refresh_token() {
+    let mut app = TestApp::new().await;
+
+    let old_refresh_token = refresh_token(&app.signup_and_login(&get_random_email(), false).await);
+
+    let response = app.post_refresh_token().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let auth_cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == JWT_COOKIE_NAME)
+        .expect("No auth cookie found");
+
+    assert!(!auth_cookie.value().is_empty());
+
+    let new_refresh_token = refresh_token(&response);
+
+    assert_ne!(new_refresh_token, old_refresh_token);
+
+    // The rotated token keeps working.
+    let response = app.post_refresh_token().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_token_family_if_refresh_token_reused() {
+    let mut app = TestApp::new().await;
+
+    let old_refresh_token = refresh_token(&app.signup_and_login(&get_random_email(), false).await);
+
+    let response = app.post_refresh_token().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_refresh_token = refresh_token(&response);
+
+    // Replaying the already rotated token is treated as theft.
+    set_refresh_cookie(&app, &old_refresh_token);
+
+    let response = app.post_refresh_token().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_auth_cookies_removed(&response);
+
+    // ...which also kills the newest token in the same family.
+    set_refresh_cookie(&app, &new_refresh_token);
+
+    let response = app.post_refresh_token().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_refresh_token_used_after_logout() {
+    let mut app = TestApp::new().await;
+
+    let refresh_token = refresh_token(&app.signup_and_login(&get_random_email(), false).await);
+
+    let response = app.post_logout().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    set_refresh_cookie(&app, &refresh_token);
+
+    let response = app.post_refresh_token().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
@@ -0,0 +1,300 @@
+use auth_service::{
+    routes::{ClientInformationResponse, InitialAccessTokenResponse},
+    ErrorResponse,
+};
+
+use crate::helpers::{admin_token, assert_oauth_error, TestApp};
+
+const REDIRECT_URI: &str = "https://app.example.com/callback";
+
+async fn create_initial_access_token(app: &TestApp) -> String {
+    let response = app.post_initial_access_token(Some(admin_token())).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    response
+        .json::<InitialAccessTokenResponse>()
+        .await
+        .expect("Could not deserialize response body to InitialAccessTokenResponse")
+        .initial_access_token
+}
+
+async fn register(app: &TestApp) -> ClientInformationResponse {
+    let initial_access_token = create_initial_access_token(app).await;
+
+    let response = app
+        .post_register(
+            Some(&initial_access_token),
+            &serde_json::json!({
+                "client_name": "Example App",
+                "redirect_uris": [REDIRECT_URI]
+            }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    response
+        .json::<ClientInformationResponse>()
+        .await
+        .expect("Could not deserialize response body to ClientInformationResponse")
+}
+
+#[tokio::test]
+async fn should_register_a_client_with_defaults() {
+    let mut app = TestApp::new().await;
+
+    let client = register(&app).await;
+
+    assert_eq!(client.client_name.as_deref(), Some("Example App"));
+    assert_eq!(client.redirect_uris, vec![REDIRECT_URI]);
+    assert_eq!(client.response_types, vec!["code"]);
+    assert_eq!(client.scope, "openid email");
+    assert_eq!(client.token_endpoint_auth_method, "client_secret_basic");
+    assert_eq!(client.client_secret_expires_at, Some(0));
+    assert!(client
+        .registration_client_uri
+        .ends_with(&format!("/register/{}", client.client_id)));
+
+    // The registered secret works at the token endpoint.
+    let client_secret = client.client_secret.expect("No client secret was issued");
+
+    let response = app
+        .post_token(
+            Some((&client.client_id, &client_secret)),
+            &[
+                ("grant_type", "authorization_code"),
+                ("code", "unknown"),
+                ("redirect_uri", REDIRECT_URI),
+                ("code_verifier", "unknown"),
+            ],
+        )
+        .await;
+
+    assert_oauth_error(response, 400, "invalid_grant").await;
+
+    let response = app
+        .post_token(
+            Some((&client.client_id, "wrong-secret")),
+            &[("grant_type", "authorization_code")],
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_only_accept_an_initial_access_token_once() {
+    let mut app = TestApp::new().await;
+
+    let body = serde_json::json!({ "redirect_uris": [REDIRECT_URI] });
+
+    let response = app.post_register(None, &body).await;
+
+    assert_oauth_error(response, 401, "invalid_token").await;
+
+    let response = app.post_register(Some("not-a-token"), &body).await;
+
+    assert_oauth_error(response, 401, "invalid_token").await;
+
+    let initial_access_token = create_initial_access_token(&app).await;
+
+    let response = app.post_register(Some(&initial_access_token), &body).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    let response = app.post_register(Some(&initial_access_token), &body).await;
+
+    assert_oauth_error(response, 401, "invalid_token").await;
+
+    let response = app.post_initial_access_token(None).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_reject_invalid_metadata() {
+    let mut app = TestApp::new().await;
+
+    let initial_access_token = create_initial_access_token(&app).await;
+
+    let test_cases = [
+        (serde_json::json!({}), "invalid_redirect_uri"),
+        (
+            serde_json::json!({ "redirect_uris": ["http://app.example.com/callback"] }),
+            "invalid_redirect_uri",
+        ),
+        (
+            serde_json::json!({ "redirect_uris": ["https://app.example.com/callback#fragment"] }),
+            "invalid_redirect_uri",
+        ),
+        (
+            serde_json::json!({ "redirect_uris": [REDIRECT_URI], "scope": "openid orders:read" }),
+            "invalid_client_metadata",
+        ),
+        (
+            serde_json::json!({ "grant_types": ["client_credentials"] }),
+            "invalid_client_metadata",
+        ),
+        (
+            serde_json::json!({ "redirect_uris": [REDIRECT_URI], "response_types": ["token"] }),
+            "invalid_client_metadata",
+        ),
+        (
+            serde_json::json!({
+                "redirect_uris": [REDIRECT_URI],
+                "token_endpoint_auth_method": "none"
+            }),
+            "invalid_client_metadata",
+        ),
+    ];
+
+    for (body, error) in test_cases {
+        let response = app.post_register(Some(&initial_access_token), &body).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "Failed for input: {}",
+            body
+        );
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            error,
+            "Failed for input: {}",
+            body
+        );
+    }
+
+    // None of the rejected registrations used up the token, and loopback redirects are
+    // allowed over plain http.
+    let response = app
+        .post_register(
+            Some(&initial_access_token),
+            &serde_json::json!({ "redirect_uris": ["http://127.0.0.1:8080/callback"] }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_read_update_and_delete_a_registration() {
+    let mut app = TestApp::new().await;
+
+    let client = register(&app).await;
+    let other = register(&app).await;
+    let token = client
+        .registration_access_token
+        .expect("No registration access token was issued");
+    let other_token = other
+        .registration_access_token
+        .expect("No registration access token was issued");
+
+    let response = app.get_registration(Some(&token), &client.client_id).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let read = response
+        .json::<ClientInformationResponse>()
+        .await
+        .expect("Could not deserialize response body to ClientInformationResponse");
+
+    assert_eq!(read.client_id, client.client_id);
+    assert_eq!(read.client_secret, None);
+    assert_eq!(read.registration_access_token, None);
+
+    // A registration access token only gives access to its own client.
+    let response = app
+        .get_registration(Some(&other_token), &client.client_id)
+        .await;
+
+    assert_oauth_error(response, 401, "invalid_token").await;
+
+    let response = app.get_registration(None, &client.client_id).await;
+
+    assert_oauth_error(response, 401, "invalid_token").await;
+
+    let response = app
+        .put_registration(
+            Some(&token),
+            &client.client_id,
+            &serde_json::json!({
+                "client_id": other.client_id,
+                "redirect_uris": [REDIRECT_URI]
+            }),
+        )
+        .await;
+
+    assert_oauth_error(response, 400, "invalid_client_metadata").await;
+
+    let response = app
+        .put_registration(
+            Some(&token),
+            &client.client_id,
+            &serde_json::json!({
+                "client_id": client.client_id,
+                "client_name": "Renamed App",
+                "redirect_uris": ["https://app.example.com/new-callback"],
+                "scope": "openid"
+            }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let updated = app
+        .get_registration(Some(&token), &client.client_id)
+        .await
+        .json::<ClientInformationResponse>()
+        .await
+        .expect("Could not deserialize response body to ClientInformationResponse");
+
+    assert_eq!(updated.client_name.as_deref(), Some("Renamed App"));
+    assert_eq!(
+        updated.redirect_uris,
+        vec!["https://app.example.com/new-callback"]
+    );
+    assert_eq!(updated.scope, "openid");
+
+    let response = app
+        .delete_registration(Some(&token), &client.client_id)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = app.get_registration(Some(&token), &client.client_id).await;
+
+    assert_oauth_error(response, 401, "invalid_token").await;
+
+    let response = app
+        .post_token(
+            Some((
+                &client.client_id,
+                client.client_secret.as_deref().unwrap_or_default(),
+            )),
+            &[("grant_type", "authorization_code")],
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Only the deleted client is gone.
+    let response = app
+        .get_registration(Some(&other_token), &other.client_id)
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
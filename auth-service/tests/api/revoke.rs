@@ -0,0 +1,237 @@
+use auth_service::{
+    domain::{GrantType, RefreshToken},
+    routes::TokenResponse,
+    ErrorResponse,
+};
+use secrecy::ExposeSecret;
+
+use crate::helpers::{
+    assert_oauth_error, auth_token, authorize, get_random_email, refresh_token, token_request,
+    TestApp,
+};
+
+/// Signs up and logs in a user, returning the access and refresh tokens.
+async fn login(app: &TestApp) -> (String, String) {
+    let response = app.signup_and_login(&get_random_email(), false).await;
+
+    (auth_token(&response), refresh_token(&response))
+}
+
+/// Registers a client for the client credentials grant and returns its id, its secret
+/// and an access token issued to it.
+async fn service_client(app: &TestApp) -> (String, String, String) {
+    let (client_id, client_secret) = app
+        .create_oauth_client(&["client_credentials"], &["orders:read"])
+        .await;
+
+    let access_token = app
+        .post_token(
+            Some((&client_id, &client_secret)),
+            &[("grant_type", "client_credentials")],
+        )
+        .await
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse")
+        .access_token;
+
+    (client_id, client_secret, access_token)
+}
+
+#[tokio::test]
+async fn should_revoke_access_token() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret, access_token) = service_client(&app).await;
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": access_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_revoke(
+            Some((&client_id, &client_secret)),
+            &[
+                ("token", access_token.as_str()),
+                ("token_type_hint", "access_token"),
+            ],
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": access_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_own_refresh_token() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = app
+        .register_oauth_client_with_grant_types(&[
+            GrantType::AuthorizationCode,
+            GrantType::RefreshToken,
+        ])
+        .await;
+    login(&app).await;
+
+    let code = authorize(&app, &client_id).await;
+    let refresh_token = app
+        .post_token(Some((&client_id, &client_secret)), &token_request(&code))
+        .await
+        .json::<TokenResponse>()
+        .await
+        .expect("Could not deserialize response body to TokenResponse")
+        .refresh_token
+        .expect("No refresh token was issued");
+
+    let response = app
+        .post_revoke(
+            Some((&client_id, &client_secret)),
+            &[
+                ("token", refresh_token.as_str()),
+                ("token_type_hint", "refresh_token"),
+            ],
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_token(
+            Some((&client_id, &client_secret)),
+            &[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", refresh_token.as_str()),
+            ],
+        )
+        .await;
+
+    assert_oauth_error(response, 400, "invalid_grant").await;
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_revoke_tokens_issued_to_others() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = app.register_oauth_client().await;
+    let (_, _, other_client_token) = service_client(&app).await;
+    let (session_token, refresh_token) = login(&app).await;
+
+    for token in [&other_client_token, &session_token, &refresh_token] {
+        let response = app
+            .post_revoke(Some((&client_id, &client_secret)), &[("token", token)])
+            .await;
+
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    for token in [&other_client_token, &session_token] {
+        let response = app
+            .post_verify_token(&serde_json::json!({ "token": token }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    // The session's refresh cookie still works.
+    let response = app.post_refresh_token().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_200_for_unknown_token() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = app.register_oauth_client().await;
+
+    let unknown_refresh_token = RefreshToken::default().as_ref().expose_secret().to_owned();
+
+    let test_cases = ["", "invalid_token", unknown_refresh_token.as_str()];
+
+    for token in test_cases {
+        let response = app
+            .post_revoke(Some((&client_id, &client_secret)), &[("token", token)])
+            .await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            200,
+            "Failed for input: {:?}",
+            token
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_client_not_authenticated() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, _) = app.register_oauth_client().await;
+    let (access_token, _) = login(&app).await;
+
+    let test_cases = [None, Some((client_id.as_str(), "wrong-secret"))];
+
+    for client in test_cases {
+        let response = app
+            .post_revoke(client, &[("token", access_token.as_str())])
+            .await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "Failed for input: {:?}",
+            client
+        );
+        assert_eq!(
+            response
+                .json::<ErrorResponse>()
+                .await
+                .expect("Could not deserialize response body to ErrorResponse")
+                .error,
+            "invalid_client"
+        );
+    }
+
+    // Nothing was revoked.
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": access_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_token_missing() {
+    let mut app = TestApp::new().await;
+
+    let (client_id, client_secret) = app.register_oauth_client().await;
+
+    let response = app
+        .post_revoke(
+            Some((&client_id, &client_secret)),
+            &[("token_type_hint", "access_token")],
+        )
+        .await;
+
+    assert_oauth_error(response, 400, "invalid_request").await;
+
+    app.clean_up().await;
+}
@@ -0,0 +1,189 @@
+use auth_service::{domain::Email, routes::SessionResponse, ErrorResponse};
+use secrecy::SecretString;
+
+use crate::helpers::{assert_auth_cookies_removed, auth_token, get_random_email, TestApp};
+
+async fn sessions(app: &TestApp) -> Vec<SessionResponse> {
+    let response = app.get_sessions().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    response
+        .json::<Vec<SessionResponse>>()
+        .await
+        .expect("Could not deserialize response body to Vec<SessionResponse>")
+}
+
+#[tokio::test]
+async fn should_list_current_session() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    app.signup(&email, false).await;
+    app.login(&email).await;
+
+    let sessions = sessions(&app).await;
+
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].current);
+    assert!(!sessions[0].two_factor);
+    assert_eq!(sessions[0].ip.as_deref(), Some("127.0.0.1"));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_record_two_factor_session() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    app.signup_and_login(&email, true).await;
+
+    let sessions = sessions(&app).await;
+
+    assert_eq!(sessions.len(), 1);
+    assert!(sessions[0].two_factor);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_revoke_other_session() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    app.signup(&email, false).await;
+    // Logging in again replaces the first session's cookies, as if it were on another
+    // device.
+    let other_token = auth_token(&app.login(&email).await);
+    let current_token = auth_token(&app.login(&email).await);
+
+    let sessions = sessions(&app).await;
+
+    assert_eq!(sessions.len(), 2);
+
+    let other = sessions
+        .iter()
+        .find(|session| !session.current)
+        .expect("No other session found");
+
+    let response = app.delete_session(&other.id.to_string()).await;
+
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": other_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": current_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app.delete_session(&other.id.to_string()).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Session not found".to_owned()
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_end_current_session_when_revoked() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    app.signup(&email, false).await;
+    let token = auth_token(&app.login(&email).await);
+
+    let session = sessions(&app).await.remove(0);
+
+    let response = app.delete_session(&session.id.to_string()).await;
+
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The cookies are gone, so the session can't be refreshed either.
+    let response = app.get_sessions().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.post_refresh_token().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_refresh_revoked_session() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    app.signup(&email, false).await;
+    app.login(&email).await;
+
+    let session = sessions(&app).await.remove(0);
+
+    // Revoke the session from another device, leaving this one's cookies in place.
+    let email = Email::parse(SecretString::new(email.into_boxed_str())).unwrap();
+    app.session_store
+        .write()
+        .await
+        .revoke_session(&email, session.id)
+        .await
+        .unwrap();
+
+    let response = app.post_refresh_token().await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_auth_cookies_removed(&response);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_revoke_session_of_another_user() {
+    let mut app = TestApp::new().await;
+
+    let first_email = get_random_email();
+    app.signup(&first_email, false).await;
+    let first_token = auth_token(&app.login(&first_email).await);
+    let first_session = sessions(&app).await.remove(0);
+
+    let second_email = get_random_email();
+    app.signup(&second_email, false).await;
+    app.login(&second_email).await;
+
+    let response = app.delete_session(&first_session.id.to_string()).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+
+    let sessions = sessions(&app).await;
+
+    assert_eq!(sessions.len(), 1);
+    assert_ne!(sessions[0].id, first_session.id);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": first_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
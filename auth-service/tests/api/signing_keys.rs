@@ -0,0 +1,193 @@
+use auth_service::{
+    routes::RotateSigningKeyResponse,
+    utils::{
+        constants::JWT_SIGNING_KEY_PATH,
+        signing_key::{Keyring, SigningKey},
+    },
+};
+use chrono::{Duration, Utc};
+use jsonwebtoken::{decode_header, jwk::JwkSet};
+
+use crate::helpers::{admin_token, auth_token, get_random_email, TestApp};
+
+fn kid(token: &str) -> String {
+    decode_header(token)
+        .expect("Token has no valid header")
+        .kid
+        .expect("Token has no kid")
+}
+
+#[tokio::test]
+async fn rotation_should_publish_new_key_before_signing_with_it() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    app.signup(&email, false).await;
+    let old_kid = kid(&auth_token(&app.login(&email).await));
+
+    let response = app.post_rotate_signing_key(Some(admin_token())).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_kid = response
+        .json::<RotateSigningKeyResponse>()
+        .await
+        .expect("Could not deserialize response body to RotateSigningKeyResponse")
+        .kid;
+
+    assert_ne!(new_kid, old_kid);
+
+    let jwk_set = app
+        .get_jwks()
+        .await
+        .json::<JwkSet>()
+        .await
+        .expect("Could not deserialize response body to JwkSet");
+
+    assert_eq!(jwk_set.keys.len(), 2);
+    assert!(jwk_set.find(&old_kid).is_some());
+    assert!(jwk_set.find(&new_kid).is_some());
+
+    // Verifiers may still have a JWKS cached without the new key.
+    assert_eq!(kid(&auth_token(&app.login(&email).await)), old_kid);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn rotation_should_keep_existing_tokens_valid() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    app.signup(&email, false).await;
+    let old_token = auth_token(&app.login(&email).await);
+
+    let new_kid = app
+        .keyring
+        .rotate(&app.signing_key_store, Duration::seconds(1))
+        .await
+        .expect("Failed to rotate signing key");
+
+    assert_ne!(new_kid, kid(&old_token));
+    assert_eq!(kid(&auth_token(&app.login(&email).await)), kid(&old_token));
+
+    // The new key signs from the first refresh after it activates.
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    app.keyring
+        .refresh(&app.signing_key_store)
+        .await
+        .expect("Failed to refresh keyring");
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": old_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let new_token = auth_token(&app.login(&email).await);
+
+    assert_eq!(kid(&new_token), new_kid);
+
+    let response = app
+        .post_verify_token(&serde_json::json!({ "token": new_token }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn reload_should_not_reactivate_removed_key_file_key() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    app.signup(&email, false).await;
+    let file_kid = kid(&auth_token(&app.login(&email).await));
+
+    let new_kid = app
+        .keyring
+        .rotate(&app.signing_key_store, Duration::zero())
+        .await
+        .expect("Failed to rotate signing key");
+
+    // As a later rotation does once the file key's tokens have all expired.
+    app.signing_key_store
+        .write()
+        .await
+        .remove_retired_keys(Utc::now())
+        .await
+        .expect("Failed to remove retired keys");
+
+    // A restart loads the keyring again, with the rotated away key still in the key file.
+    let keyring = Keyring::load(&app.signing_key_store)
+        .await
+        .expect("Failed to load JWT signing keys");
+
+    assert_eq!(keyring.signing_key().kid(), new_kid);
+    assert!(keyring.verification_key(&file_kid).is_none());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn adding_known_key_should_not_retire_active_key() {
+    let mut app = TestApp::new().await;
+
+    let pem = std::fs::read(JWT_SIGNING_KEY_PATH.as_str()).expect("Failed to read key file");
+    let file_key = SigningKey::from_pem(&pem).expect("Key file holds no valid key");
+
+    // What an instance starting alongside another with the same new key file ends up doing.
+    let added = app
+        .signing_key_store
+        .write()
+        .await
+        .add_key(file_key.kid(), file_key.private_key_pem(), Utc::now())
+        .await
+        .expect("Failed to add signing key");
+
+    assert!(!added);
+
+    app.keyring
+        .refresh(&app.signing_key_store)
+        .await
+        .expect("Failed to refresh keyring");
+
+    assert_eq!(app.keyring.signing_key().kid(), file_key.kid());
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_400_if_admin_token_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_rotate_signing_key(None).await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_return_401_if_admin_token_wrong() {
+    let mut app = TestApp::new().await;
+
+    let response = app
+        .post_rotate_signing_key(Some("not-the-admin-token"))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    // The active key must not have changed.
+    let jwk_set = app
+        .get_jwks()
+        .await
+        .json::<JwkSet>()
+        .await
+        .expect("Could not deserialize response body to JwkSet");
+
+    assert_eq!(jwk_set.keys.len(), 1);
+
+    app.clean_up().await;
+}
@@ -1,4 +1,5 @@
 use auth_service::{routes::SignupResponse, ErrorResponse};
+use std::time::{Duration, Instant};
 
 use crate::helpers::{get_random_email, TestApp};
 
@@ -101,17 +102,16 @@ async fn should_return_201_if_valid_input() {
             test_case
         );
 
-        let expected_response = SignupResponse {
-            message: "User created successfully!".to_owned(),
-        };
+        let body = response
+            .json::<SignupResponse>()
+            .await
+            .expect("Could not deserialize response body to UserBody");
 
-        assert_eq!(
-            response
-                .json::<SignupResponse>()
-                .await
-                .expect("Could not deserialize response body to UserBody"),
-            expected_response
-        );
+        assert_eq!(body.message, "User created successfully!".to_owned());
+
+        // Recovery codes are only issued to accounts with 2FA.
+        let requires_2fa = test_case["requires2FA"].as_bool().unwrap();
+        assert_eq!(body.recovery_codes.is_some(), requires_2fa);
     }
 
     app.clean_up().await;
@@ -163,48 +163,98 @@ async fn should_return_400_if_invalid_input() {
 }
 
 #[tokio::test]
-async fn should_return_409_if_email_already_exists() {
+async fn should_not_reveal_registered_email() {
     let mut app = TestApp::new().await;
 
     let email = get_random_email();
 
-    let first_test = serde_json::json!({
-        "email": email,
-        "password": "password123",
-        "requires2FA": true,
-    });
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": false,
+        }))
+        .await;
 
-    let first_response = app.post_signup(&first_test).await;
+    assert_eq!(response.status().as_u16(), 201);
 
-    assert_eq!(
-        first_response.status().as_u16(),
-        201,
-        "Failed for input: {:?}",
-        first_test
-    );
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password456",
+            "requires2FA": true,
+        }))
+        .await;
 
-    let second_test = serde_json::json!({
-        "email": email,
-        "password": "password123",
-        "requires2FA": true,
-    });
+    assert_eq!(response.status().as_u16(), 201);
 
-    let second_response = app.post_signup(&second_test).await;
+    let body = response
+        .json::<SignupResponse>()
+        .await
+        .expect("Could not deserialize response body to SignupResponse");
 
-    assert_eq!(
-        second_response.status().as_u16(),
-        409,
-        "Failed for input: {:?}",
-        second_test
-    );
+    assert_eq!(body.message, "User created successfully!".to_owned());
+    assert_eq!(body.recovery_codes.map(|codes| codes.len()), Some(10));
 
+    // The owner hears about it instead, and their account is left as it was.
     assert_eq!(
-        second_response
-            .json::<ErrorResponse>()
+        app.get_emails_to(&email, "You already have an account")
             .await
-            .expect("Could not deserialize response body to ErrorResponse")
-            .error,
-        "User already exists".to_owned()
+            .len(),
+        1
+    );
+
+    app.verify_email(&email).await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_take_as_long_for_registered_email() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+    let signup = |email: &str| {
+        serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": true,
+        })
+    };
+
+    let response = app.post_signup(&signup(&email)).await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    // The fastest of a few runs is the least disturbed by other tests running alongside.
+    let mut new_email = Duration::MAX;
+    let mut registered_email = Duration::MAX;
+    for _ in 0..3 {
+        let started = Instant::now();
+        app.post_signup(&signup(&get_random_email())).await;
+        new_email = new_email.min(started.elapsed());
+
+        let started = Instant::now();
+        app.post_signup(&signup(&email)).await;
+        registered_email = registered_email.min(started.elapsed());
+    }
+
+    // Either being much faster would give away whether the address is registered.
+    let ratio = registered_email.as_secs_f64() / new_email.as_secs_f64();
+    assert!(
+        (0.5..2.0).contains(&ratio),
+        "registered email took {:?}, new email {:?}",
+        registered_email,
+        new_email
     );
 
     app.clean_up().await;
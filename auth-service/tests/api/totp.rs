@@ -0,0 +1,309 @@
+use auth_service::{
+    domain::TwoFAMethod,
+    routes::{EnrollTotpResponse, TwoFactorAuthResponse},
+    ErrorResponse,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use totp_rs::TOTP;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{get_random_email, TestApp};
+
+const PNG_SIGNATURE: &[u8] = &[0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+async fn enroll(app: &TestApp) -> TOTP {
+    let response = app.post_totp_enroll().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<EnrollTotpResponse>()
+        .await
+        .expect("Could not deserialize response body to EnrollTotpResponse");
+
+    TOTP::from_url(&body.otpauth_uri).expect("Invalid otpauth URI")
+}
+
+async fn enroll_and_confirm(app: &TestApp) -> TOTP {
+    let totp = enroll(app).await;
+
+    let code = totp.generate(now());
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": code }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    totp
+}
+
+async fn login_with_2fa(app: &TestApp, email: &str) -> TwoFactorAuthResponse {
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+}
+
+fn now() -> u64 {
+    Utc::now().timestamp() as u64
+}
+
+#[tokio::test]
+async fn enroll_should_return_400_if_jwt_cookie_missing() {
+    let mut app = TestApp::new().await;
+
+    let response = app.post_totp_enroll().await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn enroll_should_return_otpauth_uri_and_qr_code() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+
+    app.signup_and_login(&email, false).await;
+
+    let response = app.post_totp_enroll().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = response
+        .json::<EnrollTotpResponse>()
+        .await
+        .expect("Could not deserialize response body to EnrollTotpResponse");
+
+    let totp = TOTP::from_url(&body.otpauth_uri).expect("Invalid otpauth URI");
+    assert_eq!(totp.account_name, email);
+
+    let png = STANDARD
+        .decode(&body.qr_code_png)
+        .expect("QR code is not valid base64");
+    assert!(png.starts_with(PNG_SIGNATURE));
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn confirm_should_return_400_if_not_enrolled() {
+    let mut app = TestApp::new().await;
+
+    app.signup_and_login(&get_random_email(), false).await;
+
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": "123456" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "TOTP not enrolled".to_owned()
+    );
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn confirm_should_return_401_if_incorrect_code() {
+    let mut app = TestApp::new().await;
+
+    app.signup_and_login(&get_random_email(), false).await;
+    let totp = enroll(&app).await;
+
+    // Ten minutes ago is well outside the drift window.
+    let code = totp.generate(now() - 600);
+    let response = app
+        .post_totp_confirm(&serde_json::json!({ "code": code }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn login_should_use_totp_after_confirmation() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+
+    app.signup_and_login(&email, false).await;
+    let totp = enroll_and_confirm(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let body = login_with_2fa(&app, &email).await;
+
+    assert_eq!(body.two_fa_method, TwoFAMethod::Totp);
+
+    // The code used for confirmation can't be replayed, so use the next one, which is still
+    // inside the drift window.
+    let code = totp.generate(now() + 30);
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": body.login_attempt_id,
+            "2FACode": code,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn verify_2fa_should_return_401_if_totp_code_reused() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+
+    app.signup_and_login(&email, false).await;
+    let totp = enroll_and_confirm(&app).await;
+
+    let code = totp.generate(now() + 30);
+
+    let body = login_with_2fa(&app, &email).await;
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": body.login_attempt_id,
+            "2FACode": code,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = login_with_2fa(&app, &email).await;
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": body.login_attempt_id,
+            "2FACode": code,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn pending_enrollment_should_not_replace_active_secret() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+
+    app.signup_and_login(&email, false).await;
+    let totp = enroll_and_confirm(&app).await;
+
+    // Start enrolling a new device but never confirm it.
+    enroll(&app).await;
+
+    let body = login_with_2fa(&app, &email).await;
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": body.login_attempt_id,
+            "2FACode": totp.generate(now() + 30),
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn set_method_should_return_422_if_malformed_input() {
+    let mut app = TestApp::new().await;
+
+    app.signup_and_login(&get_random_email(), false).await;
+
+    let test_cases = [
+        serde_json::json!({}),
+        serde_json::json!({ "method": "sms" }),
+    ];
+
+    for test_case in test_cases.iter() {
+        let response = app.put_two_fa_method(test_case).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            422,
+            "Failed for input: {:?}",
+            test_case
+        );
+    }
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn set_method_should_return_400_if_totp_not_enrolled() {
+    let mut app = TestApp::new().await;
+
+    app.signup_and_login(&get_random_email(), false).await;
+    enroll(&app).await;
+
+    let response = app
+        .put_two_fa_method(&serde_json::json!({ "method": "totp" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn set_method_should_switch_back_to_email_codes() {
+    let mut app = TestApp::new().await;
+
+    let email = get_random_email();
+
+    app.signup_and_login(&email, false).await;
+    enroll_and_confirm(&app).await;
+
+    let response = app
+        .put_two_fa_method(&serde_json::json!({ "method": "email" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let body = login_with_2fa(&app, &email).await;
+
+    assert_eq!(body.two_fa_method, TwoFAMethod::Email);
+
+    app.clean_up().await;
+}
@@ -6,6 +6,7 @@ use auth_service::{
 };
 
 use crate::helpers::{get_random_email, TestApp};
+use chrono::{Duration, Utc};
 use secrecy::{ExposeSecret, SecretString};
 use wiremock::matchers::{method, path};
 use wiremock::{Mock, ResponseTemplate};
@@ -108,6 +109,8 @@ async fn should_return_401_if_incorrect_credentials() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     Mock::given(path("/email"))
         .and(method("POST"))
         .respond_with(ResponseTemplate::new(200))
@@ -217,6 +220,8 @@ async fn should_return_401_if_old_code() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     Mock::given(path("/email"))
         .and(method("POST"))
         .respond_with(ResponseTemplate::new(200))
@@ -292,6 +297,8 @@ async fn should_return_200_if_correct_code() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     Mock::given(path("/email"))
         .and(method("POST"))
         .respond_with(ResponseTemplate::new(200))
@@ -364,6 +371,8 @@ async fn should_return_401_if_same_code_twice() {
 
     assert_eq!(response.status().as_u16(), 201);
 
+    app.verify_email(&random_email).await;
+
     Mock::given(path("/email"))
         .and(method("POST"))
         .respond_with(ResponseTemplate::new(200))
@@ -423,3 +432,188 @@ async fn should_return_401_if_same_code_twice() {
 
     app.clean_up().await;
 }
+
+/// Signs up a user with 2FA, logs in with their password and returns their email, the
+/// login attempt id and the emailed code.
+async fn start_2fa_login(app: &TestApp) -> (String, String, String) {
+    let email = get_random_email();
+
+    let response = app
+        .post_signup(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+            "requires2FA": true,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+
+    app.verify_email(&email).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "email": email,
+            "password": "password123",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 206);
+
+    let login_attempt_id = response
+        .json::<TwoFactorAuthResponse>()
+        .await
+        .expect("Could not deserialize response body to TwoFactorAuthResponse")
+        .login_attempt_id;
+
+    let (_, code) = app
+        .two_fa_code_store
+        .read()
+        .await
+        .get_code(&Email::parse(SecretString::new(email.clone().into())).unwrap())
+        .await
+        .unwrap();
+
+    (
+        email,
+        login_attempt_id,
+        code.as_ref().expose_secret().to_owned(),
+    )
+}
+
+// Emailed codes never start with a zero.
+const WRONG_CODE: &str = "012345";
+
+#[tokio::test]
+async fn should_return_429_if_code_retried_too_soon() {
+    let mut app = TestApp::new().await;
+
+    let (email, login_attempt_id, code) = start_2fa_login(&app).await;
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": WRONG_CODE,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let verify_2fa_body = serde_json::json!({
+        "email": email,
+        "loginAttemptId": login_attempt_id,
+        "2FACode": code,
+    });
+
+    // Even the right code is turned away until the backoff has passed.
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Too many attempts".to_owned()
+    );
+
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+
+    let response = app.post_verify_2fa(&verify_2fa_body).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_invalidate_code_after_too_many_failures() {
+    let mut app = TestApp::new().await;
+
+    let (email, login_attempt_id, code) = start_2fa_login(&app).await;
+
+    // Earlier failures, long enough ago that their backoff has passed.
+    let failed_at = Utc::now() - Duration::try_minutes(1).unwrap();
+    let parsed_login_attempt_id =
+        LoginAttemptId::parse(SecretString::new(login_attempt_id.clone().into())).unwrap();
+    for _ in 0..4 {
+        app.two_fa_code_store
+            .write()
+            .await
+            .record_failed_attempt(&parsed_login_attempt_id, failed_at)
+            .await
+            .unwrap();
+    }
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": WRONG_CODE,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 429);
+
+    assert_eq!(
+        response
+            .json::<ErrorResponse>()
+            .await
+            .expect("Could not deserialize response body to ErrorResponse")
+            .error,
+        "Too many attempts".to_owned()
+    );
+
+    // The code is gone, so the user has to log in again.
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    app.clean_up().await;
+}
+
+#[tokio::test]
+async fn should_not_count_wrong_login_attempt_id_as_failure() {
+    let mut app = TestApp::new().await;
+
+    let (email, login_attempt_id, code) = start_2fa_login(&app).await;
+
+    let wrong_login_attempt_id = LoginAttemptId::default();
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": wrong_login_attempt_id.as_ref().expose_secret(),
+            "2FACode": WRONG_CODE,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_verify_2fa(&serde_json::json!({
+            "email": email,
+            "loginAttemptId": login_attempt_id,
+            "2FACode": code,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+
+    app.clean_up().await;
+}